@@ -3,6 +3,30 @@ use serde_json::{Value, json};
 use crate::llm::provider::{AssistantPart, FunctionDeclaration};
 use crate::python::{CapabilityError, CapabilityProvider};
 
+/// Values of `error.code` in a failed tool response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToolErrorCode {
+    PythonException,
+    Timeout,
+    InvalidArgs,
+    UnknownTool,
+    Disabled,
+    Internal,
+}
+
+impl ToolErrorCode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::PythonException => "python_exception",
+            Self::Timeout => "timeout",
+            Self::InvalidArgs => "invalid_args",
+            Self::UnknownTool => "unknown_tool",
+            Self::Disabled => "disabled",
+            Self::Internal => "internal",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionCallSpec {
     pub id: Option<String>,
@@ -10,8 +34,9 @@ pub struct FunctionCallSpec {
     pub args_json: Value,
 }
 
-pub fn tool_declarations() -> Vec<FunctionDeclaration> {
-    vec![
+/// The tools offered to the model. `apply_code` is only declared when `allow_apply` is on.
+pub fn tool_declarations(allow_apply: bool) -> Vec<FunctionDeclaration> {
+    let mut tools = vec![
         FunctionDeclaration {
             name: "list_globals".to_string(),
             description: "List currently defined Python globals and their type names".to_string(),
@@ -30,17 +55,36 @@ pub fn tool_declarations() -> Vec<FunctionDeclaration> {
             description: "Evaluate a Python expression and return value/stdout/stderr".to_string(),
             parameters_json_schema: expr_schema(),
         },
-    ]
+    ];
+    if allow_apply {
+        tools.push(FunctionDeclaration {
+            name: "apply_code".to_string(),
+            description: "Execute Python statements in the user's session; definitions and assignments persist. Returns stdout/stderr".to_string(),
+            parameters_json_schema: json!({
+                "type": "object",
+                "properties": {
+                    "code": {"type": "string"}
+                },
+                "required": ["code"]
+            }),
+        });
+    }
+    tools
 }
 
+/// Runs each call against `capabilities`. With `allow_eval` off, `eval_expr` calls are
+/// rejected with a `disabled` error while the read-only tools keep working; the same
+/// goes for `apply_code` with `allow_apply` off.
 pub fn dispatch_calls<C: CapabilityProvider>(
     capabilities: &C,
     calls: &[FunctionCallSpec],
+    allow_eval: bool,
+    allow_apply: bool,
 ) -> Vec<AssistantPart> {
     calls
         .iter()
         .map(|call| {
-            let response_json = dispatch_one(capabilities, call);
+            let response_json = dispatch_one(capabilities, call, allow_eval, allow_apply);
             AssistantPart::FunctionResponse {
                 id: call.id.clone(),
                 name: call.name.clone(),
@@ -61,13 +105,29 @@ fn expr_schema() -> Value {
     })
 }
 
-fn dispatch_one<C: CapabilityProvider>(capabilities: &C, call: &FunctionCallSpec) -> Value {
+fn dispatch_one<C: CapabilityProvider>(
+    capabilities: &C,
+    call: &FunctionCallSpec,
+    allow_eval: bool,
+    allow_apply: bool,
+) -> Value {
     match call.name.as_str() {
         "list_globals" => dispatch_list_globals(capabilities, call),
         "inspect" => dispatch_inspect(capabilities, call),
+        "eval_expr" if !allow_eval => error_response(
+            ToolErrorCode::Disabled,
+            "eval_expr is disabled; use inspect or list_globals instead".to_string(),
+            json!({}),
+        ),
         "eval_expr" => dispatch_eval_expr(capabilities, call),
+        "apply_code" if !allow_apply => error_response(
+            ToolErrorCode::Disabled,
+            "apply_code is disabled; the session cannot be changed".to_string(),
+            json!({}),
+        ),
+        "apply_code" => dispatch_apply_code(capabilities, call),
         _ => error_response(
-            "unknown_function",
+            ToolErrorCode::UnknownTool,
             format!("unknown function: {}", call.name),
             json!({}),
         ),
@@ -119,6 +179,22 @@ fn dispatch_eval_expr<C: CapabilityProvider>(capabilities: &C, call: &FunctionCa
             "value_repr": info.value_repr,
             "stdout": info.stdout,
             "stderr": info.stderr,
+            "tabular": info.tabular,
+        })),
+        Err(err) => map_capability_error(err),
+    }
+}
+
+fn dispatch_apply_code<C: CapabilityProvider>(capabilities: &C, call: &FunctionCallSpec) -> Value {
+    let code = match expect_string_arg(call, "code") {
+        Ok(code) => code,
+        Err(err) => return err,
+    };
+
+    match capabilities.exec_code(code) {
+        Ok(info) => ok_response(json!({
+            "stdout": info.stdout,
+            "stderr": info.stderr,
         })),
         Err(err) => map_capability_error(err),
     }
@@ -130,38 +206,42 @@ fn expect_empty_args(call: &FunctionCallSpec) -> Result<(), Value> {
     }
 
     Err(error_response(
-        "invalid_args",
+        ToolErrorCode::InvalidArgs,
         format!("{} does not accept arguments", call.name),
         json!({ "args": call.args_json }),
     ))
 }
 
 fn expect_expr_arg(call: &FunctionCallSpec) -> Result<&str, Value> {
+    expect_string_arg(call, "expr")
+}
+
+fn expect_string_arg<'a>(call: &'a FunctionCallSpec, field: &str) -> Result<&'a str, Value> {
     let Some(args) = call.args_json.as_object() else {
         return Err(error_response(
-            "invalid_args",
-            format!("{} expects object args with expr", call.name),
+            ToolErrorCode::InvalidArgs,
+            format!("{} expects object args with {field}", call.name),
             json!({ "args": call.args_json }),
         ));
     };
 
-    let Some(expr) = args.get("expr") else {
+    let Some(value) = args.get(field) else {
         return Err(error_response(
-            "invalid_args",
-            format!("{} requires string field expr", call.name),
+            ToolErrorCode::InvalidArgs,
+            format!("{} requires string field {field}", call.name),
             json!({ "args": call.args_json }),
         ));
     };
 
-    let Some(expr) = expr.as_str() else {
+    let Some(value) = value.as_str() else {
         return Err(error_response(
-            "invalid_args",
-            format!("{} requires expr to be a string", call.name),
+            ToolErrorCode::InvalidArgs,
+            format!("{} requires {field} to be a string", call.name),
             json!({ "args": call.args_json }),
         ));
     };
 
-    Ok(expr)
+    Ok(value)
 }
 
 fn ok_response(result: Value) -> Value {
@@ -171,21 +251,23 @@ fn ok_response(result: Value) -> Value {
     })
 }
 
-fn error_response(code: &str, message: String, details: Value) -> Value {
+fn error_response(code: ToolErrorCode, message: String, details: Value) -> Value {
     json!({
         "ok": false,
         "error": {
-            "code": code,
+            "code": code.as_str(),
             "message": message,
             "details": details,
         }
     })
 }
 
+/// Maps a capability failure to an error response. A `TimeoutError` raised by the
+/// inspect timeout is reported as `timeout`; other exceptions as `python_exception`.
 fn map_capability_error(err: CapabilityError) -> Value {
     match err {
         CapabilityError::PythonException(exc) => error_response(
-            "python_exception",
+            ToolErrorCode::PythonException,
             format!("{}: {}", exc.exc_type, exc.message),
             json!({
                 "exc_type": exc.exc_type,
@@ -193,24 +275,37 @@ fn map_capability_error(err: CapabilityError) -> Value {
                 "traceback": exc.traceback,
             }),
         ),
-        CapabilityError::InvalidResultShape(msg) => error_response("internal", msg, json!({})),
-        CapabilityError::Internal(msg) => error_response("internal", msg, json!({})),
+        CapabilityError::Timeout(timeout) => error_response(
+            ToolErrorCode::Timeout,
+            err.to_string(),
+            json!({ "timeout_ms": timeout.as_millis() }),
+        ),
+        CapabilityError::InvalidResultShape(msg) => {
+            error_response(ToolErrorCode::Internal, msg, json!({}))
+        }
+        CapabilityError::Internal(msg) => error_response(ToolErrorCode::Internal, msg, json!({})),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use serde_json::json;
+    use std::time::Duration;
 
-    use crate::agent::dispatch::{FunctionCallSpec, dispatch_calls, tool_declarations};
+    use crate::agent::dispatch::{
+        FunctionCallSpec, dispatch_calls, map_capability_error, tool_declarations,
+    };
     use crate::llm::provider::AssistantPart;
-    use crate::python::PythonSession;
+    use crate::python::{CapabilityError, ExceptionInfo, PythonSession};
 
     #[test]
     fn tool_declarations_include_minimal_tools() {
-        let tools = tool_declarations();
+        let tools = tool_declarations(false);
         let names = tools.into_iter().map(|t| t.name).collect::<Vec<_>>();
         assert_eq!(names, vec!["list_globals", "inspect", "eval_expr"]);
+
+        let tools = tool_declarations(true);
+        assert_eq!(tools.last().map(|t| t.name.as_str()), Some("apply_code"));
     }
 
     #[test]
@@ -225,6 +320,8 @@ mod tests {
                 name: "list_globals".to_string(),
                 args_json: json!({}),
             }],
+            true,
+            false,
         );
 
         let first = responses.first().expect("response");
@@ -246,6 +343,8 @@ mod tests {
                 name: "inspect".to_string(),
                 args_json: json!({ "expr": "[1, 2, 3]" }),
             }],
+            true,
+            false,
         );
 
         let AssistantPart::FunctionResponse { response_json, .. } =
@@ -269,6 +368,8 @@ mod tests {
                 name: "eval_expr".to_string(),
                 args_json: json!({ "expr": "1 + 2" }),
             }],
+            true,
+            false,
         );
 
         let AssistantPart::FunctionResponse { response_json, .. } =
@@ -291,6 +392,8 @@ mod tests {
                 name: "inspect".to_string(),
                 args_json: json!({ "expr": 123 }),
             }],
+            true,
+            false,
         );
 
         let AssistantPart::FunctionResponse { response_json, .. } =
@@ -304,7 +407,7 @@ mod tests {
     }
 
     #[test]
-    fn dispatch_removed_tool_returns_unknown_function() {
+    fn dispatch_removed_tool_returns_unknown_tool() {
         let session = PythonSession::initialize().expect("python");
         let responses = dispatch_calls(
             &session,
@@ -313,6 +416,8 @@ mod tests {
                 name: "get_repr".to_string(),
                 args_json: json!({ "expr": "1" }),
             }],
+            true,
+            false,
         );
 
         let AssistantPart::FunctionResponse { response_json, .. } =
@@ -322,6 +427,162 @@ mod tests {
         };
 
         assert_eq!(response_json["ok"], json!(false));
-        assert_eq!(response_json["error"]["code"], json!("unknown_function"));
+        assert_eq!(response_json["error"]["code"], json!("unknown_tool"));
+    }
+
+    #[test]
+    fn dispatch_eval_expr_is_rejected_when_eval_disabled() {
+        let session = PythonSession::initialize().expect("python");
+        let calls = [
+            FunctionCallSpec {
+                id: Some("c6".to_string()),
+                name: "eval_expr".to_string(),
+                args_json: json!({ "expr": "globals().update(x=1)" }),
+            },
+            FunctionCallSpec {
+                id: Some("c7".to_string()),
+                name: "inspect".to_string(),
+                args_json: json!({ "expr": "1" }),
+            },
+        ];
+
+        let responses = dispatch_calls(&session, &calls, false, false);
+
+        let [
+            AssistantPart::FunctionResponse {
+                response_json: eval_json,
+                ..
+            },
+            AssistantPart::FunctionResponse {
+                response_json: inspect_json,
+                ..
+            },
+        ] = responses.as_slice()
+        else {
+            panic!("expected two function response parts");
+        };
+        assert_eq!(eval_json["ok"], json!(false));
+        assert_eq!(eval_json["error"]["code"], json!("disabled"));
+        assert_eq!(inspect_json["ok"], json!(true));
+        assert!(session.list_globals().expect("globals").is_empty());
+    }
+
+    #[test]
+    fn dispatch_apply_code_persists_globals_only_when_allowed() {
+        let session = PythonSession::initialize().expect("python");
+        let call = FunctionCallSpec {
+            id: Some("c8".to_string()),
+            name: "apply_code".to_string(),
+            args_json: json!({ "code": "def f():\n    return 2\nprint('defined')" }),
+        };
+
+        let responses = dispatch_calls(&session, std::slice::from_ref(&call), true, false);
+        let AssistantPart::FunctionResponse { response_json, .. } =
+            responses.first().expect("response")
+        else {
+            panic!("expected function response part");
+        };
+        assert_eq!(response_json["error"]["code"], json!("disabled"));
+        assert!(session.list_globals().expect("globals").is_empty());
+
+        let responses = dispatch_calls(&session, &[call], true, true);
+        let AssistantPart::FunctionResponse { response_json, .. } =
+            responses.first().expect("response")
+        else {
+            panic!("expected function response part");
+        };
+        assert_eq!(response_json["ok"], json!(true));
+        assert_eq!(response_json["result"]["stdout"], json!("defined\n"));
+        assert_eq!(session.eval_expr("f()").expect("call f").value_repr, "2");
+    }
+
+    #[test]
+    fn dispatch_apply_code_returns_structured_python_exception() {
+        let session = PythonSession::initialize().expect("python");
+        let responses = dispatch_calls(
+            &session,
+            &[FunctionCallSpec {
+                id: Some("c9".to_string()),
+                name: "apply_code".to_string(),
+                args_json: json!({ "code": "raise ValueError('nope')" }),
+            }],
+            true,
+            true,
+        );
+
+        let AssistantPart::FunctionResponse { response_json, .. } =
+            responses.first().expect("response")
+        else {
+            panic!("expected function response part");
+        };
+        assert_eq!(response_json["ok"], json!(false));
+        assert_eq!(response_json["error"]["code"], json!("python_exception"));
+        assert_eq!(
+            response_json["error"]["details"]["exc_type"],
+            json!("ValueError")
+        );
+    }
+
+    #[test]
+    fn dispatch_error_paths_report_consistent_codes() {
+        let session = PythonSession::initialize().expect("python");
+        let cases = [
+            ("inspect", json!({}), "invalid_args"),
+            ("inspect", json!("1 + 1"), "invalid_args"),
+            ("eval_expr", json!({ "expression": "1" }), "invalid_args"),
+            ("apply_code", json!({ "code": 1 }), "invalid_args"),
+            ("list_globals", json!({ "expr": "x" }), "invalid_args"),
+            ("eval_expr", json!({ "expr": "1" }), "disabled"),
+            ("run_shell", json!({}), "unknown_tool"),
+            (
+                "inspect",
+                json!({ "expr": "missing_name" }),
+                "python_exception",
+            ),
+        ];
+
+        for (name, args_json, code) in cases {
+            let call = FunctionCallSpec {
+                id: None,
+                name: name.to_string(),
+                args_json: args_json.clone(),
+            };
+            let allow_eval = code != "disabled";
+            let responses = dispatch_calls(&session, &[call], allow_eval, true);
+            let AssistantPart::FunctionResponse { response_json, .. } =
+                responses.first().expect("response")
+            else {
+                panic!("expected function response part");
+            };
+            assert_eq!(response_json["ok"], json!(false), "{name} {args_json}");
+            assert_eq!(
+                response_json["error"]["code"],
+                json!(code),
+                "{name} {args_json}"
+            );
+        }
+
+        let exception = |exc_type: &str, message: &str| {
+            CapabilityError::PythonException(ExceptionInfo {
+                exc_type: exc_type.to_string(),
+                message: message.to_string(),
+                traceback: String::new(),
+            })
+        };
+        let cases = [
+            (CapabilityError::Timeout(Duration::from_secs(1)), "timeout"),
+            (
+                exception("TimeoutError", "inspect timed out after 1 seconds"),
+                "python_exception",
+            ),
+            (CapabilityError::Internal("boom".to_string()), "internal"),
+            (
+                CapabilityError::InvalidResultShape("bad".to_string()),
+                "internal",
+            ),
+        ];
+        for (err, code) in cases {
+            assert_eq!(map_capability_error(err)["error"]["code"], json!(code));
+        }
     }
 }
@@ -5,19 +5,33 @@ use serde_json::Value;
 use tokio::time::timeout;
 
 use crate::agent::dispatch::{FunctionCallSpec, dispatch_calls, tool_declarations};
-use crate::agent::prompt::AGENT_SYSTEM_PROMPT;
+use crate::agent::prompt::{
+    AGENT_SYSTEM_PROMPT, CHAT_SYSTEM_PROMPT, agent_system_prompt, with_system_note,
+};
 use crate::llm::provider::{
-    AssistantCandidate, AssistantInput, AssistantMessage, AssistantPart, AssistantRole,
+    AssistantCandidate, AssistantInput, AssistantMessage, AssistantPart, AssistantRole, LlmError,
     LlmProvider, LlmTokenUsage, LlmTokenUsageTotals, ToolCallingMode,
 };
 use crate::python::CapabilityProvider;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AgentConfig {
     pub max_steps: usize,
     pub per_step_timeout_ms: u64,
     pub total_timeout_ms: u64,
     pub invalid_response_retries: usize,
+    /// Retries the whole turn once, from the original question and on fresh connections,
+    /// after a transport error. Request timeouts are not retried: the endpoint answered too
+    /// slowly, and a second attempt would likely spend the rest of the time budget the same way.
+    pub auto_retry_turn: bool,
+    /// When false, `eval_expr` calls are refused so the assistant can only observe state.
+    pub allow_eval: bool,
+    /// Offers the `apply_code` tool, which can change the session's globals.
+    pub allow_apply: bool,
+    /// Answers with a single tool-less request instead of running the agent loop.
+    pub chat_only: bool,
+    /// Extra instruction set with `/system`, placed before the system prompt.
+    pub system_note: Option<String>,
 }
 
 impl Default for AgentConfig {
@@ -27,6 +41,11 @@ impl Default for AgentConfig {
             per_step_timeout_ms: 8_000,
             total_timeout_ms: 20_000,
             invalid_response_retries: 1,
+            auto_retry_turn: false,
+            allow_eval: true,
+            allow_apply: false,
+            chat_only: false,
+            system_note: None,
         }
     }
 }
@@ -34,10 +53,35 @@ impl Default for AgentConfig {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AgentAnswer {
     pub text: String,
-    pub degraded: bool,
+    /// Why the text is a fallback rather than a real answer, if it is one.
+    pub degraded: Option<DegradedReason>,
     pub token_usage: LlmTokenUsageTotals,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DegradedReason {
+    RequestFailed,
+    TimeLimit,
+    StepTimeout,
+    InvalidResponse,
+    EmptyResponse,
+    /// The step limit was reached; the text may come from a final call without tools.
+    StepLimit,
+}
+
+impl DegradedReason {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::RequestFailed => "request_failed",
+            Self::TimeLimit => "time_limit",
+            Self::StepTimeout => "step_timeout",
+            Self::InvalidResponse => "invalid_response",
+            Self::EmptyResponse => "empty_response",
+            Self::StepLimit => "step_limit",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AgentProgressEvent {
     StepStarted {
@@ -49,6 +93,10 @@ pub enum AgentProgressEvent {
         tool_calls: usize,
         has_text: bool,
     },
+    TextDelta {
+        step: usize,
+        text: String,
+    },
     ToolRequest {
         step: usize,
         id: Option<String>,
@@ -61,80 +109,233 @@ pub enum AgentProgressEvent {
         name: String,
         response_json: Value,
     },
+    /// The turn failed and starts over from the original question; anything streamed so
+    /// far belongs to the failed attempt.
+    TurnRetried,
 }
 
 pub async fn run_question_with_events<
     P: LlmProvider,
     C: CapabilityProvider,
-    F: FnMut(AgentProgressEvent),
+    F: FnMut(AgentProgressEvent) + Send,
 >(
     provider: &P,
     capabilities: &C,
     question: &str,
+    history: &[AssistantMessage],
+    config: &AgentConfig,
+    on_event: &mut F,
+) -> Result<AgentAnswer> {
+    let total_deadline = Instant::now() + Duration::from_millis(config.total_timeout_ms);
+    let mut token_usage = LlmTokenUsageTotals::default();
+    let mut turn_retried = false;
+    let conversation = with_question(history, question);
+    let mut reconnected = None;
+
+    loop {
+        let provider = reconnected.as_ref().unwrap_or(provider);
+        match run_turn(
+            provider,
+            capabilities,
+            &conversation,
+            config,
+            total_deadline,
+            &mut token_usage,
+            on_event,
+        )
+        .await?
+        {
+            TurnOutcome::Finished(answer) => return Ok(answer),
+            TurnOutcome::Failed(LlmError::Transport(_))
+                if config.auto_retry_turn && !turn_retried =>
+            {
+                turn_retried = true;
+                reconnected = provider.reconnected();
+                on_event(AgentProgressEvent::TurnRetried);
+            }
+            TurnOutcome::Failed(err) => {
+                return Ok(degraded(
+                    DegradedReason::RequestFailed,
+                    format!("Assistant request failed while reasoning: {err}"),
+                    token_usage,
+                ));
+            }
+        }
+    }
+}
+
+/// Answers `question` with one provider call and no tools. Used by chat mode, where
+/// the agent loop is not worth its extra round trips.
+pub async fn run_chat_question<P: LlmProvider, F: FnMut(AgentProgressEvent) + Send>(
+    provider: &P,
+    question: &str,
+    history: &[AssistantMessage],
     config: &AgentConfig,
     on_event: &mut F,
 ) -> Result<AgentAnswer> {
-    let mut messages = vec![AssistantMessage {
+    let step = 1;
+    on_event(AgentProgressEvent::StepStarted { step });
+
+    let timeout_budget =
+        Duration::from_millis(config.per_step_timeout_ms.min(config.total_timeout_ms));
+    let mut on_text = |text: &str| {
+        on_event(AgentProgressEvent::TextDelta {
+            step,
+            text: text.to_string(),
+        });
+    };
+    let llm = timeout(
+        timeout_budget,
+        provider.generate_stream(
+            AssistantInput {
+                system_instruction: Some(with_system_note(
+                    CHAT_SYSTEM_PROMPT.to_string(),
+                    config.system_note.as_deref(),
+                )),
+                messages: with_question(history, question),
+                tools: vec![],
+                tool_calling_mode: ToolCallingMode::None,
+            },
+            &mut on_text,
+        ),
+    )
+    .await;
+
+    let mut token_usage = LlmTokenUsageTotals::default();
+    let output = match llm {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => {
+            return Ok(degraded(
+                DegradedReason::RequestFailed,
+                format!("Assistant request failed: {err}"),
+                token_usage,
+            ));
+        }
+        Err(_) => {
+            return Ok(degraded(
+                DegradedReason::TimeLimit,
+                "Assistant hit the time limit while answering your question; the model was slow to respond.",
+                token_usage,
+            ));
+        }
+    };
+    token_usage.add_usage(output.usage.as_ref());
+
+    let text = select_candidate(&output.candidates)
+        .map(|candidate| extract_text(&candidate.message.parts))
+        .unwrap_or_default();
+    on_event(AgentProgressEvent::ModelResponse {
+        step,
+        thought_signatures: 0,
+        tool_calls: 0,
+        has_text: !text.is_empty(),
+    });
+    if text.is_empty() {
+        return Ok(degraded(
+            DegradedReason::EmptyResponse,
+            "Assistant returned an empty response.",
+            token_usage,
+        ));
+    }
+
+    Ok(AgentAnswer {
+        text,
+        degraded: None,
+        token_usage,
+    })
+}
+
+/// The earlier conversation followed by `question` as the newest user message.
+fn with_question(history: &[AssistantMessage], question: &str) -> Vec<AssistantMessage> {
+    let mut messages = history.to_vec();
+    messages.push(AssistantMessage {
         role: AssistantRole::User,
         parts: vec![AssistantPart::Text {
             text: question.to_string(),
             thought_signature: None,
         }],
-    }];
-    let tools = tool_declarations();
-    let total_deadline = Instant::now() + Duration::from_millis(config.total_timeout_ms);
+    });
+    messages
+}
+
+enum TurnOutcome {
+    Finished(AgentAnswer),
+    Failed(LlmError),
+}
+
+async fn run_turn<P: LlmProvider, C: CapabilityProvider, F: FnMut(AgentProgressEvent) + Send>(
+    provider: &P,
+    capabilities: &C,
+    conversation: &[AssistantMessage],
+    config: &AgentConfig,
+    total_deadline: Instant,
+    token_usage: &mut LlmTokenUsageTotals,
+    on_event: &mut F,
+) -> Result<TurnOutcome> {
+    let mut messages = conversation.to_vec();
+    let tools = tool_declarations(config.allow_apply);
+    let system_prompt = with_system_note(
+        agent_system_prompt(config.allow_apply),
+        config.system_note.as_deref(),
+    );
     let mut invalid_response_attempts = 0usize;
-    let mut token_usage = LlmTokenUsageTotals::default();
 
     for step in 1..=config.max_steps {
         on_event(AgentProgressEvent::StepStarted { step });
 
         let now = Instant::now();
         if now >= total_deadline {
-            return Ok(degraded(
+            return Ok(TurnOutcome::Finished(degraded(
+                DegradedReason::TimeLimit,
                 "Assistant hit the total time limit while reasoning about your question.",
-                token_usage,
-            ));
+                std::mem::take(token_usage),
+            )));
         }
 
         let remaining = total_deadline.duration_since(now);
         let per_step = Duration::from_millis(config.per_step_timeout_ms);
         let timeout_budget = per_step.min(remaining);
 
+        let mut on_text = |text: &str| {
+            on_event(AgentProgressEvent::TextDelta {
+                step,
+                text: text.to_string(),
+            });
+        };
         let llm = timeout(
             timeout_budget,
-            provider.generate(AssistantInput {
-                system_instruction: Some(AGENT_SYSTEM_PROMPT.to_string()),
-                messages: messages.clone(),
-                tools: tools.clone(),
-                tool_calling_mode: ToolCallingMode::Auto,
-            }),
+            provider.generate_stream(
+                AssistantInput {
+                    system_instruction: Some(system_prompt.clone()),
+                    messages: messages.clone(),
+                    tools: tools.clone(),
+                    tool_calling_mode: ToolCallingMode::Auto,
+                },
+                &mut on_text,
+            ),
         )
         .await;
 
         let output = match llm {
             Ok(Ok(output)) => output,
-            Ok(Err(err)) => {
-                return Ok(degraded(
-                    format!("Assistant request failed while reasoning: {err}"),
-                    token_usage,
-                ));
-            }
+            Ok(Err(err)) => return Ok(TurnOutcome::Failed(err)),
             Err(_) => {
-                return Ok(degraded(
-                    "Assistant hit a per-step timeout while reasoning about your question.",
-                    token_usage,
-                ));
+                return Ok(TurnOutcome::Finished(degraded(
+                    DegradedReason::StepTimeout,
+                    "Assistant hit a per-step timeout while reasoning about your question; the model was slow to respond.",
+                    std::mem::take(token_usage),
+                )));
             }
         };
         token_usage.add_usage(output.usage.as_ref());
 
         let Some(candidate) = select_candidate(&output.candidates) else {
             if invalid_response_attempts >= config.invalid_response_retries {
-                return Ok(degraded(
+                return Ok(TurnOutcome::Finished(degraded(
+                    DegradedReason::InvalidResponse,
                     "Assistant returned an invalid response repeatedly and could not complete the tool flow.",
-                    token_usage,
-                ));
+                    std::mem::take(token_usage),
+                )));
             }
             invalid_response_attempts += 1;
             messages.push(repair_prompt_message());
@@ -154,18 +355,19 @@ pub async fn run_question_with_events<
 
         if calls.is_empty() {
             if !text.is_empty() {
-                return Ok(AgentAnswer {
+                return Ok(TurnOutcome::Finished(AgentAnswer {
                     text,
-                    degraded: false,
-                    token_usage,
-                });
+                    degraded: None,
+                    token_usage: std::mem::take(token_usage),
+                }));
             }
 
             if invalid_response_attempts >= config.invalid_response_retries {
-                return Ok(degraded(
+                return Ok(TurnOutcome::Finished(degraded(
+                    DegradedReason::EmptyResponse,
                     "Assistant returned an empty response repeatedly and could not complete the tool flow.",
-                    token_usage,
-                ));
+                    std::mem::take(token_usage),
+                )));
             }
             invalid_response_attempts += 1;
             messages.push(repair_prompt_message());
@@ -181,7 +383,7 @@ pub async fn run_question_with_events<
             });
         }
 
-        let responses = dispatch_calls(capabilities, &calls);
+        let responses = dispatch_calls(capabilities, &calls, config.allow_eval, config.allow_apply);
         for response in &responses {
             if let AssistantPart::FunctionResponse {
                 id,
@@ -210,30 +412,40 @@ pub async fn run_question_with_events<
         let per_step = Duration::from_millis(config.per_step_timeout_ms);
         let timeout_budget = per_step.min(remaining);
         if !timeout_budget.is_zero()
-            && let Some((text, usage)) =
-                finalize_without_tools(provider, &messages, timeout_budget).await
+            && let Some((text, usage)) = finalize_without_tools(
+                provider,
+                &messages,
+                config.system_note.as_deref(),
+                timeout_budget,
+            )
+            .await
         {
             token_usage.add_usage(usage.as_ref());
             if let Some(text) = text {
-                return Ok(AgentAnswer {
+                return Ok(TurnOutcome::Finished(AgentAnswer {
                     text,
-                    degraded: true,
-                    token_usage,
-                });
+                    degraded: Some(DegradedReason::StepLimit),
+                    token_usage: std::mem::take(token_usage),
+                }));
             }
         }
     }
 
-    Ok(degraded(
+    Ok(TurnOutcome::Finished(degraded(
+        DegradedReason::StepLimit,
         "Assistant reached the step limit while reasoning about your question.",
-        token_usage,
-    ))
+        std::mem::take(token_usage),
+    )))
 }
 
-fn degraded(message: impl Into<String>, token_usage: LlmTokenUsageTotals) -> AgentAnswer {
+fn degraded(
+    reason: DegradedReason,
+    message: impl Into<String>,
+    token_usage: LlmTokenUsageTotals,
+) -> AgentAnswer {
     AgentAnswer {
         text: message.into(),
-        degraded: true,
+        degraded: Some(reason),
         token_usage,
     }
 }
@@ -316,13 +528,17 @@ fn count_thought_signatures(parts: &[AssistantPart]) -> usize {
 async fn finalize_without_tools<P: LlmProvider>(
     provider: &P,
     messages: &[AssistantMessage],
+    system_note: Option<&str>,
     timeout_budget: Duration,
 ) -> Option<(Option<String>, Option<LlmTokenUsage>)> {
     let llm = timeout(
         timeout_budget,
         provider.generate(AssistantInput {
-            system_instruction: Some(format!(
-                "{AGENT_SYSTEM_PROMPT}\n\nThe tool loop is complete. Do not call functions. Provide the best concise plain-text answer from available context."
+            system_instruction: Some(with_system_note(
+                format!(
+                    "{AGENT_SYSTEM_PROMPT}\n\nThe tool loop is complete. Do not call functions. Provide the best concise plain-text answer from available context."
+                ),
+                system_note,
             )),
             messages: messages.to_vec(),
             tools: vec![],
@@ -384,16 +600,21 @@ mod tests {
 
     use serde_json::json;
 
-    use crate::agent::{AgentConfig, run_question_with_events};
+    use crate::agent::prompt::CHAT_SYSTEM_PROMPT;
+    use crate::agent::{
+        AgentConfig, AgentProgressEvent, DegradedReason, run_chat_question,
+        run_question_with_events,
+    };
     use crate::llm::provider::{
         AssistantCandidate, AssistantInput, AssistantMessage, AssistantOutput, AssistantPart,
-        AssistantRole, LlmError, LlmProvider, LlmTokenUsage,
+        AssistantRole, LlmError, LlmProvider, LlmTokenUsage, ToolCallingMode,
     };
     use crate::python::PythonSession;
 
     struct FakeProvider {
         responses: Arc<Mutex<VecDeque<Result<AssistantOutput, LlmError>>>>,
         seen_inputs: Arc<Mutex<Vec<AssistantInput>>>,
+        reconnects: Arc<Mutex<usize>>,
     }
 
     impl FakeProvider {
@@ -401,6 +622,7 @@ mod tests {
             Self {
                 responses: Arc::new(Mutex::new(VecDeque::from(responses))),
                 seen_inputs: Arc::new(Mutex::new(Vec::new())),
+                reconnects: Arc::new(Mutex::new(0)),
             }
         }
     }
@@ -414,6 +636,15 @@ mod tests {
                 .pop_front()
                 .expect("queued response")
         }
+
+        fn reconnected(&self) -> Option<Self> {
+            *self.reconnects.lock().expect("lock") += 1;
+            Some(Self {
+                responses: Arc::clone(&self.responses),
+                seen_inputs: Arc::clone(&self.seen_inputs),
+                reconnects: Arc::clone(&self.reconnects),
+            })
+        }
     }
 
     #[tokio::test]
@@ -456,6 +687,7 @@ mod tests {
             &provider,
             &session,
             "what globals?",
+            &[],
             &AgentConfig::default(),
             &mut |_| {},
         )
@@ -463,7 +695,191 @@ mod tests {
         .expect("answer");
 
         assert_eq!(answer.text, "done");
-        assert!(!answer.degraded);
+        assert_eq!(answer.degraded, None);
+    }
+
+    #[tokio::test]
+    async fn chat_question_makes_one_tool_less_call_and_returns_its_text() {
+        let provider = FakeProvider::new(vec![Ok(AssistantOutput {
+            usage: None,
+            candidates: vec![AssistantCandidate {
+                message: AssistantMessage {
+                    role: AssistantRole::Model,
+                    parts: vec![AssistantPart::Text {
+                        text: "Use a list comprehension.".to_string(),
+                        thought_signature: None,
+                    }],
+                },
+                finish_reason: Some("STOP".to_string()),
+                safety_blocked: false,
+            }],
+        })]);
+
+        let answer = run_chat_question(
+            &provider,
+            "how do I square a list?",
+            &[],
+            &AgentConfig {
+                chat_only: true,
+                system_note: Some("Answer in French.".to_string()),
+                ..AgentConfig::default()
+            },
+            &mut |_| {},
+        )
+        .await
+        .expect("answer");
+
+        assert_eq!(answer.text, "Use a list comprehension.");
+        assert_eq!(answer.degraded, None);
+        let seen = provider.seen_inputs.lock().expect("lock");
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0].tools.is_empty());
+        assert_eq!(seen[0].tool_calling_mode, ToolCallingMode::None);
+        let instruction = seen[0].system_instruction.as_deref().expect("instruction");
+        assert_eq!(
+            instruction,
+            format!("Answer in French.\n\n{CHAT_SYSTEM_PROMPT}")
+        );
+        assert!(!instruction.contains("inspect("));
+    }
+
+    #[tokio::test]
+    async fn system_note_is_placed_before_the_system_prompt() {
+        let provider = FakeProvider::new(vec![Ok(AssistantOutput {
+            usage: None,
+            candidates: vec![AssistantCandidate {
+                message: AssistantMessage {
+                    role: AssistantRole::Model,
+                    parts: vec![AssistantPart::Text {
+                        text: "df.head()".to_string(),
+                        thought_signature: None,
+                    }],
+                },
+                finish_reason: Some("STOP".to_string()),
+                safety_blocked: false,
+            }],
+        })]);
+
+        let session = PythonSession::initialize().expect("python");
+        let config = AgentConfig {
+            system_note: Some("Assume pandas is imported as pd.".to_string()),
+            ..AgentConfig::default()
+        };
+        run_question_with_events(&provider, &session, "peek at df", &[], &config, &mut |_| {})
+            .await
+            .expect("answer");
+
+        let seen = provider.seen_inputs.lock().expect("lock");
+        let instruction = seen[0].system_instruction.as_deref().expect("instruction");
+        assert!(instruction.starts_with("Assume pandas is imported as pd.\n\nYou are PyChat.ai"));
+    }
+
+    #[tokio::test]
+    async fn history_is_sent_before_the_new_question() {
+        let provider = FakeProvider::new(vec![Ok(AssistantOutput {
+            usage: None,
+            candidates: vec![AssistantCandidate {
+                message: AssistantMessage {
+                    role: AssistantRole::Model,
+                    parts: vec![AssistantPart::Text {
+                        text: "It has 3 rows.".to_string(),
+                        thought_signature: None,
+                    }],
+                },
+                finish_reason: Some("STOP".to_string()),
+                safety_blocked: false,
+            }],
+        })]);
+        let text_message = |role, text: &str| AssistantMessage {
+            role,
+            parts: vec![AssistantPart::Text {
+                text: text.to_string(),
+                thought_signature: None,
+            }],
+        };
+        let history = vec![
+            text_message(AssistantRole::User, "what is df?"),
+            text_message(AssistantRole::Model, "A pandas DataFrame."),
+        ];
+
+        run_chat_question(
+            &provider,
+            "how many rows?",
+            &history,
+            &AgentConfig::default(),
+            &mut |_| {},
+        )
+        .await
+        .expect("answer");
+
+        let seen = provider.seen_inputs.lock().expect("lock");
+        let mut expected = history.clone();
+        expected.push(text_message(AssistantRole::User, "how many rows?"));
+        assert_eq!(seen[0].messages, expected);
+    }
+
+    struct StreamingProvider {
+        chunks: Vec<&'static str>,
+    }
+
+    impl LlmProvider for StreamingProvider {
+        async fn generate(&self, _input: AssistantInput) -> Result<AssistantOutput, LlmError> {
+            Ok(AssistantOutput {
+                usage: None,
+                candidates: vec![AssistantCandidate {
+                    message: AssistantMessage {
+                        role: AssistantRole::Model,
+                        parts: vec![AssistantPart::Text {
+                            text: self.chunks.concat(),
+                            thought_signature: None,
+                        }],
+                    },
+                    finish_reason: Some("STOP".to_string()),
+                    safety_blocked: false,
+                }],
+            })
+        }
+
+        async fn generate_stream<F: FnMut(&str) + Send>(
+            &self,
+            input: AssistantInput,
+            on_text: &mut F,
+        ) -> Result<AssistantOutput, LlmError> {
+            for chunk in &self.chunks {
+                on_text(chunk);
+            }
+            self.generate(input).await
+        }
+    }
+
+    #[tokio::test]
+    async fn run_question_emits_text_deltas_from_streaming_provider() {
+        let provider = StreamingProvider {
+            chunks: vec!["Hel", "lo"],
+        };
+
+        let session = PythonSession::initialize().expect("python");
+        let mut events = Vec::new();
+        let answer = run_question_with_events(
+            &provider,
+            &session,
+            "greet me",
+            &[],
+            &AgentConfig::default(),
+            &mut |event| events.push(event),
+        )
+        .await
+        .expect("answer");
+
+        assert_eq!(answer.text, "Hello");
+        let deltas = events
+            .iter()
+            .filter_map(|event| match event {
+                AgentProgressEvent::TextDelta { step, text } => Some((*step, text.as_str())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(deltas, vec![(1, "Hel"), (1, "lo")]);
     }
 
     #[tokio::test]
@@ -501,6 +917,7 @@ mod tests {
             &provider,
             &session,
             "say something",
+            &[],
             &AgentConfig::default(),
             &mut |_| {},
         )
@@ -508,7 +925,7 @@ mod tests {
         .expect("answer");
 
         assert_eq!(answer.text, "usable");
-        assert!(!answer.degraded);
+        assert_eq!(answer.degraded, None);
     }
 
     #[tokio::test]
@@ -549,6 +966,7 @@ mod tests {
             &provider,
             &session,
             "retry flow",
+            &[],
             &AgentConfig::default(),
             &mut |_| {},
         )
@@ -556,7 +974,135 @@ mod tests {
         .expect("answer");
 
         assert_eq!(answer.text, "recovered");
-        assert!(!answer.degraded);
+        assert_eq!(answer.degraded, None);
+    }
+
+    #[tokio::test]
+    async fn run_question_honors_configured_invalid_response_retries() {
+        let text_output = |text: &str| {
+            Ok(AssistantOutput {
+                usage: None,
+                candidates: vec![AssistantCandidate {
+                    message: AssistantMessage {
+                        role: AssistantRole::Model,
+                        parts: vec![AssistantPart::Text {
+                            text: text.to_string(),
+                            thought_signature: None,
+                        }],
+                    },
+                    finish_reason: Some("STOP".to_string()),
+                    safety_blocked: false,
+                }],
+            })
+        };
+        let provider = FakeProvider::new(vec![
+            text_output(" "),
+            text_output(""),
+            text_output("recovered"),
+        ]);
+
+        let session = PythonSession::initialize().expect("python");
+        let answer = run_question_with_events(
+            &provider,
+            &session,
+            "retry twice",
+            &[],
+            &AgentConfig {
+                invalid_response_retries: 2,
+                ..AgentConfig::default()
+            },
+            &mut |_| {},
+        )
+        .await
+        .expect("answer");
+
+        assert_eq!(answer.text, "recovered");
+        assert_eq!(answer.degraded, None);
+    }
+
+    #[tokio::test]
+    async fn run_question_retries_whole_turn_after_transport_error_when_enabled() {
+        let text_output = |text: &str| AssistantOutput {
+            usage: None,
+            candidates: vec![AssistantCandidate {
+                message: AssistantMessage {
+                    role: AssistantRole::Model,
+                    parts: vec![AssistantPart::Text {
+                        text: text.to_string(),
+                        thought_signature: None,
+                    }],
+                },
+                finish_reason: Some("STOP".to_string()),
+                safety_blocked: false,
+            }],
+        };
+        let session = PythonSession::initialize().expect("python");
+
+        let provider = FakeProvider::new(vec![
+            Err(LlmError::Transport("connection reset".to_string())),
+            Ok(text_output("after reconnect")),
+        ]);
+        let config = AgentConfig {
+            auto_retry_turn: true,
+            ..AgentConfig::default()
+        };
+        let mut events = Vec::new();
+        let answer = run_question_with_events(
+            &provider,
+            &session,
+            "retry turn",
+            &[],
+            &config,
+            &mut |event| events.push(event),
+        )
+        .await
+        .expect("answer");
+
+        assert_eq!(answer.text, "after reconnect");
+        assert_eq!(answer.degraded, None);
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(event, AgentProgressEvent::StepStarted { .. }))
+                .count(),
+            2
+        );
+        assert!(events.contains(&AgentProgressEvent::TurnRetried));
+        assert_eq!(*provider.reconnects.lock().expect("lock"), 1);
+        {
+            let inputs = provider.seen_inputs.lock().expect("lock");
+            assert_eq!(inputs[0].messages, inputs[1].messages);
+        }
+
+        let provider = FakeProvider::new(vec![
+            Err(LlmError::Timeout("request timed out".to_string())),
+            Ok(text_output("unused")),
+        ]);
+        let answer =
+            run_question_with_events(&provider, &session, "retry turn", &[], &config, &mut |_| {})
+                .await
+                .expect("answer");
+
+        assert_eq!(answer.degraded, Some(DegradedReason::RequestFailed));
+        assert_eq!(*provider.reconnects.lock().expect("lock"), 0);
+
+        let provider = FakeProvider::new(vec![
+            Err(LlmError::Transport("connection reset".to_string())),
+            Ok(text_output("unused")),
+        ]);
+        let answer = run_question_with_events(
+            &provider,
+            &session,
+            "retry turn",
+            &[],
+            &AgentConfig::default(),
+            &mut |_| {},
+        )
+        .await
+        .expect("answer");
+
+        assert_eq!(answer.degraded, Some(DegradedReason::RequestFailed));
+        assert!(answer.text.contains("connection reset"));
     }
 
     #[tokio::test]
@@ -607,6 +1153,7 @@ mod tests {
             &provider,
             &session,
             "run multiple",
+            &[],
             &AgentConfig::default(),
             &mut |_| {},
         )
@@ -614,7 +1161,7 @@ mod tests {
         .expect("answer");
 
         assert_eq!(answer.text, "multi ok");
-        assert!(!answer.degraded);
+        assert_eq!(answer.degraded, None);
     }
 
     #[tokio::test]
@@ -665,6 +1212,7 @@ mod tests {
             &provider,
             &session,
             "what globals?",
+            &[],
             &AgentConfig::default(),
             &mut |_| {},
         )
@@ -708,13 +1256,14 @@ mod tests {
             &provider,
             &session,
             "retry fail",
+            &[],
             &AgentConfig::default(),
             &mut |_| {},
         )
         .await
         .expect("answer");
 
-        assert!(answer.degraded);
+        assert_eq!(answer.degraded, Some(DegradedReason::InvalidResponse));
         assert!(answer.text.contains("invalid response repeatedly"));
     }
 
@@ -775,12 +1324,12 @@ mod tests {
         };
         let session = PythonSession::initialize().expect("python");
         let answer =
-            run_question_with_events(&provider, &session, "change f", &config, &mut |_| {})
+            run_question_with_events(&provider, &session, "change f", &[], &config, &mut |_| {})
                 .await
                 .expect("answer");
 
         assert_eq!(answer.text, "Redefine it: def f():\\n    return 43");
-        assert!(answer.degraded);
+        assert_eq!(answer.degraded, Some(DegradedReason::StepLimit));
 
         let inputs = provider.seen_inputs.lock().expect("lock");
         let last = inputs.last().expect("last input");
@@ -796,14 +1345,19 @@ mod tests {
             per_step_timeout_ms: 8_000,
             total_timeout_ms: 0,
             invalid_response_retries: 1,
+            auto_retry_turn: false,
+            allow_eval: true,
+            allow_apply: false,
+            chat_only: false,
+            system_note: None,
         };
         let session = PythonSession::initialize().expect("python");
         let answer =
-            run_question_with_events(&provider, &session, "change f", &config, &mut |_| {})
+            run_question_with_events(&provider, &session, "change f", &[], &config, &mut |_| {})
                 .await
                 .expect("answer");
 
-        assert!(answer.degraded);
+        assert_eq!(answer.degraded, Some(DegradedReason::StepLimit));
         assert!(answer.text.contains("step limit"));
         assert!(provider.seen_inputs.lock().expect("lock").is_empty());
     }
@@ -837,11 +1391,11 @@ mod tests {
         };
         let session = PythonSession::initialize().expect("python");
         let answer =
-            run_question_with_events(&provider, &session, "change f", &config, &mut |_| {})
+            run_question_with_events(&provider, &session, "change f", &[], &config, &mut |_| {})
                 .await
                 .expect("answer");
 
-        assert!(answer.degraded);
+        assert_eq!(answer.degraded, Some(DegradedReason::StepLimit));
         assert!(answer.text.contains("step limit"));
         assert_eq!(answer.token_usage.input_tokens, 11);
         assert_eq!(answer.token_usage.output_tokens, 3);
@@ -2,4 +2,7 @@ mod dispatch;
 mod loop_impl;
 mod prompt;
 
-pub use loop_impl::{AgentConfig, AgentProgressEvent, run_question_with_events};
+pub use dispatch::tool_declarations;
+pub use loop_impl::{
+    AgentConfig, AgentProgressEvent, DegradedReason, run_chat_question, run_question_with_events,
+};
@@ -7,3 +7,30 @@ Rules:
 4) If enough information is available, return a concise plain-text answer.
 5) If tool results include errors, adapt and continue when possible.
 6) Do not invent runtime values not returned by tool results."#;
+
+/// Sent in chat mode, where no tools are declared, so it has no rules about using them.
+pub const CHAT_SYSTEM_PROMPT: &str = r#"You are PyChat.ai assistant answering Python questions in chat mode.
+
+Rules:
+1) You cannot see or run the user's Python session; answer from the question and the conversation so far.
+2) Do not invent runtime values; when an answer depends on them, say what the user could run to check.
+3) Return a concise plain-text answer."#;
+
+const APPLY_CODE_RULE: &str = "7) apply_code(code) runs statements in the user's session and its changes persist. Use it only when the user asks you to change state, such as redefining a function to fix a bug, and say what you changed.";
+
+/// The agent system prompt, with the `apply_code` rule when that tool is enabled.
+pub fn agent_system_prompt(allow_apply: bool) -> String {
+    if allow_apply {
+        format!("{AGENT_SYSTEM_PROMPT}\n{APPLY_CODE_RULE}")
+    } else {
+        AGENT_SYSTEM_PROMPT.to_string()
+    }
+}
+
+/// Puts the note set with `/system` ahead of `prompt`.
+pub fn with_system_note(prompt: String, note: Option<&str>) -> String {
+    match note {
+        Some(note) => format!("{note}\n\n{prompt}"),
+        None => prompt,
+    }
+}
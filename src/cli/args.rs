@@ -15,6 +15,30 @@ pub struct CliArgs {
     /// Initialize embedded Python and exit without starting the REPL.
     #[arg(long)]
     pub smoke_python: bool,
+
+    /// Read input lines from stdin and print the timeline as plain text instead of the TUI.
+    #[arg(long)]
+    pub plain: bool,
+
+    /// Run this Python input, print its output and value, and exit without the TUI.
+    #[arg(long, value_name = "CODE", conflicts_with_all = ["ask", "plain"])]
+    pub eval: Option<String>,
+
+    /// Ask the assistant this question, print the answer, and exit without the TUI.
+    #[arg(long, value_name = "QUESTION", conflicts_with = "plain")]
+    pub ask: Option<String>,
+
+    /// Print the resolved configuration (API key masked) and exit without starting Python.
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Validate the config file, print OK or the first problem, and exit.
+    #[arg(long)]
+    pub check_config: bool,
+
+    /// Write assistant requests to the trace instead of sending them to Gemini.
+    #[arg(long)]
+    pub dry_run: bool,
 }
 
 #[cfg(test)]
@@ -27,6 +51,40 @@ mod tests {
         let args = CliArgs::try_parse_from(["pychat.ai"]).expect("should parse");
         assert_eq!(args.config, None);
         assert!(!args.smoke_python);
+        assert!(!args.plain);
+        assert!(!args.dry_run);
+        assert!(!args.print_config);
+        assert!(!args.check_config);
+        assert_eq!(args.eval, None);
+        assert_eq!(args.ask, None);
+    }
+
+    #[test]
+    fn parse_plain_flag() {
+        let args = CliArgs::try_parse_from(["pychat.ai", "--plain"]).expect("parse");
+        assert!(args.plain);
+    }
+
+    #[test]
+    fn parse_dry_run_flag() {
+        let args = CliArgs::try_parse_from(["pychat.ai", "--dry-run"]).expect("parse");
+        assert!(args.dry_run);
+    }
+
+    #[test]
+    fn parse_print_config_flag() {
+        let args = CliArgs::try_parse_from(["pychat.ai", "--print-config"]).expect("parse");
+        assert!(args.print_config);
+    }
+
+    #[test]
+    fn parse_one_shot_flags() {
+        let args = CliArgs::try_parse_from(["pychat.ai", "--eval", "df.shape"]).expect("parse");
+        assert_eq!(args.eval.as_deref(), Some("df.shape"));
+        let args = CliArgs::try_parse_from(["pychat.ai", "--ask", "what is x"]).expect("parse");
+        assert_eq!(args.ask.as_deref(), Some("what is x"));
+        CliArgs::try_parse_from(["pychat.ai", "--eval", "1", "--ask", "why"])
+            .expect_err("eval and ask conflict");
     }
 
     #[test]
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Clipboard tools tried in order: Wayland, X11, then macOS.
+const CLIPBOARD_COMMANDS: &[&[&str]] = &[
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["pbcopy"],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CopiedTo {
+    /// Copied with the named clipboard tool.
+    Clipboard(String),
+    /// No clipboard tool worked, so the text was written to this file instead.
+    File(PathBuf),
+}
+
+/// Copies `text` to the system clipboard, falling back to writing it to `fallback_path`.
+pub(crate) fn copy_text(text: &str, fallback_path: &Path) -> Result<CopiedTo> {
+    copy_text_with(CLIPBOARD_COMMANDS, text, fallback_path)
+}
+
+fn copy_text_with(commands: &[&[&str]], text: &str, fallback_path: &Path) -> Result<CopiedTo> {
+    for command in commands {
+        if pipe_to_command(command, text) {
+            return Ok(CopiedTo::Clipboard(command[0].to_string()));
+        }
+    }
+
+    fs::write(fallback_path, text)
+        .with_context(|| format!("failed to write {}", fallback_path.display()))?;
+    Ok(CopiedTo::File(fallback_path.to_path_buf()))
+}
+
+fn pipe_to_command(command: &[&str], text: &str) -> bool {
+    let Some((program, args)) = command.split_first() else {
+        return false;
+    };
+    let Ok(mut child) = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+
+    let written = child
+        .stdin
+        .take()
+        .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+    child.wait().is_ok_and(|status| status.success()) && written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CopiedTo, copy_text_with};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn copy_falls_back_to_file_when_no_clipboard_tool_works() {
+        let dir = tempdir().expect("tempdir");
+        let fallback = dir.path().join("trace-copy.txt");
+
+        let target = copy_text_with(
+            &[&["pychat-ai-missing-clipboard-tool"]],
+            "/tmp/traces/session.log",
+            &fallback,
+        )
+        .expect("copy");
+
+        assert_eq!(target, CopiedTo::File(fallback.clone()));
+        assert_eq!(
+            fs::read_to_string(fallback).expect("read fallback"),
+            "/tmp/traces/session.log"
+        );
+    }
+}
@@ -1,16 +1,137 @@
+use crate::config::{ThemePreset, TraceFormat};
+
+/// Highest limit `/recursionlimit` accepts; deeper Python recursion can overflow the
+/// native stack and crash the process instead of raising `RecursionError`.
+pub(crate) const MAX_RECURSION_LIMIT: u32 = 50_000;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum Command {
-    Help,
+    Help(Option<String>),
     Mode(Option<CommandMode>),
     Clear,
     History(Option<usize>),
+    Rerun(usize),
     Trace,
+    TraceFormat {
+        format: TraceFormat,
+    },
+    TraceCopy {
+        contents: bool,
+    },
+    Copy {
+        target: CopyTarget,
+    },
     Usage,
-    Inspect { expr: String },
-    LastError,
-    Include { path: String },
-    ShowSource { name: String },
+    Endpoint,
+    Tools,
+    Diagnostics,
+    System {
+        text: Option<String>,
+    },
+    Forget,
+    PingLlm,
+    Compare {
+        models: [String; 2],
+        question: String,
+    },
+    Edit,
+    ThemeSave {
+        preset: ThemePreset,
+    },
+    ConfigCheck {
+        path: Option<String>,
+    },
+    Inspect {
+        expr: String,
+        /// Prints single-line JSON regardless of `python.inspect_compact`.
+        compact: bool,
+    },
+    InspectToFile {
+        path: String,
+        expr: String,
+    },
+    InspectWithTimeout {
+        timeout_ms: u64,
+        expr: String,
+    },
+    Page {
+        expr: String,
+    },
+    Type {
+        expr: String,
+    },
+    Time {
+        expr: String,
+    },
+    Diff {
+        left: String,
+        right: String,
+    },
+    With {
+        name: String,
+        expr: String,
+        body: String,
+    },
+    Pip {
+        args: Vec<String>,
+        dry_run: bool,
+    },
+    LastError {
+        io: bool,
+    },
+    Last,
+    RawAnswer,
+    Include {
+        path: String,
+    },
+    ShowSource {
+        name: String,
+    },
     Steps(Option<bool>),
+    StepsVerbose,
+    StepsLog,
+    Save {
+        path: String,
+        force: bool,
+    },
+    ExportScript {
+        path: String,
+        outputs: bool,
+        skip_failed: bool,
+        force: bool,
+    },
+    Watch(WatchCommand),
+    SessionSave {
+        path: String,
+        force: bool,
+    },
+    SessionLoad {
+        path: String,
+    },
+    Reset {
+        all: bool,
+    },
+    Gc,
+    RecursionLimit(Option<u32>),
+    Globals {
+        pattern: Option<String>,
+    },
+    Tokens,
+    Themes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum WatchCommand {
+    Add { label: String, expr: String },
+    Remove { label: String },
+    List,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CopyTarget {
+    Last,
+    Answer,
+    TracePath,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -22,21 +143,317 @@ pub(crate) enum CommandMode {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct ParseError {
     message: String,
+    /// Closest known command name for a mistyped one, without the slash.
+    suggestion: Option<&'static str>,
 }
 
 impl ParseError {
     pub(crate) fn new(message: impl Into<String>) -> Self {
         Self {
             message: message.into(),
+            suggestion: None,
         }
     }
 
+    fn with_suggestion(mut self, suggestion: Option<&'static str>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+
     pub(crate) fn message(&self) -> &str {
         &self.message
     }
+
+    pub(crate) fn suggestion(&self) -> Option<&'static str> {
+        self.suggestion
+    }
 }
 
-pub(crate) const HELP_TEXT: &str = "Available commands:\n  /help                Show this command list\n  /mode [py|ai]        Show or switch active mode\n  /clear               Clear the timeline output\n  /history [n]         Show command/input history (or last n)\n  /trace               Show path to the current trace file\n  /usage               Show current session LLM token usage totals\n  /inspect <expr>      Inspect a Python expression as structured JSON\n  /last_error          Show the last Python exception traceback\n  /include <file.py>   Execute a Python file in the current session\n  /run <file>          Execute a file path exactly as provided\n  /show_source <name>  Show source code for a function/class/module name\n  /steps [on|off]      Show or hide assistant reasoning steps";
+pub(crate) const HELP_TEXT: &str = "Available commands:\n  /help [command]      Show this command list, or details for one command\n  /mode [py|ai]        Show or switch active mode\n  /clear               Clear the timeline output\n  /history [n]         Show command/input history (or last n)\n  /rerun <n>           Re-run history entry n (as numbered by /history)\n  /trace               Show path to the current trace file\n  /trace copy [--contents]  Copy the trace path (or whole file) to the clipboard\n  /trace format <text|jsonl>  Continue the trace in a new file in another format\n  /copy <last|answer|trace-path>  Copy the last output, last answer, or trace path\n  /usage               Show current session LLM token usage totals\n  /tokens              Show token usage per assistant turn and for the session\n  /endpoint            Show the Gemini endpoint URL (API key redacted)\n  /tools               List the assistant's tools and whether they are enabled\n  /diagnostics         Show version, platform, and settings for bug reports\n  /system [text]       Add an instruction to the assistant's system prompt (bare /system clears it)\n  /forget              Clear the assistant's memory of earlier questions\n  /ping-llm            Send a tiny prompt and show the round-trip time and tokens\n  /compare <a> <b>: <question>  Ask two models the same question and compare the answers\n  /edit                Compose the input in $EDITOR (also Ctrl-X Ctrl-E)\n  /inspect <expr>      Inspect a Python expression as structured JSON\n  /inspect --compact <expr>  Inspect as single-line JSON\n  /inspect --to <path> <expr>  Write the inspect JSON to a file\n  /inspect --timeout <ms> <expr>  Inspect with a one-off timeout (0 disables it)\n  /page <expr>         Show the value of an expression in a scrollable pager (Esc closes it)\n  /type <expr>         Show the type of a Python expression\n  /time <code>         Run Python input and show its wall-clock time\n  /diff <a> ; <b>      Show a line diff between the values of two expressions\n  /with <name>=<expr>: <body>  Run Python input with a temporary binding (globals unchanged)\n  /last_error [--io]   Show the last Python exception traceback (--io adds its output)\n  /last                Show the last evaluated value in full\n  /raw-answer          Show the last assistant answer as unrendered text\n  /include <file.py>   Execute a Python file in the current session\n  /pip [--dry-run] <args>  Run pip for the session's Python (e.g. /pip install requests)\n  /run <file>          Execute a file path exactly as provided\n  /show_source <name>  Show source code for a function/class/module name\n  /steps [on|off]      Show or hide assistant reasoning steps\n  /steps verbose       Show steps with detailed tool results\n  /steps-log           Show every tool step of the last assistant turn\n  /themes              Preview the built-in color themes\n  /theme save <preset>  Switch to a theme preset and save it in the config file\n  /config check [path]  Validate the config file (or path) and report the first problem\n  /watch <subcommand>  Track labeled expressions (add <label> = <expr>, remove <label>, list)\n  /save <path>         Save the timeline to a file (--force to overwrite)\n  /session save <path>  Save the whole timeline, mode and scroll position to a JSON file\n  /session load <path>  Restore a timeline saved with /session save\n  /export-script [--outputs] [--skip-failed] <path>  Write Python inputs to a script (--outputs adds them as comments)\n  /reset [--all]       Clear Python globals (--all also clears the timeline)\n  /gc                  Run the garbage collector and show object counts\n  /recursionlimit [n]  Show or set Python's recursion limit\n  /globals [pattern]   List Python globals with their types (glob filter, e.g. df*)";
+
+/// Detailed help for `/help <command>`, keyed by command name without the slash.
+const COMMAND_HELP: &[(&str, &str)] = &[
+    (
+        "help",
+        "/help [command]\n\
+         Shows the command list, or detailed usage for one command.\n\
+         Examples: /help, /help inspect",
+    ),
+    (
+        "mode",
+        "/mode [py|ai]\n\
+         Shows the current mode, or switches between Python and assistant mode.\n\
+         Examples: /mode, /mode py, /mode ai",
+    ),
+    ("clear", "/clear\nClears the timeline output."),
+    (
+        "history",
+        "/history [n]\n\
+         Shows the input history, or only the last n entries.\n\
+         Examples: /history, /history 20",
+    ),
+    (
+        "rerun",
+        "/rerun <n>\n\
+         Re-submits history entry n (numbered as in /history) in the current mode.\n\
+         Example: /rerun 3",
+    ),
+    (
+        "trace",
+        "/trace\n\
+         Prints the path of the current trace file.\n\
+         /trace copy [--contents]\n\
+         Copies the trace path (or, with --contents, the whole trace) to the clipboard.\n\
+         /trace format <text|jsonl>\n\
+         Continues the trace in a new file written in the given format.\n\
+         Examples: /trace copy, /trace format jsonl",
+    ),
+    (
+        "copy",
+        "/copy <last|answer|trace-path>\n\
+         Copies the last Python output, the last assistant answer, or the trace path.\n\
+         Example: /copy answer",
+    ),
+    (
+        "usage",
+        "/usage\nShows the LLM token usage totals for this session.",
+    ),
+    (
+        "tokens",
+        "/tokens\n\
+         Shows token usage for each assistant turn, followed by the session total.",
+    ),
+    (
+        "endpoint",
+        "/endpoint\n\
+         Prints the Gemini endpoint URL for the configured model, with the API key redacted.",
+    ),
+    (
+        "tools",
+        "/tools\n\
+         Lists the tools the assistant can call and whether each one is enabled.",
+    ),
+    (
+        "diagnostics",
+        "/diagnostics\n\
+         Prints the version, platform, Python, endpoint and assistant settings for bug reports.",
+    ),
+    (
+        "system",
+        "/system [text]\n\
+         Adds text to the assistant's system prompt for every following question.\n\
+         Bare /system clears it.\n\
+         Example: /system assume pandas is imported as pd",
+    ),
+    (
+        "forget",
+        "/forget\n\
+         Clears the earlier questions and answers the assistant remembers.",
+    ),
+    (
+        "ping-llm",
+        "/ping-llm\n\
+         Sends a one-word prompt to the model and shows the round-trip time and tokens.",
+    ),
+    (
+        "compare",
+        "/compare <model-a> <model-b>: <question>\n\
+         Asks both models the same question and shows each answer with its time and tokens.\n\
+         Example: /compare gemini-2.5-flash gemini-2.5-pro: what is in df?",
+    ),
+    (
+        "edit",
+        "/edit\n\
+         Composes the input in $VISUAL or $EDITOR (also Ctrl-X Ctrl-E).",
+    ),
+    (
+        "inspect",
+        "/inspect [--compact] <expr>\n\
+         Inspects a Python expression as structured JSON (--compact prints it on one line).\n\
+         /inspect --to <path> <expr>\n\
+         Writes the inspect JSON to a file and prints a one-line summary.\n\
+         /inspect --timeout <ms> <expr>\n\
+         Inspects with a one-off timeout; 0 disables it.\n\
+         Examples: /inspect my_var[0], /inspect --to /tmp/df.json df",
+    ),
+    (
+        "page",
+        "/page <expr>\n\
+         Shows the full value of an expression in a scrollable pager over the timeline.\n\
+         Up/Down, PgUp/PgDn, Home/End scroll; Esc or q closes it.\n\
+         Example: /page big_list",
+    ),
+    (
+        "type",
+        "/type <expr>\n\
+         Shows the type of a Python expression.\n\
+         Example: /type my_var",
+    ),
+    (
+        "time",
+        "/time <code>\n\
+         Runs Python input and then shows its wall-clock time.\n\
+         Example: /time sorted(data)",
+    ),
+    (
+        "diff",
+        "/diff <expr1> ; <expr2>\n\
+         Shows a line diff between the values of two expressions.\n\
+         Example: /diff expected ; actual",
+    ),
+    (
+        "with",
+        "/with <name>=<expr>: <body>\n\
+         Runs body with name bound to expr in a copy of the globals.\n\
+         Example: /with n=1000: sum(range(n))",
+    ),
+    (
+        "last_error",
+        "/last_error [--io]\n\
+         Shows the last Python exception traceback (--io first shows its output).",
+    ),
+    (
+        "last",
+        "/last\nShows the last evaluated Python value in full.",
+    ),
+    (
+        "raw-answer",
+        "/raw-answer\n\
+         Shows the last assistant answer as unrendered text.",
+    ),
+    (
+        "include",
+        "/include <file.py>\n\
+         Executes a Python file in the current session.\n\
+         Example: /include setup.py",
+    ),
+    (
+        "pip",
+        "/pip [--dry-run] <args>\n\
+         Runs pip for the session's Python; --dry-run only prints the command.\n\
+         Example: /pip install requests",
+    ),
+    (
+        "run",
+        "/run <file>\n\
+         Executes a file path exactly as provided, without requiring a .py extension.",
+    ),
+    (
+        "show_source",
+        "/show_source <name>\n\
+         Shows the source of a function, class or module.\n\
+         Example: /show_source my_module.my_function",
+    ),
+    (
+        "steps",
+        "/steps [on|off|verbose]\n\
+         Shows or hides assistant tool steps; verbose adds detailed tool results.\n\
+         Examples: /steps, /steps off, /steps verbose",
+    ),
+    (
+        "steps-log",
+        "/steps-log\n\
+         Shows every tool step of the last assistant turn.",
+    ),
+    ("themes", "/themes\nPreviews the built-in color themes."),
+    (
+        "theme",
+        "/theme save <default|light|high-contrast>\n\
+         Switches to a theme preset and saves it in the config file.",
+    ),
+    (
+        "config",
+        "/config check [path]\n\
+         Validates the config file (or path) and reports the first problem.\n\
+         Example: /config check project.toml",
+    ),
+    (
+        "watch",
+        "/watch add <label> = <expr>\n\
+         /watch remove <label>\n\
+         /watch list\n\
+         Tracks labeled expressions, re-evaluated after each Python submission.\n\
+         Example: /watch add ratio = a / b",
+    ),
+    (
+        "save",
+        "/save [--force] <path>\n\
+         Saves the timeline to a file (Markdown for .md paths).\n\
+         Examples: /save session.txt, /save --force notes.md",
+    ),
+    (
+        "session",
+        "/session save [--force] <path>\n\
+         Saves the whole timeline, the mode and the scroll position to a JSON file.\n\
+         /session load <path>\n\
+         Replaces the timeline with a saved one, to resume reviewing it later.\n\
+         Python globals and the assistant's memory are not saved.\n\
+         Examples: /session save review.json, /session load review.json",
+    ),
+    (
+        "export-script",
+        "/export-script [--outputs] [--skip-failed] [--force] <path>\n\
+         Writes the Python inputs to a script; --outputs adds them as comments.\n\
+         Example: /export-script --outputs session.py",
+    ),
+    (
+        "reset",
+        "/reset [--all]\n\
+         Clears the Python globals; --all also clears the timeline.",
+    ),
+    (
+        "gc",
+        "/gc\n\
+         Runs the garbage collector and shows object counts.",
+    ),
+    (
+        "recursionlimit",
+        "/recursionlimit [n]\n\
+         Shows Python's recursion limit, first setting it to n when given.\n\
+         Example: /recursionlimit 5000",
+    ),
+    (
+        "globals",
+        "/globals [pattern]\n\
+         Lists Python globals with their types, filtered by a glob pattern.\n\
+         Examples: /globals, /globals df*",
+    ),
+];
+
+/// Returns the known command name closest to a mistyped one, if any is within two edits.
+pub(crate) fn suggest_command(name: &str) -> Option<&'static str> {
+    let name = name.strip_prefix('/').unwrap_or(name).to_ascii_lowercase();
+    COMMAND_HELP
+        .iter()
+        .map(|(command, _)| (levenshtein(&name, command), *command))
+        .filter(|(distance, _)| *distance <= 2 && *distance < name.chars().count())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, command)| command)
+}
+
+/// Number of single-character insertions, deletions and substitutions between two strings.
+fn levenshtein(left: &str, right: &str) -> usize {
+    let right = right.chars().collect::<Vec<_>>();
+    let mut previous = (0..=right.len()).collect::<Vec<_>>();
+    for (i, left_ch) in left.chars().enumerate() {
+        let mut current = vec![i + 1; right.len() + 1];
+        for (j, right_ch) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left_ch != *right_ch);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[right.len()]
+}
+
+/// Returns the detailed help for a command name, with or without the leading slash.
+pub(crate) fn command_help(name: &str) -> Option<&'static str> {
+    let name = name.strip_prefix('/').unwrap_or(name).to_ascii_lowercase();
+    COMMAND_HELP
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, help)| *help)
+}
 
 pub(crate) fn parse_command(line: &str) -> Result<Command, ParseError> {
     if !line.starts_with('/') {
@@ -57,23 +474,62 @@ pub(crate) fn parse_command(line: &str) -> Result<Command, ParseError> {
     let rest = parts.next().map(str::trim).unwrap_or("");
 
     match name.as_str() {
-        "help" => expect_no_args(rest, Command::Help, "usage: /help"),
+        "help" => parse_help(rest),
         "mode" => parse_mode(rest),
         "clear" => expect_no_args(rest, Command::Clear, "usage: /clear"),
         "history" => parse_history(rest),
-        "trace" => expect_no_args(rest, Command::Trace, "usage: /trace"),
+        "rerun" => parse_rerun(rest),
+        "trace" => parse_trace(rest),
+        "copy" => parse_copy(rest),
         "usage" => expect_no_args(rest, Command::Usage, "usage: /usage"),
-        "inspect" => parse_required_text_arg(rest, "usage: /inspect <expr>")
-            .map(|expr| Command::Inspect { expr }),
-        "last_error" => expect_no_args(rest, Command::LastError, "usage: /last_error"),
+        "tokens" => expect_no_args(rest, Command::Tokens, "usage: /tokens"),
+        "themes" => expect_no_args(rest, Command::Themes, "usage: /themes"),
+        "theme" => parse_theme(rest),
+        "config" => parse_config(rest),
+        "endpoint" => expect_no_args(rest, Command::Endpoint, "usage: /endpoint"),
+        "tools" => expect_no_args(rest, Command::Tools, "usage: /tools"),
+        "diagnostics" => expect_no_args(rest, Command::Diagnostics, "usage: /diagnostics"),
+        "forget" => expect_no_args(rest, Command::Forget, "usage: /forget"),
+        "ping-llm" => expect_no_args(rest, Command::PingLlm, "usage: /ping-llm"),
+        "compare" => parse_compare(rest),
+        "edit" => expect_no_args(rest, Command::Edit, "usage: /edit"),
+        "system" => Ok(Command::System {
+            text: (!rest.is_empty()).then(|| rest.to_string()),
+        }),
+        "inspect" => parse_inspect(rest),
+        "page" => {
+            parse_required_text_arg(rest, "usage: /page <expr>").map(|expr| Command::Page { expr })
+        }
+        "type" => {
+            parse_required_text_arg(rest, "usage: /type <expr>").map(|expr| Command::Type { expr })
+        }
+        "time" => {
+            parse_required_text_arg(rest, "usage: /time <code>").map(|expr| Command::Time { expr })
+        }
+        "diff" => parse_diff(rest),
+        "with" => parse_with(rest),
+        "last_error" => parse_last_error(rest),
+        "last" => expect_no_args(rest, Command::Last, "usage: /last"),
+        "raw-answer" => expect_no_args(rest, Command::RawAnswer, "usage: /raw-answer"),
         "include" => parse_include(rest),
+        "pip" => parse_pip(rest),
         "run" => parse_run(rest),
         "show_source" => parse_required_text_arg(rest, "usage: /show_source <name>")
             .map(|name| Command::ShowSource { name }),
         "steps" => parse_steps(rest),
-        _ => Err(ParseError::new(format!(
-            "unknown command '/{name}'. Try /help"
-        ))),
+        "steps-log" => expect_no_args(rest, Command::StepsLog, "usage: /steps-log"),
+        "save" => parse_save(rest),
+        "session" => parse_session(rest),
+        "export-script" => parse_export_script(rest),
+        "watch" => parse_watch(rest),
+        "reset" => parse_reset(rest),
+        "gc" => expect_no_args(rest, Command::Gc, "usage: /gc"),
+        "recursionlimit" => parse_recursion_limit(rest),
+        "globals" => parse_globals(rest),
+        _ => Err(
+            ParseError::new(format!("unknown command '/{name}'. Try /help"))
+                .with_suggestion(suggest_command(&name)),
+        ),
     }
 }
 
@@ -89,6 +545,14 @@ fn expect_no_args(rest: &str, command: Command, usage: &str) -> Result<Command,
     }
 }
 
+fn parse_help(rest: &str) -> Result<Command, ParseError> {
+    match rest.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [] => Ok(Command::Help(None)),
+        [name] => Ok(Command::Help(Some(name.to_string()))),
+        _ => Err(ParseError::new("usage: /help [command]")),
+    }
+}
+
 fn parse_mode(rest: &str) -> Result<Command, ParseError> {
     if rest.is_empty() {
         return Ok(Command::Mode(None));
@@ -116,6 +580,223 @@ fn parse_history(rest: &str) -> Result<Command, ParseError> {
     Ok(Command::History(Some(value)))
 }
 
+fn parse_recursion_limit(rest: &str) -> Result<Command, ParseError> {
+    if rest.is_empty() {
+        return Ok(Command::RecursionLimit(None));
+    }
+
+    let value = rest
+        .parse::<u32>()
+        .map_err(|_| ParseError::new("usage: /recursionlimit [n]"))?;
+    if value == 0 || value > MAX_RECURSION_LIMIT {
+        return Err(ParseError::new(format!(
+            "usage: /recursionlimit [n] (n must be between 1 and {MAX_RECURSION_LIMIT})"
+        )));
+    }
+
+    Ok(Command::RecursionLimit(Some(value)))
+}
+
+fn parse_diff(rest: &str) -> Result<Command, ParseError> {
+    let Some((left, right)) = rest.split_once(';') else {
+        return Err(ParseError::new("usage: /diff <expr1> ; <expr2>"));
+    };
+    let (left, right) = (left.trim(), right.trim());
+    if left.is_empty() || right.is_empty() {
+        return Err(ParseError::new("usage: /diff <expr1> ; <expr2>"));
+    }
+    Ok(Command::Diff {
+        left: left.to_string(),
+        right: right.to_string(),
+    })
+}
+
+fn parse_with(rest: &str) -> Result<Command, ParseError> {
+    const USAGE: &str = "usage: /with <name>=<expr>: <body>";
+    let Some((name, binding)) = rest.split_once('=') else {
+        return Err(ParseError::new(USAGE));
+    };
+    let Some(colon) = find_top_level_colon(binding) else {
+        return Err(ParseError::new(USAGE));
+    };
+    let (name, expr, body) = (
+        name.trim(),
+        binding[..colon].trim(),
+        binding[colon + 1..].trim(),
+    );
+    if !is_identifier(name) || expr.is_empty() || body.is_empty() {
+        return Err(ParseError::new(USAGE));
+    }
+    Ok(Command::With {
+        name: name.to_string(),
+        expr: expr.to_string(),
+        body: body.to_string(),
+    })
+}
+
+fn parse_compare(rest: &str) -> Result<Command, ParseError> {
+    const USAGE: &str = "usage: /compare <model-a> <model-b>: <question>";
+    let Some((models, question)) = rest.split_once(':') else {
+        return Err(ParseError::new(USAGE));
+    };
+    let question = question.trim();
+    let models: Vec<&str> = models.split_whitespace().collect();
+    let [model_a, model_b] = models.as_slice() else {
+        return Err(ParseError::new(USAGE));
+    };
+    if question.is_empty() {
+        return Err(ParseError::new(USAGE));
+    }
+    Ok(Command::Compare {
+        models: [model_a.to_string(), model_b.to_string()],
+        question: question.to_string(),
+    })
+}
+
+/// Finds the first `:` outside brackets and string literals, so slices and dict
+/// literals can appear in the bound expression.
+fn find_top_level_colon(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (pos, ch) in text.char_indices() {
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == open {
+                quote = None;
+            }
+            continue;
+        }
+        match ch {
+            '\'' | '"' => quote = Some(ch),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 => return Some(pos),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_last_error(rest: &str) -> Result<Command, ParseError> {
+    match rest {
+        "" => Ok(Command::LastError { io: false }),
+        "--io" => Ok(Command::LastError { io: true }),
+        _ => Err(ParseError::new("usage: /last_error [--io]")),
+    }
+}
+
+fn parse_trace(rest: &str) -> Result<Command, ParseError> {
+    match rest.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [] => Ok(Command::Trace),
+        ["copy"] => Ok(Command::TraceCopy { contents: false }),
+        ["copy", "--contents"] => Ok(Command::TraceCopy { contents: true }),
+        ["format", format] => format
+            .parse()
+            .map(|format| Command::TraceFormat { format })
+            .map_err(|_| ParseError::new("usage: /trace format <text|jsonl>")),
+        _ => Err(ParseError::new(
+            "usage: /trace [copy [--contents] | format <text|jsonl>]",
+        )),
+    }
+}
+
+fn parse_theme(rest: &str) -> Result<Command, ParseError> {
+    let usage = || ParseError::new("usage: /theme save <default|light|high-contrast>");
+    match rest.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["save", preset] => preset
+            .parse()
+            .map(|preset| Command::ThemeSave { preset })
+            .map_err(|_| usage()),
+        _ => Err(usage()),
+    }
+}
+
+fn parse_config(rest: &str) -> Result<Command, ParseError> {
+    let mut parts = rest.splitn(2, char::is_whitespace);
+    match (parts.next(), parts.next().map(str::trim)) {
+        (Some("check"), path) => Ok(Command::ConfigCheck {
+            path: path.filter(|path| !path.is_empty()).map(str::to_string),
+        }),
+        _ => Err(ParseError::new("usage: /config check [path]")),
+    }
+}
+
+fn parse_copy(rest: &str) -> Result<Command, ParseError> {
+    let target = match rest {
+        "last" => CopyTarget::Last,
+        "answer" => CopyTarget::Answer,
+        "trace-path" => CopyTarget::TracePath,
+        _ => return Err(ParseError::new("usage: /copy <last|answer|trace-path>")),
+    };
+    Ok(Command::Copy { target })
+}
+
+fn parse_rerun(rest: &str) -> Result<Command, ParseError> {
+    match rest.parse::<usize>() {
+        Ok(value) if value >= 1 => Ok(Command::Rerun(value)),
+        _ => Err(ParseError::new("usage: /rerun <n> (n must be >= 1)")),
+    }
+}
+
+fn parse_inspect(rest: &str) -> Result<Command, ParseError> {
+    if let Some(args) = rest.strip_prefix("--timeout") {
+        return parse_inspect_with_timeout(args);
+    }
+    if let Some(args) = rest.strip_prefix("--compact") {
+        return match args.strip_prefix(char::is_whitespace).map(str::trim) {
+            Some(expr) if !expr.is_empty() => Ok(Command::Inspect {
+                expr: expr.to_string(),
+                compact: true,
+            }),
+            _ => Err(ParseError::new("usage: /inspect --compact <expr>")),
+        };
+    }
+    let Some(args) = rest.strip_prefix("--to") else {
+        return parse_required_text_arg(rest, "usage: /inspect <expr>").map(|expr| {
+            Command::Inspect {
+                expr,
+                compact: false,
+            }
+        });
+    };
+
+    const USAGE: &str = "usage: /inspect --to <path> <expr>";
+    if !args.starts_with(char::is_whitespace) {
+        return Err(ParseError::new(USAGE));
+    }
+    let mut parts = args.trim_start().splitn(2, char::is_whitespace);
+    let path = parts.next().unwrap_or("");
+    let expr = parts.next().map(str::trim).unwrap_or("");
+    if path.is_empty() || expr.is_empty() {
+        return Err(ParseError::new(USAGE));
+    }
+    Ok(Command::InspectToFile {
+        path: path.to_string(),
+        expr: expr.to_string(),
+    })
+}
+
+fn parse_inspect_with_timeout(args: &str) -> Result<Command, ParseError> {
+    const USAGE: &str = "usage: /inspect --timeout <ms> <expr>";
+    if !args.starts_with(char::is_whitespace) {
+        return Err(ParseError::new(USAGE));
+    }
+    let mut parts = args.trim_start().splitn(2, char::is_whitespace);
+    let timeout_ms = parts.next().and_then(|value| value.parse::<u64>().ok());
+    let expr = parts.next().map(str::trim).unwrap_or("");
+    match timeout_ms {
+        Some(timeout_ms) if !expr.is_empty() => Ok(Command::InspectWithTimeout {
+            timeout_ms,
+            expr: expr.to_string(),
+        }),
+        _ => Err(ParseError::new(USAGE)),
+    }
+}
+
 fn parse_include(rest: &str) -> Result<Command, ParseError> {
     if rest.is_empty() {
         return Err(ParseError::new(
@@ -130,6 +811,22 @@ fn parse_include(rest: &str) -> Result<Command, ParseError> {
     Ok(Command::Include { path })
 }
 
+fn parse_pip(rest: &str) -> Result<Command, ParseError> {
+    let mut dry_run = false;
+    let mut args = Vec::new();
+    for arg in rest.split_whitespace() {
+        if arg == "--dry-run" && !dry_run {
+            dry_run = true;
+        } else {
+            args.push(arg.to_string());
+        }
+    }
+    if args.is_empty() {
+        return Err(ParseError::new("usage: /pip [--dry-run] <args>"));
+    }
+    Ok(Command::Pip { args, dry_run })
+}
+
 fn parse_run(rest: &str) -> Result<Command, ParseError> {
     if rest.is_empty() {
         return Err(ParseError::new("missing file argument. usage: /run <file>"));
@@ -148,10 +845,135 @@ fn parse_steps(rest: &str) -> Result<Command, ParseError> {
     match rest {
         "on" => Ok(Command::Steps(Some(true))),
         "off" => Ok(Command::Steps(Some(false))),
-        _ => Err(ParseError::new("usage: /steps [on|off]")),
+        "verbose" => Ok(Command::StepsVerbose),
+        _ => Err(ParseError::new("usage: /steps [on|off|verbose]")),
     }
 }
 
+fn parse_globals(rest: &str) -> Result<Command, ParseError> {
+    if rest.is_empty() {
+        return Ok(Command::Globals { pattern: None });
+    }
+    if rest.contains(char::is_whitespace) {
+        return Err(ParseError::new("usage: /globals [pattern]"));
+    }
+    Ok(Command::Globals {
+        pattern: Some(rest.to_string()),
+    })
+}
+
+fn parse_reset(rest: &str) -> Result<Command, ParseError> {
+    match rest {
+        "" => Ok(Command::Reset { all: false }),
+        "--all" => Ok(Command::Reset { all: true }),
+        _ => Err(ParseError::new("usage: /reset [--all]")),
+    }
+}
+
+fn parse_save(rest: &str) -> Result<Command, ParseError> {
+    let (path, force) =
+        parse_forced_path(rest).ok_or_else(|| ParseError::new("usage: /save [--force] <path>"))?;
+    Ok(Command::Save { path, force })
+}
+
+/// Splits a standalone `--force` word, before or after it, from a required path.
+fn parse_forced_path(rest: &str) -> Option<(String, bool)> {
+    let (path, force) = match rest.split_once(char::is_whitespace) {
+        Some(("--force", path)) => (path.trim(), true),
+        _ => match rest.rsplit_once(char::is_whitespace) {
+            Some((path, "--force")) => (path.trim(), true),
+            _ => (rest, false),
+        },
+    };
+
+    if path.is_empty() || path.starts_with("--") {
+        return None;
+    }
+    Some((path.to_string(), force))
+}
+
+fn parse_session(rest: &str) -> Result<Command, ParseError> {
+    const USAGE: &str = "usage: /session save [--force] <path> | /session load <path>";
+    let (subcommand, args) = rest
+        .split_once(char::is_whitespace)
+        .map(|(subcommand, args)| (subcommand, args.trim()))
+        .unwrap_or((rest, ""));
+    match subcommand {
+        "save" => parse_forced_path(args)
+            .map(|(path, force)| Command::SessionSave { path, force })
+            .ok_or_else(|| ParseError::new(USAGE)),
+        "load" if !args.is_empty() => Ok(Command::SessionLoad {
+            path: args.to_string(),
+        }),
+        _ => Err(ParseError::new(USAGE)),
+    }
+}
+
+fn parse_export_script(rest: &str) -> Result<Command, ParseError> {
+    const USAGE: &str = "usage: /export-script [--outputs] [--skip-failed] [--force] <path>";
+    let (mut outputs, mut skip_failed, mut force) = (false, false, false);
+    let mut path = rest;
+    while let Some(flag) = path
+        .split_whitespace()
+        .next()
+        .filter(|w| w.starts_with("--"))
+    {
+        match flag {
+            "--outputs" => outputs = true,
+            "--skip-failed" => skip_failed = true,
+            "--force" => force = true,
+            _ => return Err(ParseError::new(USAGE)),
+        }
+        path = path.trim_start()[flag.len()..].trim_start();
+    }
+
+    if path.is_empty() {
+        return Err(ParseError::new(USAGE));
+    }
+    Ok(Command::ExportScript {
+        path: path.to_string(),
+        outputs,
+        skip_failed,
+        force,
+    })
+}
+
+fn parse_watch(rest: &str) -> Result<Command, ParseError> {
+    const USAGE: &str = "usage: /watch add <label> = <expr> | /watch remove <label> | /watch list";
+    let mut parts = rest.splitn(2, char::is_whitespace);
+    let subcommand = parts.next().unwrap_or("");
+    let args = parts.next().map(str::trim).unwrap_or("");
+
+    match subcommand {
+        "add" => {
+            let Some((label, expr)) = args.split_once('=') else {
+                return Err(ParseError::new(USAGE));
+            };
+            let (label, expr) = (label.trim(), expr.trim());
+            if !is_identifier(label) || expr.is_empty() {
+                return Err(ParseError::new(USAGE));
+            }
+            Ok(Command::Watch(WatchCommand::Add {
+                label: label.to_string(),
+                expr: expr.to_string(),
+            }))
+        }
+        "remove" if is_identifier(args) => Ok(Command::Watch(WatchCommand::Remove {
+            label: args.to_string(),
+        })),
+        "list" if args.is_empty() => Ok(Command::Watch(WatchCommand::List)),
+        _ => Err(ParseError::new(USAGE)),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|ch| ch.is_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
 fn parse_required_text_arg(rest: &str, usage: &str) -> Result<String, ParseError> {
     if rest.is_empty() {
         return Err(ParseError::new(usage));
@@ -161,37 +983,169 @@ fn parse_required_text_arg(rest: &str, usage: &str) -> Result<String, ParseError
 
 #[cfg(test)]
 mod tests {
-    use super::{Command, CommandMode, HELP_TEXT, is_command_line, parse_command};
+    use super::{
+        Command, CommandMode, CopyTarget, HELP_TEXT, WatchCommand, command_help, is_command_line,
+        levenshtein, parse_command, suggest_command,
+    };
+    use crate::config::{ThemePreset, TraceFormat};
 
     #[test]
     fn help_text_lists_all_supported_commands() {
         for needle in [
-            "/help",
+            "/help [command]",
             "/mode [py|ai]",
             "/clear",
             "/history [n]",
+            "/rerun <n>",
             "/trace",
+            "/trace copy [--contents]",
+            "/trace format <text|jsonl>",
+            "/copy <last|answer|trace-path>",
             "/usage",
+            "/tokens",
+            "/endpoint",
+            "/tools",
+            "/diagnostics",
+            "/system [text]",
+            "/forget",
+            "/ping-llm",
+            "/compare <a> <b>: <question>",
+            "/edit",
             "/inspect <expr>",
-            "/last_error",
+            "/inspect --compact <expr>",
+            "/inspect --to <path> <expr>",
+            "/inspect --timeout <ms> <expr>",
+            "/page <expr>",
+            "/type <expr>",
+            "/time <code>",
+            "/diff <a> ; <b>",
+            "/with <name>=<expr>: <body>",
+            "/last_error [--io]",
+            "/last",
+            "/raw-answer",
             "/include <file.py>",
+            "/pip [--dry-run] <args>",
             "/run <file>",
             "/show_source <name>",
             "/steps [on|off]",
+            "/steps verbose",
+            "/steps-log",
+            "/themes",
+            "/theme save <preset>",
+            "/config check [path]",
+            "/watch <subcommand>",
+            "/save <path>",
+            "/session save <path>",
+            "/session load <path>",
+            "/export-script",
+            "/reset [--all]",
+            "/gc",
+            "/recursionlimit [n]",
+            "/globals [pattern]",
         ] {
             assert!(HELP_TEXT.contains(needle), "missing help entry: {needle}");
         }
     }
 
+    #[test]
+    fn command_help_covers_every_listed_command() {
+        for line in HELP_TEXT.lines().skip(1) {
+            let name = line.split_whitespace().next().expect("command");
+            let help = command_help(name).unwrap_or_else(|| panic!("missing help for {name}"));
+            assert!(help.starts_with(name), "help for {name} starts with {help}");
+        }
+        assert_eq!(command_help("INSPECT"), command_help("/inspect"));
+        assert_eq!(command_help("bogus"), None);
+    }
+
+    #[test]
+    fn parse_help_takes_an_optional_command_name() {
+        assert_eq!(
+            parse_command("/help inspect").expect("help inspect"),
+            Command::Help(Some("inspect".to_string()))
+        );
+        assert_eq!(
+            parse_command("/help inspect type")
+                .expect_err("two names")
+                .message(),
+            "usage: /help [command]"
+        );
+    }
+
     #[test]
     fn parse_simple_commands() {
-        assert_eq!(parse_command("/help").expect("help"), Command::Help);
+        assert_eq!(parse_command("/help").expect("help"), Command::Help(None));
         assert_eq!(parse_command("/clear").expect("clear"), Command::Clear);
         assert_eq!(parse_command("/trace").expect("trace"), Command::Trace);
         assert_eq!(parse_command("/usage").expect("usage"), Command::Usage);
+        assert_eq!(parse_command("/gc").expect("gc"), Command::Gc);
+        assert_eq!(parse_command("/tokens").expect("tokens"), Command::Tokens);
+        assert_eq!(parse_command("/themes").expect("themes"), Command::Themes);
+        assert_eq!(
+            parse_command("/theme save light").expect("theme save"),
+            Command::ThemeSave {
+                preset: ThemePreset::Light
+            }
+        );
+        assert_eq!(
+            parse_command("/config check").expect("config check"),
+            Command::ConfigCheck { path: None }
+        );
+        assert_eq!(
+            parse_command("/config check configs/my config.toml").expect("config check path"),
+            Command::ConfigCheck {
+                path: Some("configs/my config.toml".to_string())
+            }
+        );
+        assert_eq!(
+            parse_command("/config")
+                .expect_err("no subcommand")
+                .message(),
+            "usage: /config check [path]"
+        );
+        for invalid in ["/theme save neon", "/theme light", "/theme"] {
+            assert_eq!(
+                parse_command(invalid).expect_err("invalid theme").message(),
+                "usage: /theme save <default|light|high-contrast>"
+            );
+        }
+        assert_eq!(parse_command("/tools").expect("tools"), Command::Tools);
+        assert_eq!(
+            parse_command("/diagnostics").expect("diagnostics"),
+            Command::Diagnostics
+        );
+        assert_eq!(
+            parse_command("/system  assume pandas is pd ").expect("system"),
+            Command::System {
+                text: Some("assume pandas is pd".to_string())
+            }
+        );
+        assert_eq!(
+            parse_command("/system").expect("bare system"),
+            Command::System { text: None }
+        );
+        assert_eq!(parse_command("/forget").expect("forget"), Command::Forget);
+        assert_eq!(
+            parse_command("/ping-llm").expect("ping-llm"),
+            Command::PingLlm
+        );
+        assert_eq!(parse_command("/edit").expect("edit"), Command::Edit);
+        assert_eq!(
+            parse_command("/endpoint").expect("endpoint"),
+            Command::Endpoint
+        );
         assert_eq!(
             parse_command("/last_error").expect("last_error"),
-            Command::LastError
+            Command::LastError { io: false }
+        );
+        assert_eq!(
+            parse_command("/last_error --io").expect("last_error --io"),
+            Command::LastError { io: true }
+        );
+        assert_eq!(parse_command("/last").expect("last"), Command::Last);
+        assert_eq!(
+            parse_command("/raw-answer").expect("raw-answer"),
+            Command::RawAnswer
         );
     }
 
@@ -220,14 +1174,181 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_recursion_limit_bounds_n() {
+        assert_eq!(
+            parse_command("/recursionlimit").expect("recursionlimit"),
+            Command::RecursionLimit(None)
+        );
+        assert_eq!(
+            parse_command("/recursionlimit 5000").expect("recursionlimit 5000"),
+            Command::RecursionLimit(Some(5000))
+        );
+        assert_eq!(
+            parse_command("/recursionlimit deep")
+                .expect_err("not a number")
+                .message(),
+            "usage: /recursionlimit [n]"
+        );
+        for invalid in ["/recursionlimit 0", "/recursionlimit 50001"] {
+            assert_eq!(
+                parse_command(invalid).expect_err(invalid).message(),
+                "usage: /recursionlimit [n] (n must be between 1 and 50000)"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_trace_copy_variants() {
+        assert_eq!(parse_command("/trace").expect("trace"), Command::Trace);
+        assert_eq!(
+            parse_command("/trace copy").expect("trace copy"),
+            Command::TraceCopy { contents: false }
+        );
+        assert_eq!(
+            parse_command("/trace copy --contents").expect("trace copy contents"),
+            Command::TraceCopy { contents: true }
+        );
+        assert_eq!(
+            parse_command("/trace paste")
+                .expect_err("invalid trace")
+                .message(),
+            "usage: /trace [copy [--contents] | format <text|jsonl>]"
+        );
+        assert_eq!(
+            parse_command("/trace format jsonl").expect("trace format"),
+            Command::TraceFormat {
+                format: TraceFormat::Jsonl
+            }
+        );
+        assert_eq!(
+            parse_command("/trace format yaml")
+                .expect_err("invalid trace format")
+                .message(),
+            "usage: /trace format <text|jsonl>"
+        );
+    }
+
+    #[test]
+    fn parse_copy_targets() {
+        assert_eq!(
+            parse_command("/copy last").expect("copy last"),
+            Command::Copy {
+                target: CopyTarget::Last
+            }
+        );
+        assert_eq!(
+            parse_command("/copy answer").expect("copy answer"),
+            Command::Copy {
+                target: CopyTarget::Answer
+            }
+        );
+        assert_eq!(
+            parse_command("/copy trace-path").expect("copy trace-path"),
+            Command::Copy {
+                target: CopyTarget::TracePath
+            }
+        );
+        assert_eq!(
+            parse_command("/copy")
+                .expect_err("missing target")
+                .message(),
+            "usage: /copy <last|answer|trace-path>"
+        );
+    }
+
+    #[test]
+    fn parse_rerun_requires_positive_index() {
+        assert_eq!(
+            parse_command("/rerun 3").expect("rerun 3"),
+            Command::Rerun(3)
+        );
+        for line in ["/rerun", "/rerun 0", "/rerun x"] {
+            assert_eq!(
+                parse_command(line).expect_err("invalid rerun").message(),
+                "usage: /rerun <n> (n must be >= 1)"
+            );
+        }
+    }
+
     #[test]
     fn parse_inspect_source_and_include_arguments() {
         assert_eq!(
             parse_command("/inspect x[0]").expect("inspect"),
             Command::Inspect {
-                expr: "x[0]".to_string()
+                expr: "x[0]".to_string(),
+                compact: false,
+            }
+        );
+        assert_eq!(
+            parse_command("/inspect --compact x[0]").expect("compact inspect"),
+            Command::Inspect {
+                expr: "x[0]".to_string(),
+                compact: true,
+            }
+        );
+        assert_eq!(
+            parse_command("/inspect --compact")
+                .expect_err("missing compact expr")
+                .message(),
+            "usage: /inspect --compact <expr>"
+        );
+        assert_eq!(
+            parse_command("/inspect --to out.json data[0]").expect("inspect to file"),
+            Command::InspectToFile {
+                path: "out.json".to_string(),
+                expr: "data[0]".to_string()
+            }
+        );
+        assert_eq!(
+            parse_command("/inspect --to out.json")
+                .expect_err("missing inspect expr")
+                .message(),
+            "usage: /inspect --to <path> <expr>"
+        );
+        assert_eq!(
+            parse_command("/inspect --timeout 5000 data[0]").expect("inspect with timeout"),
+            Command::InspectWithTimeout {
+                timeout_ms: 5000,
+                expr: "data[0]".to_string()
+            }
+        );
+        assert_eq!(
+            parse_command("/inspect --timeout soon data[0]")
+                .expect_err("non-numeric timeout")
+                .message(),
+            "usage: /inspect --timeout <ms> <expr>"
+        );
+        assert_eq!(
+            parse_command("/page list(range(100))").expect("page"),
+            Command::Page {
+                expr: "list(range(100))".to_string()
             }
         );
+        assert_eq!(
+            parse_command("/page")
+                .expect_err("missing page expr")
+                .message(),
+            "usage: /page <expr>"
+        );
+        assert_eq!(
+            parse_command("/type {}").expect("type"),
+            Command::Type {
+                expr: "{}".to_string()
+            }
+        );
+        assert_eq!(
+            parse_command("/time sum(range(10))").expect("time"),
+            Command::Time {
+                expr: "sum(range(10))".to_string()
+            }
+        );
+        assert_eq!(
+            parse_command("/time")
+                .expect_err("missing time code")
+                .message(),
+            "usage: /time <code>"
+        );
         assert_eq!(
             parse_command("/show_source my_fn").expect("show_source"),
             Command::ShowSource {
@@ -254,6 +1375,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_with_splits_binding_and_body() {
+        assert_eq!(
+            parse_command("/with x = d[1:3]: {'k': x}[\"k\"]").expect("with"),
+            Command::With {
+                name: "x".to_string(),
+                expr: "d[1:3]".to_string(),
+                body: "{'k': x}[\"k\"]".to_string()
+            }
+        );
+        assert_eq!(
+            parse_command("/with s='a:b': s.split(':')").expect("with string colon"),
+            Command::With {
+                name: "s".to_string(),
+                expr: "'a:b'".to_string(),
+                body: "s.split(':')".to_string()
+            }
+        );
+        for invalid in [
+            "/with",
+            "/with x: 1",
+            "/with x=1",
+            "/with 1x=2: x",
+            "/with x=: x",
+        ] {
+            assert_eq!(
+                parse_command(invalid).expect_err(invalid).message(),
+                "usage: /with <name>=<expr>: <body>"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_compare_splits_models_and_question() {
+        assert_eq!(
+            parse_command("/compare  flash  pro : what is df: a frame?").expect("compare"),
+            Command::Compare {
+                models: ["flash".to_string(), "pro".to_string()],
+                question: "what is df: a frame?".to_string()
+            }
+        );
+        for invalid in [
+            "/compare",
+            "/compare flash pro what",
+            "/compare flash: what",
+            "/compare a b c: what",
+            "/compare flash pro:  ",
+        ] {
+            assert_eq!(
+                parse_command(invalid).expect_err(invalid).message(),
+                "usage: /compare <model-a> <model-b>: <question>"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_pip_arguments_and_dry_run_flag() {
+        assert_eq!(
+            parse_command("/pip install requests==2.31").expect("pip"),
+            Command::Pip {
+                args: vec!["install".to_string(), "requests==2.31".to_string()],
+                dry_run: false
+            }
+        );
+        assert_eq!(
+            parse_command("/pip --dry-run install  rich").expect("pip dry run"),
+            Command::Pip {
+                args: vec!["install".to_string(), "rich".to_string()],
+                dry_run: true
+            }
+        );
+        assert_eq!(
+            parse_command("/pip --dry-run")
+                .expect_err("missing pip args")
+                .message(),
+            "usage: /pip [--dry-run] <args>"
+        );
+    }
+
     #[test]
     fn parse_steps_optional_state() {
         assert_eq!(
@@ -268,6 +1468,208 @@ mod tests {
             parse_command("/steps off").expect("steps off"),
             Command::Steps(Some(false))
         );
+        assert_eq!(
+            parse_command("/steps verbose").expect("steps verbose"),
+            Command::StepsVerbose
+        );
+        assert_eq!(
+            parse_command("/steps-log").expect("steps-log"),
+            Command::StepsLog
+        );
+    }
+
+    #[test]
+    fn parse_globals_with_optional_pattern() {
+        assert_eq!(
+            parse_command("/globals").expect("globals"),
+            Command::Globals { pattern: None }
+        );
+        assert_eq!(
+            parse_command("/globals df*").expect("globals pattern"),
+            Command::Globals {
+                pattern: Some("df*".to_string())
+            }
+        );
+        assert_eq!(
+            parse_command("/globals a b")
+                .expect_err("too many patterns")
+                .message(),
+            "usage: /globals [pattern]"
+        );
+    }
+
+    #[test]
+    fn parse_reset_with_optional_all_flag() {
+        assert_eq!(
+            parse_command("/reset").expect("reset"),
+            Command::Reset { all: false }
+        );
+        assert_eq!(
+            parse_command("/reset --all").expect("reset all"),
+            Command::Reset { all: true }
+        );
+        assert_eq!(
+            parse_command("/reset now")
+                .expect_err("unexpected reset arg")
+                .message(),
+            "usage: /reset [--all]"
+        );
+    }
+
+    #[test]
+    fn parse_export_script_flags_and_path() {
+        assert_eq!(
+            parse_command("/export-script session.py").expect("export"),
+            Command::ExportScript {
+                path: "session.py".to_string(),
+                outputs: false,
+                skip_failed: false,
+                force: false
+            }
+        );
+        assert_eq!(
+            parse_command("/export-script --skip-failed --outputs out dir/s.py").expect("flags"),
+            Command::ExportScript {
+                path: "out dir/s.py".to_string(),
+                outputs: true,
+                skip_failed: true,
+                force: false
+            }
+        );
+        assert_eq!(
+            parse_command("/export-script --outputs")
+                .expect_err("missing path")
+                .message(),
+            "usage: /export-script [--outputs] [--skip-failed] [--force] <path>"
+        );
+        assert!(parse_command("/export-script --all s.py").is_err());
+    }
+
+    #[test]
+    fn parse_save_path_and_force_flag() {
+        assert_eq!(
+            parse_command("/save transcript.md").expect("save"),
+            Command::Save {
+                path: "transcript.md".to_string(),
+                force: false
+            }
+        );
+        assert_eq!(
+            parse_command("/save --force out.txt").expect("save force"),
+            Command::Save {
+                path: "out.txt".to_string(),
+                force: true
+            }
+        );
+        assert_eq!(
+            parse_command("/save out.txt --force").expect("save trailing force"),
+            Command::Save {
+                path: "out.txt".to_string(),
+                force: true
+            }
+        );
+        assert_eq!(
+            parse_command("/save notes--force").expect("save path ending in --force"),
+            Command::Save {
+                path: "notes--force".to_string(),
+                force: false
+            }
+        );
+        assert_eq!(
+            parse_command("/save --forcefoo.md")
+                .expect_err("flag-like save path")
+                .message(),
+            "usage: /save [--force] <path>"
+        );
+        assert_eq!(
+            parse_command("/save --force")
+                .expect_err("missing save path")
+                .message(),
+            "usage: /save [--force] <path>"
+        );
+        assert_eq!(
+            parse_command("/save")
+                .expect_err("missing save path")
+                .message(),
+            "usage: /save [--force] <path>"
+        );
+    }
+
+    #[test]
+    fn parse_session_save_and_load() {
+        assert_eq!(
+            parse_command("/session save review.json").expect("session save"),
+            Command::SessionSave {
+                path: "review.json".to_string(),
+                force: false
+            }
+        );
+        assert_eq!(
+            parse_command("/session save review.json --force").expect("session save force"),
+            Command::SessionSave {
+                path: "review.json".to_string(),
+                force: true
+            }
+        );
+        assert_eq!(
+            parse_command("/session load review.json").expect("session load"),
+            Command::SessionLoad {
+                path: "review.json".to_string()
+            }
+        );
+        for line in [
+            "/session",
+            "/session load",
+            "/session save --force",
+            "/session open x",
+        ] {
+            assert_eq!(
+                parse_command(line)
+                    .expect_err("bad session command")
+                    .message(),
+                "usage: /session save [--force] <path> | /session load <path>",
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_watch_subcommands() {
+        assert_eq!(
+            parse_command("/watch add ratio = a / b").expect("watch add"),
+            Command::Watch(WatchCommand::Add {
+                label: "ratio".to_string(),
+                expr: "a / b".to_string()
+            })
+        );
+        assert_eq!(
+            parse_command("/watch add same = a == b").expect("watch add comparison"),
+            Command::Watch(WatchCommand::Add {
+                label: "same".to_string(),
+                expr: "a == b".to_string()
+            })
+        );
+        assert_eq!(
+            parse_command("/watch remove ratio").expect("watch remove"),
+            Command::Watch(WatchCommand::Remove {
+                label: "ratio".to_string()
+            })
+        );
+        assert_eq!(
+            parse_command("/watch list").expect("watch list"),
+            Command::Watch(WatchCommand::List)
+        );
+        for invalid in [
+            "/watch",
+            "/watch add ratio",
+            "/watch add 1x = a",
+            "/watch remove",
+        ] {
+            assert_eq!(
+                parse_command(invalid).expect_err("invalid watch").message(),
+                "usage: /watch add <label> = <expr> | /watch remove <label> | /watch list"
+            );
+        }
     }
 
     #[test]
@@ -306,7 +1708,7 @@ mod tests {
             parse_command("/steps maybe")
                 .expect_err("invalid steps")
                 .message(),
-            "usage: /steps [on|off]"
+            "usage: /steps [on|off|verbose]"
         );
     }
 
@@ -318,6 +1720,29 @@ mod tests {
                 .message(),
             "unknown command '/bogus'. Try /help"
         );
+        assert_eq!(
+            parse_command("/bogus")
+                .expect_err("unknown command")
+                .suggestion(),
+            None
+        );
+        assert_eq!(
+            parse_command("/inspct x")
+                .expect_err("mistyped command")
+                .suggestion(),
+            Some("inspect")
+        );
+    }
+
+    #[test]
+    fn suggest_command_picks_the_closest_name_within_two_edits() {
+        assert_eq!(suggest_command("histroy"), Some("history"));
+        assert_eq!(suggest_command("/globls"), Some("globals"));
+        assert_eq!(suggest_command("CLEAR"), Some("clear"));
+        assert_eq!(suggest_command("qq"), None);
+        assert_eq!(suggest_command("completely-unknown"), None);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "gc"), 2);
     }
 
     #[test]
@@ -0,0 +1,87 @@
+/// Above this many line pairs the changed region is shown as a plain remove/add block
+/// instead of computing the longest common subsequence.
+const MAX_LCS_CELLS: usize = 1_000_000;
+
+/// Diffs `old` and `new` line by line. Unchanged lines are prefixed with two spaces,
+/// removed lines with `- ` and added lines with `+ `. Returns `None` when they match.
+pub(crate) fn line_diff(old: &str, new: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+    let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+
+    let mut out = Vec::with_capacity(old_lines.len() + new_lines.len());
+    out.extend(old_lines[..prefix].iter().map(|line| format!("  {line}")));
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_LCS_CELLS {
+        out.extend(old_mid.iter().map(|line| format!("- {line}")));
+        out.extend(new_mid.iter().map(|line| format!("+ {line}")));
+    } else {
+        diff_middle(old_mid, new_mid, &mut out);
+    }
+    out.extend(
+        old_lines[old_lines.len() - suffix..]
+            .iter()
+            .map(|line| format!("  {line}")),
+    );
+    Some(out.join("\n"))
+}
+
+fn diff_middle(old: &[&str], new: &[&str], out: &mut Vec<String>) {
+    // lcs[i][j] is the LCS length of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            out.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    out.extend(old[i..].iter().map(|line| format!("- {line}")));
+    out.extend(new[j..].iter().map(|line| format!("+ {line}")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::line_diff;
+
+    #[test]
+    fn line_diff_marks_changed_lines_and_keeps_context() {
+        assert_eq!(line_diff("a\nb\nc", "a\nb\nc"), None);
+        assert_eq!(
+            line_diff("a\nb\nc\nd", "a\nx\nc\nd\ne").as_deref(),
+            Some("  a\n- b\n+ x\n  c\n  d\n+ e")
+        );
+    }
+}
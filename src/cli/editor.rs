@@ -0,0 +1,76 @@
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Opens `text` in `$VISUAL` or `$EDITOR` via a file at `path` and returns the edited
+/// text once the editor exits.
+pub(crate) fn edit_text(text: &str, path: &Path) -> Result<String> {
+    let editor = env::var("VISUAL")
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .or_else(|| env::var("EDITOR").ok());
+    edit_text_with(editor.as_deref(), text, path)
+}
+
+fn edit_text_with(editor: Option<&str>, text: &str, path: &Path) -> Result<String> {
+    // The editor may carry its own arguments, such as `code --wait`.
+    let mut words = editor.unwrap_or_default().split_whitespace();
+    let Some(program) = words.next() else {
+        bail!("no editor configured: set $EDITOR");
+    };
+
+    fs::write(path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .with_context(|| format!("failed to start editor `{program}`"));
+    let edited = status.and_then(|status| {
+        if !status.success() {
+            bail!("editor `{program}` exited with {status}");
+        }
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+    });
+    let _ = fs::remove_file(path);
+
+    let mut edited = edited?;
+    // Editors usually end the file with a newline the input did not have.
+    if edited.ends_with('\n') {
+        edited.pop();
+    }
+    Ok(edited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::edit_text_with;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn editor_changes_are_read_back_and_failures_reported() {
+        let dir = tempdir().expect("tempdir");
+        let editor = dir.path().join("append-editor");
+        fs::write(&editor, "#!/bin/sh\necho 'y = 2' >> \"$1\"\n").expect("write editor");
+        fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).expect("chmod");
+        let path = dir.path().join("input.py");
+
+        let edited = edit_text_with(editor.to_str(), "x = 1\n", &path).expect("edit");
+        assert_eq!(edited, "x = 1\ny = 2");
+        assert!(!path.exists());
+
+        let err = edit_text_with(None, "x = 1", &path).expect_err("no editor");
+        assert_eq!(err.to_string(), "no editor configured: set $EDITOR");
+        let err = edit_text_with(Some("false"), "x = 1", &path).expect_err("editor fails");
+        assert!(err.to_string().starts_with("editor `false` exited with"));
+        let err = edit_text_with(Some("pychat-ai-missing-editor"), "x = 1", &path)
+            .expect_err("missing editor");
+        assert_eq!(
+            err.to_string(),
+            "failed to start editor `pychat-ai-missing-editor`"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "match", "case", "nonlocal", "not", "or", "pass", "raise", "return",
+    "try", "while", "with", "yield",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PyTokenKind {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+}
+
+/// Lexer state carried between lines, so a triple-quoted string keeps its highlighting
+/// on the lines after the one that opened it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct LexState {
+    open_triple_quote: Option<char>,
+}
+
+/// Splits one line of Python into highlighted segments that together cover the line.
+pub(crate) fn lex_line<'a>(line: &'a str, state: &mut LexState) -> Vec<(PyTokenKind, &'a str)> {
+    let mut segments = Vec::new();
+    let mut plain_start = 0;
+    let mut pos = 0;
+
+    if let Some(quote) = state.open_triple_quote {
+        let Some(end) = scan_string_body(line, 0, quote, true) else {
+            return vec![(PyTokenKind::String, line)];
+        };
+        segments.push((PyTokenKind::String, &line[..end]));
+        state.open_triple_quote = None;
+        plain_start = end;
+        pos = end;
+    }
+
+    while let Some(ch) = line[pos..].chars().next() {
+        let (kind, end) = if ch == '#' {
+            (PyTokenKind::Comment, line.len())
+        } else if ch == '\'' || ch == '"' {
+            (PyTokenKind::String, scan_string(line, pos, state))
+        } else if ch.is_ascii_digit() || (ch == '.' && next_is_digit(line, pos + 1)) {
+            (PyTokenKind::Number, scan_number(line, pos))
+        } else if ch.is_alphabetic() || ch == '_' {
+            let ident_end = scan_identifier(line, pos);
+            let ident = &line[pos..ident_end];
+            match line[ident_end..].chars().next() {
+                Some('\'' | '"') if is_string_prefix(ident) => {
+                    (PyTokenKind::String, scan_string(line, ident_end, state))
+                }
+                _ if KEYWORDS.contains(&ident) => (PyTokenKind::Keyword, ident_end),
+                _ => (PyTokenKind::Plain, ident_end),
+            }
+        } else {
+            (PyTokenKind::Plain, pos + ch.len_utf8())
+        };
+
+        if kind != PyTokenKind::Plain {
+            if plain_start < pos {
+                segments.push((PyTokenKind::Plain, &line[plain_start..pos]));
+            }
+            segments.push((kind, &line[pos..end]));
+            plain_start = end;
+        }
+        pos = end;
+    }
+
+    if plain_start < line.len() {
+        segments.push((PyTokenKind::Plain, &line[plain_start..]));
+    }
+    segments
+}
+
+fn is_string_prefix(ident: &str) -> bool {
+    matches!(
+        ident.to_ascii_lowercase().as_str(),
+        "r" | "b" | "u" | "f" | "rb" | "br" | "fr" | "rf"
+    )
+}
+
+/// Scans a string literal whose opening quote is at `quote_pos`. Returns the end of the
+/// literal, or the end of the line when it is not closed; an unclosed triple-quoted
+/// string is recorded in `state`.
+fn scan_string(line: &str, quote_pos: usize, state: &mut LexState) -> usize {
+    let quote = line[quote_pos..].chars().next().unwrap_or('"');
+    let triple = line[quote_pos..].starts_with(&quote.to_string().repeat(3));
+    let body_start = quote_pos + if triple { 3 } else { 1 };
+    match scan_string_body(line, body_start, quote, triple) {
+        Some(end) => end,
+        None => {
+            if triple {
+                state.open_triple_quote = Some(quote);
+            }
+            line.len()
+        }
+    }
+}
+
+/// Finds the end (just past the closing quote) of a string body starting at `pos`,
+/// skipping backslash escapes.
+fn scan_string_body(line: &str, pos: usize, quote: char, triple: bool) -> Option<usize> {
+    let closing = if triple {
+        quote.to_string().repeat(3)
+    } else {
+        quote.to_string()
+    };
+    let mut chars = line[pos..].char_indices();
+    while let Some((offset, ch)) = chars.next() {
+        if ch == '\\' {
+            chars.next();
+        } else if line[pos + offset..].starts_with(&closing) {
+            return Some(pos + offset + closing.len());
+        }
+    }
+    None
+}
+
+fn scan_number(line: &str, pos: usize) -> usize {
+    let mut end = pos;
+    let mut prev = '\0';
+    for (offset, ch) in line[pos..].char_indices() {
+        let exponent_sign = (ch == '+' || ch == '-') && matches!(prev, 'e' | 'E');
+        if !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '.' || exponent_sign) {
+            break;
+        }
+        end = pos + offset + ch.len_utf8();
+        prev = ch;
+    }
+    end
+}
+
+fn scan_identifier(line: &str, pos: usize) -> usize {
+    line[pos..]
+        .char_indices()
+        .find(|(_, ch)| !(ch.is_alphanumeric() || *ch == '_'))
+        .map_or(line.len(), |(offset, _)| pos + offset)
+}
+
+fn next_is_digit(line: &str, pos: usize) -> bool {
+    line[pos..]
+        .chars()
+        .next()
+        .is_some_and(|ch| ch.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LexState, PyTokenKind, lex_line};
+
+    fn kinds(line: &str) -> Vec<(PyTokenKind, &str)> {
+        lex_line(line, &mut LexState::default())
+    }
+
+    #[test]
+    fn lex_line_marks_keywords_numbers_strings_and_comments() {
+        assert_eq!(
+            kinds("if x == 1.5e-3: return 'a' # done"),
+            vec![
+                (PyTokenKind::Keyword, "if"),
+                (PyTokenKind::Plain, " x == "),
+                (PyTokenKind::Number, "1.5e-3"),
+                (PyTokenKind::Plain, ": "),
+                (PyTokenKind::Keyword, "return"),
+                (PyTokenKind::Plain, " "),
+                (PyTokenKind::String, "'a'"),
+                (PyTokenKind::Plain, " "),
+                (PyTokenKind::Comment, "# done"),
+            ]
+        );
+        assert_eq!(
+            kinds("value2 = f'x' + b\"y\""),
+            vec![
+                (PyTokenKind::Plain, "value2 = "),
+                (PyTokenKind::String, "f'x'"),
+                (PyTokenKind::Plain, " + "),
+                (PyTokenKind::String, "b\"y\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_line_handles_escapes_and_hash_inside_strings() {
+        assert_eq!(
+            kinds(r#"s = "say \"hi\" # not a comment" if True"#),
+            vec![
+                (PyTokenKind::Plain, "s = "),
+                (PyTokenKind::String, r#""say \"hi\" # not a comment""#),
+                (PyTokenKind::Plain, " "),
+                (PyTokenKind::Keyword, "if"),
+                (PyTokenKind::Plain, " "),
+                (PyTokenKind::Keyword, "True"),
+            ]
+        );
+        assert_eq!(
+            kinds(r"'it\'s' + '\\'"),
+            vec![
+                (PyTokenKind::String, r"'it\'s'"),
+                (PyTokenKind::Plain, " + "),
+                (PyTokenKind::String, r"'\\'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_line_carries_triple_quoted_strings_across_lines() {
+        let mut state = LexState::default();
+        assert_eq!(
+            lex_line("doc = \"\"\"first 'line'", &mut state),
+            vec![
+                (PyTokenKind::Plain, "doc = "),
+                (PyTokenKind::String, "\"\"\"first 'line'"),
+            ]
+        );
+        assert_eq!(
+            lex_line("middle \" # still text", &mut state),
+            vec![(PyTokenKind::String, "middle \" # still text")]
+        );
+        assert_eq!(
+            lex_line("end\"\"\" + 1", &mut state),
+            vec![
+                (PyTokenKind::String, "end\"\"\""),
+                (PyTokenKind::Plain, " + "),
+                (PyTokenKind::Number, "1"),
+            ]
+        );
+        assert_eq!(state, LexState::default());
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::{Result, anyhow, bail};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+const HISTORY_FILE_NAME: &str = "pychat.ai/history";
+
+/// Input history persisted across sessions, one entry per line. Newlines inside
+/// multi-line Python entries are escaped so each entry stays on a single line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryFile {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl HistoryFile {
+    pub fn new(path: PathBuf, max_entries: usize) -> Self {
+        Self { path, max_entries }
+    }
+
+    pub fn from_env(max_entries: usize) -> Result<Self> {
+        Ok(Self::new(resolve_history_path_from_env()?, max_entries))
+    }
+
+    /// Reads the most recent `max_entries` entries. When the file holds more than that,
+    /// it is rewritten with only the kept entries so it does not grow without bound.
+    pub(crate) fn load(&self) -> Vec<String> {
+        let Ok(content) = fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+
+        let mut entries: Vec<String> = Vec::new();
+        for entry in content.lines().map(decode_entry) {
+            if is_history_entry(&entry) && entries.last() != Some(&entry) {
+                entries.push(entry);
+            }
+        }
+        let line_count = content.lines().count();
+        if entries.len() > self.max_entries {
+            entries.drain(..entries.len() - self.max_entries);
+        }
+        if line_count > entries.len() {
+            self.rewrite(&entries);
+        }
+        entries
+    }
+
+    pub(crate) fn append(&self, entry: &str) {
+        if self.max_entries == 0 {
+            return;
+        }
+        if let Ok(mut file) = open_history_file(&self.path, false) {
+            let _ = writeln!(file, "{}", encode_entry(entry));
+        }
+    }
+
+    fn rewrite(&self, entries: &[String]) {
+        if let Ok(mut file) = open_history_file(&self.path, true) {
+            for entry in entries {
+                let _ = writeln!(file, "{}", encode_entry(entry));
+            }
+        }
+    }
+}
+
+pub(crate) fn is_history_entry(line: &str) -> bool {
+    !line.trim().is_empty()
+        && !line.eq_ignore_ascii_case("exit")
+        && !line.eq_ignore_ascii_case("quit")
+}
+
+fn encode_entry(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn decode_entry(line: &str) -> String {
+    let mut decoded = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            decoded.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => decoded.push('\n'),
+            Some(other) => decoded.push(other),
+            None => decoded.push('\\'),
+        }
+    }
+    decoded
+}
+
+fn open_history_file(path: &Path, truncate: bool) -> std::io::Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.create(true);
+    if truncate {
+        options.write(true).truncate(true);
+    } else {
+        options.append(true);
+    }
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)
+}
+
+pub fn resolve_history_path_from_env() -> Result<PathBuf> {
+    let xdg_state = env::var("XDG_STATE_HOME").ok();
+    let home = dirs::home_dir();
+    resolve_history_path(xdg_state.as_deref(), home.as_deref())
+}
+
+fn resolve_history_path(xdg_state_home: Option<&str>, home_dir: Option<&Path>) -> Result<PathBuf> {
+    if let Some(xdg) = xdg_state_home {
+        let trimmed = xdg.trim();
+        if trimmed.is_empty() {
+            bail!("Failed to resolve history path: XDG_STATE_HOME is set but empty");
+        }
+        return Ok(PathBuf::from(trimmed).join(HISTORY_FILE_NAME));
+    }
+
+    let home = home_dir
+        .ok_or_else(|| anyhow!("Failed to resolve history path: HOME directory is unavailable"))?;
+    Ok(home.join(".local/state").join(HISTORY_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HistoryFile, decode_entry, encode_entry, resolve_history_path};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn resolve_history_path_uses_xdg_state_or_home_fallback() {
+        assert_eq!(
+            resolve_history_path(Some("/tmp/state"), Some(Path::new("/home/fallback")))
+                .expect("history path"),
+            Path::new("/tmp/state/pychat.ai/history")
+        );
+        assert_eq!(
+            resolve_history_path(None, Some(Path::new("/home/alice"))).expect("history path"),
+            Path::new("/home/alice/.local/state/pychat.ai/history")
+        );
+        assert!(resolve_history_path(Some(" "), None).is_err());
+    }
+
+    #[test]
+    fn entries_round_trip_through_escaping() {
+        let entry = "def f():\n    return '\\\\n'";
+        let encoded = encode_entry(entry);
+        assert!(!encoded.contains('\n'));
+        assert_eq!(decode_entry(&encoded), entry);
+    }
+
+    #[test]
+    fn append_then_load_restores_entries() {
+        let dir = tempdir().expect("tempdir");
+        let history = HistoryFile::new(dir.path().join("state/history"), 10);
+
+        history.append("x = 1");
+        history.append("for i in range(3):\n    print(i)");
+
+        assert_eq!(
+            history.load(),
+            vec!["x = 1", "for i in range(3):\n    print(i)"]
+        );
+    }
+
+    #[test]
+    fn load_caps_dedupes_and_skips_exit_then_compacts_file() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("history");
+        fs::write(&path, "a\na\nexit\nb\nquit\nc\nd\n").expect("write history");
+        let history = HistoryFile::new(path.clone(), 3);
+
+        assert_eq!(history.load(), vec!["b", "c", "d"]);
+        assert_eq!(fs::read_to_string(path).expect("read history"), "b\nc\nd\n");
+    }
+}
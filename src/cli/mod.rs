@@ -1,10 +1,16 @@
 mod args;
+mod clipboard;
 mod commands;
+mod diff;
+mod editor;
+mod highlight;
+mod history;
 mod repl;
 pub(crate) mod theme;
 mod timeline;
 
 pub use args::CliArgs;
+pub use history::HistoryFile;
 #[cfg(feature = "test-support")]
 pub use repl::test_support;
-pub use repl::{AppState, Mode, run_repl};
+pub use repl::{AppState, Mode, OneShot, run_one_shot, run_plain, run_repl};
@@ -1,20 +1,39 @@
-use crate::agent::{AgentConfig, AgentProgressEvent, run_question_with_events};
-use crate::cli::commands::{Command, CommandMode, HELP_TEXT, is_command_line, parse_command};
+use crate::agent::{
+    AgentConfig, AgentProgressEvent, DegradedReason, run_chat_question, run_question_with_events,
+    tool_declarations,
+};
+use crate::cli::clipboard::{CopiedTo, copy_text};
+use crate::cli::commands::{
+    Command, CommandMode, CopyTarget, HELP_TEXT, WatchCommand, command_help, is_command_line,
+    parse_command, suggest_command,
+};
+use crate::cli::diff::line_diff;
+use crate::cli::editor::edit_text;
+use crate::cli::history::{HistoryFile, is_history_entry};
 use crate::cli::theme::Theme;
 use crate::cli::timeline::{
-    AssistantStepEvent, AssistantTurn, AssistantTurnState, OutputKind, Timeline,
+    AssistantStepEvent, AssistantTurn, AssistantTurnState, LineClick, OutputKind, Timeline,
+    TimelineSnapshot, TranscriptFormat, output_token_for,
+};
+use crate::config::{
+    AppConfig, EditMode, HistoryRecall, KeyAction, KeyBindings, ThemeConfig, ThemePreset,
+    ThemeToken, save_theme_preset,
 };
-use crate::config::{ThemeConfig, ThemeToken};
 use crate::llm::gemini::GeminiProvider;
-use crate::llm::provider::LlmTokenUsageTotals;
+use crate::llm::provider::{
+    AssistantInput, AssistantMessage, AssistantPart, AssistantRole, LlmProvider,
+    LlmTokenUsageTotals, ToolCallingMode,
+};
 use crate::python::{
-    CapabilityError, CapabilityProvider, InputCompleteness, PythonSession, UserRunResult,
+    CapabilityError, CapabilityProvider, CapabilityResult, EvalResult, ExecResult, GlobalEntry,
+    InputCompleteness, InspectInfo, PythonInterrupt, PythonSession, UserRunResult,
 };
 use crate::trace::SessionTrace;
 use anyhow::Result;
+use crossterm::clipboard::CopyToClipboard;
 use crossterm::event::{
-    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
-    MouseEvent, MouseEventKind,
+    self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 };
 use crossterm::execute;
 use crossterm::terminal::{
@@ -24,14 +43,25 @@ use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Padding, Paragraph, Wrap};
+use ratatui::widgets::{Block, Clear, Padding, Paragraph, Wrap};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::VecDeque;
 use std::fs;
-use std::io::{self, ErrorKind, IsTerminal};
-use std::path::Path;
-use std::time::Duration;
+use std::io::{self, BufRead, ErrorKind, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 
 const TIMELINE_SCROLL_STEP: usize = 3;
+const ASSISTANT_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const PYTHON_STREAM_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+const TABULAR_PREVIEW_MAX_LINES: usize = 20;
+const TAB_WIDTH: usize = 8;
+const TOOL_PREVIEW_MAX_CHARS: usize = 80;
+const VERBOSE_TOOL_PREVIEW_MAX_CHARS: usize = 400;
+const VERBOSE_GLOBALS_MAX_NAMES: usize = 10;
 
 #[derive(Debug, Clone, Copy)]
 struct UiLayout {
@@ -41,12 +71,25 @@ struct UiLayout {
     footer: Rect,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Mode {
     Python,
     Assistant,
 }
 
+/// Bumped when the `/session save` file layout changes incompatibly.
+const SESSION_FILE_VERSION: u32 = 1;
+
+/// What `/session save` writes: the timeline plus where the user was looking at it.
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionFile {
+    version: u32,
+    mode: Mode,
+    timeline_scroll: usize,
+    timeline: TimelineSnapshot,
+}
+
 pub struct AppState {
     pub mode: Mode,
     pub session_id: String,
@@ -54,42 +97,223 @@ pub struct AppState {
     pub llm: Option<GeminiProvider>,
     pub agent_config: AgentConfig,
     pub theme_config: ThemeConfig,
+    pub key_bindings: KeyBindings,
+    pub edit_mode: EditMode,
+    pub thinking_delay: Duration,
+    /// Prefixes timeline entries with the time they were added.
+    pub timeline_timestamps: bool,
+    pub timeline_max_bytes: usize,
+    /// Tool steps shown per assistant turn before older ones collapse; 0 shows all.
+    pub max_steps_shown: usize,
+    /// Values and inspect output longer than this many lines open in the pager; 0 never does.
+    pub auto_page_lines: usize,
+    /// Longest value repr shown in the timeline; 0 shows values in full.
+    pub repr_max_chars: usize,
+    /// Copy commands set the clipboard through the terminal with OSC 52.
+    pub clipboard_osc52: bool,
+    /// Python input that runs at least this long reports its duration.
+    pub auto_time_threshold: Option<Duration>,
+    /// Runs Python input on a worker thread, showing stdout lines as they are printed.
+    pub stream_output: bool,
+    /// `/inspect` prints single-line JSON unless told otherwise.
+    pub inspect_compact: bool,
+    /// Python values are shown as `expr => value`.
+    pub echo_expr: bool,
+    /// Expression input ending in `;` shows no value line.
+    pub semicolon_suppresses_output: bool,
+    /// Questions and answers kept for the next question; 0 turns keeps none.
+    pub memory_turns: usize,
+    /// Earlier user questions and final answers, oldest first, without tool traffic.
+    pub conversation: Vec<AssistantMessage>,
+    pub history_file: Option<HistoryFile>,
+    pub history_recall: HistoryRecall,
     pub startup_message: Option<String>,
+    /// Config file that `/theme save` writes to.
+    pub config_path: PathBuf,
+    /// Whether `config_path` came from `--config`, which skips project config discovery.
+    pub config_is_explicit: bool,
     pub trace: SessionTrace,
 }
 
+/// Reverse incremental search state (Ctrl-R). `match_index` points into the history;
+/// `original_input` is restored when the search is cancelled.
+#[derive(Debug, Clone, Default)]
+struct HistorySearch {
+    query: String,
+    match_index: Option<usize>,
+    original_input: String,
+}
+
+/// Long output shown in a scrollable overlay over the timeline by `/page` or
+/// `auto_page_lines`. `scroll` is the first wrapped line shown.
+#[derive(Debug, Clone)]
+struct Pager {
+    title: String,
+    text: String,
+    kind: OutputKind,
+    scroll: usize,
+}
+
+impl Pager {
+    /// Splits the text into rows of at most `width` characters.
+    fn wrapped_lines(&self, width: usize) -> Vec<String> {
+        let width = width.max(1);
+        let mut rows = Vec::new();
+        for line in self.text.lines() {
+            let chars = line.chars().collect::<Vec<_>>();
+            if chars.is_empty() {
+                rows.push(String::new());
+            }
+            rows.extend(chars.chunks(width).map(|chunk| chunk.iter().collect()));
+        }
+        rows
+    }
+}
+
+/// Candidates from a Tab completion with several matches; further Tab presses cycle
+/// through them. `start` is where the completed word begins in the Python input.
+#[derive(Debug, Clone)]
+struct Completion {
+    start: usize,
+    candidates: Vec<String>,
+    selected: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Watch {
+    label: String,
+    expr: String,
+}
+
+/// Mode of the input box with `edit_mode = "vi"`: Insert edits like the default
+/// bindings, Normal turns letters into motions and edits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ViMode {
+    Normal,
+    Insert,
+}
+
 #[derive(Debug, Clone)]
 struct UiState {
     mode: Mode,
     python_input: String,
     assistant_input: String,
+    /// Byte offset of the cursor within the current mode's input.
+    input_cursor: usize,
     show_assistant_steps: bool,
+    /// Tool results in the steps include values and names instead of terse summaries.
+    verbose_steps: bool,
     history: Vec<String>,
+    history_file: Option<HistoryFile>,
+    history_recall: HistoryRecall,
     history_index: Option<usize>,
+    history_search: Option<HistorySearch>,
+    completion: Option<Completion>,
+    /// Open while long output is shown over the timeline; Esc closes it.
+    pager: Option<Pager>,
+    /// The pager needs the TUI; without it `/page` output goes to the timeline.
+    pager_available: bool,
+    /// History entry queued by `/rerun`, submitted right after the command.
+    pending_rerun: Option<String>,
+    /// Set by `/ping-llm`; the request is sent once the command returns.
+    pending_llm_ping: bool,
+    /// Models and question queued by `/compare`, asked once the command returns.
+    pending_compare: Option<([String; 2], String)>,
+    /// Set by `/edit` or Ctrl-X Ctrl-E; the editor opens once the key is handled.
+    pending_edit: bool,
+    /// The previous key was Ctrl-X, the first half of the Ctrl-X Ctrl-E chord.
+    ctrl_x_pressed: bool,
+    /// Set with `edit_mode = "vi"`; `None` keeps the default bindings.
+    vi_mode: Option<ViMode>,
+    /// The previous Normal-mode key was `d`, the first half of `dd`.
+    vi_d_pressed: bool,
     timeline_scroll: usize,
     timeline: Timeline,
+    /// Untruncated repr of the last evaluated Python value, shown by `/last`.
+    last_value_repr: Option<String>,
+    /// Most recent Python output and assistant answer, for `/copy`.
+    last_output: Option<String>,
+    last_answer: Option<String>,
+    /// Text waiting to be sent to the terminal clipboard with OSC 52.
+    pending_clipboard: Option<String>,
     session_token_usage: LlmTokenUsageTotals,
+    turn_token_usage: Vec<LlmTokenUsageTotals>,
+    /// Completeness of the Python input as of the last check, keyed by the checked text
+    /// so the interpreter is only consulted again once the input changes.
+    input_completeness: Option<(String, InputCompleteness)>,
+    watches: Vec<Watch>,
+    /// Terminal events read while an assistant turn was in flight, handled next.
+    queued_events: VecDeque<Event>,
     should_quit: bool,
     theme: Theme,
+    key_bindings: KeyBindings,
 }
 
 impl UiState {
-    fn new(mode: Mode, color_enabled: bool, theme_config: &ThemeConfig) -> Self {
+    fn new(
+        mode: Mode,
+        color_enabled: bool,
+        theme_config: &ThemeConfig,
+        key_bindings: KeyBindings,
+        timeline: Timeline,
+        history_file: Option<HistoryFile>,
+        history_recall: HistoryRecall,
+    ) -> Self {
+        let history = history_file
+            .as_ref()
+            .map(HistoryFile::load)
+            .unwrap_or_default();
         Self {
             mode,
             python_input: String::new(),
             assistant_input: String::new(),
+            input_cursor: 0,
             show_assistant_steps: true,
-            history: Vec::new(),
+            verbose_steps: false,
+            history,
+            history_file,
+            history_recall,
             history_index: None,
+            history_search: None,
+            completion: None,
+            pager: None,
+            pager_available: false,
+            pending_rerun: None,
+            pending_llm_ping: false,
+            pending_compare: None,
+            pending_edit: false,
+            ctrl_x_pressed: false,
+            vi_mode: None,
+            vi_d_pressed: false,
             timeline_scroll: 0,
-            timeline: Timeline::new(),
+            timeline,
+            last_value_repr: None,
+            last_output: None,
+            last_answer: None,
+            pending_clipboard: None,
             session_token_usage: LlmTokenUsageTotals::default(),
+            turn_token_usage: Vec::new(),
+            input_completeness: None,
+            watches: Vec::new(),
+            queued_events: VecDeque::new(),
             should_quit: false,
             theme: Theme::from_config(color_enabled, theme_config),
+            key_bindings,
         }
     }
 
+    /// Starts vi-style editing, in Insert mode, when the config asks for it.
+    fn with_edit_mode(mut self, edit_mode: EditMode) -> Self {
+        self.vi_mode = (edit_mode == EditMode::Vi).then_some(ViMode::Insert);
+        self
+    }
+
+    /// Lets long output open in the pager, which only the TUI can draw.
+    fn with_pager(mut self) -> Self {
+        self.pager_available = true;
+        self
+    }
+
     fn current_input(&self) -> &str {
         match self.mode {
             Mode::Python => &self.python_input,
@@ -104,7 +328,241 @@ impl UiState {
         }
     }
 
+    fn set_current_input(&mut self, text: String) {
+        self.input_cursor = text.len();
+        *self.current_input_mut() = text;
+    }
+
+    fn cursor(&self) -> usize {
+        self.input_cursor.min(self.current_input().len())
+    }
+
+    fn insert_at_cursor(&mut self, text: &str) {
+        let cursor = self.cursor();
+        self.current_input_mut().insert_str(cursor, text);
+        self.input_cursor = cursor + text.len();
+        self.history_index = None;
+    }
+
+    /// Replaces the input between `start` and the cursor, leaving the cursor after `text`.
+    fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        let cursor = self.cursor();
+        self.current_input_mut().replace_range(start..cursor, text);
+        self.input_cursor = start + text.len();
+        self.history_index = None;
+    }
+
+    fn delete_before_cursor(&mut self) {
+        let cursor = self.cursor();
+        let Some(ch) = self.current_input()[..cursor].chars().next_back() else {
+            return;
+        };
+        let start = cursor - ch.len_utf8();
+        self.current_input_mut().replace_range(start..cursor, "");
+        self.input_cursor = start;
+        self.history_index = None;
+    }
+
+    fn delete_at_cursor(&mut self) {
+        let cursor = self.cursor();
+        let Some(ch) = self.current_input()[cursor..].chars().next() else {
+            return;
+        };
+        self.current_input_mut()
+            .replace_range(cursor..cursor + ch.len_utf8(), "");
+        self.history_index = None;
+    }
+
+    fn delete_range(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.current_input_mut().replace_range(start..end, "");
+        self.input_cursor = start;
+        self.history_index = None;
+    }
+
+    /// Deletes the word before the cursor (Ctrl-W), first skipping any separators.
+    /// At the start of a line it joins the line with the previous one.
+    fn delete_word_before_cursor(&mut self) {
+        let cursor = self.cursor();
+        let before = &self.current_input()[..cursor];
+        if before.ends_with('\n') {
+            self.delete_range(cursor - 1, cursor);
+            return;
+        }
+
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let line = &before[line_start..];
+        let trimmed = line.trim_end_matches(is_word_separator);
+        let word_start = trimmed.rfind(is_word_separator).map_or(0, |index| {
+            index + trimmed[index..].chars().next().map_or(1, char::len_utf8)
+        });
+        self.delete_range(line_start + word_start, cursor);
+    }
+
+    /// Deletes from the cursor to the end of its line (Ctrl-K); at the end of a line it
+    /// removes the line break instead.
+    fn kill_to_line_end(&mut self) {
+        let cursor = self.cursor();
+        let input = self.current_input();
+        let end = match input[cursor..].find('\n') {
+            Some(0) => cursor + 1,
+            Some(index) => cursor + index,
+            None => input.len(),
+        };
+        self.delete_range(cursor, end);
+    }
+
+    /// Deletes from the start of the cursor's line up to the cursor (Ctrl-U).
+    fn kill_to_line_start(&mut self) {
+        let cursor = self.cursor();
+        let start = self.current_input()[..cursor]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        self.delete_range(start, cursor);
+    }
+
+    fn move_cursor_left(&mut self) {
+        let cursor = self.cursor();
+        if let Some(ch) = self.current_input()[..cursor].chars().next_back() {
+            self.input_cursor = cursor - ch.len_utf8();
+        }
+    }
+
+    fn move_cursor_right(&mut self) {
+        let cursor = self.cursor();
+        if let Some(ch) = self.current_input()[cursor..].chars().next() {
+            self.input_cursor = cursor + ch.len_utf8();
+        }
+    }
+
+    /// Moves to the start of the line holding the cursor.
+    fn move_cursor_line_start(&mut self) {
+        let cursor = self.cursor();
+        self.input_cursor = self.current_input()[..cursor]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+    }
+
+    /// Moves to the end of the line holding the cursor.
+    fn move_cursor_line_end(&mut self) {
+        let cursor = self.cursor();
+        let input = self.current_input();
+        self.input_cursor = input[cursor..]
+            .find('\n')
+            .map_or(input.len(), |index| cursor + index);
+    }
+
+    /// Moves one character left without leaving the cursor's line (vi `h`).
+    fn move_cursor_left_in_line(&mut self) {
+        if !self.current_input()[..self.cursor()].ends_with('\n') {
+            self.move_cursor_left();
+        }
+    }
+
+    /// Moves one character right without leaving the cursor's line (vi `l`).
+    fn move_cursor_right_in_line(&mut self) {
+        if !self.current_input()[self.cursor()..].starts_with('\n') {
+            self.move_cursor_right();
+        }
+    }
+
+    /// Moves to the same column of the next or previous line, clamped to its length.
+    /// On the last or first line it recalls history instead (vi `j` and `k`).
+    fn move_cursor_vertically(&mut self, down: bool) {
+        let cursor = self.cursor();
+        let input = self.current_input();
+        let line_start = input[..cursor].rfind('\n').map_or(0, |index| index + 1);
+        let column = input[line_start..cursor].chars().count();
+        let target_start = if down {
+            match input[cursor..].find('\n') {
+                Some(index) => cursor + index + 1,
+                None => return self.history_next(),
+            }
+        } else {
+            if line_start == 0 {
+                return self.history_prev();
+            }
+            input[..line_start - 1]
+                .rfind('\n')
+                .map_or(0, |index| index + 1)
+        };
+        let target_line = input[target_start..].split('\n').next().unwrap_or_default();
+        self.input_cursor = target_start
+            + target_line
+                .char_indices()
+                .nth(column)
+                .map_or(target_line.len(), |(index, _)| index);
+    }
+
+    /// Moves to the start of the next word (vi `w`). Runs of word characters and of
+    /// punctuation each count as a word.
+    fn move_cursor_word_forward(&mut self) {
+        let cursor = self.cursor();
+        let input = self.current_input();
+        let mut chars = input[cursor..].char_indices().peekable();
+        if let Some(&(_, first)) = chars.peek() {
+            let class = vi_char_class(first);
+            while chars
+                .next_if(|&(_, ch)| vi_char_class(ch) == class)
+                .is_some()
+            {}
+        }
+        while chars.next_if(|&(_, ch)| ch.is_whitespace()).is_some() {}
+        self.input_cursor = chars
+            .peek()
+            .map_or(input.len(), |&(index, _)| cursor + index);
+    }
+
+    /// Moves to the start of the current or previous word (vi `b`).
+    fn move_cursor_word_back(&mut self) {
+        let cursor = self.cursor();
+        let input = &self.current_input()[..cursor];
+        let mut chars = input.char_indices().rev().peekable();
+        while chars.next_if(|&(_, ch)| ch.is_whitespace()).is_some() {}
+        let mut start = chars.peek().map_or(0, |&(index, _)| index);
+        if let Some(&(_, last)) = chars.peek() {
+            let class = vi_char_class(last);
+            while let Some((index, _)) = chars.next_if(|&(_, ch)| vi_char_class(ch) == class) {
+                start = index;
+            }
+        }
+        self.input_cursor = start;
+    }
+
+    /// Deletes the character under the cursor, but not a line break (vi `x`).
+    fn delete_at_cursor_in_line(&mut self) {
+        if !self.current_input()[self.cursor()..].starts_with('\n') {
+            self.delete_at_cursor();
+        }
+    }
+
+    /// Deletes the cursor's line with its line break (vi `dd`), leaving the cursor at
+    /// the start of the line that takes its place.
+    fn delete_current_line(&mut self) {
+        let cursor = self.cursor();
+        let input = self.current_input();
+        let start = input[..cursor].rfind('\n').map_or(0, |index| index + 1);
+        let (start, end) = match input[cursor..].find('\n') {
+            Some(index) => (start, cursor + index + 1),
+            // The last line takes the line break before it along.
+            None => (start.saturating_sub(1), input.len()),
+        };
+        self.delete_range(start, end);
+        self.move_cursor_line_start();
+    }
+
     fn push_timeline_output(&mut self, kind: OutputKind, text: &str) {
+        if matches!(
+            kind,
+            OutputKind::PythonValue
+                | OutputKind::PythonStdout
+                | OutputKind::PythonStderr
+                | OutputKind::PythonTraceback
+        ) {
+            self.last_output = Some(text.to_string());
+        }
         self.timeline.push_output(kind, text);
     }
 
@@ -120,9 +578,28 @@ impl UiState {
         self.timeline.assistant_turn_mut(index)
     }
 
-    fn push_history(&mut self, line: &str) {
-        self.history.push(line.to_string());
+    /// Records a submission. With line recall, each line of a multi-line submission
+    /// becomes its own entry.
+    fn push_history(&mut self, submission: &str) {
         self.history_index = None;
+        match self.history_recall {
+            HistoryRecall::Block => self.push_history_entry(submission),
+            HistoryRecall::Line => {
+                for line in submission.lines() {
+                    self.push_history_entry(line);
+                }
+            }
+        }
+    }
+
+    fn push_history_entry(&mut self, line: &str) {
+        if !is_history_entry(line) || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if let Some(history_file) = &self.history_file {
+            history_file.append(line);
+        }
     }
 
     fn history_prev(&mut self) {
@@ -137,7 +614,7 @@ impl UiState {
         };
 
         self.history_index = Some(next_index);
-        *self.current_input_mut() = self.history[next_index].clone();
+        self.set_current_input(self.history[next_index].clone());
     }
 
     fn history_next(&mut self) {
@@ -149,16 +626,77 @@ impl UiState {
             Some(i) if i + 1 < self.history.len() => {
                 let next_index = i + 1;
                 self.history_index = Some(next_index);
-                *self.current_input_mut() = self.history[next_index].clone();
+                self.set_current_input(self.history[next_index].clone());
             }
             Some(_) => {
                 self.history_index = None;
-                self.current_input_mut().clear();
+                self.set_current_input(String::new());
             }
             None => {}
         }
     }
 
+    fn start_history_search(&mut self) {
+        self.history_search = Some(HistorySearch {
+            original_input: self.current_input().to_string(),
+            ..HistorySearch::default()
+        });
+    }
+
+    /// Finds the most recent history entry containing the query, looking only at entries
+    /// older than `before`.
+    fn find_history_match(&self, query: &str, before: usize) -> Option<usize> {
+        self.history[..before.min(self.history.len())]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+
+    fn update_history_search(&mut self, query: String) {
+        let match_index = self.find_history_match(&query, self.history.len());
+        if let Some(search) = &mut self.history_search {
+            search.query = query;
+            search.match_index = match_index;
+        }
+    }
+
+    fn search_older_history_match(&mut self) {
+        let Some(search) = &self.history_search else {
+            return;
+        };
+        let before = search.match_index.unwrap_or(self.history.len());
+        if let Some(index) = self.find_history_match(&search.query, before)
+            && let Some(search) = &mut self.history_search
+        {
+            search.match_index = Some(index);
+        }
+    }
+
+    fn history_search_match(&self) -> Option<&str> {
+        let index = self.history_search.as_ref()?.match_index?;
+        self.history.get(index).map(String::as_str)
+    }
+
+    fn finish_history_search(&mut self, accept: bool) {
+        let Some(search) = self.history_search.take() else {
+            return;
+        };
+        let input = match search.match_index {
+            Some(index) if accept => self.history[index].clone(),
+            _ => search.original_input,
+        };
+        self.set_current_input(input);
+        self.history_index = None;
+    }
+
+    /// Text shown in the input box: the search match while searching, else the input.
+    fn displayed_input(&self) -> &str {
+        if self.history_search.is_some() {
+            self.history_search_match().unwrap_or("")
+        } else {
+            self.current_input()
+        }
+    }
+
     fn scroll_timeline_up(&mut self, lines: usize, max_scroll: usize) {
         self.timeline_scroll = self.timeline_scroll.saturating_add(lines).min(max_scroll);
     }
@@ -172,14 +710,30 @@ impl UiState {
     }
 }
 
+fn timeline_for(state: &AppState) -> Timeline {
+    Timeline::with_thinking_delay(state.thinking_delay)
+        .with_timestamps(state.timeline_timestamps)
+        .with_max_bytes(state.timeline_max_bytes)
+        .with_max_steps_shown(state.max_steps_shown)
+}
+
 pub async fn run_repl(state: &mut AppState) -> Result<()> {
     let color_enabled = resolve_color_enabled();
-    let mut ui_state = UiState::new(state.mode, color_enabled, &state.theme_config);
+    let mut ui_state = UiState::new(
+        state.mode,
+        color_enabled,
+        &state.theme_config,
+        state.key_bindings.clone(),
+        timeline_for(state),
+        state.history_file.clone(),
+        state.history_recall,
+    )
+    .with_edit_mode(state.edit_mode)
+    .with_pager();
     initialize_timeline(state, &mut ui_state);
 
-    enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    enter_tui_screen(&mut stdout)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
@@ -188,21 +742,265 @@ pub async fn run_repl(state: &mut AppState) -> Result<()> {
         .trace
         .log_session_token_summary(&ui_state.session_token_usage);
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        DisableMouseCapture,
-        LeaveAlternateScreen
-    )?;
+    leave_tui_screen(terminal.backend_mut())?;
     terminal.show_cursor()?;
     println!(
         "{}",
-        session_closed_message(state.trace.file_path(), &ui_state.session_token_usage)
+        session_closed_message(&state.trace.file_path(), &ui_state.session_token_usage)
     );
 
     run_result
 }
 
+fn enter_tui_screen(out: &mut impl Write) -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(
+        out,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )
+}
+
+fn leave_tui_screen(out: &mut impl Write) -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(
+        out,
+        DisableBracketedPaste,
+        DisableMouseCapture,
+        LeaveAlternateScreen
+    )
+}
+
+/// Suspends the TUI while `$EDITOR` edits the current input, then loads the result back
+/// into the input.
+fn edit_input_in_editor(
+    terminal: &mut Terminal<impl ratatui::backend::Backend>,
+    state: &AppState,
+    ui_state: &mut UiState,
+) -> Result<()> {
+    let extension = match ui_state.mode {
+        Mode::Python => "py",
+        Mode::Assistant => "md",
+    };
+    let path =
+        std::env::temp_dir().join(format!("pychat.ai-{}-edit.{extension}", state.session_id));
+    let mut stdout = io::stdout();
+    leave_tui_screen(&mut stdout)?;
+    let edited = edit_text(ui_state.current_input(), &path);
+    enter_tui_screen(&mut stdout)?;
+    terminal.clear()?;
+
+    match edited {
+        Ok(text) => {
+            ui_state.history_index = None;
+            ui_state.set_current_input(text);
+        }
+        Err(err) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!("edit failed: {err:#}"),
+        ),
+    }
+    Ok(())
+}
+
+/// A single request run without the TUI: `--eval`, `--ask`, or a script piped to stdin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OneShot {
+    Eval(String),
+    Ask(String),
+    /// Python source executed as a whole module, without echoing a final value.
+    Script(String),
+}
+
+/// Runs `request`, writing results to `out` and errors to `err`. Returns whether it
+/// succeeded: false when the Python input raised, or the answer is degraded or missing.
+pub async fn run_one_shot(
+    state: &mut AppState,
+    request: &OneShot,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<bool> {
+    if let Some(message) = &state.startup_message {
+        state.trace.log_output("sys.info", message);
+    }
+    match request {
+        OneShot::Eval(code) | OneShot::Script(code) => {
+            state.trace.log_input_python(code);
+            let result = if matches!(request, OneShot::Script(_)) {
+                state.python.run_exec_input(code)?
+            } else {
+                state.python.run_user_input(code)?
+            };
+            let (stdout, stderr, value, exception) = match result {
+                UserRunResult::Evaluated(result) => {
+                    (result.stdout, result.stderr, Some(result.value_repr), None)
+                }
+                UserRunResult::Executed(result) => (result.stdout, result.stderr, None, None),
+                UserRunResult::Failed {
+                    stdout,
+                    stderr,
+                    exception,
+                    ..
+                } => (stdout, stderr, None, Some(exception)),
+            };
+            write!(out, "{stdout}")?;
+            write!(err, "{stderr}")?;
+            if let Some(value) = value {
+                writeln!(out, "{value}")?;
+            }
+            if let Some(exception) = &exception {
+                writeln!(err, "{}", exception.traceback.trim_end())?;
+            }
+            Ok(exception.is_none())
+        }
+        OneShot::Ask(question) => {
+            let Some(provider) = &state.llm else {
+                writeln!(err, "Assistant unavailable: missing GEMINI_API_KEY")?;
+                return Ok(false);
+            };
+            state.trace.log_input_assistant(question);
+            let mut on_event = |_: AgentProgressEvent| {};
+            let result = if state.agent_config.chat_only {
+                run_chat_question(provider, question, &[], &state.agent_config, &mut on_event).await
+            } else {
+                run_question_with_events(
+                    provider,
+                    &state.python,
+                    question,
+                    &[],
+                    &state.agent_config,
+                    &mut on_event,
+                )
+                .await
+            };
+            match result {
+                Ok(answer) => {
+                    state.trace.log_assistant_answer(
+                        &answer.text,
+                        answer.degraded.map(DegradedReason::name),
+                    );
+                    state.trace.log_session_token_summary(&answer.token_usage);
+                    if answer.degraded.is_some() {
+                        writeln!(err, "{}", answer.text)?;
+                    } else {
+                        writeln!(out, "{}", answer.text)?;
+                    }
+                    Ok(answer.degraded.is_none())
+                }
+                Err(error) => {
+                    writeln!(err, "Assistant request failed: {error}")?;
+                    Ok(false)
+                }
+            }
+        }
+    }
+}
+
+/// Runs the session without the TUI: input lines are read from stdin and the timeline
+/// is written to stdout as plain text, suitable for piping and logging.
+pub async fn run_plain(state: &mut AppState) -> Result<()> {
+    let mut ui_state = UiState::new(
+        state.mode,
+        false,
+        &state.theme_config,
+        state.key_bindings.clone(),
+        timeline_for(state),
+        state.history_file.clone(),
+        state.history_recall,
+    );
+    initialize_timeline(state, &mut ui_state);
+
+    let mut stdout = io::stdout().lock();
+    let mut printed = write_plain_lines(&mut stdout, &ui_state, 0)?;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if !buffer_plain_input(state, &mut ui_state, &line) {
+            continue;
+        }
+
+        submit_line(state, &mut ui_state, &mut |_| Ok(())).await?;
+        show_trace_write_failure(state, &mut ui_state);
+        if std::mem::take(&mut ui_state.pending_edit) {
+            push_output(
+                &mut ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                "/edit needs the TUI; it is not available with --plain",
+            );
+        }
+        printed = write_plain_lines(&mut stdout, &ui_state, printed)?;
+        if stdout.is_terminal() {
+            flush_pending_clipboard(&mut ui_state, &mut stdout)?;
+        }
+        if ui_state.should_quit {
+            break;
+        }
+    }
+
+    if ui_state.mode == Mode::Python && !ui_state.python_input.is_empty() {
+        submit_line(state, &mut ui_state, &mut |_| Ok(())).await?;
+        write_plain_lines(&mut stdout, &ui_state, printed)?;
+    }
+    state
+        .trace
+        .log_session_token_summary(&ui_state.session_token_usage);
+
+    Ok(())
+}
+
+/// Adds a stdin line to the current input and reports whether it is ready to submit.
+/// Python blocks keep buffering until they are complete or a blank line ends them.
+fn buffer_plain_input(state: &AppState, ui_state: &mut UiState, line: &str) -> bool {
+    let input = ui_state.current_input_mut();
+    if !input.is_empty() {
+        input.push('\n');
+    }
+    input.push_str(line);
+
+    if ui_state.mode != Mode::Python || line.trim().is_empty() {
+        return true;
+    }
+    !matches!(
+        state
+            .python
+            .check_input_completeness(ui_state.current_input()),
+        Ok(InputCompleteness::Incomplete)
+    )
+}
+
+/// Writes timeline lines past `already_printed` and returns the new total. A timeline that
+/// shrank (after `/clear`) is printed from the start.
+fn write_plain_lines(
+    out: &mut impl Write,
+    ui_state: &UiState,
+    already_printed: usize,
+) -> Result<usize> {
+    let lines = ui_state
+        .timeline
+        .render_lines(&ui_state.theme, ui_state.show_assistant_steps);
+    let start = if lines.len() < already_printed {
+        0
+    } else {
+        already_printed
+    };
+    for line in &lines[start..] {
+        writeln!(out, "{line}")?;
+    }
+    out.flush()?;
+    Ok(lines.len())
+}
+
+/// Shows the first trace write failure in the timeline. It is not traced, since the
+/// trace is what failed.
+fn show_trace_write_failure(state: &AppState, ui_state: &mut UiState) {
+    if let Some(message) = state.trace.take_write_failure() {
+        ui_state.push_timeline_output(OutputKind::SystemError, &message);
+    }
+}
+
 fn initialize_timeline(state: &AppState, ui_state: &mut UiState) {
     if let Some(message) = state.startup_message.as_deref() {
         push_output(ui_state, &state.trace, OutputKind::SystemInfo, message);
@@ -217,29 +1015,37 @@ async fn run_tui_loop(
     let poll_timeout = Duration::from_millis(50);
 
     loop {
+        flush_pending_clipboard(ui_state, &mut io::stdout())?;
+        show_trace_write_failure(state, ui_state);
+        refresh_input_completeness(state, ui_state);
         terminal.draw(|frame| draw_ui(frame, ui_state))?;
 
         if ui_state.should_quit {
             break;
         }
 
-        if !event::poll(poll_timeout)? {
-            continue;
-        }
+        let event = match ui_state.queued_events.pop_front() {
+            Some(event) => event,
+            None => {
+                if !event::poll(poll_timeout)? {
+                    continue;
+                }
+                event::read()?
+            }
+        };
 
-        match event::read()? {
-            Event::Key(key) => handle_key_event(terminal, state, ui_state, key).await?,
+        match event {
+            Event::Key(key) => {
+                handle_key_event(terminal, state, ui_state, key).await?;
+                if std::mem::take(&mut ui_state.pending_edit) {
+                    edit_input_in_editor(terminal, state, ui_state)?;
+                }
+            }
+            Event::Paste(text) => handle_paste(ui_state, &text),
+            Event::Mouse(_) if ui_state.pager.is_some() => {}
             Event::Mouse(mouse) => {
-                let size = terminal.size()?;
-                let area = Rect::new(0, 0, size.width, size.height);
-                let layout = ui_layout(area, ui_state.current_input());
-                let line_count = ui_state
-                    .timeline
-                    .render_lines(&ui_state.theme, ui_state.show_assistant_steps)
-                    .len();
-                let max_scroll =
-                    timeline_max_scroll(line_count, usize::from(layout.timeline.height));
-                handle_mouse_event(ui_state, mouse, layout.timeline, max_scroll);
+                let (timeline_area, max_scroll) = timeline_viewport(terminal, ui_state)?;
+                handle_mouse_event(state, ui_state, mouse, timeline_area, max_scroll);
             }
             _ => {}
         }
@@ -249,10 +1055,28 @@ async fn run_tui_loop(
     Ok(())
 }
 
-fn handle_mouse_event(
-    ui_state: &mut UiState,
-    mouse: MouseEvent,
-    timeline_area: Rect,
+/// Inserts pasted text as-is, so embedded newlines become part of the input instead of
+/// submitting it line by line. Carriage returns from the terminal are normalized.
+fn handle_paste(ui_state: &mut UiState, text: &str) {
+    if ui_state.pager.is_some() {
+        return;
+    }
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    if let Some(search) = &ui_state.history_search {
+        let first_line = text.lines().next().unwrap_or_default();
+        let query = format!("{}{first_line}", search.query);
+        ui_state.update_history_search(query);
+        return;
+    }
+    ui_state.completion = None;
+    ui_state.insert_at_cursor(&text);
+}
+
+fn handle_mouse_event(
+    state: &AppState,
+    ui_state: &mut UiState,
+    mouse: MouseEvent,
+    timeline_area: Rect,
     max_timeline_scroll: usize,
 ) {
     if !area_contains_point(timeline_area, mouse.column, mouse.row) {
@@ -266,6 +1090,24 @@ fn handle_mouse_event(
         MouseEventKind::ScrollDown => {
             ui_state.scroll_timeline_down(TIMELINE_SCROLL_STEP);
         }
+        MouseEventKind::Down(MouseButton::Left) => {
+            // Rows count from the paragraph scroll; the text sits inside one column
+            // of padding on each side, which is where it wraps.
+            let scroll = max_timeline_scroll - ui_state.timeline_scroll_offset(max_timeline_scroll);
+            let row = scroll + usize::from(mouse.row - timeline_area.y);
+            match ui_state.timeline.click_target(
+                &ui_state.theme,
+                ui_state.show_assistant_steps,
+                timeline_area.width.saturating_sub(2),
+                row,
+            ) {
+                Some(LineClick::ToggleTurnSteps(index)) => {
+                    ui_state.timeline.toggle_turn_steps(index);
+                }
+                Some(LineClick::Copy(text)) => copy_to_clipboard(state, ui_state, "line", text),
+                None => {}
+            }
+        }
         _ => {}
     }
 }
@@ -276,21 +1118,70 @@ async fn handle_key_event(
     ui_state: &mut UiState,
     key: KeyEvent,
 ) -> Result<()> {
+    if ui_state.pager.is_some() {
+        let area = pager_viewport(terminal, ui_state)?;
+        handle_pager_key(ui_state, key, area);
+        return Ok(());
+    }
+    if ui_state.history_search.is_some() {
+        handle_history_search_key(ui_state, key);
+        return Ok(());
+    }
+
+    if key.code != KeyCode::Tab {
+        ui_state.completion = None;
+    }
+    let after_ctrl_x = std::mem::take(&mut ui_state.ctrl_x_pressed);
+    if handle_vi_key(ui_state, key) {
+        return Ok(());
+    }
+
+    if key.code == KeyCode::Tab && wants_completion(ui_state) {
+        complete_python_input(state, ui_state);
+        return Ok(());
+    }
+    if let Some(action) = ui_state.key_bindings.action_for(key) {
+        apply_key_action(ui_state, action);
+        return Ok(());
+    }
+
     match key.code {
-        KeyCode::Tab | KeyCode::BackTab => {
-            ui_state.mode = toggle_mode(ui_state.mode);
-            ui_state.history_index = None;
-        }
-        KeyCode::Enter => {
-            if key.modifiers.contains(KeyModifiers::SHIFT) {
-                insert_python_newline(ui_state);
-            } else {
-                handle_enter(terminal, state, ui_state).await?;
-            }
+        KeyCode::Enter => handle_enter(terminal, state, ui_state).await?,
+        KeyCode::Backspace if key.modifiers.contains(KeyModifiers::ALT) => {
+            ui_state.delete_word_before_cursor();
         }
         KeyCode::Backspace => {
-            ui_state.current_input_mut().pop();
-            ui_state.history_index = None;
+            ui_state.delete_before_cursor();
+        }
+        KeyCode::Delete => {
+            ui_state.delete_at_cursor();
+        }
+        KeyCode::Left => {
+            ui_state.move_cursor_left();
+        }
+        KeyCode::Right => {
+            ui_state.move_cursor_right();
+        }
+        KeyCode::PageUp => {
+            let (timeline_area, max_scroll) = timeline_viewport(terminal, ui_state)?;
+            ui_state.scroll_timeline_up(usize::from(timeline_area.height), max_scroll);
+        }
+        KeyCode::PageDown => {
+            let (timeline_area, _) = timeline_viewport(terminal, ui_state)?;
+            ui_state.scroll_timeline_down(usize::from(timeline_area.height));
+        }
+        KeyCode::Home if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let (_, max_scroll) = timeline_viewport(terminal, ui_state)?;
+            ui_state.scroll_timeline_up(max_scroll, max_scroll);
+        }
+        KeyCode::End if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.scroll_timeline_down(ui_state.timeline_scroll);
+        }
+        KeyCode::Home => {
+            ui_state.move_cursor_line_start();
+        }
+        KeyCode::End => {
+            ui_state.move_cursor_line_end();
         }
         KeyCode::Up => {
             ui_state.history_prev();
@@ -298,21 +1189,33 @@ async fn handle_key_event(
         KeyCode::Down => {
             ui_state.history_next();
         }
-        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            ui_state.should_quit = true;
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.start_history_search();
         }
-        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            ui_state.should_quit = true;
+        KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.move_cursor_line_start();
         }
-        KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            insert_python_newline(ui_state);
+        KeyCode::Char('x') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.ctrl_x_pressed = true;
         }
-        KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-            ui_state.show_assistant_steps = !ui_state.show_assistant_steps;
+        KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) && after_ctrl_x => {
+            ui_state.pending_edit = true;
+        }
+        KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.move_cursor_line_end();
+        }
+        KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.delete_word_before_cursor();
+        }
+        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.kill_to_line_end();
+        }
+        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.kill_to_line_start();
         }
         KeyCode::Char(ch) => {
-            ui_state.current_input_mut().push(ch);
-            ui_state.history_index = None;
+            let mut buffer = [0; 4];
+            ui_state.insert_at_cursor(ch.encode_utf8(&mut buffer));
         }
         _ => {}
     }
@@ -320,6 +1223,144 @@ async fn handle_key_event(
     Ok(())
 }
 
+/// Handles a key with `edit_mode = "vi"`. Returns false for keys that keep their usual
+/// meaning: everything in Insert mode but Esc, and Enter, Tab, arrows and control
+/// chords in Normal mode.
+fn handle_vi_key(ui_state: &mut UiState, key: KeyEvent) -> bool {
+    let Some(vi_mode) = ui_state.vi_mode else {
+        return false;
+    };
+    let after_d = std::mem::take(&mut ui_state.vi_d_pressed);
+    if vi_mode == ViMode::Insert {
+        if key.code != KeyCode::Esc {
+            return false;
+        }
+        // Like vi, leaving Insert mode puts the cursor on the last inserted character.
+        ui_state.vi_mode = Some(ViMode::Normal);
+        ui_state.move_cursor_left_in_line();
+        return true;
+    }
+
+    let KeyCode::Char(ch) = key.code else {
+        return false;
+    };
+    if key
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        return false;
+    }
+    match ch {
+        'h' => ui_state.move_cursor_left_in_line(),
+        'l' => ui_state.move_cursor_right_in_line(),
+        'j' => ui_state.move_cursor_vertically(true),
+        'k' => ui_state.move_cursor_vertically(false),
+        'w' => ui_state.move_cursor_word_forward(),
+        'b' => ui_state.move_cursor_word_back(),
+        '0' => ui_state.move_cursor_line_start(),
+        '$' => ui_state.move_cursor_line_end(),
+        'i' => ui_state.vi_mode = Some(ViMode::Insert),
+        'a' => {
+            ui_state.move_cursor_right_in_line();
+            ui_state.vi_mode = Some(ViMode::Insert);
+        }
+        'o' => {
+            ui_state.move_cursor_line_end();
+            ui_state.insert_at_cursor("\n");
+            ui_state.vi_mode = Some(ViMode::Insert);
+        }
+        'x' => ui_state.delete_at_cursor_in_line(),
+        'd' if after_d => ui_state.delete_current_line(),
+        'd' => ui_state.vi_d_pressed = true,
+        // Other letters are not commands and are not inserted either.
+        _ => {}
+    }
+    true
+}
+
+/// Groups characters for vi word motions: whitespace, punctuation, or word characters.
+fn vi_char_class(ch: char) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if ch.is_alphanumeric() || ch == '_' {
+        2
+    } else {
+        1
+    }
+}
+
+fn apply_key_action(ui_state: &mut UiState, action: KeyAction) {
+    match action {
+        KeyAction::ToggleMode => {
+            ui_state.mode = toggle_mode(ui_state.mode);
+            ui_state.input_cursor = ui_state.current_input().len();
+            ui_state.history_index = None;
+        }
+        KeyAction::Newline => insert_newline(ui_state),
+        KeyAction::ToggleSteps => {
+            ui_state.show_assistant_steps = !ui_state.show_assistant_steps;
+        }
+        KeyAction::SelectPreviousTurn => ui_state.timeline.select_adjacent_turn(true),
+        KeyAction::SelectNextTurn => ui_state.timeline.select_adjacent_turn(false),
+        KeyAction::ToggleTurnSteps => ui_state.timeline.toggle_selected_turn_steps(),
+        KeyAction::Quit => ui_state.should_quit = true,
+    }
+}
+
+/// Scrolls or closes the pager; `area` is where its text is drawn.
+fn handle_pager_key(ui_state: &mut UiState, key: KeyEvent, area: Rect) {
+    let Some(pager) = &mut ui_state.pager else {
+        return;
+    };
+    let page = usize::from(area.height).max(1);
+    let max_scroll = pager
+        .wrapped_lines(usize::from(area.width))
+        .len()
+        .saturating_sub(page);
+    pager.scroll = match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            ui_state.pager = None;
+            return;
+        }
+        KeyCode::Up | KeyCode::Char('k') => pager.scroll.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => pager.scroll + 1,
+        KeyCode::PageUp => pager.scroll.saturating_sub(page),
+        KeyCode::PageDown | KeyCode::Char(' ') => pager.scroll + page,
+        KeyCode::Home | KeyCode::Char('g') => 0,
+        KeyCode::End | KeyCode::Char('G') => max_scroll,
+        _ => pager.scroll,
+    }
+    .min(max_scroll);
+}
+
+fn handle_history_search_key(ui_state: &mut UiState, key: KeyEvent) {
+    let Some(search) = &ui_state.history_search else {
+        return;
+    };
+    match key.code {
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.search_older_history_match();
+        }
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui_state.finish_history_search(false);
+            ui_state.should_quit = true;
+        }
+        KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let mut query = search.query.clone();
+            query.push(ch);
+            ui_state.update_history_search(query);
+        }
+        KeyCode::Backspace => {
+            let mut query = search.query.clone();
+            query.pop();
+            ui_state.update_history_search(query);
+        }
+        KeyCode::Enter => ui_state.finish_history_search(true),
+        KeyCode::Esc => ui_state.finish_history_search(false),
+        _ => {}
+    }
+}
+
 async fn handle_enter(
     terminal: &mut Terminal<impl ratatui::backend::Backend>,
     state: &mut AppState,
@@ -339,7 +1380,7 @@ async fn handle_enter(
         .python
         .check_input_completeness(ui_state.current_input())
     {
-        Ok(InputCompleteness::Incomplete) => insert_python_newline(ui_state),
+        Ok(InputCompleteness::Incomplete) => insert_newline(ui_state),
         Ok(InputCompleteness::Complete) | Ok(InputCompleteness::Invalid) => {
             submit_current_line(terminal, state, ui_state).await?;
         }
@@ -357,12 +1398,101 @@ async fn handle_enter(
     Ok(())
 }
 
-fn insert_python_newline(ui_state: &mut UiState) {
-    if ui_state.mode != Mode::Python {
+fn is_word_separator(ch: char) -> bool {
+    ch.is_whitespace()
+        || matches!(
+            ch,
+            '.' | '(' | ')' | '[' | ']' | '{' | '}' | ',' | ':' | '='
+        )
+}
+
+/// Tab completes Python input; it switches modes when the input is empty or a command.
+fn wants_completion(ui_state: &UiState) -> bool {
+    ui_state.mode == Mode::Python
+        && !ui_state.python_input.trim().is_empty()
+        && !is_command_line(&ui_state.python_input)
+}
+
+/// Completes the identifier or dotted name before the cursor. A single match is
+/// inserted; several matches insert their common prefix and are listed in the footer,
+/// and pressing Tab again cycles through them. With no word before the cursor, Tab
+/// indents instead.
+fn complete_python_input(state: &AppState, ui_state: &mut UiState) {
+    if let Some(completion) = &mut ui_state.completion {
+        let next = completion
+            .selected
+            .map_or(0, |index| (index + 1) % completion.candidates.len());
+        completion.selected = Some(next);
+        let (start, candidate) = (completion.start, completion.candidates[next].clone());
+        ui_state.replace_before_cursor(start, &candidate);
         return;
     }
-    append_newline_with_indent(&mut ui_state.python_input);
-    ui_state.history_index = None;
+
+    let cursor = ui_state.cursor();
+    let start = completion_word_start(&ui_state.python_input[..cursor]);
+    let word = &ui_state.python_input[start..cursor];
+    if word.is_empty() {
+        ui_state.insert_at_cursor("    ");
+        return;
+    }
+
+    let candidates = state.python.complete(word).unwrap_or_default();
+    match candidates.as_slice() {
+        [] => {}
+        [only] => {
+            let only = only.clone();
+            ui_state.replace_before_cursor(start, &only);
+        }
+        _ => {
+            let prefix = common_prefix(&candidates);
+            if prefix.len() > word.len() {
+                ui_state.replace_before_cursor(start, &prefix);
+            }
+            ui_state.completion = Some(Completion {
+                start,
+                candidates,
+                selected: None,
+            });
+        }
+    }
+}
+
+fn completion_word_start(before_cursor: &str) -> usize {
+    before_cursor
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| ch.is_alphanumeric() || *ch == '_' || *ch == '.')
+        .last()
+        .map_or(before_cursor.len(), |(index, _)| index)
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for candidate in rest {
+        let shared = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(candidate.len()), |((index, _), _)| index);
+        prefix = &prefix[..shared];
+    }
+    prefix.to_string()
+}
+
+/// Inserts a line break at the cursor. Python input keeps the indent of the cursor
+/// line; assistant input gets a plain newline.
+fn insert_newline(ui_state: &mut UiState) {
+    let newline = match ui_state.mode {
+        Mode::Python => {
+            let cursor = ui_state.cursor();
+            format!("\n{}", last_line_indent(&ui_state.python_input[..cursor]))
+        }
+        Mode::Assistant => "\n".to_string(),
+    };
+    ui_state.insert_at_cursor(&newline);
 }
 
 async fn submit_current_line(
@@ -370,7 +1500,57 @@ async fn submit_current_line(
     state: &mut AppState,
     ui_state: &mut UiState,
 ) -> Result<()> {
-    let line = match ui_state.mode {
+    let mut redraw = |ui_state: &UiState| -> Result<()> {
+        terminal.draw(|frame| draw_ui(frame, ui_state))?;
+        Ok(())
+    };
+    // Input typed while the assistant works is kept for the main loop, except Ctrl-C,
+    // Esc and Ctrl-G, which cancel the request (Ctrl-C does not quit here).
+    let mut queued = Vec::new();
+    let mut cancel_requested = || {
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            match event::read() {
+                Ok(Event::Key(key)) if is_cancel_key(key) => return true,
+                Ok(event) => queued.push(event),
+                Err(_) => break,
+            }
+        }
+        false
+    };
+    let result = submit_line_cancellable(state, ui_state, &mut redraw, &mut cancel_requested).await;
+    ui_state.queued_events.extend(queued);
+    result
+}
+
+/// Keys that cancel an in-flight assistant turn: Ctrl-C, Esc, or Ctrl-G.
+fn is_cancel_key(key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Esc => true,
+        KeyCode::Char('c' | 'g') => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+/// Submits the current input, calling `redraw` whenever an in-flight assistant turn
+/// changes so the caller can refresh its view.
+async fn submit_line(
+    state: &mut AppState,
+    ui_state: &mut UiState,
+    redraw: &mut impl FnMut(&UiState) -> Result<()>,
+) -> Result<()> {
+    submit_line_cancellable(state, ui_state, redraw, &mut || false).await
+}
+
+/// Like [`submit_line`], but an in-flight assistant turn is abandoned and marked
+/// cancelled as soon as `cancel_requested` returns true. It is polled on every redraw
+/// tick.
+async fn submit_line_cancellable(
+    state: &mut AppState,
+    ui_state: &mut UiState,
+    redraw: &mut impl FnMut(&UiState) -> Result<()>,
+    cancel_requested: &mut impl FnMut() -> bool,
+) -> Result<()> {
+    let mut line = match ui_state.mode {
         Mode::Python => {
             let line = ui_state.python_input.clone();
             ui_state.python_input.clear();
@@ -382,20 +1562,36 @@ async fn submit_current_line(
             line
         }
     };
-
-    if line.trim().is_empty() {
-        return Ok(());
+    ui_state.input_cursor = 0;
+    if ui_state.vi_mode.is_some() {
+        ui_state.vi_mode = Some(ViMode::Insert);
     }
 
-    if line.eq_ignore_ascii_case("exit") || line.eq_ignore_ascii_case("quit") {
-        ui_state.should_quit = true;
-        return Ok(());
-    }
+    loop {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        if line.eq_ignore_ascii_case("exit") || line.eq_ignore_ascii_case("quit") {
+            ui_state.should_quit = true;
+            return Ok(());
+        }
 
-    if is_command_line(&line) {
+        if !is_command_line(&line) {
+            break;
+        }
         ui_state.push_history(&line);
         execute_command(state, ui_state, &line);
-        return Ok(());
+        if std::mem::take(&mut ui_state.pending_llm_ping) {
+            ping_llm(state, ui_state).await;
+        }
+        if let Some((models, question)) = ui_state.pending_compare.take() {
+            compare_models(state, ui_state, &models, &question).await;
+        }
+        match ui_state.pending_rerun.take() {
+            Some(entry) => line = entry,
+            None => return Ok(()),
+        }
     }
 
     if ui_state.mode == Mode::Python {
@@ -408,76 +1604,38 @@ async fn submit_current_line(
     ui_state.push_history(&line);
 
     match ui_state.mode {
-        Mode::Python => match state.python.run_user_input(&line) {
-            Ok(UserRunResult::Evaluated(result)) => {
-                if !result.stdout.is_empty() {
-                    push_output(
-                        ui_state,
-                        &state.trace,
-                        OutputKind::PythonStdout,
-                        &result.stdout,
-                    );
-                }
-                if !result.stderr.is_empty() {
-                    push_output(
-                        ui_state,
-                        &state.trace,
-                        OutputKind::PythonStderr,
-                        &result.stderr,
-                    );
-                }
-                push_output(
-                    ui_state,
-                    &state.trace,
-                    OutputKind::PythonValue,
-                    &result.value_repr,
-                );
-            }
-            Ok(UserRunResult::Executed(result)) => {
-                if !result.stdout.is_empty() {
-                    push_output(
-                        ui_state,
-                        &state.trace,
-                        OutputKind::PythonStdout,
-                        &result.stdout,
-                    );
-                }
-                if !result.stderr.is_empty() {
-                    push_output(
-                        ui_state,
-                        &state.trace,
-                        OutputKind::PythonStderr,
-                        &result.stderr,
-                    );
-                }
-            }
-            Ok(UserRunResult::Failed {
-                stdout,
-                stderr,
-                exception,
-            }) => {
-                if !stdout.is_empty() {
-                    push_output(ui_state, &state.trace, OutputKind::PythonStdout, &stdout);
-                }
-                if !stderr.is_empty() {
-                    push_output(ui_state, &state.trace, OutputKind::PythonStderr, &stderr);
-                }
-                push_output(
-                    ui_state,
-                    &state.trace,
-                    OutputKind::PythonTraceback,
-                    &exception.traceback,
-                );
+        Mode::Python => {
+            let (code, suppress_value) = match line.trim_end().strip_suffix(';') {
+                Some(code) if state.semicolon_suppresses_output => (code, true),
+                _ => (line.as_str(), false),
+            };
+            let started = Instant::now();
+            let mut result = if state.stream_output {
+                run_python_streaming(state, ui_state, code, redraw, cancel_requested)?
+            } else {
+                state.python.run_user_input(code)
+            };
+            let elapsed = started.elapsed();
+            if suppress_value && let Ok(UserRunResult::Evaluated(evaluated)) = result {
+                result = Ok(UserRunResult::Executed(ExecResult {
+                    stdout: evaluated.stdout,
+                    stderr: evaluated.stderr,
+                    warnings: evaluated.warnings,
+                }));
             }
-            Err(err) => {
+            push_python_run_result(state, ui_state, result, Some(&line));
+            if let Some(threshold) = state.auto_time_threshold
+                && elapsed >= threshold
+            {
                 push_output(
                     ui_state,
                     &state.trace,
-                    OutputKind::SystemError,
-                    &format!("error: {err}"),
+                    OutputKind::SystemInfo,
+                    &format!("(took {})", format_elapsed(elapsed)),
                 );
             }
-        },
+            push_watch_values(state, ui_state);
+        }
         Mode::Assistant => {
             let Some(provider) = &state.llm else {
                 push_output(
@@ -490,71 +1648,91 @@ async fn submit_current_line(
             };
 
             let turn_index = ui_state.push_assistant_turn(line.clone());
-            terminal.draw(|frame| draw_ui(frame, ui_state))?;
-
-            let mut on_event = |event: AgentProgressEvent| {
-                match event {
-                    AgentProgressEvent::StepStarted { .. } => {}
-                    AgentProgressEvent::ModelResponse { .. } => {
-                        // Keep model response metadata internal; show only tool-level progress.
+            redraw(ui_state)?;
+
+            // Scoped so the question future, which borrows `state`, is dropped before
+            // the answer is remembered.
+            let result = {
+                let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+                let mut on_event = |event: AgentProgressEvent| {
+                    let _ = event_tx.send(event);
+                };
+                let question = async {
+                    if state.agent_config.chat_only {
+                        run_chat_question(
+                            provider,
+                            &line,
+                            &state.conversation,
+                            &state.agent_config,
+                            &mut on_event,
+                        )
+                        .await
+                    } else {
+                        run_question_with_events(
+                            provider,
+                            &state.python,
+                            &line,
+                            &state.conversation,
+                            &state.agent_config,
+                            &mut on_event,
+                        )
+                        .await
                     }
-                    AgentProgressEvent::ToolRequest {
-                        step: _,
-                        name,
-                        args_json,
-                        id: _,
-                    } => {
-                        state.trace.log_output(
-                            output_trace_kind(OutputKind::AssistantProgressRequest),
-                            &format_tool_request_line(&name, &args_json),
-                        );
-                        if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
-                            turn.events.push(AssistantStepEvent::ToolRequest {
-                                text: format_tool_request_line(&name, &args_json),
-                            });
+                };
+                tokio::pin!(question);
+
+                // Redraw periodically so time-based rendering (the thinking delay) updates
+                // even while the model is silent.
+                let mut redraw_interval = tokio::time::interval(ASSISTANT_REDRAW_INTERVAL);
+                // `None` means the user cancelled; dropping the future aborts the request.
+                let result = loop {
+                    tokio::select! {
+                        result = &mut question => break Some(result),
+                        Some(event) = event_rx.recv() => {
+                            apply_agent_progress_event(ui_state, &state.trace, turn_index, event);
+                            redraw(ui_state)?;
                         }
-                    }
-                    AgentProgressEvent::ToolResult {
-                        step: _,
-                        name,
-                        response_json,
-                        id: _,
-                    } => {
-                        state.trace.log_output(
-                            output_trace_kind(OutputKind::AssistantProgressResult),
-                            &format_tool_result_line(&name, &response_json),
-                        );
-                        if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
-                            turn.events.push(AssistantStepEvent::ToolResult {
-                                text: format_tool_result_line(&name, &response_json),
-                            });
+                        _ = redraw_interval.tick() => {
+                            if cancel_requested() {
+                                break None;
+                            }
+                            redraw(ui_state)?;
                         }
                     }
+                };
+                while let Ok(event) = event_rx.try_recv() {
+                    apply_agent_progress_event(ui_state, &state.trace, turn_index, event);
                 }
-                let _ = terminal.draw(|frame| draw_ui(frame, ui_state));
+                result
             };
 
-            match run_question_with_events(
-                provider,
-                &state.python,
-                &line,
-                &state.agent_config,
-                &mut on_event,
-            )
-            .await
-            {
-                Ok(answer) => {
+            match result {
+                None => {
                     state
                         .trace
-                        .log_output(output_trace_kind(OutputKind::AssistantText), &answer.text);
+                        .log_output(output_trace_kind(OutputKind::SystemInfo), "cancelled");
+                    if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
+                        turn.state = AssistantTurnState::CompletedError("cancelled".to_string());
+                    }
+                }
+                Some(Ok(answer)) => {
+                    state.trace.log_assistant_answer(
+                        &answer.text,
+                        answer.degraded.map(DegradedReason::name),
+                    );
                     let turn_usage = answer.token_usage.clone();
                     ui_state.session_token_usage.add_totals(&turn_usage);
+                    ui_state.turn_token_usage.push(turn_usage.clone());
+                    ui_state.last_answer = Some(answer.text.clone());
+                    if answer.degraded.is_none() {
+                        remember_turn(state, &line, &answer.text);
+                    }
                     if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
                         turn.token_usage = Some(turn_usage);
                         turn.state = AssistantTurnState::CompletedText(answer.text);
                     }
                 }
-                Err(err) => {
+                Some(Err(err)) => {
                     let message = format!("Assistant request failed: {err}");
                     state
                         .trace
@@ -570,1591 +1748,5099 @@ async fn submit_current_line(
     Ok(())
 }
 
-fn execute_command(state: &mut AppState, ui_state: &mut UiState, line: &str) {
-    ui_state.timeline.push_user_input_command(line);
-    state.trace.log_output("cmd.in", line);
+/// Adds a question and its answer to the conversation memory, dropping the oldest turns
+/// beyond `memory_turns` so the prompt size stays bounded.
+fn remember_turn(state: &mut AppState, question: &str, answer: &str) {
+    if state.memory_turns == 0 {
+        return;
+    }
+    for (role, text) in [
+        (AssistantRole::User, question),
+        (AssistantRole::Model, answer),
+    ] {
+        state.conversation.push(AssistantMessage {
+            role,
+            parts: vec![AssistantPart::Text {
+                text: text.to_string(),
+                thought_signature: None,
+            }],
+        });
+    }
+    let excess = state
+        .conversation
+        .len()
+        .saturating_sub(state.memory_turns * 2);
+    state.conversation.drain(..excess);
+}
 
-    let command = match parse_command(line) {
-        Ok(command) => command,
-        Err(err) => {
-            push_output(
-                ui_state,
-                &state.trace,
-                OutputKind::SystemError,
-                err.message(),
-            );
-            return;
+fn apply_agent_progress_event(
+    ui_state: &mut UiState,
+    trace: &SessionTrace,
+    turn_index: usize,
+    event: AgentProgressEvent,
+) {
+    match event {
+        AgentProgressEvent::StepStarted { .. } => {
+            // Text streamed by an earlier step was followed by tool calls, so it is not the answer.
+            if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
+                turn.streamed_text.clear();
+            }
         }
-    };
-
-    match command {
-        Command::Help => {
-            push_output(ui_state, &state.trace, OutputKind::SystemInfo, HELP_TEXT);
+        AgentProgressEvent::ModelResponse { .. } => {
+            // Keep model response metadata internal; show only tool-level progress.
         }
-        Command::Mode(mode) => match mode {
-            Some(CommandMode::Python) => {
-                ui_state.mode = Mode::Python;
-                ui_state.history_index = None;
-                push_output(ui_state, &state.trace, OutputKind::SystemInfo, "mode: py");
+        AgentProgressEvent::TurnRetried => {
+            // The retry starts over from the question, so the failed attempt's text and
+            // steps would otherwise be shown twice.
+            if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
+                turn.streamed_text.clear();
+                turn.events.clear();
             }
-            Some(CommandMode::Assistant) => {
-                ui_state.mode = Mode::Assistant;
-                ui_state.history_index = None;
-                push_output(ui_state, &state.trace, OutputKind::SystemInfo, "mode: ai");
+        }
+        AgentProgressEvent::TextDelta { step: _, text } => {
+            if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
+                turn.streamed_text.push_str(&text);
             }
-            None => {
-                let current = match ui_state.mode {
-                    Mode::Python => "py",
-                    Mode::Assistant => "ai",
-                };
-                push_output(
-                    ui_state,
-                    &state.trace,
-                    OutputKind::SystemInfo,
-                    &format!("mode: {current}"),
-                );
+        }
+        AgentProgressEvent::ToolRequest {
+            step: _,
+            name,
+            args_json,
+            id: _,
+        } => {
+            trace.log_output(
+                output_trace_kind(OutputKind::AssistantProgressRequest),
+                &format_tool_request_line(&name, &args_json),
+            );
+            if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
+                turn.events.push(AssistantStepEvent::ToolRequest {
+                    text: format_tool_request_line(&name, &args_json),
+                });
             }
-        },
-        Command::Clear => {
-            ui_state.timeline.clear();
-            push_output(ui_state, &state.trace, OutputKind::SystemInfo, "cleared");
-        }
-        Command::History(limit) => {
-            let text = format_history_output(&ui_state.history, limit);
-            push_output(ui_state, &state.trace, OutputKind::SystemInfo, &text);
-        }
-        Command::Trace => {
-            push_output(
-                ui_state,
-                &state.trace,
-                OutputKind::SystemInfo,
-                &state.trace.file_path().display().to_string(),
-            );
         }
-        Command::Usage => {
-            push_output(
-                ui_state,
-                &state.trace,
-                OutputKind::SystemInfo,
-                &format_session_token_usage(&ui_state.session_token_usage),
+        AgentProgressEvent::ToolResult {
+            step: _,
+            name,
+            response_json,
+            id: _,
+        } => {
+            let text = format_tool_result_line(&name, &response_json, ui_state.verbose_steps);
+            trace.log_output(
+                output_trace_kind(OutputKind::AssistantProgressResult),
+                &text,
             );
+            if let Some(turn) = ui_state.assistant_turn_mut(turn_index) {
+                turn.events.push(AssistantStepEvent::ToolResult { text });
+            }
         }
-        Command::Inspect { expr } => match state.python.inspect(&expr) {
-            Ok(info) => match serde_json::to_string_pretty(&info.value) {
-                Ok(pretty) => push_output(ui_state, &state.trace, OutputKind::SystemInfo, &pretty),
-                Err(err) => push_output(
-                    ui_state,
-                    &state.trace,
-                    OutputKind::SystemError,
-                    &format!("failed to format inspect result: {err}"),
-                ),
-            },
-            Err(CapabilityError::PythonException(exc)) => {
-                push_output(
-                    ui_state,
-                    &state.trace,
-                    OutputKind::PythonTraceback,
-                    &exc.traceback,
-                );
+    }
+}
+
+/// Runs `line` on a worker thread, pushing each complete stdout line to the timeline as
+/// it is printed. The stdout already shown is removed from the returned result. Since
+/// signals only reach the main thread, `cancel_requested` is polled instead and raises
+/// `KeyboardInterrupt` in the worker.
+fn run_python_streaming(
+    state: &AppState,
+    ui_state: &mut UiState,
+    line: &str,
+    redraw: &mut impl FnMut(&UiState) -> Result<()>,
+    cancel_requested: &mut impl FnMut() -> bool,
+) -> Result<Result<UserRunResult>> {
+    let (chunk_tx, chunk_rx) = std::sync::mpsc::channel();
+    let python = &state.python;
+    let interrupt = PythonInterrupt::default();
+    std::thread::scope(|scope| {
+        let interrupt = &interrupt;
+        let worker =
+            scope.spawn(move || python.run_user_input_streaming(line, chunk_tx, interrupt));
+        let mut pending = String::new();
+        let mut streamed = String::new();
+        loop {
+            if cancel_requested() {
+                interrupt.interrupt();
             }
-            Err(err) => {
-                push_output(
-                    ui_state,
-                    &state.trace,
-                    OutputKind::SystemError,
-                    &format!("inspect failed: {err}"),
-                );
+            match chunk_rx.recv_timeout(PYTHON_STREAM_FLUSH_INTERVAL) {
+                Ok(chunk) => pending.push_str(&chunk),
+                Err(RecvTimeoutError::Timeout) if !worker.is_finished() => {}
+                Err(_) => break,
             }
-        },
-        Command::LastError => match state.python.get_last_exception() {
-            Ok(Some(exc)) => {
-                push_output(
-                    ui_state,
-                    &state.trace,
-                    OutputKind::PythonTraceback,
-                    &exc.traceback,
-                );
+            pending.extend(chunk_rx.try_iter());
+            if let Some(end) = pending.rfind('\n') {
+                let complete: String = pending.drain(..=end).collect();
+                push_output(ui_state, &state.trace, OutputKind::PythonStdout, &complete);
+                streamed.push_str(&complete);
+                redraw(ui_state)?;
             }
-            Ok(None) => {
+        }
+        let result = worker
+            .join()
+            .map_err(|_| anyhow::anyhow!("python worker thread panicked"))?;
+        Ok(result.map(|result| strip_streamed_stdout(result, &streamed)))
+    })
+}
+
+fn strip_streamed_stdout(result: UserRunResult, streamed: &str) -> UserRunResult {
+    let strip = |stdout: String| match stdout.strip_prefix(streamed) {
+        Some(rest) => rest.to_string(),
+        None => stdout,
+    };
+    match result {
+        UserRunResult::Evaluated(result) => UserRunResult::Evaluated(EvalResult {
+            stdout: strip(result.stdout),
+            ..result
+        }),
+        UserRunResult::Executed(result) => UserRunResult::Executed(ExecResult {
+            stdout: strip(result.stdout),
+            ..result
+        }),
+        UserRunResult::Failed {
+            stdout,
+            stderr,
+            warnings,
+            exception,
+        } => UserRunResult::Failed {
+            stdout: strip(stdout),
+            stderr,
+            warnings,
+            exception,
+        },
+    }
+}
+
+/// Shows the outcome of running Python input. `source` is the input the user typed,
+/// echoed before the value when `echo_expr` is set.
+fn push_python_run_result(
+    state: &AppState,
+    ui_state: &mut UiState,
+    result: Result<UserRunResult>,
+    source: Option<&str>,
+) {
+    match result {
+        Ok(UserRunResult::Evaluated(result)) => {
+            if !result.stdout.is_empty() {
                 push_output(
                     ui_state,
                     &state.trace,
-                    OutputKind::SystemInfo,
-                    "no python exception recorded",
+                    OutputKind::PythonStdout,
+                    &result.stdout,
                 );
             }
-            Err(err) => {
+            if !result.stderr.is_empty() {
                 push_output(
                     ui_state,
                     &state.trace,
-                    OutputKind::SystemError,
-                    &format!("failed to read last error: {err}"),
+                    OutputKind::PythonStderr,
+                    &result.stderr,
                 );
             }
-        },
-        Command::Include { path } => execute_include_command(state, ui_state, &path),
-        Command::ShowSource { name } => execute_source_command(state, ui_state, &name),
-        Command::Steps(steps) => {
-            if let Some(value) = steps {
-                ui_state.show_assistant_steps = value;
-            } else {
-                ui_state.show_assistant_steps = !ui_state.show_assistant_steps;
-            }
-            let steps_text = if ui_state.show_assistant_steps {
-                "on"
+            push_python_warnings(ui_state, &state.trace, &result.warnings);
+            let value_repr = truncate_repr(&result.value_repr, state.repr_max_chars);
+            let value_text = if result.tabular {
+                value_repr
+                    .lines()
+                    .map(expand_tabs)
+                    .collect::<Vec<_>>()
+                    .join("\n")
             } else {
-                "off"
+                value_repr
             };
-            push_output(
-                ui_state,
-                &state.trace,
-                OutputKind::SystemInfo,
-                &format!("steps: {steps_text}"),
-            );
-        }
-    }
-}
-
-fn execute_include_command(state: &mut AppState, ui_state: &mut UiState, path: &str) {
-    let path_ref = Path::new(path);
-    let source = match fs::read_to_string(path_ref) {
-        Ok(content) => content,
-        Err(err) => {
-            let message = if err.kind() == ErrorKind::NotFound {
-                format!("file not found: {}", path_ref.display())
-            } else {
-                format!("failed to read {}: {err}", path_ref.display())
+            let value_text = match source {
+                Some(source) if state.echo_expr => echo_expr_value(source, &value_text),
+                _ => value_text,
             };
-            push_output(ui_state, &state.trace, OutputKind::SystemError, &message);
-            return;
+            let title = source
+                .and_then(|source| source.lines().next())
+                .unwrap_or("value");
+            push_output_or_page(state, ui_state, OutputKind::PythonValue, title, &value_text);
+            ui_state.last_value_repr = Some(result.value_repr);
         }
-    };
-
-    render_include_command_result(
-        ui_state,
-        &state.trace,
-        path_ref,
-        state.python.run_exec_input(&source),
-    );
-}
-
-fn render_include_command_result(
-    ui_state: &mut UiState,
-    trace: &SessionTrace,
-    path_ref: &Path,
-    result: Result<UserRunResult>,
-) {
-    match result {
         Ok(UserRunResult::Executed(result)) => {
             if !result.stdout.is_empty() {
-                push_output(ui_state, trace, OutputKind::PythonStdout, &result.stdout);
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::PythonStdout,
+                    &result.stdout,
+                );
             }
             if !result.stderr.is_empty() {
-                push_output(ui_state, trace, OutputKind::PythonStderr, &result.stderr);
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::PythonStderr,
+                    &result.stderr,
+                );
             }
-            push_output(
-                ui_state,
-                trace,
-                OutputKind::SystemInfo,
-                &format!("included {}", path_ref.display()),
-            );
+            push_python_warnings(ui_state, &state.trace, &result.warnings);
         }
         Ok(UserRunResult::Failed {
             stdout,
             stderr,
+            warnings,
             exception,
         }) => {
             if !stdout.is_empty() {
-                push_output(ui_state, trace, OutputKind::PythonStdout, &stdout);
+                push_output(ui_state, &state.trace, OutputKind::PythonStdout, &stdout);
             }
             if !stderr.is_empty() {
-                push_output(ui_state, trace, OutputKind::PythonStderr, &stderr);
+                push_output(ui_state, &state.trace, OutputKind::PythonStderr, &stderr);
             }
+            push_python_warnings(ui_state, &state.trace, &warnings);
             push_output(
                 ui_state,
-                trace,
+                &state.trace,
                 OutputKind::PythonTraceback,
                 &exception.traceback,
             );
         }
-        Ok(UserRunResult::Evaluated(_)) => {
+        Err(err) => {
             push_output(
                 ui_state,
-                trace,
+                &state.trace,
                 OutputKind::SystemError,
-                "internal error: include unexpectedly evaluated expression",
+                &format!("error: {err}"),
             );
         }
-        Err(err) => {
+    }
+}
+
+fn push_python_warnings(ui_state: &mut UiState, trace: &SessionTrace, warnings: &[String]) {
+    for warning in warnings {
+        push_output(ui_state, trace, OutputKind::PythonWarning, warning);
+    }
+}
+
+/// Sends a one-word prompt with no tools through the streaming call answers use, and
+/// reports how long the complete response took. The exchange is not remembered and
+/// does not count toward `/tokens`.
+async fn ping_llm(state: &AppState, ui_state: &mut UiState) {
+    let Some(provider) = &state.llm else {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            "llm ping unavailable: missing GEMINI_API_KEY",
+        );
+        return;
+    };
+    let input = AssistantInput {
+        system_instruction: None,
+        messages: vec![AssistantMessage {
+            role: AssistantRole::User,
+            parts: vec![AssistantPart::Text {
+                text: "ping".to_string(),
+                thought_signature: None,
+            }],
+        }],
+        tools: vec![],
+        tool_calling_mode: ToolCallingMode::None,
+    };
+    let started = Instant::now();
+    let result = provider.generate_stream(input, &mut |_| {}).await;
+    let elapsed = format_elapsed(started.elapsed());
+    match result {
+        Ok(output) => {
+            let mut usage = LlmTokenUsageTotals::default();
+            usage.add_usage(output.usage.as_ref());
             push_output(
                 ui_state,
-                trace,
-                OutputKind::SystemError,
-                &format!("include failed: {err}"),
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format!("llm round trip {elapsed} ({})", format_token_counts(&usage)),
             );
         }
+        Err(err) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!("llm ping failed after {elapsed}: {err}"),
+        ),
     }
 }
 
-fn execute_source_command(state: &mut AppState, ui_state: &mut UiState, name: &str) {
-    if !is_safe_source_target(name) {
+/// Asks each model the question in turn, with the session's tools and memory, and shows
+/// the answers one after the other with their latency and token usage. Like `/ping-llm`,
+/// the exchanges are not remembered or counted in `/tokens`.
+async fn compare_models(
+    state: &AppState,
+    ui_state: &mut UiState,
+    models: &[String; 2],
+    question: &str,
+) {
+    let Some(provider) = &state.llm else {
         push_output(
             ui_state,
             &state.trace,
             OutputKind::SystemError,
-            "usage: /show_source <name>",
+            "compare unavailable: missing GEMINI_API_KEY",
         );
         return;
-    }
-
-    let code = format!("print(__import__('inspect').getsource({name}), end='')");
-    match state.python.exec_code(&code) {
-        Ok(result) => {
-            if !result.stdout.is_empty() {
+    };
+    state.trace.log_input_assistant(question);
+    for model in models {
+        let provider = provider.clone().with_model(model.as_str());
+        let started = Instant::now();
+        let result = if state.agent_config.chat_only {
+            run_chat_question(
+                &provider,
+                question,
+                &state.conversation,
+                &state.agent_config,
+                &mut |_| {},
+            )
+            .await
+        } else {
+            run_question_with_events(
+                &provider,
+                &state.python,
+                question,
+                &state.conversation,
+                &state.agent_config,
+                &mut |_| {},
+            )
+            .await
+        };
+        let elapsed = format_elapsed(started.elapsed());
+        match result {
+            Ok(answer) => {
                 push_output(
                     ui_state,
                     &state.trace,
                     OutputKind::SystemInfo,
-                    &result.stdout,
+                    &format!(
+                        "{model}: {elapsed} ({})",
+                        format_token_counts(&answer.token_usage)
+                    ),
                 );
-            }
-            if !result.stderr.is_empty() {
                 push_output(
                     ui_state,
                     &state.trace,
-                    OutputKind::PythonStderr,
-                    &result.stderr,
+                    OutputKind::AssistantText,
+                    &answer.text,
                 );
             }
-        }
-        Err(err) => {
-            push_output(
+            Err(err) => push_output(
                 ui_state,
                 &state.trace,
                 OutputKind::SystemError,
-                &format!("source failed: {err}"),
-            );
+                &format!("{model}: failed after {elapsed}: {err}"),
+            ),
         }
     }
 }
 
-fn is_safe_source_target(name: &str) -> bool {
-    if name.is_empty() {
-        return false;
+/// Formats a value after its expression, as `x + 1 => 4`; multi-line values start on
+/// their own line.
+fn echo_expr_value(source: &str, value: &str) -> String {
+    let source = source.trim();
+    if value.contains('\n') {
+        format!("{source} =>\n{value}")
+    } else {
+        format!("{source} => {value}")
     }
-
-    name.split('.').all(is_ascii_identifier)
 }
 
-fn is_ascii_identifier(segment: &str) -> bool {
-    let mut chars = segment.bytes();
-    let Some(first) = chars.next() else {
-        return false;
-    };
-    if !first.is_ascii_alphabetic() && first != b'_' {
-        return false;
+/// Formats a wall-clock duration as milliseconds below one second, seconds above.
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs < 1.0 {
+        format!("{:.1}ms", secs * 1000.0)
+    } else {
+        format!("{secs:.1}s")
     }
+}
 
-    chars.all(|ch| ch.is_ascii_alphanumeric() || ch == b'_')
+fn execute_time_command(state: &mut AppState, ui_state: &mut UiState, expr: &str) {
+    let started = Instant::now();
+    let result = state.python.run_user_input(expr);
+    let elapsed = started.elapsed();
+    push_python_run_result(state, ui_state, result, Some(expr));
+    push_output(
+        ui_state,
+        &state.trace,
+        OutputKind::SystemInfo,
+        &format!("wall time: {}", format_elapsed(elapsed)),
+    );
 }
 
-fn format_history_output(history: &[String], limit: Option<usize>) -> String {
-    if history.is_empty() {
-        return "history is empty".to_string();
+fn execute_diff_command(state: &AppState, ui_state: &mut UiState, left: &str, right: &str) {
+    let mut texts = Vec::with_capacity(2);
+    for expr in [left, right] {
+        match state.python.diff_text(expr) {
+            Ok(text) => texts.push(text),
+            Err(CapabilityError::PythonException(exc)) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::PythonTraceback,
+                    &exc.traceback,
+                );
+                return;
+            }
+            Err(err) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("diff failed: {err}"),
+                );
+                return;
+            }
+        }
     }
 
-    let count = limit.unwrap_or(history.len()).min(history.len());
-    let start = history.len().saturating_sub(count);
+    let text = match line_diff(&texts[0], &texts[1]) {
+        Some(diff) => format!("--- {left}\n+++ {right}\n{diff}"),
+        None => "no differences".to_string(),
+    };
+    push_output(ui_state, &state.trace, OutputKind::SystemInfo, &text);
+}
+
+fn execute_command(state: &mut AppState, ui_state: &mut UiState, line: &str) {
+    ui_state.timeline.push_user_input_command(line);
+    state.trace.log_output("cmd.in", line);
+
+    let command = match parse_command(line) {
+        Ok(command) => command,
+        Err(err) => {
+            let message = match err.suggestion() {
+                Some(suggestion) => format!("{} (did you mean /{suggestion}?)", err.message()),
+                None => err.message().to_string(),
+            };
+            push_output(ui_state, &state.trace, OutputKind::SystemError, &message);
+            return;
+        }
+    };
+
+    match command {
+        Command::Help(None) => {
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, HELP_TEXT);
+        }
+        Command::Help(Some(name)) => match command_help(&name) {
+            Some(help) => push_output(ui_state, &state.trace, OutputKind::SystemInfo, help),
+            None => {
+                let mut message = format!(
+                    "unknown command '/{}'. Try /help",
+                    name.trim_start_matches('/')
+                );
+                if let Some(suggestion) = suggest_command(&name) {
+                    message.push_str(&format!(" (did you mean /{suggestion}?)"));
+                }
+                push_output(ui_state, &state.trace, OutputKind::SystemError, &message);
+            }
+        },
+        Command::Mode(mode) => match mode {
+            Some(CommandMode::Python) => {
+                ui_state.mode = Mode::Python;
+                ui_state.history_index = None;
+                push_output(ui_state, &state.trace, OutputKind::SystemInfo, "mode: py");
+            }
+            Some(CommandMode::Assistant) => {
+                ui_state.mode = Mode::Assistant;
+                ui_state.history_index = None;
+                push_output(ui_state, &state.trace, OutputKind::SystemInfo, "mode: ai");
+            }
+            None => {
+                let current = match ui_state.mode {
+                    Mode::Python => "py",
+                    Mode::Assistant => "ai",
+                };
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    &format!("mode: {current}"),
+                );
+            }
+        },
+        Command::Clear => {
+            ui_state.timeline.clear();
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, "cleared");
+        }
+        Command::Reset { all } => match state.python.reset() {
+            Ok(()) => {
+                if all {
+                    ui_state.timeline.clear();
+                }
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    "python session reset",
+                );
+            }
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("reset failed: {err}"),
+            ),
+        },
+        Command::Globals { pattern } => match state.python.list_globals() {
+            Ok(globals) => {
+                let text = format_globals_output(&globals, pattern.as_deref());
+                push_output(ui_state, &state.trace, OutputKind::SystemInfo, &text);
+            }
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("globals failed: {err}"),
+            ),
+        },
+        Command::Tokens => {
+            let text =
+                format_token_breakdown(&ui_state.turn_token_usage, &ui_state.session_token_usage);
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, &text);
+        }
+        Command::Themes => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                "themes (set with [theme] name = \"...\" in the config file):",
+            );
+            for preset in ThemePreset::all() {
+                let current = preset == state.theme_config.preset;
+                ui_state.timeline.push_theme_preview(preset, current);
+                state
+                    .trace
+                    .log_output(output_trace_kind(OutputKind::SystemInfo), preset.name());
+            }
+        }
+        Command::Gc => match state.python.collect_garbage() {
+            Ok((collected, tracked)) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format!("gc: collected {collected} objects, {tracked} objects tracked"),
+            ),
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("gc failed: {err}"),
+            ),
+        },
+        Command::RecursionLimit(limit) => {
+            let set = match limit {
+                Some(limit) => state
+                    .python
+                    .exec_code(&format!("__import__('sys').setrecursionlimit({limit})"))
+                    .map(drop),
+                None => Ok(()),
+            };
+            match set.and_then(|()| {
+                state
+                    .python
+                    .eval_expr("__import__('sys').getrecursionlimit()")
+            }) {
+                Ok(result) => push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    &format!("recursion limit: {}", result.value_repr),
+                ),
+                Err(err) => push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("recursionlimit failed: {err}"),
+                ),
+            }
+        }
+        Command::History(limit) => {
+            let text = format_history_output(&ui_state.history, limit);
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, &text);
+        }
+        Command::Rerun(index) => match ui_state.history.get(index - 1) {
+            Some(entry) if matches!(parse_command(entry), Ok(Command::Rerun(_))) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("history entry {index} is itself a /rerun and cannot be re-run"),
+                );
+            }
+            Some(entry) => ui_state.pending_rerun = Some(entry.clone()),
+            None => {
+                let len = ui_state.history.len();
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("no history entry {index} (history has {len} entries)"),
+                );
+            }
+        },
+        Command::Trace => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &state.trace.file_path().display().to_string(),
+            );
+        }
+        Command::Copy { target } => execute_copy_command(state, ui_state, target),
+        Command::TraceCopy { contents } => execute_trace_copy_command(state, ui_state, contents),
+        Command::TraceFormat { format } => match state.trace.switch_format(format) {
+            Ok(path) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format!("trace continues in {}", path.display()),
+            ),
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("trace format switch failed: {err}"),
+            ),
+        },
+        Command::Usage => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format_session_token_usage(&ui_state.session_token_usage),
+            );
+        }
+        Command::Endpoint => match &state.llm {
+            Some(provider) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    &provider.redacted_endpoint(),
+                );
+            }
+            None => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    "endpoint unavailable: missing GEMINI_API_KEY",
+                );
+            }
+        },
+        Command::Tools => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format_tools(
+                    state.agent_config.allow_eval,
+                    state.agent_config.allow_apply,
+                ),
+            );
+        }
+        Command::Diagnostics => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format_diagnostics(state, ui_state.theme.color_enabled()),
+            );
+        }
+        Command::System { text } => {
+            let message = match &text {
+                Some(text) => format!("system note set: {text}"),
+                None => "system note cleared".to_string(),
+            };
+            state.agent_config.system_note = text;
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, &message);
+        }
+        Command::Forget => {
+            let turns = state.conversation.len() / 2;
+            state.conversation.clear();
+            let message = match turns {
+                0 => "no earlier questions to forget".to_string(),
+                1 => "forgot 1 earlier question".to_string(),
+                _ => format!("forgot {turns} earlier questions"),
+            };
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, &message);
+        }
+        Command::PingLlm => ui_state.pending_llm_ping = true,
+        Command::Compare { models, question } => {
+            ui_state.pending_compare = Some((models, question));
+        }
+        Command::Edit => ui_state.pending_edit = true,
+        Command::ConfigCheck { path } => {
+            // Without a path, repeat this session's lookup: the `--config` file, or the
+            // default location plus any project config, as `--check-config` does.
+            let path = path
+                .map(PathBuf::from)
+                .or_else(|| state.config_is_explicit.then(|| state.config_path.clone()));
+            let (kind, report) = match AppConfig::check(path.as_deref()) {
+                Ok(report) => (OutputKind::SystemInfo, report),
+                Err(err) => (OutputKind::SystemError, err.to_string()),
+            };
+            push_output(ui_state, &state.trace, kind, &report);
+        }
+        Command::ThemeSave { preset } => match save_theme_preset(&state.config_path, preset) {
+            Ok(()) => {
+                state.theme_config.preset = preset;
+                ui_state.theme =
+                    Theme::from_config(ui_state.theme.color_enabled(), &state.theme_config);
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    &format!(
+                        "saved theme preset '{}' to {}",
+                        preset.name(),
+                        state.config_path.display()
+                    ),
+                );
+            }
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("failed to save theme preset: {err:#}"),
+            ),
+        },
+        Command::Inspect { expr, compact } => {
+            let result = state.python.inspect(&expr);
+            push_inspect_result(state, ui_state, result, compact || state.inspect_compact);
+        }
+        Command::InspectWithTimeout { timeout_ms, expr } => {
+            let result = state
+                .python
+                .inspect_with_timeout(&expr, Duration::from_millis(timeout_ms));
+            push_inspect_result(state, ui_state, result, state.inspect_compact);
+        }
+        Command::InspectToFile { path, expr } => {
+            execute_inspect_to_file_command(state, ui_state, &path, &expr);
+        }
+        Command::Page { expr } => match state.python.eval_expr(&expr) {
+            Ok(result) => {
+                let kind = OutputKind::PythonValue;
+                open_pager(state, ui_state, kind, &expr, &result.value_repr);
+            }
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::PythonTraceback,
+                &err.to_string(),
+            ),
+        },
+        Command::Type { expr } => match state.python.type_of(&expr) {
+            Ok(type_name) => {
+                push_output(ui_state, &state.trace, OutputKind::SystemInfo, &type_name);
+            }
+            Err(CapabilityError::PythonException(exc)) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::PythonTraceback,
+                    &exc.traceback,
+                );
+            }
+            Err(err) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("type lookup failed: {err}"),
+                );
+            }
+        },
+        Command::Last => match ui_state.last_value_repr.clone() {
+            Some(value_repr) => {
+                push_output(ui_state, &state.trace, OutputKind::PythonValue, &value_repr)
+            }
+            None => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                "no value evaluated yet",
+            ),
+        },
+        Command::StepsLog => {
+            let events = ui_state
+                .timeline
+                .last_assistant_turn()
+                .map(|turn| turn.events.clone())
+                .unwrap_or_default();
+            if events.is_empty() {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    "no assistant steps recorded",
+                );
+            }
+            for event in events {
+                let (kind, text) = match event {
+                    AssistantStepEvent::ToolRequest { text } => {
+                        (OutputKind::AssistantProgressRequest, text)
+                    }
+                    AssistantStepEvent::ToolResult { text } => {
+                        (OutputKind::AssistantProgressResult, text)
+                    }
+                };
+                push_output(ui_state, &state.trace, kind, &text);
+            }
+        }
+        Command::RawAnswer => match ui_state.last_answer.clone() {
+            Some(answer) => push_output(ui_state, &state.trace, OutputKind::SystemInfo, &answer),
+            None => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                "no assistant answer yet",
+            ),
+        },
+        Command::LastError { io } => match state.python.get_last_exception() {
+            Ok(Some(exc)) => {
+                if io {
+                    push_last_exception_output(state, ui_state);
+                }
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::PythonTraceback,
+                    &exc.traceback,
+                );
+            }
+            Ok(None) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    "no python exception recorded",
+                );
+            }
+            Err(err) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("failed to read last error: {err}"),
+                );
+            }
+        },
+        Command::Include { path } => execute_include_command(state, ui_state, &path),
+        Command::Time { expr } => execute_time_command(state, ui_state, &expr),
+        Command::Diff { left, right } => execute_diff_command(state, ui_state, &left, &right),
+        Command::Pip { args, dry_run } => {
+            let result = state.python.run_pip(&args, dry_run);
+            push_python_run_result(state, ui_state, result, None);
+        }
+        Command::With { name, expr, body } => {
+            let result = state.python.run_with_binding(&name, &expr, &body);
+            push_python_run_result(state, ui_state, result, None);
+        }
+        Command::ShowSource { name } => execute_source_command(state, ui_state, &name),
+        Command::Steps(steps) => {
+            if let Some(value) = steps {
+                ui_state.show_assistant_steps = value;
+                ui_state.verbose_steps = false;
+            } else {
+                ui_state.show_assistant_steps = !ui_state.show_assistant_steps;
+            }
+            let steps_text = match (ui_state.show_assistant_steps, ui_state.verbose_steps) {
+                (false, _) => "off",
+                (true, false) => "on",
+                (true, true) => "on (verbose)",
+            };
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format!("steps: {steps_text}"),
+            );
+        }
+        Command::StepsVerbose => {
+            ui_state.show_assistant_steps = true;
+            ui_state.verbose_steps = true;
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                "steps: on (verbose)",
+            );
+        }
+        Command::Save { path, force } => execute_save_command(state, ui_state, &path, force),
+        Command::SessionSave { path, force } => {
+            execute_session_save_command(state, ui_state, &path, force);
+        }
+        Command::SessionLoad { path } => execute_session_load_command(state, ui_state, &path),
+        Command::ExportScript {
+            path,
+            outputs,
+            skip_failed,
+            force,
+        } => execute_export_script_command(state, ui_state, &path, outputs, skip_failed, force),
+        Command::Watch(watch) => execute_watch_command(state, ui_state, watch),
+    }
+}
+
+fn push_last_exception_output(state: &AppState, ui_state: &mut UiState) {
+    let output = match state.python.get_last_exception_output() {
+        Ok(output) => output.unwrap_or_default(),
+        Err(err) => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("failed to read last error output: {err}"),
+            );
+            return;
+        }
+    };
+    if output.stdout.is_empty() && output.stderr.is_empty() {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            "no output was captured before the exception",
+        );
+    }
+    if !output.stdout.is_empty() {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::PythonStdout,
+            &output.stdout,
+        );
+    }
+    if !output.stderr.is_empty() {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::PythonStderr,
+            &output.stderr,
+        );
+    }
+}
+
+fn execute_trace_copy_command(state: &AppState, ui_state: &mut UiState, contents: bool) {
+    let trace_path = state.trace.file_path();
+    let (what, text) = if contents {
+        match fs::read_to_string(trace_path) {
+            Ok(text) => ("trace contents", text),
+            Err(err) => {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("failed to read trace file: {err}"),
+                );
+                return;
+            }
+        }
+    } else {
+        ("trace path", trace_path.display().to_string())
+    };
+
+    copy_to_clipboard(state, ui_state, what, text);
+}
+
+fn execute_copy_command(state: &AppState, ui_state: &mut UiState, target: CopyTarget) {
+    let (what, text) = match target {
+        CopyTarget::Last => ("last output", ui_state.last_output.clone()),
+        CopyTarget::Answer => ("last answer", ui_state.last_answer.clone()),
+        CopyTarget::TracePath => (
+            "trace path",
+            Some(state.trace.file_path().display().to_string()),
+        ),
+    };
+    match text {
+        Some(text) => copy_to_clipboard(state, ui_state, what, text),
+        None => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            &format!("no {what} to copy"),
+        ),
+    }
+}
+
+/// Copies through the terminal with OSC 52 when enabled, otherwise with a clipboard
+/// tool or, failing that, a temp file.
+fn copy_to_clipboard(state: &AppState, ui_state: &mut UiState, what: &str, text: String) {
+    if state.clipboard_osc52 {
+        ui_state.pending_clipboard = Some(text);
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            &format!("copied {what} to the clipboard (OSC 52)"),
+        );
+        return;
+    }
+
+    let fallback_path =
+        std::env::temp_dir().join(format!("pychat.ai-{}-copy.txt", state.session_id));
+    match copy_text(&text, &fallback_path) {
+        Ok(CopiedTo::Clipboard(tool)) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            &format!("copied {what} to the clipboard ({tool})"),
+        ),
+        Ok(CopiedTo::File(path)) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            &format!("clipboard unavailable; wrote {what} to {}", path.display()),
+        ),
+        Err(err) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!("failed to copy {what}: {err}"),
+        ),
+    }
+}
+
+/// Writes the OSC 52 sequence for text queued by a copy command, if any.
+fn flush_pending_clipboard(ui_state: &mut UiState, out: &mut impl Write) -> io::Result<()> {
+    match ui_state.pending_clipboard.take() {
+        Some(text) => execute!(out, CopyToClipboard::to_clipboard_from(text)),
+        None => Ok(()),
+    }
+}
+
+/// Shows `text` in the pager, or in the timeline when the pager is not available.
+fn open_pager(state: &AppState, ui_state: &mut UiState, kind: OutputKind, title: &str, text: &str) {
+    if !ui_state.pager_available {
+        push_output(ui_state, &state.trace, kind, text);
+        return;
+    }
+    state.trace.log_output(output_trace_kind(kind), text);
+    ui_state.pager = Some(Pager {
+        title: title.to_string(),
+        text: text.to_string(),
+        kind,
+        scroll: 0,
+    });
+}
+
+/// Shows output in the timeline, or in the pager when it is longer than `auto_page_lines`.
+fn push_output_or_page(
+    state: &AppState,
+    ui_state: &mut UiState,
+    kind: OutputKind,
+    title: &str,
+    text: &str,
+) {
+    let line_count = text.lines().count();
+    if !ui_state.pager_available
+        || state.auto_page_lines == 0
+        || line_count <= state.auto_page_lines
+    {
+        push_output(ui_state, &state.trace, kind, text);
+        return;
+    }
+    ui_state.push_timeline_output(
+        OutputKind::SystemInfo,
+        &format!("{line_count} lines opened in the pager (Esc closes it)"),
+    );
+    open_pager(state, ui_state, kind, title, text);
+}
+
+fn push_inspect_result(
+    state: &AppState,
+    ui_state: &mut UiState,
+    result: CapabilityResult<InspectInfo>,
+    compact: bool,
+) {
+    let formatted = |value: &Value| {
+        if compact {
+            serde_json::to_string(value)
+        } else {
+            serde_json::to_string_pretty(value)
+        }
+    };
+    match result {
+        Ok(info) => match formatted(&info.value) {
+            Ok(pretty) => {
+                push_output_or_page(state, ui_state, OutputKind::SystemInfo, "inspect", &pretty);
+            }
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("failed to format inspect result: {err}"),
+            ),
+        },
+        Err(CapabilityError::PythonException(exc)) => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::PythonTraceback,
+                &exc.traceback,
+            );
+        }
+        Err(err) => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("inspect failed: {err}"),
+            );
+        }
+    }
+}
+
+fn execute_inspect_to_file_command(
+    state: &AppState,
+    ui_state: &mut UiState,
+    path: &str,
+    expr: &str,
+) {
+    let info = match state.python.inspect(expr) {
+        Ok(info) => info,
+        Err(CapabilityError::PythonException(exc)) => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::PythonTraceback,
+                &exc.traceback,
+            );
+            return;
+        }
+        Err(err) => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("inspect failed: {err}"),
+            );
+            return;
+        }
+    };
+
+    let path_ref = Path::new(path);
+    let written = serde_json::to_string_pretty(&info.value)
+        .map_err(|err| format!("failed to format inspect result: {err}"))
+        .and_then(|json| {
+            fs::write(path_ref, json + "\n")
+                .map_err(|err| format!("failed to write {}: {err}", path_ref.display()))
+        });
+    match written {
+        Ok(()) => {
+            let type_name = info.value["type"]["qualified"].as_str().unwrap_or("?");
+            let kind = info.value["kind"].as_str().unwrap_or("?");
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format!(
+                    "inspect {expr}: {type_name} ({kind}) written to {}",
+                    path_ref.display()
+                ),
+            );
+        }
+        Err(message) => push_output(ui_state, &state.trace, OutputKind::SystemError, &message),
+    }
+}
+
+fn execute_watch_command(state: &AppState, ui_state: &mut UiState, command: WatchCommand) {
+    match command {
+        WatchCommand::Add { label, expr } => {
+            let message = format!("watching {label}: {expr}");
+            match ui_state
+                .watches
+                .iter_mut()
+                .find(|watch| watch.label == label)
+            {
+                Some(watch) => watch.expr = expr,
+                None => ui_state.watches.push(Watch { label, expr }),
+            }
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, &message);
+        }
+        WatchCommand::Remove { label } => {
+            let before = ui_state.watches.len();
+            ui_state.watches.retain(|watch| watch.label != label);
+            if ui_state.watches.len() == before {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemError,
+                    &format!("no watch named {label}"),
+                );
+            } else {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    &format!("removed watch {label}"),
+                );
+            }
+        }
+        WatchCommand::List => {
+            let text = if ui_state.watches.is_empty() {
+                "no watches".to_string()
+            } else {
+                ui_state
+                    .watches
+                    .iter()
+                    .map(|watch| format!("{}: {}", watch.label, watch.expr))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            push_output(ui_state, &state.trace, OutputKind::SystemInfo, &text);
+        }
+    }
+}
+
+/// Re-evaluates every watch after a Python submission, one `label = value` line each.
+fn push_watch_values(state: &AppState, ui_state: &mut UiState) {
+    for watch in ui_state.watches.clone() {
+        match state.python.watch_repr(&watch.expr) {
+            Ok(value) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemInfo,
+                &format!("{} = {value}", watch.label),
+            ),
+            Err(CapabilityError::PythonException(exc)) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("{}: {}: {}", watch.label, exc.exc_type, exc.message),
+            ),
+            Err(err) => push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("{}: {err}", watch.label),
+            ),
+        }
+    }
+}
+
+fn execute_save_command(state: &AppState, ui_state: &mut UiState, path: &str, force: bool) {
+    let path_ref = Path::new(path);
+    if !force && path_ref.exists() {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!(
+                "file already exists: {} (use /save --force to overwrite)",
+                path_ref.display()
+            ),
+        );
+        return;
+    }
+
+    let format = transcript_format_for_path(path_ref);
+    let transcript =
+        ui_state
+            .timeline
+            .transcript(&ui_state.theme, ui_state.show_assistant_steps, format);
+    match fs::write(path_ref, transcript) {
+        Ok(()) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            &format!("saved transcript to {}", path_ref.display()),
+        ),
+        Err(err) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!("failed to write {}: {err}", path_ref.display()),
+        ),
+    }
+}
+
+fn execute_session_save_command(state: &AppState, ui_state: &mut UiState, path: &str, force: bool) {
+    let path_ref = Path::new(path);
+    if !force && path_ref.exists() {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!(
+                "file already exists: {} (use /session save --force to overwrite)",
+                path_ref.display()
+            ),
+        );
+        return;
+    }
+
+    let session = SessionFile {
+        version: SESSION_FILE_VERSION,
+        mode: ui_state.mode,
+        timeline_scroll: ui_state.timeline_scroll,
+        timeline: ui_state.timeline.snapshot(),
+    };
+    let written = serde_json::to_string_pretty(&session)
+        .map_err(|err| err.to_string())
+        .and_then(|json| fs::write(path_ref, json).map_err(|err| err.to_string()));
+    match written {
+        Ok(()) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            &format!(
+                "saved session ({} entries) to {}",
+                session.timeline.len(),
+                path_ref.display()
+            ),
+        ),
+        Err(err) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!("failed to write {}: {err}", path_ref.display()),
+        ),
+    }
+}
+
+fn execute_session_load_command(state: &AppState, ui_state: &mut UiState, path: &str) {
+    let path_ref = Path::new(path);
+    let session = fs::read_to_string(path_ref)
+        .map_err(|err| err.to_string())
+        .and_then(|json| {
+            serde_json::from_str::<SessionFile>(&json)
+                .map_err(|err| format!("not a saved session: {err}"))
+        })
+        .and_then(|session| {
+            if session.version == SESSION_FILE_VERSION {
+                Ok(session)
+            } else {
+                Err(format!(
+                    "unsupported session file version {}",
+                    session.version
+                ))
+            }
+        });
+    let session = match session {
+        Ok(session) => session,
+        Err(err) => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("failed to load {}: {err}", path_ref.display()),
+            );
+            return;
+        }
+    };
+
+    let entries = session.timeline.len();
+    ui_state.timeline.restore(session.timeline);
+    ui_state.mode = session.mode;
+    ui_state.history_index = None;
+    ui_state.timeline_scroll = session.timeline_scroll;
+    push_output(
+        ui_state,
+        &state.trace,
+        OutputKind::SystemInfo,
+        &format!(
+            "loaded session ({entries} entries) from {}",
+            path_ref.display()
+        ),
+    );
+}
+
+fn execute_export_script_command(
+    state: &AppState,
+    ui_state: &mut UiState,
+    path: &str,
+    outputs: bool,
+    skip_failed: bool,
+    force: bool,
+) {
+    let path_ref = Path::new(path);
+    if !force && path_ref.exists() {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!(
+                "file already exists: {} (use /export-script --force to overwrite)",
+                path_ref.display()
+            ),
+        );
+        return;
+    }
+
+    let script = ui_state.timeline.python_script(outputs, skip_failed);
+    match fs::write(path_ref, script) {
+        Ok(()) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemInfo,
+            &format!("exported script to {}", path_ref.display()),
+        ),
+        Err(err) => push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            &format!("failed to write {}: {err}", path_ref.display()),
+        ),
+    }
+}
+
+fn transcript_format_for_path(path: &Path) -> TranscriptFormat {
+    let is_markdown = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"));
+    if is_markdown {
+        TranscriptFormat::Markdown
+    } else {
+        TranscriptFormat::PlainText
+    }
+}
+
+fn execute_include_command(state: &mut AppState, ui_state: &mut UiState, path: &str) {
+    let path_ref = Path::new(path);
+    let source = match fs::read_to_string(path_ref) {
+        Ok(content) => content,
+        Err(err) => {
+            let message = if err.kind() == ErrorKind::NotFound {
+                format!("file not found: {}", path_ref.display())
+            } else {
+                format!("failed to read {}: {err}", path_ref.display())
+            };
+            push_output(ui_state, &state.trace, OutputKind::SystemError, &message);
+            return;
+        }
+    };
+
+    render_include_command_result(
+        ui_state,
+        &state.trace,
+        path_ref,
+        state.python.run_exec_input(&source),
+    );
+}
+
+fn render_include_command_result(
+    ui_state: &mut UiState,
+    trace: &SessionTrace,
+    path_ref: &Path,
+    result: Result<UserRunResult>,
+) {
+    match result {
+        Ok(UserRunResult::Executed(result)) => {
+            if !result.stdout.is_empty() {
+                push_output(ui_state, trace, OutputKind::PythonStdout, &result.stdout);
+            }
+            if !result.stderr.is_empty() {
+                push_output(ui_state, trace, OutputKind::PythonStderr, &result.stderr);
+            }
+            push_python_warnings(ui_state, trace, &result.warnings);
+            push_output(
+                ui_state,
+                trace,
+                OutputKind::SystemInfo,
+                &format!("included {}", path_ref.display()),
+            );
+        }
+        Ok(UserRunResult::Failed {
+            stdout,
+            stderr,
+            warnings,
+            exception,
+        }) => {
+            if !stdout.is_empty() {
+                push_output(ui_state, trace, OutputKind::PythonStdout, &stdout);
+            }
+            if !stderr.is_empty() {
+                push_output(ui_state, trace, OutputKind::PythonStderr, &stderr);
+            }
+            push_python_warnings(ui_state, trace, &warnings);
+            push_output(
+                ui_state,
+                trace,
+                OutputKind::PythonTraceback,
+                &exception.traceback,
+            );
+        }
+        Ok(UserRunResult::Evaluated(_)) => {
+            push_output(
+                ui_state,
+                trace,
+                OutputKind::SystemError,
+                "internal error: include unexpectedly evaluated expression",
+            );
+        }
+        Err(err) => {
+            push_output(
+                ui_state,
+                trace,
+                OutputKind::SystemError,
+                &format!("include failed: {err}"),
+            );
+        }
+    }
+}
+
+fn execute_source_command(state: &mut AppState, ui_state: &mut UiState, name: &str) {
+    if !is_safe_source_target(name) {
+        push_output(
+            ui_state,
+            &state.trace,
+            OutputKind::SystemError,
+            "usage: /show_source <name>",
+        );
+        return;
+    }
+
+    let code = format!("print(__import__('inspect').getsource({name}), end='')");
+    match state.python.exec_code(&code) {
+        Ok(result) => {
+            if !result.stdout.is_empty() {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::SystemInfo,
+                    &result.stdout,
+                );
+            }
+            if !result.stderr.is_empty() {
+                push_output(
+                    ui_state,
+                    &state.trace,
+                    OutputKind::PythonStderr,
+                    &result.stderr,
+                );
+            }
+        }
+        Err(err) => {
+            push_output(
+                ui_state,
+                &state.trace,
+                OutputKind::SystemError,
+                &format!("source failed: {err}"),
+            );
+        }
+    }
+}
+
+fn is_safe_source_target(name: &str) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+
+    name.split('.').all(is_ascii_identifier)
+}
+
+fn is_ascii_identifier(segment: &str) -> bool {
+    let mut chars = segment.bytes();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    if !first.is_ascii_alphabetic() && first != b'_' {
+        return false;
+    }
+
+    chars.all(|ch| ch.is_ascii_alphanumeric() || ch == b'_')
+}
+
+fn format_history_output(history: &[String], limit: Option<usize>) -> String {
+    if history.is_empty() {
+        return "history is empty".to_string();
+    }
+
+    let count = limit.unwrap_or(history.len()).min(history.len());
+    let start = history.len().saturating_sub(count);
     history[start..]
         .iter()
         .enumerate()
-        .map(|(idx, line)| format!("{:>4}: {}", start + idx + 1, line))
-        .collect::<Vec<_>>()
-        .join("\n")
+        .map(|(idx, line)| format!("{:>4}: {}", start + idx + 1, line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_globals_output(globals: &[GlobalEntry], pattern: Option<&str>) -> String {
+    let matching = globals
+        .iter()
+        .filter(|entry| pattern.is_none_or(|pattern| glob_matches(pattern, &entry.name)))
+        .collect::<Vec<_>>();
+    if matching.is_empty() {
+        return match pattern {
+            Some(pattern) => format!("no globals match '{pattern}'"),
+            None => "no globals defined".to_string(),
+        };
+    }
+
+    let width = matching
+        .iter()
+        .map(|entry| entry.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    matching
+        .iter()
+        .map(|entry| format!("{:<width$}  {}", entry.name, entry.type_name))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Matches `name` against a shell-style pattern where `*` matches any run of
+/// characters and `?` matches exactly one.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|ch| *ch == '*')
+}
+
+fn ui_layout(area: Rect, current_input: &str) -> UiLayout {
+    let input_line_count = render_input_lines(current_input).len().max(1);
+    let max_input_lines = 6usize;
+    let input_visible_lines = input_line_count.min(max_input_lines);
+    let input_height = u16::try_from(input_visible_lines.saturating_add(2)).unwrap_or(u16::MAX);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(input_height),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    let timeline_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(chunks[0]);
+
+    UiLayout {
+        timeline_banner: timeline_chunks[0],
+        timeline: timeline_chunks[1],
+        input: chunks[1],
+        footer: chunks[2],
+    }
+}
+
+fn timeline_max_scroll(total_lines: usize, visible_lines: usize) -> usize {
+    total_lines.saturating_sub(visible_lines)
+}
+
+/// The timeline area for the current terminal size and how far it can scroll up.
+fn timeline_viewport(
+    terminal: &Terminal<impl ratatui::backend::Backend>,
+    ui_state: &UiState,
+) -> Result<(Rect, usize)> {
+    let size = terminal.size()?;
+    let area = Rect::new(0, 0, size.width, size.height);
+    let layout = ui_layout(area, ui_state.displayed_input());
+    let line_count = ui_state
+        .timeline
+        .render_lines(&ui_state.theme, ui_state.show_assistant_steps)
+        .len();
+    let max_scroll = timeline_max_scroll(line_count, usize::from(layout.timeline.height));
+    Ok((layout.timeline, max_scroll))
+}
+
+/// Area inside the pager's border, where its text is drawn over the timeline.
+fn pager_viewport(
+    terminal: &Terminal<impl ratatui::backend::Backend>,
+    ui_state: &UiState,
+) -> Result<Rect> {
+    let size = terminal.size()?;
+    let area = Rect::new(0, 0, size.width, size.height);
+    let layout = ui_layout(area, ui_state.displayed_input());
+    Ok(Block::bordered().inner(layout.timeline))
+}
+
+fn timeline_paragraph_scroll(
+    total_lines: usize,
+    visible_lines: usize,
+    timeline_scroll: usize,
+) -> u16 {
+    let max_scroll = timeline_max_scroll(total_lines, visible_lines);
+    let scroll = max_scroll.saturating_sub(timeline_scroll.min(max_scroll));
+    u16::try_from(scroll).unwrap_or(u16::MAX)
+}
+
+fn area_contains_point(area: Rect, column: u16, row: u16) -> bool {
+    if area.width == 0 || area.height == 0 {
+        return false;
+    }
+    let in_x = column >= area.x && column < area.x.saturating_add(area.width);
+    let in_y = row >= area.y && row < area.y.saturating_add(area.height);
+    in_x && in_y
+}
+
+fn refresh_input_completeness(state: &AppState, ui_state: &mut UiState) {
+    let input = ui_state.current_input();
+    if ui_state.mode != Mode::Python || input.trim().is_empty() || is_command_line(input) {
+        ui_state.input_completeness = None;
+        return;
+    }
+    if ui_state
+        .input_completeness
+        .as_ref()
+        .is_some_and(|(checked, _)| checked == input)
+    {
+        return;
+    }
+
+    ui_state.input_completeness = state
+        .python
+        .check_input_completeness(input)
+        .ok()
+        .map(|completeness| (input.to_string(), completeness));
+}
+
+fn draw_ui(frame: &mut ratatui::Frame<'_>, ui_state: &UiState) {
+    let command_input = is_command_line(ui_state.current_input());
+    let prompt = match &ui_state.history_search {
+        Some(search) => history_search_prompt(&search.query),
+        None => prompt_for(ui_state.mode, command_input).to_string(),
+    };
+    let displayed_input = ui_state.displayed_input();
+    let input_lines = render_input_lines(displayed_input);
+    let input_line_count = input_lines.len().max(1);
+    let max_input_lines = 6usize;
+    let input_visible_lines = input_line_count.min(max_input_lines);
+    let layout = ui_layout(frame.area(), displayed_input);
+
+    render_sticky_motd(frame, ui_state, layout.timeline_banner);
+
+    let lines = ui_state
+        .timeline
+        .render_lines(&ui_state.theme, ui_state.show_assistant_steps);
+
+    let visible_lines = usize::from(layout.timeline.height);
+    let scroll = timeline_paragraph_scroll(
+        lines.len(),
+        visible_lines,
+        ui_state.timeline_scroll_offset(timeline_max_scroll(lines.len(), visible_lines)),
+    );
+
+    let output = Paragraph::new(lines)
+        .block(Block::default().padding(Padding::new(1, 1, 0, 0)))
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    frame.render_widget(output, layout.timeline);
+
+    let is_empty_input = ui_state.history_search.is_none() && displayed_input.is_empty();
+    let cursor = if ui_state.history_search.is_some() {
+        displayed_input.len()
+    } else {
+        ui_state.cursor()
+    };
+    let (cursor_row, cursor_col) = input_cursor_position(displayed_input, cursor);
+    // Show the tail of long input, scrolling back only as far as needed to keep the cursor visible.
+    let input_scroll = input_line_count
+        .saturating_sub(input_visible_lines)
+        .min(cursor_row);
+    let cursor_row = cursor_row - input_scroll;
+    let input_scroll = u16::try_from(input_scroll).unwrap_or(u16::MAX);
+    let prompt_padding = " ".repeat(prompt.chars().count());
+    let prompt_style = ui_state
+        .theme
+        .style(input_prompt_token(ui_state, command_input));
+    let mut rendered_lines = Vec::with_capacity(input_lines.len());
+    for (idx, line) in input_lines.into_iter().enumerate() {
+        let prompt_span = if idx == 0 {
+            Span::styled(prompt.clone(), prompt_style)
+        } else {
+            Span::styled(prompt_padding.clone(), prompt_style)
+        };
+        let input_span = if is_empty_input && idx == 0 {
+            Span::styled(
+                input_hint_for_empty(ui_state.mode),
+                ui_state.theme.style(ThemeToken::FooterSecondary),
+            )
+        } else {
+            Span::styled(
+                line.to_string(),
+                ui_state.theme.style(ThemeToken::InputBlock),
+            )
+        };
+        rendered_lines.push(Line::from(vec![prompt_span, input_span]));
+    }
+
+    let input_widget = Paragraph::new(rendered_lines)
+        .block(
+            Block::default()
+                .padding(Padding::new(1, 1, 1, 1))
+                .style(ui_state.theme.style(ThemeToken::InputBlock)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((input_scroll, 0));
+    frame.render_widget(input_widget, layout.input);
+
+    render_footer(frame, ui_state, &layout);
+    if let Some(pager) = &ui_state.pager {
+        render_pager(frame, ui_state, pager, layout.timeline);
+    }
+
+    let cursor_x = layout
+        .input
+        .x
+        .saturating_add(1)
+        .saturating_add(u16::try_from(prompt.chars().count()).unwrap_or(u16::MAX))
+        .saturating_add(u16::try_from(cursor_col).unwrap_or(u16::MAX));
+    let cursor_y = layout
+        .input
+        .y
+        .saturating_add(1)
+        .saturating_add(u16::try_from(cursor_row).unwrap_or(u16::MAX));
+    frame.set_cursor_position((cursor_x, cursor_y));
+}
+
+fn render_pager(frame: &mut ratatui::Frame<'_>, ui_state: &UiState, pager: &Pager, area: Rect) {
+    let block = Block::bordered()
+        .title(Span::styled(
+            format!(" {} ", pager.title),
+            ui_state.theme.style(ThemeToken::FooterAccent),
+        ))
+        .title_bottom(Span::styled(
+            " Esc close | Up/Down PgUp/PgDn scroll ",
+            ui_state.theme.style(ThemeToken::FooterSecondary),
+        ))
+        .border_style(ui_state.theme.style(ThemeToken::FooterSecondary));
+    let inner = block.inner(area);
+    let style = ui_state.theme.style(output_token_for(pager.kind));
+    let lines = pager
+        .wrapped_lines(usize::from(inner.width))
+        .into_iter()
+        .skip(pager.scroll)
+        .take(usize::from(inner.height))
+        .map(|line| Line::styled(line, style))
+        .collect::<Vec<_>>();
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_sticky_motd(frame: &mut ratatui::Frame<'_>, ui_state: &UiState, area: Rect) {
+    if area.width == 0 || area.height == 0 {
+        return;
+    }
+    let line = header_line(&ui_state.theme, usize::from(area.width));
+    let widget = Paragraph::new(line);
+    frame.render_widget(widget, area);
+}
+
+fn render_footer(frame: &mut ratatui::Frame<'_>, ui_state: &UiState, layout: &UiLayout) {
+    if layout.footer.width == 0 || layout.footer.height == 0 {
+        return;
+    }
+
+    let right_text = footer_right_text(&ui_state.session_token_usage);
+    let right_width = right_text.chars().count().saturating_add(1);
+    let right_width = right_width.min(usize::from(layout.footer.width));
+    let right_width = u16::try_from(right_width).unwrap_or(u16::MAX);
+    let bottom_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(right_width)])
+        .split(layout.footer);
+
+    let left_available = usize::from(bottom_chunks[0].width);
+    let left = Paragraph::new(match &ui_state.completion {
+        Some(completion) => completion_footer_line(&ui_state.theme, completion),
+        None => footer_left_line(
+            &ui_state.theme,
+            ui_state.mode,
+            ui_state.vi_mode,
+            ui_state.show_assistant_steps,
+            left_available,
+        ),
+    });
+    frame.render_widget(left, bottom_chunks[0]);
+
+    let right = Paragraph::new(footer_right_line(
+        &ui_state.theme,
+        &ui_state.session_token_usage,
+        usize::from(bottom_chunks[1].width),
+    ))
+    .alignment(ratatui::layout::Alignment::Right);
+    frame.render_widget(right, bottom_chunks[1]);
+}
+
+/// Tints the Python prompt while the input is an unfinished block or will not compile.
+fn input_prompt_token(ui_state: &UiState, command_input: bool) -> ThemeToken {
+    if ui_state.history_search.is_some() {
+        return prompt_token_for(ui_state.mode, command_input);
+    }
+    match ui_state.input_completeness {
+        Some((ref checked, InputCompleteness::Incomplete))
+            if checked == ui_state.current_input() =>
+        {
+            ThemeToken::ContinuationPrompt
+        }
+        Some((ref checked, InputCompleteness::Invalid)) if checked == ui_state.current_input() => {
+            ThemeToken::InvalidInputPrompt
+        }
+        _ => prompt_token_for(ui_state.mode, command_input),
+    }
+}
+
+fn prompt_token_for(mode: Mode, command_input: bool) -> ThemeToken {
+    if command_input {
+        return ThemeToken::CommandPrompt;
+    }
+
+    match mode {
+        Mode::Python => ThemeToken::PythonPrompt,
+        Mode::Assistant => ThemeToken::AssistantPrompt,
+    }
+}
+
+fn format_tool_request_line(name: &str, args_json: &Value) -> String {
+    match name {
+        "list_globals" => "-> Listing globals".to_string(),
+        "inspect" => format!(
+            "-> Inspecting: {}",
+            extract_expr_preview(args_json).unwrap_or_else(|| "<missing expr>".to_string())
+        ),
+        "eval_expr" => format!(
+            "-> Evaluating: {}",
+            extract_expr_preview(args_json).unwrap_or_else(|| "<missing expr>".to_string())
+        ),
+        "apply_code" => format!(
+            "-> Applying code: {}",
+            args_json.get("code").and_then(Value::as_str).map_or_else(
+                || "<missing code>".to_string(),
+                |code| preview_text(code, 80)
+            )
+        ),
+        _ => format!("-> Calling tool: {name}"),
+    }
+}
+
+/// Summarizes a tool response for the steps block. Verbose mode lists the first global
+/// names and keeps much longer evaluated values.
+fn format_tool_result_line(name: &str, response_json: &Value, verbose: bool) -> String {
+    if !response_json
+        .get("ok")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        return format_tool_error_line(name, response_json);
+    }
+
+    let Some(result) = response_json.get("result") else {
+        return format!("Tool completed: {name}");
+    };
+    let max_chars = if verbose {
+        VERBOSE_TOOL_PREVIEW_MAX_CHARS
+    } else {
+        TOOL_PREVIEW_MAX_CHARS
+    };
+
+    match name {
+        "list_globals" => {
+            let globals = result
+                .get("globals")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let count = globals.len();
+            if !verbose || globals.is_empty() {
+                return format!("<- Found {count} globals");
+            }
+            let mut names = globals
+                .iter()
+                .take(VERBOSE_GLOBALS_MAX_NAMES)
+                .map(|entry| entry.get("name").and_then(Value::as_str).unwrap_or("?"))
+                .collect::<Vec<_>>();
+            if count > VERBOSE_GLOBALS_MAX_NAMES {
+                names.push("...");
+            }
+            format!("<- Found {count} globals: {}", names.join(", "))
+        }
+        "inspect" => {
+            let info = result
+                .get("type")
+                .and_then(|ty| ty.get("name"))
+                .and_then(Value::as_str)
+                .or_else(|| result.get("kind").and_then(Value::as_str))
+                .unwrap_or("value");
+            format!("<- Inspection complete: {}", preview_text(info, max_chars))
+        }
+        "eval_expr" => {
+            let value_repr = result
+                .get("value_repr")
+                .and_then(Value::as_str)
+                .unwrap_or("<unknown>");
+            if result
+                .get("tabular")
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+            {
+                format!("<- Evaluated:\n{}", tabular_preview(value_repr))
+            } else {
+                format!("<- Evaluated: {}", preview_text(value_repr, max_chars))
+            }
+        }
+        "apply_code" => {
+            let stdout = result.get("stdout").and_then(Value::as_str).unwrap_or("");
+            if stdout.trim().is_empty() {
+                "<- Applied code".to_string()
+            } else {
+                format!("<- Applied code: {}", preview_text(stdout, max_chars))
+            }
+        }
+        _ => format!("<- Tool completed: {name}"),
+    }
+}
+
+fn format_tool_error_line(name: &str, response_json: &Value) -> String {
+    let (code, message) = response_json
+        .get("error")
+        .and_then(Value::as_object)
+        .map(|error| {
+            let code = error
+                .get("code")
+                .and_then(Value::as_str)
+                .unwrap_or("error")
+                .to_string();
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("tool failed")
+                .to_string();
+            (code, message)
+        })
+        .unwrap_or_else(|| ("error".to_string(), "tool failed".to_string()));
+    format!(
+        "<- Tool error ({name}): {code}: {}",
+        preview_text(&message, 100)
+    )
+}
+
+fn extract_expr_preview(args_json: &Value) -> Option<String> {
+    args_json
+        .as_object()
+        .and_then(|args| args.get("expr"))
+        .and_then(Value::as_str)
+        .map(|expr| preview_text(expr, 80))
+}
+
+fn preview_text(value: &str, max_len: usize) -> String {
+    let normalized = normalize_whitespace(value);
+    truncate_chars(&normalized, max_len)
+}
+
+/// Keeps the column layout of pandas/numpy reprs: only tabs are expanded and the
+/// number of lines is capped, since whitespace normalization would collapse the columns.
+fn tabular_preview(value: &str) -> String {
+    let lines = value.lines().collect::<Vec<_>>();
+    let mut preview = lines
+        .iter()
+        .take(TABULAR_PREVIEW_MAX_LINES)
+        .map(|line| expand_tabs(line))
+        .collect::<Vec<_>>();
+    if lines.len() > TABULAR_PREVIEW_MAX_LINES {
+        preview.push("...".to_string());
+    }
+    preview.join("\n")
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for ch in line.chars() {
+        if ch == '\t' {
+            let width = TAB_WIDTH - column % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', width));
+            column += width;
+        } else {
+            expanded.push(ch);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+fn normalize_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Cuts a value repr to `max_chars` characters, noting how many were dropped. A limit
+/// of 0 keeps the repr whole.
+fn truncate_repr(value_repr: &str, max_chars: usize) -> String {
+    let total = value_repr.chars().count();
+    if max_chars == 0 || total <= max_chars {
+        return value_repr.to_string();
+    }
+    let kept = value_repr.chars().take(max_chars).collect::<String>();
+    format!("{kept}... ({} more chars)", total - max_chars)
+}
+
+fn truncate_chars(value: &str, max_len: usize) -> String {
+    let mut chars = value.chars();
+    let preview: String = chars.by_ref().take(max_len).collect();
+    if chars.next().is_some() {
+        format!("{preview}...")
+    } else {
+        preview
+    }
+}
+
+fn last_line_indent(input: &str) -> String {
+    input
+        .rsplit('\n')
+        .next()
+        .unwrap_or("")
+        .chars()
+        .take_while(|ch| *ch == ' ' || *ch == '\t')
+        .collect()
+}
+
+fn render_input_lines(input: &str) -> Vec<&str> {
+    if input.is_empty() {
+        return vec![""];
+    }
+    input.split('\n').collect()
+}
+
+/// Returns the (row, column) of byte offset `cursor` within `input`, counting columns in chars.
+fn input_cursor_position(input: &str, cursor: usize) -> (usize, usize) {
+    let before = &input[..cursor.min(input.len())];
+    let row = before.matches('\n').count();
+    let col = before.rsplit('\n').next().unwrap_or("").chars().count();
+    (row, col)
+}
+
+fn resolve_color_enabled() -> bool {
+    resolve_color_enabled_with(
+        std::env::var("NO_COLOR").ok(),
+        std::env::var("PYCHAT_AI_FORCE_COLOR").ok(),
+        io::stdout().is_terminal(),
+    )
+}
+
+fn resolve_color_enabled_with(
+    no_color: Option<String>,
+    force_color: Option<String>,
+    is_tty: bool,
+) -> bool {
+    if let Some(value) = force_color
+        && matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        )
+    {
+        return true;
+    }
+
+    if no_color.is_some() {
+        return false;
+    }
+
+    is_tty
+}
+
+fn history_search_prompt(query: &str) -> String {
+    format!("(reverse-i-search)`{query}': ")
+}
+
+pub fn prompt_for(mode: Mode, command_input: bool) -> &'static str {
+    if command_input {
+        return "cmd> ";
+    }
+
+    match mode {
+        Mode::Python => "py> ",
+        Mode::Assistant => "ai> ",
+    }
+}
+
+fn toggle_mode(mode: Mode) -> Mode {
+    match mode {
+        Mode::Python => Mode::Assistant,
+        Mode::Assistant => Mode::Python,
+    }
+}
+
+fn header_line(theme: &Theme, width: usize) -> Line<'static> {
+    let brand = truncate_with_ellipsis("PyChat.AI", width);
+    Line::from(Span::styled(brand, theme.style(ThemeToken::MotdBrand)))
+}
+
+fn footer_left_text(
+    mode: Mode,
+    vi_mode: Option<ViMode>,
+    show_assistant_steps: bool,
+    width: usize,
+) -> String {
+    let steps = if show_assistant_steps { "On" } else { "Off" };
+    let mode_text = match mode {
+        Mode::Python => "Python",
+        Mode::Assistant => "AI Assistant",
+    };
+    let vi_text = vi_mode.map_or(String::new(), |vi_mode| {
+        format!("{} | ", vi_mode_text(vi_mode))
+    });
+    truncate_with_ellipsis(&format!("{vi_text}{mode_text} | Thinking: {steps}"), width)
+}
+
+fn vi_mode_text(vi_mode: ViMode) -> &'static str {
+    match vi_mode {
+        ViMode::Normal => "NORMAL",
+        ViMode::Insert => "INSERT",
+    }
+}
+
+fn footer_right_text(usage: &LlmTokenUsageTotals) -> String {
+    format!(
+        "Questions? /help | Tokens: {}",
+        usage.display_count(usage.total_tokens)
+    )
+}
+
+/// Lists the completion candidates, highlighting the one currently inserted.
+fn completion_footer_line(theme: &Theme, completion: &Completion) -> Line<'static> {
+    let mut spans = Vec::with_capacity(completion.candidates.len() * 2);
+    for (index, candidate) in completion.candidates.iter().enumerate() {
+        if index > 0 {
+            spans.push(Span::styled(
+                "  ".to_string(),
+                theme.style(ThemeToken::FooterSecondary),
+            ));
+        }
+        let token = if completion.selected == Some(index) {
+            ThemeToken::FooterAccent
+        } else {
+            ThemeToken::FooterSecondary
+        };
+        spans.push(Span::styled(candidate.clone(), theme.style(token)));
+    }
+    Line::from(spans)
+}
+
+fn footer_left_line(
+    theme: &Theme,
+    mode: Mode,
+    vi_mode: Option<ViMode>,
+    show_assistant_steps: bool,
+    width: usize,
+) -> Line<'static> {
+    let text = footer_left_text(mode, vi_mode, show_assistant_steps, width);
+    if text != footer_left_text(mode, vi_mode, show_assistant_steps, usize::MAX) {
+        return Line::from(Span::styled(text, theme.style(ThemeToken::FooterPrimary)));
+    }
+
+    let (mode_text, steps) = match mode {
+        Mode::Python => ("Python", if show_assistant_steps { "On" } else { "Off" }),
+        Mode::Assistant => (
+            "AI Assistant",
+            if show_assistant_steps { "On" } else { "Off" },
+        ),
+    };
+
+    let mut spans = Vec::with_capacity(6);
+    if let Some(vi_mode) = vi_mode {
+        spans.push(Span::styled(
+            vi_mode_text(vi_mode).to_string(),
+            theme.style(ThemeToken::FooterAccent),
+        ));
+        spans.push(Span::styled(
+            " | ".to_string(),
+            theme.style(ThemeToken::FooterSecondary),
+        ));
+    }
+    spans.extend([
+        Span::styled(
+            mode_text.to_string(),
+            theme.style(ThemeToken::FooterPrimary),
+        ),
+        Span::styled(" | ".to_string(), theme.style(ThemeToken::FooterSecondary)),
+        Span::styled(
+            "Thinking: ".to_string(),
+            theme.style(ThemeToken::FooterSecondary),
+        ),
+        Span::styled(steps.to_string(), theme.style(ThemeToken::FooterAccent)),
+    ]);
+    Line::from(spans)
+}
+
+fn footer_right_line(theme: &Theme, usage: &LlmTokenUsageTotals, width: usize) -> Line<'static> {
+    let text = footer_right_text(usage);
+    let total = usage.display_count(usage.total_tokens);
+    let full = format!("Questions? /help | Tokens: {total}");
+    if text.chars().count() > width || text != full {
+        return Line::from(Span::styled(text, theme.style(ThemeToken::FooterSecondary)));
+    }
+
+    Line::from(vec![
+        Span::styled(
+            "Questions? ".to_string(),
+            theme.style(ThemeToken::FooterSecondary),
+        ),
+        Span::styled("/help".to_string(), theme.style(ThemeToken::FooterAccent)),
+        Span::styled(" | ".to_string(), theme.style(ThemeToken::FooterSecondary)),
+        Span::styled(
+            "Tokens: ".to_string(),
+            theme.style(ThemeToken::FooterSecondary),
+        ),
+        Span::styled(total, theme.style(ThemeToken::FooterAccent)),
+    ])
+}
+
+fn input_hint_for_empty(mode: Mode) -> String {
+    match mode {
+        Mode::Python => "/help for commands".to_string(),
+        Mode::Assistant => "Ask about runtime state or /help".to_string(),
+    }
+}
+
+fn truncate_with_ellipsis(text: &str, width: usize) -> String {
+    if width == 0 {
+        return String::new();
+    }
+    let count = text.chars().count();
+    if count <= width {
+        return text.to_string();
+    }
+    if width <= 3 {
+        return ".".repeat(width);
+    }
+    let keep = width.saturating_sub(3);
+    let mut out = text.chars().take(keep).collect::<String>();
+    out.push_str("...");
+    out
+}
+
+/// Lists the tools the model can be offered, marking those turned off by `allow_eval`
+/// or `allow_apply`.
+fn format_tools(allow_eval: bool, allow_apply: bool) -> String {
+    let tools = tool_declarations(true);
+    let width = tools.iter().map(|tool| tool.name.len()).max().unwrap_or(0);
+    let mut out = String::from("Assistant tools:");
+    for tool in tools {
+        let status = match tool.name.as_str() {
+            "eval_expr" if !allow_eval => " (disabled: allow_eval = false)",
+            "apply_code" if !allow_apply => " (disabled: allow_apply = false)",
+            _ => "",
+        };
+        out.push_str(&format!(
+            "\n  {:<width$}  {}{status}",
+            tool.name, tool.description
+        ));
+    }
+    out
+}
+
+/// Environment details worth pasting into a bug report. The API key is never included.
+fn format_diagnostics(state: &AppState, color_enabled: bool) -> String {
+    let terminal = std::env::var("TERM").unwrap_or_else(|_| "unknown".to_string());
+    let terminal = match std::env::var("TERM_PROGRAM") {
+        Ok(program) => format!("{terminal} ({program})"),
+        Err(_) => terminal,
+    };
+    let provider = match &state.llm {
+        Some(provider) => format!("gemini {}", provider.redacted_endpoint()),
+        None => "gemini (unavailable: missing GEMINI_API_KEY)".to_string(),
+    };
+    let config = &state.agent_config;
+    [
+        format!("pychat.ai {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "os: {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ),
+        format!("terminal: {terminal}"),
+        format!(
+            "color: {}",
+            if color_enabled { "enabled" } else { "disabled" }
+        ),
+        format!("python: {}", state.python.python_version()),
+        format!("provider: {provider}"),
+        format!("session: {}", state.session_id),
+        format!("trace: {}", state.trace.file_path().display()),
+        format!(
+            "assistant_mode: {}",
+            if config.chat_only { "chat" } else { "agent" }
+        ),
+        format!(
+            "flags: allow_eval={} allow_apply={} stream_output={} clipboard_osc52={} timeline_timestamps={}",
+            config.allow_eval,
+            config.allow_apply,
+            state.stream_output,
+            state.clipboard_osc52,
+            state.timeline_timestamps
+        ),
+    ]
+    .join("\n")
+}
+
+fn format_session_token_usage(usage: &LlmTokenUsageTotals) -> String {
+    format!("session tokens {}", format_token_counts(usage))
+}
+
+fn format_token_breakdown(turns: &[LlmTokenUsageTotals], session: &LlmTokenUsageTotals) -> String {
+    if turns.is_empty() {
+        return "no assistant turns yet".to_string();
+    }
+
+    let mut lines = turns
+        .iter()
+        .enumerate()
+        .map(|(idx, usage)| format!("turn {}: {}", idx + 1, format_token_counts(usage)))
+        .collect::<Vec<_>>();
+    lines.push(format!("session: {}", format_token_counts(session)));
+    lines.join("\n")
+}
+
+fn format_token_counts(usage: &LlmTokenUsageTotals) -> String {
+    format!(
+        "in={} out={} total={}",
+        usage.display_count(usage.input_tokens),
+        usage.display_count(usage.output_tokens),
+        usage.display_count(usage.total_tokens)
+    )
+}
+
+fn session_closed_message(
+    trace_file_path: &std::path::Path,
+    usage: &LlmTokenUsageTotals,
+) -> String {
+    format!(
+        "PyChat.ai session ended.\nTokens: {}\nTrace file: {}",
+        usage.total_tokens,
+        trace_file_path.display()
+    )
+}
+
+fn push_output(ui_state: &mut UiState, trace: &SessionTrace, kind: OutputKind, text: &str) {
+    ui_state.push_timeline_output(kind, text);
+    trace.log_output(output_trace_kind(kind), text);
+}
+
+fn output_trace_kind(kind: OutputKind) -> &'static str {
+    match kind {
+        OutputKind::UserInputPython => "py.in",
+        OutputKind::UserInputAssistant => "ai.in",
+        OutputKind::PythonValue => "py.out",
+        OutputKind::PythonStdout => "py.out",
+        OutputKind::PythonStderr => "py.err",
+        OutputKind::PythonWarning => "py.warn",
+        OutputKind::PythonTraceback => "py.tb",
+        OutputKind::AssistantText => "ai.out",
+        OutputKind::AssistantWaiting => "ai.wait",
+        OutputKind::AssistantProgressRequest => "ai.step",
+        OutputKind::AssistantProgressResult => "ai.step",
+        OutputKind::SystemInfo => "sys.info",
+        OutputKind::SystemError => "sys.err",
+    }
+}
+
+#[cfg(feature = "test-support")]
+pub mod test_support {
+    use super::{
+        AppState, Mode, UiState, draw_ui, flush_pending_clipboard, handle_key_event,
+        handle_mouse_event, handle_paste, input_prompt_token, is_command_line, prompt_for,
+        refresh_input_completeness, timeline_for, timeline_viewport, ui_layout,
+    };
+    use crate::agent::AgentConfig;
+    use crate::cli::timeline::{AssistantStepEvent, AssistantTurnState};
+    use crate::config::{EditMode, HistoryRecall, KeyBindings, ThemeConfig, ThemeToken};
+    use crate::python::PythonSession;
+    use crate::trace::SessionTrace;
+    use anyhow::{Context, Result, bail};
+    use crossterm::event::{KeyEvent, MouseEvent};
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use ratatui::buffer::Buffer;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[derive(Debug)]
+    pub struct UiStateView {
+        pub mode: Mode,
+        pub prompt: &'static str,
+        pub prompt_token: ThemeToken,
+        pub input: String,
+        pub timeline_scroll: usize,
+        pub show_assistant_steps: bool,
+    }
+
+    pub struct UiHarness {
+        terminal: Terminal<TestBackend>,
+        app_state: AppState,
+        ui_state: UiState,
+        clipboard_bytes: Vec<u8>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct DeterministicTestEnv {
+        pub xdg_config_home: PathBuf,
+        pub xdg_state_home: PathBuf,
+        pub no_color: String,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct UiRegions {
+        pub motd: Rect,
+        pub timeline: Rect,
+        pub input: Rect,
+        pub status: Rect,
+    }
+
+    impl DeterministicTestEnv {
+        pub fn apply_to_command(&self, command: &mut std::process::Command) {
+            command
+                .env("NO_COLOR", &self.no_color)
+                .env("XDG_CONFIG_HOME", &self.xdg_config_home)
+                .env("XDG_STATE_HOME", &self.xdg_state_home);
+        }
+    }
+
+    impl UiHarness {
+        pub fn new(width: u16, height: u16, app_state: AppState) -> Result<Self> {
+            Self::with_color(width, height, app_state, false)
+        }
+
+        pub fn with_color(
+            width: u16,
+            height: u16,
+            app_state: AppState,
+            color_enabled: bool,
+        ) -> Result<Self> {
+            let backend = TestBackend::new(width, height);
+            let terminal = Terminal::new(backend)?;
+            let ui_state = UiState::new(
+                app_state.mode,
+                color_enabled,
+                &app_state.theme_config,
+                app_state.key_bindings.clone(),
+                timeline_for(&app_state),
+                app_state.history_file.clone(),
+                app_state.history_recall,
+            )
+            .with_edit_mode(app_state.edit_mode)
+            .with_pager();
+
+            Ok(Self {
+                terminal,
+                app_state,
+                ui_state,
+                clipboard_bytes: Vec::new(),
+            })
+        }
+
+        pub fn app_state(&self) -> &AppState {
+            &self.app_state
+        }
+
+        pub fn app_state_mut(&mut self) -> &mut AppState {
+            &mut self.app_state
+        }
+
+        pub fn ui_state_view(&self) -> UiStateView {
+            let input = self.ui_state.current_input().to_string();
+            let command_input = is_command_line(&input);
+            UiStateView {
+                mode: self.ui_state.mode,
+                prompt: prompt_for(self.ui_state.mode, command_input),
+                prompt_token: input_prompt_token(&self.ui_state, command_input),
+                input,
+                timeline_scroll: self.ui_state.timeline_scroll,
+                show_assistant_steps: self.ui_state.show_assistant_steps,
+            }
+        }
+
+        pub fn render(&mut self) -> Result<()> {
+            refresh_input_completeness(&self.app_state, &mut self.ui_state);
+            self.terminal.draw(|frame| draw_ui(frame, &self.ui_state))?;
+            Ok(())
+        }
+
+        pub async fn send_key(&mut self, key: KeyEvent) -> Result<()> {
+            handle_key_event(
+                &mut self.terminal,
+                &mut self.app_state,
+                &mut self.ui_state,
+                key,
+            )
+            .await?;
+            flush_pending_clipboard(&mut self.ui_state, &mut self.clipboard_bytes)?;
+            Ok(())
+        }
+
+        pub fn send_paste(&mut self, text: &str) {
+            handle_paste(&mut self.ui_state, text);
+        }
+
+        /// Escape sequences written for OSC 52 clipboard copies so far.
+        pub fn clipboard_bytes(&self) -> &[u8] {
+            &self.clipboard_bytes
+        }
+
+        pub fn send_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
+            let (timeline_area, max_scroll) = timeline_viewport(&self.terminal, &self.ui_state)?;
+            handle_mouse_event(
+                &self.app_state,
+                &mut self.ui_state,
+                mouse,
+                timeline_area,
+                max_scroll,
+            );
+            flush_pending_clipboard(&mut self.ui_state, &mut self.clipboard_bytes)?;
+            Ok(())
+        }
+
+        pub fn buffer_text(&self) -> String {
+            buffer_to_string(self.terminal.backend().buffer())
+        }
+
+        pub fn buffer_lines(&self) -> Vec<String> {
+            buffer_to_lines(self.terminal.backend().buffer())
+        }
+
+        pub fn line(&self, row: u16) -> Option<String> {
+            self.buffer_lines().get(usize::from(row)).cloned()
+        }
+
+        pub fn cell_style(&self, column: u16, row: u16) -> Option<Style> {
+            self.terminal
+                .backend()
+                .buffer()
+                .cell((column, row))
+                .map(|cell| cell.style())
+        }
+
+        pub fn terminal_size(&self) -> (u16, u16) {
+            let area = self.terminal.backend().buffer().area;
+            (area.width, area.height)
+        }
+
+        pub fn regions(&self) -> Result<UiRegions> {
+            let size = self.terminal.size()?;
+            let area = Rect::new(0, 0, size.width, size.height);
+            let layout = ui_layout(area, self.ui_state.displayed_input());
+            Ok(UiRegions {
+                motd: layout.timeline_banner,
+                timeline: layout.timeline,
+                input: layout.input,
+                status: layout.footer,
+            })
+        }
+
+        pub fn buffer_snapshot(&self) -> String {
+            normalize_snapshot(&self.buffer_text())
+        }
+
+        pub fn seed_assistant_turn_completed(
+            &mut self,
+            prompt: &str,
+            tool_events: &[(&str, &str)],
+            response: &str,
+        ) -> Result<()> {
+            let index = self.ui_state.push_assistant_turn(prompt.to_string());
+            let Some(turn) = self.ui_state.assistant_turn_mut(index) else {
+                bail!("failed to find seeded assistant turn at index {index}");
+            };
+
+            for (kind, text) in tool_events {
+                if kind.eq_ignore_ascii_case("request") {
+                    turn.events.push(AssistantStepEvent::ToolRequest {
+                        text: (*text).to_string(),
+                    });
+                } else if kind.eq_ignore_ascii_case("result") {
+                    turn.events.push(AssistantStepEvent::ToolResult {
+                        text: (*text).to_string(),
+                    });
+                } else {
+                    bail!("unsupported assistant event kind: {kind}");
+                }
+            }
+            turn.state = AssistantTurnState::CompletedText(response.to_string());
+            Ok(())
+        }
+
+        pub fn seed_assistant_turn_error(&mut self, prompt: &str, error: &str) -> Result<()> {
+            let index = self.ui_state.push_assistant_turn(prompt.to_string());
+            let Some(turn) = self.ui_state.assistant_turn_mut(index) else {
+                bail!("failed to find seeded assistant turn at index {index}");
+            };
+            turn.state = AssistantTurnState::CompletedError(error.to_string());
+            Ok(())
+        }
+    }
+
+    pub fn deterministic_app_state(session_id: &str) -> Result<AppState> {
+        let (state, _) = deterministic_app_state_with_env(session_id)?;
+        Ok(state)
+    }
+
+    pub fn deterministic_app_state_with_env(
+        session_id: &str,
+    ) -> Result<(AppState, DeterministicTestEnv)> {
+        let env = deterministic_test_env()?;
+        let trace_dir = env.xdg_state_home.join("pychat.ai").join("traces");
+        let state = AppState {
+            mode: Mode::Python,
+            session_id: session_id.to_string(),
+            python: PythonSession::initialize()?,
+            llm: None,
+            agent_config: AgentConfig::default(),
+            theme_config: ThemeConfig::default(),
+            key_bindings: KeyBindings::default(),
+            edit_mode: EditMode::Emacs,
+            thinking_delay: Duration::ZERO,
+            timeline_timestamps: false,
+            timeline_max_bytes: 0,
+            max_steps_shown: 0,
+            auto_page_lines: 0,
+            repr_max_chars: 0,
+            clipboard_osc52: false,
+            auto_time_threshold: None,
+            stream_output: false,
+            inspect_compact: false,
+            echo_expr: false,
+            semicolon_suppresses_output: false,
+            memory_turns: 0,
+            conversation: Vec::new(),
+            history_file: None,
+            config_path: PathBuf::from("config.toml"),
+            config_is_explicit: false,
+            history_recall: HistoryRecall::Block,
+            startup_message: None,
+            trace: SessionTrace::create_in_temp_dir(session_id, &trace_dir)?,
+        };
+        Ok((state, env))
+    }
+
+    pub fn deterministic_app_state_with_theme(
+        session_id: &str,
+        theme_config: ThemeConfig,
+    ) -> Result<AppState> {
+        let mut state = deterministic_app_state(session_id)?;
+        state.theme_config = theme_config;
+        Ok(state)
+    }
+
+    pub fn deterministic_test_env() -> Result<DeterministicTestEnv> {
+        let root = unique_test_root_dir()?;
+        let xdg_config_home = root.join("config-home");
+        let xdg_state_home = root.join("state-home");
+        fs::create_dir_all(&xdg_config_home)
+            .with_context(|| format!("failed to create {}", xdg_config_home.display()))?;
+        fs::create_dir_all(&xdg_state_home)
+            .with_context(|| format!("failed to create {}", xdg_state_home.display()))?;
+        Ok(DeterministicTestEnv {
+            xdg_config_home,
+            xdg_state_home,
+            no_color: "1".to_string(),
+        })
+    }
+
+    fn unique_test_root_dir() -> Result<PathBuf> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        let dir = std::env::temp_dir().join(format!(
+            "pychat.ai-test-support-{}-{nanos}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        Ok(dir)
+    }
+
+    fn buffer_to_string(buffer: &Buffer) -> String {
+        buffer_to_lines(buffer).join("\n")
+    }
+
+    fn buffer_to_lines(buffer: &Buffer) -> Vec<String> {
+        let mut lines = Vec::with_capacity(usize::from(buffer.area.height));
+        for y in 0..buffer.area.height {
+            let mut line = String::new();
+            for x in 0..buffer.area.width {
+                let cell = buffer
+                    .cell((x, y))
+                    .expect("buffer index should be in-bounds");
+                line.push_str(cell.symbol());
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn normalize_snapshot(text: &str) -> String {
+        text.replace("\r\n", "\n")
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
-fn ui_layout(area: Rect, current_input: &str) -> UiLayout {
-    let input_line_count = render_input_lines(current_input).len().max(1);
-    let max_input_lines = 6usize;
-    let input_visible_lines = input_line_count.min(max_input_lines);
-    let input_height = u16::try_from(input_visible_lines.saturating_add(2)).unwrap_or(u16::MAX);
+#[cfg(test)]
+mod tests {
+    use super::{
+        AppState, Mode, OneShot, UiState, ViMode, apply_agent_progress_event, area_contains_point,
+        common_prefix, completion_word_start, execute_command, footer_left_text, footer_right_text,
+        format_elapsed, format_globals_output, format_history_output, format_session_token_usage,
+        format_token_breakdown, format_tool_error_line, format_tool_request_line,
+        format_tool_result_line, glob_matches, handle_key_event, handle_mouse_event, header_line,
+        input_cursor_position, input_hint_for_empty, insert_newline, is_cancel_key,
+        is_safe_source_target, last_line_indent, output_trace_kind, preview_text, prompt_for,
+        remember_turn, render_include_command_result, resolve_color_enabled_with, run_one_shot,
+        session_closed_message, show_trace_write_failure, submit_line, submit_line_cancellable,
+        timeline_max_scroll, timeline_paragraph_scroll, toggle_mode, truncate_with_ellipsis,
+    };
+    use crate::agent::{AgentConfig, AgentProgressEvent};
+    use crate::cli::history::HistoryFile;
+    use crate::cli::theme::Theme;
+    use crate::cli::timeline::{AssistantTurnState, OutputKind, Timeline};
+    use crate::config::{
+        EditMode, HistoryRecall, KeyAction, KeyBindings, ThemeConfig, ThemePreset, ThemeToken,
+    };
+    use crate::http::client::HttpClient;
+    use crate::llm::gemini::GeminiProvider;
+    use crate::llm::provider::{AssistantPart, AssistantRole, LlmTokenUsageTotals};
+    use crate::python::{GlobalEntry, PythonSession};
+    use crate::trace::{SessionTrace, TraceOptions};
+    use crossterm::event::{
+        KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    };
+    use ratatui::layout::Rect;
+    use serde_json::json;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::tempdir;
+    use wiremock::matchers::{method, path, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Min(1),
-            Constraint::Length(input_height),
-            Constraint::Length(1),
-        ])
-        .split(area);
+    #[test]
+    fn test_toggle_mode() {
+        assert_eq!(toggle_mode(Mode::Python), Mode::Assistant);
+        assert_eq!(toggle_mode(Mode::Assistant), Mode::Python);
+    }
 
-    let timeline_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(1), Constraint::Min(0)])
-        .split(chunks[0]);
+    #[test]
+    fn test_prompt_for() {
+        assert_eq!(prompt_for(Mode::Python, false), "py> ");
+        assert_eq!(prompt_for(Mode::Assistant, false), "ai> ");
+        assert_eq!(prompt_for(Mode::Python, true), "cmd> ");
+    }
+
+    #[test]
+    fn force_color_overrides_no_color() {
+        assert!(resolve_color_enabled_with(
+            Some("1".to_string()),
+            Some("true".to_string()),
+            false
+        ));
+    }
+
+    #[test]
+    fn no_color_disables_when_not_forced() {
+        assert!(!resolve_color_enabled_with(
+            Some("1".to_string()),
+            None,
+            true
+        ));
+    }
+
+    #[test]
+    fn tty_enables_colors_by_default() {
+        assert!(resolve_color_enabled_with(None, None, true));
+        assert!(!resolve_color_enabled_with(None, None, false));
+    }
+
+    #[test]
+    fn preview_text_truncates_and_normalizes_whitespace() {
+        let text = preview_text("a  \n\t b", 3);
+        assert_eq!(text, "a b");
+        let text = preview_text(&"x".repeat(20), 10);
+        assert_eq!(text, format!("{}...", "x".repeat(10)));
+    }
+
+    #[test]
+    fn last_line_indent_uses_only_leading_whitespace() {
+        assert_eq!(last_line_indent("    if True:"), "    ");
+        assert_eq!(last_line_indent("x = 1"), "");
+        assert_eq!(last_line_indent("x = 1\n\t  y = 2"), "\t  ");
+    }
 
-    UiLayout {
-        timeline_banner: timeline_chunks[0],
-        timeline: timeline_chunks[1],
-        input: chunks[1],
-        footer: chunks[2],
+    #[test]
+    fn completion_word_start_and_common_prefix() {
+        assert_eq!(completion_word_start("x = os.pa"), 4);
+        assert_eq!(completion_word_start("print(value"), 6);
+        assert_eq!(completion_word_start("x = "), 4);
+        assert_eq!(
+            common_prefix(&["my_other".to_string(), "my_value".to_string()]),
+            "my_"
+        );
+        assert_eq!(common_prefix(&["abc".to_string(), "ab".to_string()]), "ab");
     }
-}
 
-fn timeline_max_scroll(total_lines: usize, visible_lines: usize) -> usize {
-    total_lines.saturating_sub(visible_lines)
-}
+    #[test]
+    fn insert_newline_copies_indent_of_cursor_line() {
+        let mut ui_state = test_ui_state();
+        ui_state.set_current_input("if True:\n    x = 1".to_string());
+        insert_newline(&mut ui_state);
+        assert_eq!(ui_state.python_input, "if True:\n    x = 1\n    ");
+
+        ui_state.set_current_input("if True:\n    x = 1".to_string());
+        ui_state.input_cursor = "if True:".len();
+        insert_newline(&mut ui_state);
+        assert_eq!(ui_state.python_input, "if True:\n\n    x = 1");
+        assert_eq!(ui_state.cursor(), "if True:\n".len());
+    }
 
-fn timeline_paragraph_scroll(
-    total_lines: usize,
-    visible_lines: usize,
-    timeline_scroll: usize,
-) -> u16 {
-    let max_scroll = timeline_max_scroll(total_lines, visible_lines);
-    let scroll = max_scroll.saturating_sub(timeline_scroll.min(max_scroll));
-    u16::try_from(scroll).unwrap_or(u16::MAX)
-}
+    #[test]
+    fn word_delete_stops_at_whitespace_and_python_punctuation() {
+        let mut ui_state = test_ui_state();
+        ui_state.set_current_input("x = obj.attr(value".to_string());
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "x = obj.attr(");
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "x = obj.");
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "x = ");
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "");
+    }
 
-fn area_contains_point(area: Rect, column: u16, row: u16) -> bool {
-    if area.width == 0 || area.height == 0 {
-        return false;
+    #[test]
+    fn word_delete_in_multiline_buffer_stays_on_line_then_joins() {
+        let mut ui_state = test_ui_state();
+        ui_state.set_current_input("for i in items:\n    total += i".to_string());
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "for i in items:\n    total += ");
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "for i in items:\n    total ");
+        ui_state.delete_word_before_cursor();
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "for i in items:\n");
+        ui_state.delete_word_before_cursor();
+        assert_eq!(ui_state.python_input, "for i in items:");
     }
-    let in_x = column >= area.x && column < area.x.saturating_add(area.width);
-    let in_y = row >= area.y && row < area.y.saturating_add(area.height);
-    in_x && in_y
-}
 
-fn draw_ui(frame: &mut ratatui::Frame<'_>, ui_state: &UiState) {
-    let command_input = is_command_line(ui_state.current_input());
-    let prompt = prompt_for(ui_state.mode, command_input);
-    let input_lines = render_input_lines(ui_state.current_input());
-    let input_line_count = input_lines.len().max(1);
-    let max_input_lines = 6usize;
-    let input_visible_lines = input_line_count.min(max_input_lines);
-    let layout = ui_layout(frame.area(), ui_state.current_input());
+    #[test]
+    fn kill_line_commands_respect_logical_lines() {
+        let mut ui_state = test_ui_state();
+        ui_state.set_current_input("a = 1\nbb = 2\nc = 3".to_string());
+        ui_state.input_cursor = "a = 1\nbb".len();
+
+        ui_state.kill_to_line_end();
+        assert_eq!(ui_state.python_input, "a = 1\nbb\nc = 3");
+        ui_state.kill_to_line_end();
+        assert_eq!(ui_state.python_input, "a = 1\nbbc = 3");
+        ui_state.kill_to_line_start();
+        assert_eq!(ui_state.python_input, "a = 1\nc = 3");
+        assert_eq!(ui_state.cursor(), "a = 1\n".len());
+    }
 
-    render_sticky_motd(frame, ui_state, layout.timeline_banner);
+    #[test]
+    fn input_cursor_position_tracks_cursor_offset() {
+        assert_eq!(input_cursor_position("", 0), (0, 0));
+        assert_eq!(input_cursor_position("abc", 3), (0, 3));
+        assert_eq!(input_cursor_position("a\nbc", 4), (1, 2));
+        assert_eq!(input_cursor_position("a\nbc", 1), (0, 1));
+        assert_eq!(input_cursor_position("é\nbc", 4), (1, 1));
+    }
 
-    let lines = ui_state
-        .timeline
-        .render_lines(&ui_state.theme, ui_state.show_assistant_steps);
+    #[test]
+    fn cursor_editing_inserts_and_deletes_mid_line() {
+        let mut ui_state = test_ui_state();
+        ui_state.set_current_input("pint(x)".to_string());
+        ui_state.move_cursor_line_start();
+        ui_state.move_cursor_right();
+        ui_state.insert_at_cursor("r");
+        assert_eq!(ui_state.python_input, "print(x)");
+
+        ui_state.move_cursor_line_end();
+        ui_state.move_cursor_left();
+        ui_state.delete_before_cursor();
+        ui_state.insert_at_cursor("é");
+        assert_eq!(ui_state.python_input, "print(é)");
+        ui_state.move_cursor_left();
+        ui_state.delete_at_cursor();
+        assert_eq!(ui_state.python_input, "print()");
+
+        ui_state.set_current_input("a = 1\nbb = 2".to_string());
+        ui_state.input_cursor = 2;
+        ui_state.move_cursor_line_end();
+        assert_eq!(ui_state.cursor(), "a = 1".len());
+        ui_state.move_cursor_right();
+        ui_state.move_cursor_line_end();
+        assert_eq!(ui_state.cursor(), "a = 1\nbb = 2".len());
+        ui_state.move_cursor_line_start();
+        assert_eq!(ui_state.cursor(), "a = 1\n".len());
+    }
 
-    let visible_lines = usize::from(layout.timeline.height);
-    let scroll = timeline_paragraph_scroll(
-        lines.len(),
-        visible_lines,
-        ui_state.timeline_scroll_offset(timeline_max_scroll(lines.len(), visible_lines)),
-    );
+    #[test]
+    fn header_line_renders_brand() {
+        let theme = Theme::new(false);
+        assert_eq!(header_line(&theme, 80).to_string(), "PyChat.AI");
+    }
 
-    let output = Paragraph::new(lines)
-        .block(Block::default().padding(Padding::new(1, 1, 0, 0)))
-        .wrap(Wrap { trim: false })
-        .scroll((scroll, 0));
-    frame.render_widget(output, layout.timeline);
+    #[test]
+    fn footer_text_helpers_match_requested_copy() {
+        assert_eq!(
+            footer_left_text(Mode::Python, None, true, 80),
+            "Python | Thinking: On"
+        );
+        assert_eq!(
+            footer_left_text(Mode::Assistant, None, false, 80),
+            "AI Assistant | Thinking: Off"
+        );
+        assert_eq!(
+            footer_left_text(Mode::Python, Some(ViMode::Normal), true, 80),
+            "NORMAL | Python | Thinking: On"
+        );
+        assert_eq!(
+            footer_right_text(&LlmTokenUsageTotals {
+                input_tokens: 12,
+                output_tokens: 34,
+                total_tokens: 46,
+                unreported_calls: 0,
+            }),
+            "Questions? /help | Tokens: 46"
+        );
+    }
 
-    let is_empty_input = ui_state.current_input().is_empty();
-    let input_scroll =
-        u16::try_from(input_line_count.saturating_sub(input_visible_lines)).unwrap_or(u16::MAX);
-    let prompt_padding = " ".repeat(prompt.chars().count());
-    let mut rendered_lines = Vec::with_capacity(input_lines.len());
-    for (idx, line) in input_lines.into_iter().enumerate() {
-        let prompt_span = if idx == 0 {
-            Span::styled(
-                prompt,
-                ui_state
-                    .theme
-                    .style(prompt_token_for(ui_state.mode, command_input)),
-            )
-        } else {
-            Span::styled(
-                prompt_padding.clone(),
-                ui_state
-                    .theme
-                    .style(prompt_token_for(ui_state.mode, command_input)),
-            )
-        };
-        let input_span = if is_empty_input && idx == 0 {
-            Span::styled(
-                input_hint_for_empty(ui_state.mode),
-                ui_state.theme.style(ThemeToken::FooterSecondary),
-            )
-        } else {
-            Span::styled(
-                line.to_string(),
-                ui_state.theme.style(ThemeToken::InputBlock),
-            )
-        };
-        rendered_lines.push(Line::from(vec![prompt_span, input_span]));
+    #[test]
+    fn empty_input_hint_mentions_help() {
+        assert!(input_hint_for_empty(Mode::Python).contains("/help"));
+        assert!(input_hint_for_empty(Mode::Assistant).contains("/help"));
     }
 
-    let input_widget = Paragraph::new(rendered_lines)
-        .block(
-            Block::default()
-                .padding(Padding::new(1, 1, 1, 1))
-                .style(ui_state.theme.style(ThemeToken::InputBlock)),
-        )
-        .wrap(Wrap { trim: false })
-        .scroll((input_scroll, 0));
-    frame.render_widget(input_widget, layout.input);
+    #[test]
+    fn truncate_with_ellipsis_handles_small_widths() {
+        assert_eq!(truncate_with_ellipsis("abcdef", 0), "");
+        assert_eq!(truncate_with_ellipsis("abcdef", 2), "..");
+        assert_eq!(truncate_with_ellipsis("abcdef", 6), "abcdef");
+        assert_eq!(truncate_with_ellipsis("abcdef", 5), "ab...");
+    }
 
-    render_footer(frame, ui_state, &layout);
+    #[test]
+    fn execute_command_tools_lists_tools_with_eval_status() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("tools", dir.path());
+        let mut ui_state = test_ui_state();
 
-    let (cursor_row, cursor_col) = input_cursor_position(ui_state.current_input());
-    let cursor_row = cursor_row.saturating_sub(usize::from(input_scroll));
-    let cursor_x = layout
-        .input
-        .x
-        .saturating_add(1)
-        .saturating_add(u16::try_from(prompt.chars().count()).unwrap_or(u16::MAX))
-        .saturating_add(u16::try_from(cursor_col).unwrap_or(u16::MAX));
-    let cursor_y = layout
-        .input
-        .y
-        .saturating_add(1)
-        .saturating_add(u16::try_from(cursor_row).unwrap_or(u16::MAX));
-    frame.set_cursor_position((cursor_x, cursor_y));
-}
+        execute_command(&mut state, &mut ui_state, "/tools");
+        let lines = timeline_text_lines(&ui_state);
+        for name in ["list_globals", "inspect", "eval_expr"] {
+            assert!(
+                lines
+                    .iter()
+                    .any(|line| line.starts_with(&format!("  {name} "))),
+                "{lines:?}"
+            );
+        }
+        assert!(lines.iter().any(|line| line.starts_with("  apply_code")
+            && line.ends_with("(disabled: allow_apply = false)")));
+        assert!(!lines.iter().any(|line| line.contains("allow_eval = false")));
 
-fn render_sticky_motd(frame: &mut ratatui::Frame<'_>, ui_state: &UiState, area: Rect) {
-    if area.width == 0 || area.height == 0 {
-        return;
+        state.agent_config.allow_eval = false;
+        execute_command(&mut state, &mut ui_state, "/tools");
+        assert!(
+            timeline_text_lines(&ui_state)
+                .iter()
+                .any(|line| line.starts_with("  eval_expr")
+                    && line.ends_with("(disabled: allow_eval = false)"))
+        );
     }
-    let line = header_line(&ui_state.theme, usize::from(area.width));
-    let widget = Paragraph::new(line);
-    frame.render_widget(widget, area);
-}
 
-fn render_footer(frame: &mut ratatui::Frame<'_>, ui_state: &UiState, layout: &UiLayout) {
-    if layout.footer.width == 0 || layout.footer.height == 0 {
-        return;
+    #[test]
+    fn format_session_token_usage_includes_in_out_total() {
+        assert_eq!(
+            format_session_token_usage(&LlmTokenUsageTotals {
+                input_tokens: 3,
+                output_tokens: 2,
+                total_tokens: 5,
+                unreported_calls: 0,
+            }),
+            "session tokens in=3 out=2 total=5"
+        );
     }
 
-    let right_text = footer_right_text(&ui_state.session_token_usage);
-    let right_width = right_text.chars().count().saturating_add(1);
-    let right_width = right_width.min(usize::from(layout.footer.width));
-    let right_width = u16::try_from(right_width).unwrap_or(u16::MAX);
-    let bottom_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Min(0), Constraint::Length(right_width)])
-        .split(layout.footer);
-
-    let left_available = usize::from(bottom_chunks[0].width);
-    let left = Paragraph::new(footer_left_line(
-        &ui_state.theme,
-        ui_state.mode,
-        ui_state.show_assistant_steps,
-        left_available,
-    ));
-    frame.render_widget(left, bottom_chunks[0]);
+    #[test]
+    fn format_token_breakdown_lists_turns_and_marks_unreported_usage() {
+        let first = LlmTokenUsageTotals {
+            input_tokens: 10,
+            output_tokens: 4,
+            total_tokens: 14,
+            unreported_calls: 0,
+        };
+        let second = LlmTokenUsageTotals {
+            input_tokens: 0,
+            output_tokens: 0,
+            total_tokens: 0,
+            unreported_calls: 1,
+        };
+        let mut session = LlmTokenUsageTotals::default();
+        session.add_totals(&first);
+        session.add_totals(&second);
 
-    let right = Paragraph::new(footer_right_line(
-        &ui_state.theme,
-        &ui_state.session_token_usage,
-        usize::from(bottom_chunks[1].width),
-    ))
-    .alignment(ratatui::layout::Alignment::Right);
-    frame.render_widget(right, bottom_chunks[1]);
-}
+        assert_eq!(
+            format_token_breakdown(&[first, second], &session),
+            "turn 1: in=10 out=4 total=14\nturn 2: in=? out=? total=?\nsession: in=10+? out=4+? total=14+?"
+        );
+        assert_eq!(
+            format_token_breakdown(&[], &LlmTokenUsageTotals::default()),
+            "no assistant turns yet"
+        );
+    }
 
-fn prompt_token_for(mode: Mode, command_input: bool) -> ThemeToken {
-    if command_input {
-        return ThemeToken::CommandPrompt;
+    #[test]
+    fn session_closed_message_includes_trace_file_path() {
+        assert_eq!(
+            session_closed_message(
+                std::path::Path::new("/tmp/pychat.ai/traces/session-abc123.log"),
+                &LlmTokenUsageTotals {
+                    input_tokens: 12,
+                    output_tokens: 3,
+                    total_tokens: 15,
+                    unreported_calls: 0,
+                }
+            ),
+            "PyChat.ai session ended.\nTokens: 15\nTrace file: /tmp/pychat.ai/traces/session-abc123.log"
+        );
     }
 
-    match mode {
-        Mode::Python => ThemeToken::PythonPrompt,
-        Mode::Assistant => ThemeToken::AssistantPrompt,
+    #[test]
+    fn output_trace_kind_maps_tokens() {
+        assert_eq!(output_trace_kind(OutputKind::PythonStdout), "py.out");
+        assert_eq!(
+            output_trace_kind(OutputKind::AssistantProgressResult),
+            "ai.step"
+        );
     }
-}
 
-fn format_tool_request_line(name: &str, args_json: &Value) -> String {
-    match name {
-        "list_globals" => "-> Listing globals".to_string(),
-        "inspect" => format!(
-            "-> Inspecting: {}",
-            extract_expr_preview(args_json).unwrap_or_else(|| "<missing expr>".to_string())
-        ),
-        "eval_expr" => format!(
-            "-> Evaluating: {}",
-            extract_expr_preview(args_json).unwrap_or_else(|| "<missing expr>".to_string())
-        ),
-        _ => format!("-> Calling tool: {name}"),
+    #[test]
+    fn format_tool_request_line_uses_semantic_labels() {
+        assert_eq!(
+            format_tool_request_line("list_globals", &json!({})),
+            "-> Listing globals"
+        );
+        assert_eq!(
+            format_tool_request_line("inspect", &json!({"expr":"value [ 0 ]"})),
+            "-> Inspecting: value [ 0 ]"
+        );
+        assert_eq!(
+            format_tool_request_line("eval_expr", &json!({"expr":"a + b"})),
+            "-> Evaluating: a + b"
+        );
     }
-}
 
-fn format_tool_result_line(name: &str, response_json: &Value) -> String {
-    if !response_json
-        .get("ok")
-        .and_then(Value::as_bool)
-        .unwrap_or(false)
-    {
-        return format_tool_error_line(name, response_json);
+    #[test]
+    fn format_tool_result_line_summarizes_known_tools() {
+        assert_eq!(
+            format_tool_result_line(
+                "list_globals",
+                &json!({"ok":true,"result":{"globals":[{"name":"a"},{"name":"b"}]}}),
+                false
+            ),
+            "<- Found 2 globals"
+        );
+        assert_eq!(
+            format_tool_result_line(
+                "inspect",
+                &json!({"ok":true,"result":{"type":{"name":"dict"}}}),
+                false
+            ),
+            "<- Inspection complete: dict"
+        );
+        assert_eq!(
+            format_tool_result_line(
+                "eval_expr",
+                &json!({"ok":true,"result":{"value_repr":"3"}}),
+                false
+            ),
+            "<- Evaluated: 3"
+        );
     }
 
-    let Some(result) = response_json.get("result") else {
-        return format!("Tool completed: {name}");
-    };
+    #[test]
+    fn format_tool_result_line_verbose_adds_names_and_longer_values() {
+        let names = (0..12)
+            .map(|index| json!({"name": format!("g{index}")}))
+            .collect::<Vec<_>>();
+        let globals = json!({"ok":true,"result":{"globals":names}});
+        assert_eq!(
+            format_tool_result_line("list_globals", &globals, false),
+            "<- Found 12 globals"
+        );
+        assert_eq!(
+            format_tool_result_line("list_globals", &globals, true),
+            "<- Found 12 globals: g0, g1, g2, g3, g4, g5, g6, g7, g8, g9, ..."
+        );
 
-    match name {
-        "list_globals" => {
-            let count = result
-                .get("globals")
-                .and_then(Value::as_array)
-                .map_or(0, |globals| globals.len());
-            format!("<- Found {count} globals")
-        }
-        "inspect" => {
-            let info = result
-                .get("type")
-                .and_then(|ty| ty.get("name"))
-                .and_then(Value::as_str)
-                .or_else(|| result.get("kind").and_then(Value::as_str))
-                .unwrap_or("value");
-            format!("<- Inspection complete: {}", preview_text(info, 80))
-        }
-        "eval_expr" => {
-            let value_repr = result
-                .get("value_repr")
-                .and_then(Value::as_str)
-                .unwrap_or("<unknown>");
-            format!("<- Evaluated: {}", preview_text(value_repr, 80))
-        }
-        _ => format!("<- Tool completed: {name}"),
+        let long_value = "x".repeat(120);
+        let eval = json!({"ok":true,"result":{"value_repr":long_value}});
+        assert_eq!(
+            format_tool_result_line("eval_expr", &eval, false),
+            format!("<- Evaluated: {}...", "x".repeat(80))
+        );
+        assert_eq!(
+            format_tool_result_line("eval_expr", &eval, true),
+            format!("<- Evaluated: {long_value}")
+        );
     }
-}
-
-fn format_tool_error_line(name: &str, response_json: &Value) -> String {
-    let (code, message) = response_json
-        .get("error")
-        .and_then(Value::as_object)
-        .map(|error| {
-            let code = error
-                .get("code")
-                .and_then(Value::as_str)
-                .unwrap_or("error")
-                .to_string();
-            let message = error
-                .get("message")
-                .and_then(Value::as_str)
-                .unwrap_or("tool failed")
-                .to_string();
-            (code, message)
-        })
-        .unwrap_or_else(|| ("error".to_string(), "tool failed".to_string()));
-    format!(
-        "<- Tool error ({name}): {code}: {}",
-        preview_text(&message, 100)
-    )
-}
-
-fn extract_expr_preview(args_json: &Value) -> Option<String> {
-    args_json
-        .as_object()
-        .and_then(|args| args.get("expr"))
-        .and_then(Value::as_str)
-        .map(|expr| preview_text(expr, 80))
-}
 
-fn preview_text(value: &str, max_len: usize) -> String {
-    let normalized = normalize_whitespace(value);
-    truncate_chars(&normalized, max_len)
-}
+    #[test]
+    fn format_tool_result_line_keeps_tabular_repr_alignment() {
+        let repr = "   a    b\n0  1   10\n1  2\t20";
+        assert_eq!(
+            format_tool_result_line(
+                "eval_expr",
+                &json!({"ok":true,"result":{"value_repr":repr,"tabular":true}}),
+                false
+            ),
+            "<- Evaluated:\n   a    b\n0  1   10\n1  2    20"
+        );
+    }
 
-fn normalize_whitespace(value: &str) -> String {
-    value.split_whitespace().collect::<Vec<_>>().join(" ")
-}
+    #[test]
+    fn format_tool_error_line_includes_code_and_reason() {
+        assert_eq!(
+            format_tool_error_line(
+                "inspect",
+                &json!({"ok":false,"error":{"code":"python_exception","message":"NameError: x"}})
+            ),
+            "<- Tool error (inspect): python_exception: NameError: x"
+        );
+        assert_eq!(
+            format_tool_result_line(
+                "eval_expr",
+                &json!({"ok":false,"error":{"code":"disabled","message":"eval_expr is disabled"}}),
+                false
+            ),
+            "<- Tool error (eval_expr): disabled: eval_expr is disabled"
+        );
+    }
 
-fn truncate_chars(value: &str, max_len: usize) -> String {
-    let mut chars = value.chars();
-    let preview: String = chars.by_ref().take(max_len).collect();
-    if chars.next().is_some() {
-        format!("{preview}...")
-    } else {
-        preview
+    #[test]
+    fn format_history_output_limits_tail_entries() {
+        let history = vec![
+            "a = 1".to_string(),
+            "/help".to_string(),
+            "x + 1".to_string(),
+            "/history 2".to_string(),
+        ];
+        assert_eq!(
+            format_history_output(&history, Some(2)),
+            "   3: x + 1\n   4: /history 2"
+        );
     }
-}
 
-fn last_line_indent(input: &str) -> String {
-    input
-        .rsplit('\n')
-        .next()
-        .unwrap_or("")
-        .chars()
-        .take_while(|ch| *ch == ' ' || *ch == '\t')
-        .collect()
-}
+    #[test]
+    fn glob_matches_supports_star_and_question_mark() {
+        assert!(glob_matches("df*", "df"));
+        assert!(glob_matches("df*", "df_sales"));
+        assert!(glob_matches("*_id", "user_id"));
+        assert!(glob_matches("x?", "x1"));
+        assert!(glob_matches("a*b*c", "axxbyyc"));
+        assert!(!glob_matches("df*", "my_df"));
+        assert!(!glob_matches("x?", "x12"));
+    }
 
-fn append_newline_with_indent(input: &mut String) {
-    let indent = last_line_indent(input);
-    input.push('\n');
-    input.push_str(&indent);
-}
+    #[test]
+    fn format_globals_output_aligns_types_and_applies_filter() {
+        let globals = vec![
+            GlobalEntry {
+                name: "df".to_string(),
+                type_name: "DataFrame".to_string(),
+            },
+            GlobalEntry {
+                name: "df_sales".to_string(),
+                type_name: "DataFrame".to_string(),
+            },
+            GlobalEntry {
+                name: "x".to_string(),
+                type_name: "int".to_string(),
+            },
+        ];
 
-fn render_input_lines(input: &str) -> Vec<&str> {
-    if input.is_empty() {
-        return vec![""];
+        assert_eq!(
+            format_globals_output(&globals, None),
+            "df        DataFrame\ndf_sales  DataFrame\nx         int"
+        );
+        assert_eq!(
+            format_globals_output(&globals, Some("df_*")),
+            "df_sales  DataFrame"
+        );
+        assert_eq!(
+            format_globals_output(&globals, Some("y*")),
+            "no globals match 'y*'"
+        );
+        assert_eq!(format_globals_output(&[], None), "no globals defined");
     }
-    input.split('\n').collect()
-}
 
-fn input_cursor_position(input: &str) -> (usize, usize) {
-    if input.is_empty() {
-        return (0, 0);
+    #[test]
+    fn timeline_paragraph_scroll_follows_manual_offset() {
+        assert_eq!(timeline_paragraph_scroll(20, 5, 0), 15);
+        assert_eq!(timeline_paragraph_scroll(20, 5, 3), 12);
+        assert_eq!(timeline_paragraph_scroll(20, 5, 99), 0);
     }
 
-    let lines: Vec<&str> = input.split('\n').collect();
-    let row = lines.len().saturating_sub(1);
-    let col = lines[row].chars().count();
-    (row, col)
-}
+    #[test]
+    fn area_contains_point_matches_rect_bounds() {
+        let area = Rect::new(10, 5, 3, 2);
+        assert!(area_contains_point(area, 10, 5));
+        assert!(area_contains_point(area, 12, 6));
+        assert!(!area_contains_point(area, 13, 6));
+        assert!(!area_contains_point(area, 12, 7));
+    }
 
-fn resolve_color_enabled() -> bool {
-    resolve_color_enabled_with(
-        std::env::var("NO_COLOR").ok(),
-        std::env::var("PYCHAT_AI_FORCE_COLOR").ok(),
-        io::stdout().is_terminal(),
-    )
-}
+    #[test]
+    fn mouse_wheel_scrolls_timeline_with_clamp() {
+        let dir = tempdir().expect("tempdir");
+        let state = test_app_state("mouse", dir.path());
+        let mut ui_state = test_ui_state();
+        let timeline_area = Rect::new(0, 0, 80, 8);
+        let max_scroll = 7usize;
 
-fn resolve_color_enabled_with(
-    no_color: Option<String>,
-    force_color: Option<String>,
-    is_tty: bool,
-) -> bool {
-    if let Some(value) = force_color
-        && matches!(
-            value.trim().to_ascii_lowercase().as_str(),
-            "1" | "true" | "yes" | "on"
-        )
-    {
-        return true;
-    }
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollUp, 2, 2),
+            timeline_area,
+            max_scroll,
+        );
+        assert_eq!(ui_state.timeline_scroll, 3);
 
-    if no_color.is_some() {
-        return false;
-    }
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollUp, 2, 2),
+            timeline_area,
+            max_scroll,
+        );
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollUp, 2, 2),
+            timeline_area,
+            max_scroll,
+        );
+        assert_eq!(ui_state.timeline_scroll, max_scroll);
 
-    is_tty
-}
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollDown, 2, 2),
+            timeline_area,
+            max_scroll,
+        );
+        assert_eq!(ui_state.timeline_scroll, 4);
 
-pub fn prompt_for(mode: Mode, command_input: bool) -> &'static str {
-    if command_input {
-        return "cmd> ";
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollDown, 2, 2),
+            timeline_area,
+            max_scroll,
+        );
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollDown, 2, 2),
+            timeline_area,
+            max_scroll,
+        );
+        assert_eq!(ui_state.timeline_scroll, 0);
     }
 
-    match mode {
-        Mode::Python => "py> ",
-        Mode::Assistant => "ai> ",
-    }
-}
+    #[test]
+    fn mouse_wheel_outside_timeline_is_ignored() {
+        let dir = tempdir().expect("tempdir");
+        let state = test_app_state("mouse", dir.path());
+        let mut ui_state = test_ui_state();
+        ui_state.timeline_scroll = 4;
+        let timeline_area = Rect::new(0, 0, 80, 8);
 
-fn toggle_mode(mode: Mode) -> Mode {
-    match mode {
-        Mode::Python => Mode::Assistant,
-        Mode::Assistant => Mode::Python,
-    }
-}
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollUp, 2, 10),
+            timeline_area,
+            20,
+        );
 
-fn header_line(theme: &Theme, width: usize) -> Line<'static> {
-    let brand = truncate_with_ellipsis("PyChat.AI", width);
-    Line::from(Span::styled(brand, theme.style(ThemeToken::MotdBrand)))
-}
+        assert_eq!(ui_state.timeline_scroll, 4);
+    }
 
-fn footer_left_text(mode: Mode, show_assistant_steps: bool, width: usize) -> String {
-    let steps = if show_assistant_steps { "On" } else { "Off" };
-    let mode_text = match mode {
-        Mode::Python => "Python",
-        Mode::Assistant => "AI Assistant",
-    };
-    truncate_with_ellipsis(&format!("{mode_text} | Thinking: {steps}"), width)
-}
+    #[test]
+    fn mouse_wheel_does_not_change_history_selection() {
+        let dir = tempdir().expect("tempdir");
+        let state = test_app_state("mouse", dir.path());
+        let mut ui_state = test_ui_state();
+        ui_state.history = vec!["x = 1".to_string(), "x + 1".to_string()];
+        ui_state.history_index = Some(1);
+        ui_state.python_input = "x + 1".to_string();
+        let timeline_area = Rect::new(0, 0, 80, 8);
 
-fn footer_right_text(usage: &LlmTokenUsageTotals) -> String {
-    format!("Questions? /help | Tokens: {}", usage.total_tokens)
-}
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::ScrollUp, 3, 3),
+            timeline_area,
+            20,
+        );
 
-fn footer_left_line(
-    theme: &Theme,
-    mode: Mode,
-    show_assistant_steps: bool,
-    width: usize,
-) -> Line<'static> {
-    let text = footer_left_text(mode, show_assistant_steps, width);
-    let full = match mode {
-        Mode::Python => {
-            if show_assistant_steps {
-                "Python | Thinking: On"
-            } else {
-                "Python | Thinking: Off"
-            }
-        }
-        Mode::Assistant => {
-            if show_assistant_steps {
-                "AI Assistant | Thinking: On"
-            } else {
-                "AI Assistant | Thinking: Off"
-            }
-        }
-    };
-    if text != full {
-        return Line::from(Span::styled(text, theme.style(ThemeToken::FooterPrimary)));
+        assert_eq!(ui_state.history_index, Some(1));
+        assert_eq!(ui_state.python_input, "x + 1");
     }
 
-    let (mode_text, steps) = match mode {
-        Mode::Python => ("Python", if show_assistant_steps { "On" } else { "Off" }),
-        Mode::Assistant => (
-            "AI Assistant",
-            if show_assistant_steps { "On" } else { "Off" },
-        ),
-    };
+    #[test]
+    fn left_click_copies_the_clicked_timeline_line() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("mouse", dir.path());
+        state.clipboard_osc52 = true;
+        let mut ui_state = test_ui_state();
+        ui_state.push_timeline_output(OutputKind::PythonStdout, "hello");
+        ui_state.push_timeline_output(OutputKind::PythonStdout, "world");
+        let timeline_area = Rect::new(0, 2, 80, 8);
 
-    Line::from(vec![
-        Span::styled(
-            mode_text.to_string(),
-            theme.style(ThemeToken::FooterPrimary),
-        ),
-        Span::styled(" | ".to_string(), theme.style(ThemeToken::FooterSecondary)),
-        Span::styled(
-            "Thinking: ".to_string(),
-            theme.style(ThemeToken::FooterSecondary),
-        ),
-        Span::styled(steps.to_string(), theme.style(ThemeToken::FooterAccent)),
-    ])
-}
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::Down(MouseButton::Left), 4, 3),
+            timeline_area,
+            0,
+        );
 
-fn footer_right_line(theme: &Theme, usage: &LlmTokenUsageTotals, width: usize) -> Line<'static> {
-    let text = footer_right_text(usage);
-    let full = format!("Questions? /help | Tokens: {}", usage.total_tokens);
-    if text.chars().count() > width || text != full {
-        return Line::from(Span::styled(text, theme.style(ThemeToken::FooterSecondary)));
+        assert_eq!(ui_state.pending_clipboard.as_deref(), Some("world"));
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("copied line to the clipboard (OSC 52)")
+        );
     }
 
-    Line::from(vec![
-        Span::styled(
-            "Questions? ".to_string(),
-            theme.style(ThemeToken::FooterSecondary),
-        ),
-        Span::styled("/help".to_string(), theme.style(ThemeToken::FooterAccent)),
-        Span::styled(" | ".to_string(), theme.style(ThemeToken::FooterSecondary)),
-        Span::styled(
-            "Tokens: ".to_string(),
-            theme.style(ThemeToken::FooterSecondary),
-        ),
-        Span::styled(
-            usage.total_tokens.to_string(),
-            theme.style(ThemeToken::FooterAccent),
-        ),
-    ])
-}
+    #[test]
+    fn left_click_without_osc52_uses_the_clipboard_fallback() {
+        let dir = tempdir().expect("tempdir");
+        let state = test_app_state("mouse-no-osc52", dir.path());
+        let mut ui_state = test_ui_state();
+        ui_state.push_timeline_output(OutputKind::PythonStdout, "hello");
+        let timeline_area = Rect::new(0, 2, 80, 8);
 
-fn input_hint_for_empty(mode: Mode) -> String {
-    match mode {
-        Mode::Python => "/help for commands".to_string(),
-        Mode::Assistant => "Ask about runtime state or /help".to_string(),
-    }
-}
+        handle_mouse_event(
+            &state,
+            &mut ui_state,
+            mouse_event(MouseEventKind::Down(MouseButton::Left), 4, 2),
+            timeline_area,
+            0,
+        );
 
-fn truncate_with_ellipsis(text: &str, width: usize) -> String {
-    if width == 0 {
-        return String::new();
+        assert_eq!(ui_state.pending_clipboard, None);
+        let last = timeline_text_lines(&ui_state)
+            .last()
+            .cloned()
+            .expect("copy message");
+        assert!(!last.contains("OSC 52"), "{last}");
+        assert!(last.contains("line"), "{last}");
     }
-    let count = text.chars().count();
-    if count <= width {
-        return text.to_string();
+
+    #[test]
+    fn timeline_manual_scroll_is_preserved_when_new_output_arrives() {
+        let mut ui_state = test_ui_state();
+        ui_state.timeline_scroll = 5;
+        ui_state.push_timeline_output(OutputKind::PythonStdout, "hello");
+        ui_state.push_timeline_output(OutputKind::PythonStdout, "world");
+        assert_eq!(ui_state.timeline_scroll, 5);
     }
-    if width <= 3 {
-        return ".".repeat(width);
+
+    #[test]
+    fn timeline_max_scroll_matches_content_and_viewport() {
+        assert_eq!(timeline_max_scroll(0, 10), 0);
+        assert_eq!(timeline_max_scroll(5, 10), 0);
+        assert_eq!(timeline_max_scroll(11, 10), 1);
     }
-    let keep = width.saturating_sub(3);
-    let mut out = text.chars().take(keep).collect::<String>();
-    out.push_str("...");
-    out
-}
 
-fn format_session_token_usage(usage: &LlmTokenUsageTotals) -> String {
-    format!(
-        "session tokens in={} out={} total={}",
-        usage.input_tokens, usage.output_tokens, usage.total_tokens
-    )
-}
+    #[test]
+    fn source_target_validation_allows_identifier_paths_only() {
+        assert!(is_safe_source_target("my_fn"));
+        assert!(is_safe_source_target("module.ClassName"));
+        assert!(!is_safe_source_target(""));
+        assert!(!is_safe_source_target("1name"));
+        assert!(!is_safe_source_target("obj.method()"));
+        assert!(!is_safe_source_target("__import__('os').system"));
+    }
 
-fn session_closed_message(
-    trace_file_path: &std::path::Path,
-    usage: &LlmTokenUsageTotals,
-) -> String {
-    format!(
-        "PyChat.ai session ended.\nTokens: {}\nTrace file: {}",
-        usage.total_tokens,
-        trace_file_path.display()
-    )
-}
+    #[test]
+    fn trace_write_failure_is_shown_once_in_the_timeline() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("trace-failure", dir.path());
+        let trace_dir = dir.path().join("rotating");
+        state.trace = SessionTrace::create_in_dir(
+            "trace-failure",
+            &trace_dir,
+            &TraceOptions {
+                max_bytes: 1,
+                ..TraceOptions::default()
+            },
+        )
+        .expect("trace");
+        let mut ui_state = test_ui_state();
 
-fn push_output(ui_state: &mut UiState, trace: &SessionTrace, kind: OutputKind, text: &str) {
-    ui_state.push_timeline_output(kind, text);
-    trace.log_output(output_trace_kind(kind), text);
-}
+        // The next rotation cannot create its file once the directory is gone.
+        state.trace.log_output("py.out", "first");
+        fs::remove_dir_all(&trace_dir).expect("remove trace dir");
+        state.trace.log_output("py.out", "second");
+        state.trace.log_output("py.out", "third");
 
-fn output_trace_kind(kind: OutputKind) -> &'static str {
-    match kind {
-        OutputKind::UserInputPython => "py.in",
-        OutputKind::UserInputAssistant => "ai.in",
-        OutputKind::PythonValue => "py.out",
-        OutputKind::PythonStdout => "py.out",
-        OutputKind::PythonStderr => "py.err",
-        OutputKind::PythonTraceback => "py.tb",
-        OutputKind::AssistantText => "ai.out",
-        OutputKind::AssistantWaiting => "ai.wait",
-        OutputKind::AssistantProgressRequest => "ai.step",
-        OutputKind::AssistantProgressResult => "ai.step",
-        OutputKind::SystemInfo => "sys.info",
-        OutputKind::SystemError => "sys.err",
+        show_trace_write_failure(&state, &mut ui_state);
+        show_trace_write_failure(&state, &mut ui_state);
+        let lines = timeline_text_lines(&ui_state);
+        let warnings: Vec<_> = lines
+            .iter()
+            .filter(|line| line.starts_with("trace warning: failed to rotate trace file"))
+            .collect();
+        assert_eq!(warnings.len(), 1);
     }
-}
 
-#[cfg(feature = "test-support")]
-pub mod test_support {
-    use super::{
-        AppState, Mode, UiState, draw_ui, handle_key_event, handle_mouse_event, is_command_line,
-        prompt_for, timeline_max_scroll, ui_layout,
-    };
-    use crate::agent::AgentConfig;
-    use crate::cli::timeline::{AssistantStepEvent, AssistantTurnState};
-    use crate::config::ThemeConfig;
-    use crate::python::PythonSession;
-    use crate::trace::SessionTrace;
-    use anyhow::{Context, Result, bail};
-    use crossterm::event::{KeyEvent, MouseEvent};
-    use ratatui::Terminal;
-    use ratatui::backend::TestBackend;
-    use ratatui::buffer::Buffer;
-    use ratatui::layout::Rect;
-    use std::fs;
-    use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
+    #[test]
+    fn execute_command_mode_and_steps_updates_ui_state() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("mode-steps", dir.path());
+        let mut ui_state = test_ui_state();
 
-    #[derive(Debug)]
-    pub struct UiStateView {
-        pub mode: Mode,
-        pub prompt: &'static str,
-        pub input: String,
-        pub timeline_scroll: usize,
-        pub show_assistant_steps: bool,
-    }
+        execute_command(&mut state, &mut ui_state, "/mode ai");
+        assert_eq!(ui_state.mode, Mode::Assistant);
 
-    pub struct UiHarness {
-        terminal: Terminal<TestBackend>,
-        app_state: AppState,
-        ui_state: UiState,
-    }
+        execute_command(&mut state, &mut ui_state, "/steps off");
+        assert!(!ui_state.show_assistant_steps);
+
+        execute_command(&mut state, &mut ui_state, "/steps");
+        assert!(ui_state.show_assistant_steps);
+
+        execute_command(&mut state, &mut ui_state, "/steps off");
+        execute_command(&mut state, &mut ui_state, "/steps verbose");
+        assert!(ui_state.show_assistant_steps);
+        assert!(ui_state.verbose_steps);
 
-    #[derive(Debug, Clone)]
-    pub struct DeterministicTestEnv {
-        pub xdg_config_home: PathBuf,
-        pub xdg_state_home: PathBuf,
-        pub no_color: String,
+        execute_command(&mut state, &mut ui_state, "/steps on");
+        assert!(!ui_state.verbose_steps);
     }
 
-    #[derive(Debug, Clone, Copy)]
-    pub struct UiRegions {
-        pub motd: Rect,
-        pub timeline: Rect,
-        pub input: Rect,
-        pub status: Rect,
-    }
+    #[test]
+    fn execute_command_help_prints_help_text() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("help", dir.path());
+        let mut ui_state = test_ui_state();
 
-    impl DeterministicTestEnv {
-        pub fn apply_to_command(&self, command: &mut std::process::Command) {
-            command
-                .env("NO_COLOR", &self.no_color)
-                .env("XDG_CONFIG_HOME", &self.xdg_config_home)
-                .env("XDG_STATE_HOME", &self.xdg_state_home);
-        }
+        execute_command(&mut state, &mut ui_state, "/help");
+
+        let lines = timeline_text_lines(&ui_state);
+        assert!(lines.iter().any(|line| line == "cmd> /help"));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.contains("Available commands:"))
+        );
+        assert!(lines.iter().any(|line| line.contains("/inspect <expr>")));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.contains("/show_source <name>"))
+        );
     }
 
-    impl UiHarness {
-        pub fn new(width: u16, height: u16, app_state: AppState) -> Result<Self> {
-            let backend = TestBackend::new(width, height);
-            let terminal = Terminal::new(backend)?;
-            let ui_state = UiState::new(app_state.mode, false, &app_state.theme_config);
+    #[test]
+    fn execute_command_clear_removes_prior_timeline_and_keeps_repl_usable() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("clear", dir.path());
+        let mut ui_state = test_ui_state();
 
-            Ok(Self {
-                terminal,
-                app_state,
-                ui_state,
-            })
-        }
+        execute_command(&mut state, &mut ui_state, "/help");
+        let before = timeline_text_lines(&ui_state);
+        assert!(
+            before
+                .iter()
+                .any(|line| line.contains("Available commands:"))
+        );
 
-        pub fn app_state(&self) -> &AppState {
-            &self.app_state
-        }
+        execute_command(&mut state, &mut ui_state, "/clear");
+        let after_clear = timeline_text_lines(&ui_state);
+        assert!(
+            !after_clear
+                .iter()
+                .any(|line| line.contains("Available commands:")),
+            "prior timeline output should be cleared"
+        );
+        assert!(after_clear.iter().any(|line| line == "cleared"));
 
-        pub fn app_state_mut(&mut self) -> &mut AppState {
-            &mut self.app_state
-        }
+        execute_command(&mut state, &mut ui_state, "/mode");
+        let after_followup = timeline_text_lines(&ui_state);
+        assert!(after_followup.iter().any(|line| line == "mode: py"));
+    }
 
-        pub fn ui_state_view(&self) -> UiStateView {
-            let input = self.ui_state.current_input().to_string();
-            let command_input = is_command_line(&input);
-            UiStateView {
-                mode: self.ui_state.mode,
-                prompt: prompt_for(self.ui_state.mode, command_input),
-                input,
-                timeline_scroll: self.ui_state.timeline_scroll,
-                show_assistant_steps: self.ui_state.show_assistant_steps,
-            }
-        }
+    #[test]
+    fn execute_command_history_outputs_live_history_with_optional_limit() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("history", dir.path());
+        let mut ui_state = test_ui_state();
 
-        pub fn render(&mut self) -> Result<()> {
-            self.terminal.draw(|frame| draw_ui(frame, &self.ui_state))?;
-            Ok(())
-        }
+        execute_command(&mut state, &mut ui_state, "/help");
+        execute_command(&mut state, &mut ui_state, "/mode ai");
+        execute_command(&mut state, &mut ui_state, "/history");
 
-        pub async fn send_key(&mut self, key: KeyEvent) -> Result<()> {
-            handle_key_event(
-                &mut self.terminal,
-                &mut self.app_state,
-                &mut self.ui_state,
-                key,
-            )
-            .await
-        }
+        let lines = timeline_text_lines(&ui_state);
+        let joined = lines.join("\n");
+        assert!(joined.contains("/help"));
+        assert!(joined.contains("/mode ai"));
+        assert!(joined.contains("/history"));
 
-        pub fn send_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
-            let regions = self.regions()?;
-            let line_count = self
-                .ui_state
-                .timeline
-                .render_lines(&self.ui_state.theme, self.ui_state.show_assistant_steps)
-                .len();
-            let max_scroll = timeline_max_scroll(line_count, usize::from(regions.timeline.height));
-            handle_mouse_event(&mut self.ui_state, mouse, regions.timeline, max_scroll);
-            Ok(())
-        }
+        let mut ui_state = test_ui_state();
+        execute_command(&mut state, &mut ui_state, "/history 2");
+        let lines = timeline_text_lines(&ui_state);
+        let joined = lines.join("\n");
+        assert!(joined.contains("/history"));
+        assert!(joined.contains("/history 2"));
+        assert!(
+            !joined.contains("/help"),
+            "limited history output should omit older entries"
+        );
+    }
 
-        pub fn buffer_text(&self) -> String {
-            buffer_to_string(self.terminal.backend().buffer())
-        }
+    #[test]
+    fn history_is_loaded_from_and_appended_to_history_file() {
+        let dir = tempdir().expect("tempdir");
+        let history_path = dir.path().join("history");
+        fs::write(&history_path, "x = 1\n/help\n").expect("seed history");
+        let history_file = HistoryFile::new(history_path.clone(), 100);
+
+        let mut ui_state = UiState::new(
+            Mode::Python,
+            false,
+            &ThemeConfig::default(),
+            KeyBindings::default(),
+            Timeline::new(),
+            Some(history_file),
+            HistoryRecall::Block,
+        );
+        assert_eq!(ui_state.history, vec!["x = 1", "/help"]);
 
-        pub fn buffer_lines(&self) -> Vec<String> {
-            buffer_to_lines(self.terminal.backend().buffer())
-        }
+        ui_state.push_history("/help");
+        ui_state.push_history("y = 2");
+        ui_state.push_history("y = 2");
+        ui_state.push_history("exit");
+        assert_eq!(ui_state.history, vec!["x = 1", "/help", "y = 2"]);
+        assert_eq!(
+            fs::read_to_string(history_path).expect("read history"),
+            "x = 1\n/help\ny = 2\n"
+        );
 
-        pub fn line(&self, row: u16) -> Option<String> {
-            self.buffer_lines().get(usize::from(row)).cloned()
-        }
+        ui_state.history_prev();
+        assert_eq!(ui_state.current_input(), "y = 2");
+    }
 
-        pub fn terminal_size(&self) -> (u16, u16) {
-            let area = self.terminal.backend().buffer().area;
-            (area.width, area.height)
-        }
+    #[test]
+    fn history_recalls_multi_line_submissions_as_blocks_or_lines() {
+        let mut ui_state = test_ui_state();
+        ui_state.push_history("for i in range(3):\n    print(i)");
+        ui_state.history_prev();
+        assert_eq!(ui_state.current_input(), "for i in range(3):\n    print(i)");
+
+        let mut ui_state = UiState::new(
+            Mode::Python,
+            false,
+            &ThemeConfig::default(),
+            KeyBindings::default(),
+            Timeline::new(),
+            None,
+            HistoryRecall::Line,
+        );
+        ui_state.push_history("for i in range(3):\n    print(i)\n");
+        assert_eq!(ui_state.history, vec!["for i in range(3):", "    print(i)"]);
+        ui_state.history_prev();
+        assert_eq!(ui_state.current_input(), "    print(i)");
+        ui_state.history_prev();
+        assert_eq!(ui_state.current_input(), "for i in range(3):");
+    }
 
-        pub fn regions(&self) -> Result<UiRegions> {
-            let size = self.terminal.size()?;
-            let area = Rect::new(0, 0, size.width, size.height);
-            let layout = ui_layout(area, self.ui_state.current_input());
-            Ok(UiRegions {
-                motd: layout.timeline_banner,
-                timeline: layout.timeline,
-                input: layout.input,
-                status: layout.footer,
-            })
-        }
+    #[test]
+    fn execute_command_trace_prints_exact_path() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("trace", dir.path());
+        let mut ui_state = test_ui_state();
 
-        pub fn buffer_snapshot(&self) -> String {
-            normalize_snapshot(&self.buffer_text())
-        }
+        execute_command(&mut state, &mut ui_state, "/trace");
+        let lines = timeline_text_lines(&ui_state);
+        assert!(
+            lines.iter().any(|line| line == "cmd> /trace"),
+            "command input should be rendered in timeline"
+        );
+        let trace_path = state.trace.file_path().display().to_string();
+        assert!(lines.contains(&trace_path));
+    }
 
-        pub fn seed_assistant_turn_completed(
-            &mut self,
-            prompt: &str,
-            tool_events: &[(&str, &str)],
-            response: &str,
-        ) -> Result<()> {
-            let index = self.ui_state.push_assistant_turn(prompt.to_string());
-            let Some(turn) = self.ui_state.assistant_turn_mut(index) else {
-                bail!("failed to find seeded assistant turn at index {index}");
-            };
+    #[test]
+    fn text_deltas_stream_into_inflight_turn_and_reset_on_new_step() {
+        let dir = tempdir().expect("tempdir");
+        let state = test_app_state("text-delta", dir.path());
+        let mut ui_state = test_ui_state();
+        ui_state.show_assistant_steps = false;
+        let turn_index = ui_state.push_assistant_turn("question".to_string());
 
-            for (kind, text) in tool_events {
-                if kind.eq_ignore_ascii_case("request") {
-                    turn.events.push(AssistantStepEvent::ToolRequest {
-                        text: (*text).to_string(),
-                    });
-                } else if kind.eq_ignore_ascii_case("result") {
-                    turn.events.push(AssistantStepEvent::ToolResult {
-                        text: (*text).to_string(),
-                    });
-                } else {
-                    bail!("unsupported assistant event kind: {kind}");
-                }
-            }
-            turn.state = AssistantTurnState::CompletedText(response.to_string());
-            Ok(())
+        for text in ["Let me ", "check"] {
+            apply_agent_progress_event(
+                &mut ui_state,
+                &state.trace,
+                turn_index,
+                AgentProgressEvent::TextDelta {
+                    step: 1,
+                    text: text.to_string(),
+                },
+            );
         }
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec!["ai> question", "Let me check"]
+        );
 
-        pub fn seed_assistant_turn_error(&mut self, prompt: &str, error: &str) -> Result<()> {
-            let index = self.ui_state.push_assistant_turn(prompt.to_string());
-            let Some(turn) = self.ui_state.assistant_turn_mut(index) else {
-                bail!("failed to find seeded assistant turn at index {index}");
-            };
-            turn.state = AssistantTurnState::CompletedError(error.to_string());
-            Ok(())
-        }
+        apply_agent_progress_event(
+            &mut ui_state,
+            &state.trace,
+            turn_index,
+            AgentProgressEvent::StepStarted { step: 2 },
+        );
+        apply_agent_progress_event(
+            &mut ui_state,
+            &state.trace,
+            turn_index,
+            AgentProgressEvent::TextDelta {
+                step: 2,
+                text: "Answer".to_string(),
+            },
+        );
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec!["ai> question", "Answer"]
+        );
     }
 
-    pub fn deterministic_app_state(session_id: &str) -> Result<AppState> {
-        let (state, _) = deterministic_app_state_with_env(session_id)?;
-        Ok(state)
-    }
+    #[test]
+    fn turn_retry_drops_text_streamed_by_the_failed_attempt() {
+        let dir = tempdir().expect("tempdir");
+        let state = test_app_state("turn-retry", dir.path());
+        let mut ui_state = test_ui_state();
+        ui_state.show_assistant_steps = false;
+        let turn_index = ui_state.push_assistant_turn("question".to_string());
 
-    pub fn deterministic_app_state_with_env(
-        session_id: &str,
-    ) -> Result<(AppState, DeterministicTestEnv)> {
-        let env = deterministic_test_env()?;
-        let trace_dir = env.xdg_state_home.join("pychat.ai").join("traces");
-        let state = AppState {
-            mode: Mode::Python,
-            session_id: session_id.to_string(),
-            python: PythonSession::initialize()?,
-            llm: None,
-            agent_config: AgentConfig::default(),
-            theme_config: ThemeConfig::default(),
-            startup_message: None,
-            trace: SessionTrace::create_in_temp_dir(session_id, &trace_dir)?,
+        for event in [
+            AgentProgressEvent::TextDelta {
+                step: 1,
+                text: "Half an ans".to_string(),
+            },
+            AgentProgressEvent::TurnRetried,
+            AgentProgressEvent::StepStarted { step: 1 },
+        ] {
+            apply_agent_progress_event(&mut ui_state, &state.trace, turn_index, event);
+        }
+
+        assert_eq!(timeline_text_lines(&ui_state), vec!["ai> question"]);
+    }
+
+    #[test]
+    fn execute_command_diagnostics_reports_versions_without_key() {
+        let dir = tempdir().expect("tempdir");
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("secret-key".to_string()),
+            "test-model".to_string(),
+            "https://example.com/".to_string(),
+        )
+        .expect("provider");
+        let mut state = AppState {
+            llm: Some(provider),
+            ..test_app_state("diagnostics", dir.path())
         };
-        Ok((state, env))
+        let mut ui_state = test_ui_state();
+        let python_version = state.python.python_version();
+
+        execute_command(&mut state, &mut ui_state, "/diagnostics");
+
+        let lines = timeline_text_lines(&ui_state);
+        assert_eq!(lines[1], format!("pychat.ai {}", env!("CARGO_PKG_VERSION")));
+        assert!(lines.contains(&format!("python: {python_version}")));
+        assert!(lines.iter().any(|line| line
+            == "provider: gemini https://example.com/v1beta/models/test-model:streamGenerateContent?alt=sse&key=<redacted>"));
+        assert!(!lines.iter().any(|line| line.contains("secret-key")));
     }
 
-    pub fn deterministic_app_state_with_theme(
-        session_id: &str,
-        theme_config: ThemeConfig,
-    ) -> Result<AppState> {
-        let mut state = deterministic_app_state(session_id)?;
-        state.theme_config = theme_config;
-        Ok(state)
+    #[test]
+    fn execute_command_inspect_compact_prints_one_json_line() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("inspect-compact", dir.path());
+        state
+            .python
+            .exec_code("data = {'a': [1, 2]}")
+            .expect("seed data");
+
+        let mut ui_state = test_ui_state();
+        execute_command(&mut state, &mut ui_state, "/inspect --compact data");
+        let lines = timeline_text_lines(&ui_state);
+        assert_eq!(lines.len(), 2);
+        let value: serde_json::Value = serde_json::from_str(&lines[1]).expect("single-line json");
+        assert_eq!(value["type"]["name"], "dict");
+
+        let mut ui_state = test_ui_state();
+        execute_command(&mut state, &mut ui_state, "/inspect data");
+        let lines = timeline_text_lines(&ui_state);
+        assert!(lines.len() > 2);
+        assert_eq!(lines[1], "{");
     }
 
-    pub fn deterministic_test_env() -> Result<DeterministicTestEnv> {
-        let root = unique_test_root_dir()?;
-        let xdg_config_home = root.join("config-home");
-        let xdg_state_home = root.join("state-home");
-        fs::create_dir_all(&xdg_config_home)
-            .with_context(|| format!("failed to create {}", xdg_config_home.display()))?;
-        fs::create_dir_all(&xdg_state_home)
-            .with_context(|| format!("failed to create {}", xdg_state_home.display()))?;
-        Ok(DeterministicTestEnv {
-            xdg_config_home,
-            xdg_state_home,
-            no_color: "1".to_string(),
-        })
+    #[test]
+    fn execute_command_system_sets_and_clears_the_note() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("system", dir.path());
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/system assume pd is pandas");
+        assert_eq!(
+            state.agent_config.system_note.as_deref(),
+            Some("assume pd is pandas")
+        );
+        execute_command(&mut state, &mut ui_state, "/system");
+        assert_eq!(state.agent_config.system_note, None);
+
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "cmd> /system assume pd is pandas",
+                "system note set: assume pd is pandas",
+                "cmd> /system",
+                "system note cleared"
+            ]
+        );
     }
 
-    fn unique_test_root_dir() -> Result<PathBuf> {
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map_or(0, |duration| duration.as_nanos());
-        let dir = std::env::temp_dir().join(format!(
-            "pychat.ai-test-support-{}-{nanos}",
-            std::process::id()
-        ));
-        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
-        Ok(dir)
+    #[test]
+    fn remember_turn_keeps_the_newest_turns_and_forget_clears_them() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("memory", dir.path());
+        let mut ui_state = test_ui_state();
+
+        remember_turn(&mut state, "q0", "a0");
+        assert!(state.conversation.is_empty());
+
+        state.memory_turns = 2;
+        for turn in 1..=3 {
+            remember_turn(&mut state, &format!("q{turn}"), &format!("a{turn}"));
+        }
+        let texts: Vec<(AssistantRole, &str)> = state
+            .conversation
+            .iter()
+            .map(|message| match message.parts.as_slice() {
+                [AssistantPart::Text { text, .. }] => (message.role.clone(), text.as_str()),
+                other => panic!("unexpected parts {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (AssistantRole::User, "q2"),
+                (AssistantRole::Model, "a2"),
+                (AssistantRole::User, "q3"),
+                (AssistantRole::Model, "a3"),
+            ]
+        );
+
+        execute_command(&mut state, &mut ui_state, "/forget");
+        execute_command(&mut state, &mut ui_state, "/forget");
+        assert!(state.conversation.is_empty());
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "cmd> /forget",
+                "forgot 2 earlier questions",
+                "cmd> /forget",
+                "no earlier questions to forget"
+            ]
+        );
     }
 
-    fn buffer_to_string(buffer: &Buffer) -> String {
-        buffer_to_lines(buffer).join("\n")
+    #[tokio::test]
+    async fn ping_llm_reports_round_trip_time_and_tokens() {
+        let server = MockServer::start().await;
+        let body = concat!(
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"pong\"}]}}],",
+            "\"usageMetadata\":{\"promptTokenCount\":1,\"candidatesTokenCount\":1,\"totalTokenCount\":2}}\r\n\r\n",
+        );
+        Mock::given(method("POST"))
+            .and(path("/v1beta/models/test-model:streamGenerateContent"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let dir = tempdir().expect("tempdir");
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("secret-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider");
+        let mut state = AppState {
+            llm: Some(provider),
+            ..test_app_state("ping", dir.path())
+        };
+        let mut ui_state = test_ui_state();
+        ui_state.python_input = "/ping-llm".to_string();
+
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+
+        let lines = timeline_text_lines(&ui_state);
+        let report = lines.last().expect("report line");
+        assert!(report.starts_with("llm round trip "), "lines: {lines:?}");
+        assert!(report.ends_with("s (in=1 out=1 total=2)"));
+        assert!(state.conversation.is_empty());
+        assert!(ui_state.turn_token_usage.is_empty());
+
+        let mut state = test_app_state("ping-missing", dir.path());
+        ui_state.python_input = "/ping-llm".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("llm ping unavailable: missing GEMINI_API_KEY")
+        );
     }
 
-    fn buffer_to_lines(buffer: &Buffer) -> Vec<String> {
-        let mut lines = Vec::with_capacity(usize::from(buffer.area.height));
-        for y in 0..buffer.area.height {
-            let mut line = String::new();
-            for x in 0..buffer.area.width {
-                let cell = buffer
-                    .cell((x, y))
-                    .expect("buffer index should be in-bounds");
-                line.push_str(cell.symbol());
-            }
-            lines.push(line);
+    #[tokio::test]
+    async fn compare_shows_each_models_answer_with_latency_and_tokens() {
+        let server = MockServer::start().await;
+        for (model, answer, tokens) in [("model-a", "df has 3 rows", 5), ("model-b", "three", 7)] {
+            Mock::given(method("POST"))
+                .and(path_regex(format!("/models/{model}:")))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(
+                    format!(
+                        "data: {}\r\n\r\n",
+                        json!({
+                            "candidates": [{"content": {"parts": [{"text": answer}]}}],
+                            "usageMetadata": {
+                                "promptTokenCount": tokens,
+                                "candidatesTokenCount": 1,
+                                "totalTokenCount": tokens + 1
+                            }
+                        })
+                    ),
+                    "text/event-stream",
+                ))
+                .expect(1)
+                .mount(&server)
+                .await;
         }
-        lines
-    }
+        let dir = tempdir().expect("tempdir");
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("secret-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider");
+        let mut state = AppState {
+            llm: Some(provider),
+            ..test_app_state("compare", dir.path())
+        };
+        let mut ui_state = test_ui_state();
+        ui_state.python_input = "/compare model-a model-b: how many rows?".to_string();
 
-    fn normalize_snapshot(text: &str) -> String {
-        text.replace("\r\n", "\n")
-            .lines()
-            .map(str::trim_end)
-            .collect::<Vec<_>>()
-            .join("\n")
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+
+        let lines = timeline_text_lines(&ui_state);
+        let report = &lines[lines.len() - 4..];
+        assert!(report[0].starts_with("model-a: "), "lines: {lines:?}");
+        assert!(report[0].ends_with(" (in=5 out=1 total=6)"));
+        assert_eq!(report[1], "df has 3 rows");
+        assert!(report[2].starts_with("model-b: "));
+        assert!(report[2].ends_with(" (in=7 out=1 total=8)"));
+        assert_eq!(report[3], "three");
+        assert!(state.conversation.is_empty());
+        assert!(ui_state.turn_token_usage.is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{
-        AppState, Mode, UiState, append_newline_with_indent, area_contains_point, execute_command,
-        footer_left_text, footer_right_text, format_history_output, format_session_token_usage,
-        format_tool_error_line, format_tool_request_line, format_tool_result_line,
-        handle_mouse_event, header_line, input_cursor_position, input_hint_for_empty,
-        is_safe_source_target, last_line_indent, output_trace_kind, preview_text, prompt_for,
-        render_include_command_result, resolve_color_enabled_with, session_closed_message,
-        timeline_max_scroll, timeline_paragraph_scroll, toggle_mode, truncate_with_ellipsis,
-    };
-    use crate::agent::AgentConfig;
-    use crate::cli::theme::Theme;
-    use crate::cli::timeline::OutputKind;
-    use crate::config::ThemeConfig;
-    use crate::llm::provider::LlmTokenUsageTotals;
-    use crate::python::PythonSession;
-    use crate::trace::SessionTrace;
-    use crossterm::event::{KeyModifiers, MouseEvent, MouseEventKind};
-    use ratatui::layout::Rect;
-    use serde_json::json;
-    use tempfile::tempdir;
+    #[tokio::test]
+    async fn echo_expr_shows_the_expression_on_the_value_line() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = AppState {
+            echo_expr: true,
+            ..test_app_state("echo-expr", dir.path())
+        };
+        state.python.exec_code("x = 3").expect("seed x");
+        let mut ui_state = test_ui_state();
 
-    #[test]
-    fn test_toggle_mode() {
-        assert_eq!(toggle_mode(Mode::Python), Mode::Assistant);
-        assert_eq!(toggle_mode(Mode::Assistant), Mode::Python);
+        ui_state.python_input = "x + 1".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("x + 1 => 4")
+        );
+
+        state.echo_expr = false;
+        ui_state.python_input = "x + 1".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("4")
+        );
     }
 
-    #[test]
-    fn test_prompt_for() {
-        assert_eq!(prompt_for(Mode::Python, false), "py> ");
-        assert_eq!(prompt_for(Mode::Assistant, false), "ai> ");
-        assert_eq!(prompt_for(Mode::Python, true), "cmd> ");
+    #[tokio::test]
+    async fn trailing_semicolon_hides_the_value_but_still_sets_underscore() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = AppState {
+            semicolon_suppresses_output: true,
+            ..test_app_state("semicolon", dir.path())
+        };
+        let mut ui_state = test_ui_state();
+
+        ui_state.python_input = "print('side effect') or 1 + 1;".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        assert_eq!(
+            timeline_text_lines(&ui_state)
+                .iter()
+                .rev()
+                .take(2)
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            vec!["side effect", "py> print('side effect') or 1 + 1;"]
+        );
+
+        ui_state.python_input = "_".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("2")
+        );
     }
 
-    #[test]
-    fn force_color_overrides_no_color() {
-        assert!(resolve_color_enabled_with(
-            Some("1".to_string()),
-            Some("true".to_string()),
-            false
-        ));
+    #[tokio::test]
+    async fn one_shot_eval_prints_the_value_and_reports_exceptions() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("one-shot", dir.path());
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+
+        let succeeded = run_one_shot(
+            &mut state,
+            &OneShot::Eval("print('hi') or 40 + 2".to_string()),
+            &mut out,
+            &mut err,
+        )
+        .await
+        .expect("one shot");
+        assert!(succeeded);
+        assert_eq!(String::from_utf8(out).expect("utf8"), "hi\n42\n");
+        assert!(err.is_empty());
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let succeeded = run_one_shot(
+            &mut state,
+            &OneShot::Eval("1 / 0".to_string()),
+            &mut out,
+            &mut err,
+        )
+        .await
+        .expect("one shot");
+        assert!(!succeeded);
+        assert!(out.is_empty());
+        assert!(
+            String::from_utf8(err)
+                .expect("utf8")
+                .ends_with("ZeroDivisionError: division by zero\n")
+        );
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let succeeded = run_one_shot(
+            &mut state,
+            &OneShot::Ask("what is x".to_string()),
+            &mut out,
+            &mut err,
+        )
+        .await
+        .expect("one shot");
+        assert!(!succeeded);
+        assert_eq!(
+            String::from_utf8(err).expect("utf8"),
+            "Assistant unavailable: missing GEMINI_API_KEY\n"
+        );
     }
 
-    #[test]
-    fn no_color_disables_when_not_forced() {
-        assert!(!resolve_color_enabled_with(
-            Some("1".to_string()),
-            None,
-            true
-        ));
+    #[tokio::test]
+    async fn one_shot_script_runs_piped_source_as_a_module() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("one-shot-script", dir.path());
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+
+        let succeeded = run_one_shot(
+            &mut state,
+            &OneShot::Script("x = 1\nprint(x + 1)\nx\n".to_string()),
+            &mut out,
+            &mut err,
+        )
+        .await
+        .expect("one shot");
+
+        assert!(succeeded);
+        assert_eq!(String::from_utf8(out).expect("utf8"), "2\n");
+        assert!(err.is_empty());
+        let trace = fs::read_to_string(state.trace.file_path()).expect("read trace");
+        assert!(trace.contains("print(x + 1)"), "trace: {trace}");
     }
 
-    #[test]
-    fn tty_enables_colors_by_default() {
-        assert!(resolve_color_enabled_with(None, None, true));
-        assert!(!resolve_color_enabled_with(None, None, false));
+    #[tokio::test]
+    async fn degraded_answers_are_traced_with_their_reason() {
+        let dir = tempdir().expect("tempdir");
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("secret-key".to_string()),
+            "test-model".to_string(),
+            "http://127.0.0.1:9".to_string(),
+        )
+        .expect("provider");
+        let mut state = AppState {
+            llm: Some(provider),
+            agent_config: AgentConfig {
+                total_timeout_ms: 0,
+                ..AgentConfig::default()
+            },
+            ..test_app_state("degraded", dir.path())
+        };
+        let mut ui_state = test_ui_state();
+        ui_state.mode = Mode::Assistant;
+        ui_state.assistant_input = "what is x".to_string();
+
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+
+        let trace = fs::read_to_string(state.trace.file_path()).expect("read trace");
+        assert!(
+            trace.contains("[ai.degraded] reason: time_limit"),
+            "trace: {trace}"
+        );
+        assert!(
+            trace.contains("[ai.degraded] Assistant hit the total time limit"),
+            "trace: {trace}"
+        );
+        assert!(!trace.contains("ai.out"), "trace: {trace}");
     }
 
-    #[test]
-    fn preview_text_truncates_and_normalizes_whitespace() {
-        let text = preview_text("a  \n\t b", 3);
-        assert_eq!(text, "a b");
-        let text = preview_text(&"x".repeat(20), 10);
-        assert_eq!(text, format!("{}...", "x".repeat(10)));
+    #[tokio::test]
+    async fn cancelling_an_in_flight_question_marks_the_turn_cancelled() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+            .mount(&server)
+            .await;
+        let dir = tempdir().expect("tempdir");
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("secret-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider");
+        let mut state = AppState {
+            llm: Some(provider),
+            ..test_app_state("cancel", dir.path())
+        };
+        let mut ui_state = test_ui_state();
+        ui_state.mode = Mode::Assistant;
+        ui_state.assistant_input = "slow question".to_string();
+
+        let started = std::time::Instant::now();
+        let mut polls = 0;
+        submit_line_cancellable(&mut state, &mut ui_state, &mut |_| Ok(()), &mut || {
+            polls += 1;
+            polls > 1
+        })
+        .await
+        .expect("submit");
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!ui_state.should_quit);
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec!["ai> slow question", "", "  Thinking...", "", "cancelled"]
+        );
     }
 
-    #[test]
-    fn last_line_indent_uses_only_leading_whitespace() {
-        assert_eq!(last_line_indent("    if True:"), "    ");
-        assert_eq!(last_line_indent("x = 1"), "");
-        assert_eq!(last_line_indent("x = 1\n\t  y = 2"), "\t  ");
+    #[tokio::test]
+    async fn key_presses_dispatch_through_the_configured_bindings() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("keys", dir.path());
+        let mut ui_state = test_ui_state();
+        ui_state.key_bindings.rebind(
+            KeyAction::ToggleSteps,
+            vec!["alt-s".parse().expect("key combo")],
+        );
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).expect("terminal");
+
+        handle_key_event(
+            &mut terminal,
+            &mut state,
+            &mut ui_state,
+            KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL),
+        )
+        .await
+        .expect("key");
+        assert!(ui_state.show_assistant_steps);
+        handle_key_event(
+            &mut terminal,
+            &mut state,
+            &mut ui_state,
+            KeyEvent::new(KeyCode::Char('s'), KeyModifiers::ALT),
+        )
+        .await
+        .expect("key");
+        assert!(!ui_state.show_assistant_steps);
+        ui_state.python_input.clear();
+        handle_key_event(
+            &mut terminal,
+            &mut state,
+            &mut ui_state,
+            KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT),
+        )
+        .await
+        .expect("key");
+        assert_eq!(ui_state.mode, Mode::Assistant);
+        handle_key_event(
+            &mut terminal,
+            &mut state,
+            &mut ui_state,
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT),
+        )
+        .await
+        .expect("key");
+        assert_eq!(ui_state.assistant_input, "\n");
     }
 
-    #[test]
-    fn append_newline_with_indent_copies_previous_indent() {
-        let mut input = "if True:\n    x = 1".to_string();
-        append_newline_with_indent(&mut input);
-        assert_eq!(input, "if True:\n    x = 1\n    ");
+    #[tokio::test]
+    async fn ctrl_x_ctrl_e_and_edit_command_request_the_editor() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("edit", dir.path());
+        let mut ui_state = test_ui_state();
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).expect("terminal");
+        let ctrl = |ch| KeyEvent::new(KeyCode::Char(ch), KeyModifiers::CONTROL);
+
+        handle_key_event(&mut terminal, &mut state, &mut ui_state, ctrl('e'))
+            .await
+            .expect("key");
+        assert!(!ui_state.pending_edit);
+        handle_key_event(&mut terminal, &mut state, &mut ui_state, ctrl('x'))
+            .await
+            .expect("key");
+        handle_key_event(&mut terminal, &mut state, &mut ui_state, ctrl('e'))
+            .await
+            .expect("key");
+        assert!(ui_state.pending_edit);
+
+        ui_state.pending_edit = false;
+        execute_command(&mut state, &mut ui_state, "/edit");
+        assert!(ui_state.pending_edit);
     }
 
-    #[test]
-    fn input_cursor_position_tracks_multiline_tail() {
-        assert_eq!(input_cursor_position(""), (0, 0));
-        assert_eq!(input_cursor_position("abc"), (0, 3));
-        assert_eq!(input_cursor_position("a\nbc"), (1, 2));
+    #[tokio::test]
+    async fn vi_edit_mode_moves_and_edits_in_normal_mode() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("vi", dir.path());
+        let mut ui_state = test_ui_state().with_edit_mode(EditMode::Vi);
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).expect("terminal");
+        assert_eq!(ui_state.vi_mode, Some(ViMode::Insert));
+        ui_state.set_current_input("foo.bar baz\nqux".to_string());
+
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        handle_key_event(&mut terminal, &mut state, &mut ui_state, esc)
+            .await
+            .expect("key");
+        assert_eq!(ui_state.vi_mode, Some(ViMode::Normal));
+        assert_eq!(ui_state.cursor(), 14);
+
+        let mut cursors = Vec::new();
+        for ch in "k0wwwb$xhx".chars() {
+            let key = KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE);
+            handle_key_event(&mut terminal, &mut state, &mut ui_state, key)
+                .await
+                .expect("key");
+            cursors.push(ui_state.cursor());
+        }
+        assert_eq!(cursors, [2, 0, 3, 4, 8, 4, 11, 11, 10, 10]);
+        assert_eq!(ui_state.current_input(), "foo.bar ba\nqux");
+
+        for ch in "jddqoz".chars() {
+            let key = KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE);
+            handle_key_event(&mut terminal, &mut state, &mut ui_state, key)
+                .await
+                .expect("key");
+        }
+        assert_eq!(ui_state.current_input(), "foo.bar ba\nz");
+        assert_eq!(ui_state.vi_mode, Some(ViMode::Insert));
     }
 
     #[test]
-    fn header_line_renders_brand() {
-        let theme = Theme::new(false);
-        assert_eq!(header_line(&theme, 80).to_string(), "PyChat.AI");
+    fn esc_ctrl_g_and_ctrl_c_cancel_an_in_flight_turn() {
+        assert!(is_cancel_key(KeyEvent::new(
+            KeyCode::Esc,
+            KeyModifiers::NONE
+        )));
+        assert!(is_cancel_key(KeyEvent::new(
+            KeyCode::Char('g'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(is_cancel_key(KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::CONTROL
+        )));
+        assert!(!is_cancel_key(KeyEvent::new(
+            KeyCode::Char('g'),
+            KeyModifiers::NONE
+        )));
+        assert!(!is_cancel_key(KeyEvent::new(
+            KeyCode::Enter,
+            KeyModifiers::NONE
+        )));
     }
 
     #[test]
-    fn footer_text_helpers_match_requested_copy() {
-        assert_eq!(
-            footer_left_text(Mode::Python, true, 80),
-            "Python | Thinking: On"
-        );
+    fn execute_command_theme_save_switches_and_persists_the_preset() {
+        let dir = tempdir().expect("tempdir");
+        let config_path = dir.path().join("pychat.ai").join("config.toml");
+        let mut state = AppState {
+            config_path: config_path.clone(),
+            ..test_app_state("theme-save", dir.path())
+        };
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/theme save light");
+
+        assert_eq!(state.theme_config.preset, ThemePreset::Light);
         assert_eq!(
-            footer_left_text(Mode::Assistant, false, 80),
-            "AI Assistant | Thinking: Off"
+            fs::read_to_string(&config_path).expect("read config"),
+            "[theme]\nname = \"light\"\n"
         );
         assert_eq!(
-            footer_right_text(&LlmTokenUsageTotals {
-                input_tokens: 12,
-                output_tokens: 34,
-                total_tokens: 46,
-            }),
-            "Questions? /help | Tokens: 46"
+            timeline_text_lines(&ui_state).last(),
+            Some(&format!(
+                "saved theme preset 'light' to {}",
+                config_path.display()
+            ))
         );
     }
 
     #[test]
-    fn empty_input_hint_mentions_help() {
-        assert!(input_hint_for_empty(Mode::Python).contains("/help"));
-        assert!(input_hint_for_empty(Mode::Assistant).contains("/help"));
-    }
+    fn execute_command_endpoint_prints_model_path_without_key() {
+        let dir = tempdir().expect("tempdir");
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("secret-key".to_string()),
+            "test-model".to_string(),
+            "https://example.com/".to_string(),
+        )
+        .expect("provider");
+        let mut state = AppState {
+            llm: Some(provider),
+            ..test_app_state("endpoint", dir.path())
+        };
+        let mut ui_state = test_ui_state();
 
-    #[test]
-    fn truncate_with_ellipsis_handles_small_widths() {
-        assert_eq!(truncate_with_ellipsis("abcdef", 0), "");
-        assert_eq!(truncate_with_ellipsis("abcdef", 2), "..");
-        assert_eq!(truncate_with_ellipsis("abcdef", 6), "abcdef");
-        assert_eq!(truncate_with_ellipsis("abcdef", 5), "ab...");
-    }
+        execute_command(&mut state, &mut ui_state, "/endpoint");
 
-    #[test]
-    fn format_session_token_usage_includes_in_out_total() {
-        assert_eq!(
-            format_session_token_usage(&LlmTokenUsageTotals {
-                input_tokens: 3,
-                output_tokens: 2,
-                total_tokens: 5,
-            }),
-            "session tokens in=3 out=2 total=5"
-        );
+        let lines = timeline_text_lines(&ui_state);
+        assert!(lines.iter().any(|line| line
+            == "https://example.com/v1beta/models/test-model:streamGenerateContent?alt=sse&key=<redacted>"));
+        assert!(!lines.iter().any(|line| line.contains("secret-key")));
     }
 
     #[test]
-    fn session_closed_message_includes_trace_file_path() {
-        assert_eq!(
-            session_closed_message(
-                std::path::Path::new("/tmp/pychat.ai/traces/session-abc123.log"),
-                &LlmTokenUsageTotals {
-                    input_tokens: 12,
-                    output_tokens: 3,
-                    total_tokens: 15,
-                }
-            ),
-            "PyChat.ai session ended.\nTokens: 15\nTrace file: /tmp/pychat.ai/traces/session-abc123.log"
+    fn execute_command_endpoint_reports_missing_provider() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("endpoint-missing", dir.path());
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/endpoint");
+
+        assert!(
+            timeline_text_lines(&ui_state)
+                .iter()
+                .any(|line| line == "endpoint unavailable: missing GEMINI_API_KEY")
         );
     }
 
     #[test]
-    fn output_trace_kind_maps_tokens() {
-        assert_eq!(output_trace_kind(OutputKind::PythonStdout), "py.out");
-        assert_eq!(
-            output_trace_kind(OutputKind::AssistantProgressResult),
-            "ai.step"
+    fn execute_command_save_writes_markdown_transcript_and_respects_force() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("save", dir.path());
+        let mut ui_state = test_ui_state();
+        ui_state.timeline.push_user_input_python("x = 41 + 1");
+        let turn_index = ui_state
+            .timeline
+            .push_assistant_turn("what is x?".to_string());
+        ui_state
+            .timeline
+            .assistant_turn_mut(turn_index)
+            .expect("assistant turn")
+            .state = AssistantTurnState::CompletedText("x is 42".to_string());
+
+        let path = dir.path().join("transcript.md");
+        let command = format!("/save {}", path.display());
+        execute_command(&mut state, &mut ui_state, &command);
+
+        let saved = fs::read_to_string(&path).expect("read transcript");
+        assert!(saved.contains("py> x = 41 + 1"));
+        assert!(saved.contains("**ai>** what is x?"));
+        assert!(saved.contains("x is 42"));
+
+        execute_command(&mut state, &mut ui_state, &command);
+        assert!(
+            timeline_text_lines(&ui_state)
+                .iter()
+                .any(|line| line.starts_with("file already exists:"))
         );
+
+        execute_command(&mut state, &mut ui_state, &format!("{command} --force"));
+        let saved = fs::read_to_string(&path).expect("read overwritten transcript");
+        assert!(saved.contains("file already exists:"));
     }
 
-    #[test]
-    fn format_tool_request_line_uses_semantic_labels() {
-        assert_eq!(
-            format_tool_request_line("list_globals", &json!({})),
-            "-> Listing globals"
-        );
-        assert_eq!(
-            format_tool_request_line("inspect", &json!({"expr":"value [ 0 ]"})),
-            "-> Inspecting: value [ 0 ]"
+    #[tokio::test]
+    async fn execute_command_export_script_writes_python_inputs_in_order() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("export-script", dir.path());
+        let mut ui_state = test_ui_state();
+        for line in ["x = 40", "print(x + 2)", "1 / 0", "def f():\n    return x"] {
+            ui_state.python_input = line.to_string();
+            submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+                .await
+                .expect("submit");
+        }
+        execute_command(&mut state, &mut ui_state, "/type x");
+
+        let path = dir.path().join("session.py");
+        execute_command(
+            &mut state,
+            &mut ui_state,
+            &format!("/export-script {}", path.display()),
         );
         assert_eq!(
-            format_tool_request_line("eval_expr", &json!({"expr":"a + b"})),
-            "-> Evaluating: a + b"
+            fs::read_to_string(&path).expect("read script"),
+            "x = 40\nprint(x + 2)\n1 / 0\ndef f():\n    return x\n"
         );
-    }
 
-    #[test]
-    fn format_tool_result_line_summarizes_known_tools() {
-        assert_eq!(
-            format_tool_result_line(
-                "list_globals",
-                &json!({"ok":true,"result":{"globals":[{"name":"a"},{"name":"b"}]}})
+        execute_command(
+            &mut state,
+            &mut ui_state,
+            &format!(
+                "/export-script --force --outputs --skip-failed {}",
+                path.display()
             ),
-            "<- Found 2 globals"
         );
         assert_eq!(
-            format_tool_result_line(
-                "inspect",
-                &json!({"ok":true,"result":{"type":{"name":"dict"}}})
-            ),
-            "<- Inspection complete: dict"
+            fs::read_to_string(&path).expect("read script with outputs"),
+            "x = 40\nprint(x + 2)\n# 42\n# None\ndef f():\n    return x\n"
         );
-        assert_eq!(
-            format_tool_result_line("eval_expr", &json!({"ok":true,"result":{"value_repr":"3"}})),
-            "<- Evaluated: 3"
+    }
+
+    #[tokio::test]
+    async fn python_warnings_render_as_their_own_output_kind() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("warnings", dir.path());
+        let mut ui_state = test_ui_state();
+
+        ui_state.python_input = "import warnings; warnings.warn('careful')".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+
+        let lines = timeline_text_lines(&ui_state);
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.ends_with("UserWarning: careful")),
+            "{lines:?}"
         );
+        let style = ui_state.theme.style(ThemeToken::PythonWarning);
+        let rendered = ui_state
+            .timeline
+            .render_lines(&ui_state.theme, true)
+            .into_iter()
+            .find(|line| line.to_string().ends_with("UserWarning: careful"))
+            .expect("warning line");
+        assert!(rendered.spans.iter().all(|span| span.style == style));
     }
 
     #[test]
-    fn format_tool_error_line_includes_code_and_reason() {
+    fn execute_command_type_prints_type_name_or_traceback() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("type", dir.path());
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/type {}");
         assert_eq!(
-            format_tool_error_line(
-                "inspect",
-                &json!({"ok":false,"error":{"code":"python_exception","message":"NameError: x"}})
-            ),
-            "<- Tool error (inspect): python_exception: NameError: x"
+            timeline_text_lines(&ui_state),
+            vec!["cmd> /type {}", "dict"]
         );
+
+        execute_command(&mut state, &mut ui_state, "/type undefined_name");
+        let timeline_text = timeline_text_lines(&ui_state).join("\n");
+        assert!(timeline_text.contains("Traceback"));
+        assert!(timeline_text.contains("NameError"));
     }
 
-    #[test]
-    fn format_history_output_limits_tail_entries() {
-        let history = vec![
-            "a = 1".to_string(),
-            "/help".to_string(),
-            "x + 1".to_string(),
-            "/history 2".to_string(),
-        ];
+    #[tokio::test]
+    async fn tabular_value_repr_keeps_column_alignment() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("tabular", dir.path());
+        let mut ui_state = test_ui_state();
+        state
+            .python
+            .run_exec_input(
+                "class Frame:\n    __module__ = 'pandas.core.frame'\n    def __repr__(self):\n        return '   a    b\\n0  1\\t10\\n1  22   20'",
+            )
+            .expect("define stub");
+
+        ui_state.python_input = "Frame()".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
         assert_eq!(
-            format_history_output(&history, Some(2)),
-            "   3: x + 1\n   4: /history 2"
+            timeline_text_lines(&ui_state),
+            vec!["py> Frame()", "   a    b", "0  1    10", "1  22   20"]
         );
     }
 
     #[test]
-    fn timeline_paragraph_scroll_follows_manual_offset() {
-        assert_eq!(timeline_paragraph_scroll(20, 5, 0), 15);
-        assert_eq!(timeline_paragraph_scroll(20, 5, 3), 12);
-        assert_eq!(timeline_paragraph_scroll(20, 5, 99), 0);
-    }
+    fn gc_command_reports_collected_and_tracked_counts() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("gc", dir.path());
+        let mut ui_state = test_ui_state();
+        state
+            .python
+            .run_exec_input("class Node:\n    pass\nn = Node()\nn.me = n\ndel n")
+            .expect("create reference cycle");
 
-    #[test]
-    fn area_contains_point_matches_rect_bounds() {
-        let area = Rect::new(10, 5, 3, 2);
-        assert!(area_contains_point(area, 10, 5));
-        assert!(area_contains_point(area, 12, 6));
-        assert!(!area_contains_point(area, 13, 6));
-        assert!(!area_contains_point(area, 12, 7));
+        execute_command(&mut state, &mut ui_state, "/gc");
+
+        let lines = timeline_text_lines(&ui_state);
+        let report = lines.last().expect("gc report");
+        let collected = report
+            .strip_prefix("gc: collected ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|count| count.parse::<usize>().ok())
+            .expect("collected count");
+        assert!(collected >= 1, "unexpected gc report: {report}");
+        assert!(report.ends_with("objects tracked"));
     }
 
     #[test]
-    fn mouse_wheel_scrolls_timeline_with_clamp() {
+    fn session_save_and_load_restore_the_timeline_mode_and_scroll() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("review.json");
+        let mut state = test_app_state("session-file", dir.path());
         let mut ui_state = test_ui_state();
-        let timeline_area = Rect::new(0, 0, 80, 8);
-        let max_scroll = 7usize;
+        ui_state.push_timeline_output(OutputKind::PythonStdout, "hello");
+        ui_state.mode = Mode::Assistant;
+        ui_state.timeline_scroll = 3;
 
-        handle_mouse_event(
+        execute_command(
+            &mut state,
             &mut ui_state,
-            mouse_event(MouseEventKind::ScrollUp, 2, 2),
-            timeline_area,
-            max_scroll,
+            &format!("/session save {}", path.display()),
+        );
+        let saved = timeline_text_lines(&ui_state);
+        assert!(
+            saved
+                .last()
+                .is_some_and(|line| line.starts_with("saved session (2 entries) to "))
+        );
+        execute_command(
+            &mut state,
+            &mut ui_state,
+            &format!("/session save {}", path.display()),
+        );
+        assert!(
+            timeline_text_lines(&ui_state)
+                .last()
+                .is_some_and(|line| line.contains("(use /session save --force to overwrite)"))
+        );
+
+        let mut ui_state = test_ui_state();
+        execute_command(
+            &mut state,
+            &mut ui_state,
+            &format!("/session load {}", path.display()),
+        );
+        let lines = timeline_text_lines(&ui_state);
+        assert_eq!(lines[..saved.len() - 1], saved[..saved.len() - 1]);
+        assert!(
+            lines
+                .last()
+                .is_some_and(|line| line.starts_with("loaded session (2 entries) from "))
         );
+        assert_eq!(ui_state.mode, Mode::Assistant);
         assert_eq!(ui_state.timeline_scroll, 3);
 
-        handle_mouse_event(
+        fs::write(&path, "{\"version\": 99}").expect("write bad session");
+        execute_command(
+            &mut state,
             &mut ui_state,
-            mouse_event(MouseEventKind::ScrollUp, 2, 2),
-            timeline_area,
-            max_scroll,
+            &format!("/session load {}", path.display()),
+        );
+        assert!(
+            timeline_text_lines(&ui_state)
+                .last()
+                .is_some_and(|line| line.contains("not a saved session"))
+        );
+    }
+
+    #[test]
+    fn execute_command_suggests_the_closest_command_for_a_typo() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("command-suggestion", dir.path());
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/inspct x");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("unknown command '/inspct'. Try /help (did you mean /inspect?)")
+        );
+
+        execute_command(&mut state, &mut ui_state, "/bogus");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("unknown command '/bogus'. Try /help")
+        );
+    }
+
+    #[test]
+    fn execute_command_help_shows_details_for_one_command() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("help-command", dir.path());
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/help inspect");
+        let lines = timeline_text_lines(&ui_state);
+        assert!(
+            lines
+                .iter()
+                .any(|line| line == "/inspect --to <path> <expr>")
+        );
+        assert!(
+            !lines
+                .iter()
+                .any(|line| line.starts_with("Available commands"))
         );
-        handle_mouse_event(
-            &mut ui_state,
-            mouse_event(MouseEventKind::ScrollUp, 2, 2),
-            timeline_area,
-            max_scroll,
+
+        execute_command(&mut state, &mut ui_state, "/help inspcet");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last().map(String::as_str),
+            Some("unknown command '/inspcet'. Try /help (did you mean /inspect?)")
         );
-        assert_eq!(ui_state.timeline_scroll, max_scroll);
+    }
 
-        handle_mouse_event(
+    #[test]
+    fn execute_command_recursion_limit_reads_and_sets_the_limit() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("recursionlimit", dir.path());
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/recursionlimit");
+        let lines = timeline_text_lines(&ui_state);
+        let original = lines
+            .last()
+            .and_then(|line| line.strip_prefix("recursion limit: "))
+            .and_then(|limit| limit.parse::<u32>().ok())
+            .expect("current limit");
+
+        // The limit is process-wide, so it is raised and then put back.
+        let raised = original + 500;
+        execute_command(
+            &mut state,
             &mut ui_state,
-            mouse_event(MouseEventKind::ScrollDown, 2, 2),
-            timeline_area,
-            max_scroll,
+            &format!("/recursionlimit {raised}"),
+        );
+        execute_command(&mut state, &mut ui_state, "/recursionlimit");
+        assert_eq!(
+            timeline_text_lines(&ui_state).last(),
+            Some(&format!("recursion limit: {raised}"))
         );
-        assert_eq!(ui_state.timeline_scroll, 4);
 
-        handle_mouse_event(
+        execute_command(
+            &mut state,
             &mut ui_state,
-            mouse_event(MouseEventKind::ScrollDown, 2, 2),
-            timeline_area,
-            max_scroll,
+            &format!("/recursionlimit {original}"),
         );
-        handle_mouse_event(
-            &mut ui_state,
-            mouse_event(MouseEventKind::ScrollDown, 2, 2),
-            timeline_area,
-            max_scroll,
+        assert_eq!(
+            timeline_text_lines(&ui_state).last(),
+            Some(&format!("recursion limit: {original}"))
         );
-        assert_eq!(ui_state.timeline_scroll, 0);
     }
 
     #[test]
-    fn mouse_wheel_outside_timeline_is_ignored() {
+    fn reset_command_drops_globals_and_optionally_clears_timeline() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("reset", dir.path());
         let mut ui_state = test_ui_state();
-        ui_state.timeline_scroll = 4;
-        let timeline_area = Rect::new(0, 0, 80, 8);
+        state
+            .python
+            .run_exec_input("polluted = 1")
+            .expect("seed python state");
+        ui_state.timeline.push_user_input_python("polluted = 1");
 
-        handle_mouse_event(
-            &mut ui_state,
-            mouse_event(MouseEventKind::ScrollUp, 2, 10),
-            timeline_area,
-            20,
+        execute_command(&mut state, &mut ui_state, "/reset");
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec!["py> polluted = 1", "cmd> /reset", "python session reset"]
         );
+        assert!(state.python.eval_expr("polluted").is_err());
 
-        assert_eq!(ui_state.timeline_scroll, 4);
+        execute_command(&mut state, &mut ui_state, "/reset --all");
+        assert_eq!(timeline_text_lines(&ui_state), vec!["python session reset"]);
     }
 
-    #[test]
-    fn mouse_wheel_does_not_change_history_selection() {
+    #[tokio::test]
+    async fn long_value_repr_is_truncated_and_last_shows_it_in_full() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = AppState {
+            repr_max_chars: 10,
+            ..test_app_state("repr-max", dir.path())
+        };
         let mut ui_state = test_ui_state();
-        ui_state.history = vec!["x = 1".to_string(), "x + 1".to_string()];
-        ui_state.history_index = Some(1);
-        ui_state.python_input = "x + 1".to_string();
-        let timeline_area = Rect::new(0, 0, 80, 8);
 
-        handle_mouse_event(
-            &mut ui_state,
-            mouse_event(MouseEventKind::ScrollUp, 3, 3),
-            timeline_area,
-            20,
-        );
+        ui_state.python_input = "'x' * 30".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        execute_command(&mut state, &mut ui_state, "/last");
 
-        assert_eq!(ui_state.history_index, Some(1));
-        assert_eq!(ui_state.python_input, "x + 1");
+        let full = format!("'{}'", "x".repeat(30));
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "py> 'x' * 30".to_string(),
+                format!("'{}... (22 more chars)", "x".repeat(9)),
+                "cmd> /last".to_string(),
+                full,
+            ]
+        );
     }
 
     #[test]
-    fn timeline_manual_scroll_is_preserved_when_new_output_arrives() {
+    fn raw_answer_command_shows_markdown_source_verbatim() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("raw-answer", dir.path());
         let mut ui_state = test_ui_state();
-        ui_state.timeline_scroll = 5;
-        ui_state.push_timeline_output(OutputKind::PythonStdout, "hello");
-        ui_state.push_timeline_output(OutputKind::PythonStdout, "world");
-        assert_eq!(ui_state.timeline_scroll, 5);
-    }
 
-    #[test]
-    fn timeline_max_scroll_matches_content_and_viewport() {
-        assert_eq!(timeline_max_scroll(0, 10), 0);
-        assert_eq!(timeline_max_scroll(5, 10), 0);
-        assert_eq!(timeline_max_scroll(11, 10), 1);
-    }
+        execute_command(&mut state, &mut ui_state, "/raw-answer");
+        ui_state.last_answer =
+            Some("Call `total()` for the **sum**:\n\n- first\n- second".to_string());
+        execute_command(&mut state, &mut ui_state, "/raw-answer");
 
-    #[test]
-    fn source_target_validation_allows_identifier_paths_only() {
-        assert!(is_safe_source_target("my_fn"));
-        assert!(is_safe_source_target("module.ClassName"));
-        assert!(!is_safe_source_target(""));
-        assert!(!is_safe_source_target("1name"));
-        assert!(!is_safe_source_target("obj.method()"));
-        assert!(!is_safe_source_target("__import__('os').system"));
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "cmd> /raw-answer",
+                "no assistant answer yet",
+                "cmd> /raw-answer",
+                "Call `total()` for the **sum**:",
+                "",
+                "- first",
+                "- second",
+            ]
+        );
     }
 
-    #[test]
-    fn execute_command_mode_and_steps_updates_ui_state() {
+    #[tokio::test]
+    async fn rerun_command_resubmits_history_entry() {
         let dir = tempdir().expect("tempdir");
-        let mut state = test_app_state("mode-steps", dir.path());
+        let mut state = test_app_state("rerun", dir.path());
         let mut ui_state = test_ui_state();
 
-        execute_command(&mut state, &mut ui_state, "/mode ai");
-        assert_eq!(ui_state.mode, Mode::Assistant);
+        ui_state.python_input = "counter = globals().get('counter', 0) + 1".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        ui_state.timeline.clear();
 
-        execute_command(&mut state, &mut ui_state, "/steps off");
-        assert!(!ui_state.show_assistant_steps);
+        ui_state.python_input = "/rerun 1".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit rerun");
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "cmd> /rerun 1",
+                "py> counter = globals().get('counter', 0) + 1"
+            ]
+        );
+        assert_eq!(
+            state
+                .python
+                .eval_expr("counter")
+                .expect("counter")
+                .value_repr,
+            "2"
+        );
 
-        execute_command(&mut state, &mut ui_state, "/steps");
-        assert!(ui_state.show_assistant_steps);
+        ui_state.timeline.clear();
+        ui_state.python_input = "/rerun 2".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit self rerun");
+        ui_state.python_input = "/rerun 9".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit missing rerun");
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "cmd> /rerun 2",
+                "history entry 2 is itself a /rerun and cannot be re-run",
+                "cmd> /rerun 9",
+                "no history entry 9 (history has 5 entries)"
+            ]
+        );
     }
 
-    #[test]
-    fn execute_command_help_prints_help_text() {
+    #[tokio::test]
+    async fn stream_output_shows_stdout_lines_before_the_run_finishes() {
         let dir = tempdir().expect("tempdir");
-        let mut state = test_app_state("help", dir.path());
+        let mut state = test_app_state("stream", dir.path());
+        state.stream_output = true;
         let mut ui_state = test_ui_state();
+        let mut redraws = Vec::new();
 
-        execute_command(&mut state, &mut ui_state, "/help");
+        ui_state.python_input =
+            "import time; print('first'); time.sleep(0.3); print('second')".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |ui_state| {
+            redraws.push(timeline_text_lines(ui_state));
+            Ok(())
+        })
+        .await
+        .expect("submit streamed");
 
-        let lines = timeline_text_lines(&ui_state);
-        assert!(lines.iter().any(|line| line == "cmd> /help"));
-        assert!(
-            lines
-                .iter()
-                .any(|line| line.contains("Available commands:"))
-        );
-        assert!(lines.iter().any(|line| line.contains("/inspect <expr>")));
-        assert!(
-            lines
-                .iter()
-                .any(|line| line.contains("/show_source <name>"))
+        let input = "py> import time; print('first'); time.sleep(0.3); print('second')";
+        assert_eq!(redraws[0], vec![input, "first"]);
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![input, "first", "second"]
         );
     }
 
-    #[test]
-    fn execute_command_clear_removes_prior_timeline_and_keeps_repl_usable() {
+    #[tokio::test]
+    async fn cancelling_streamed_python_raises_keyboard_interrupt_in_the_worker() {
         let dir = tempdir().expect("tempdir");
-        let mut state = test_app_state("clear", dir.path());
+        let mut state = test_app_state("stream-cancel", dir.path());
+        state.stream_output = true;
         let mut ui_state = test_ui_state();
+        let started = std::cell::Cell::new(false);
 
-        execute_command(&mut state, &mut ui_state, "/help");
-        let before = timeline_text_lines(&ui_state);
-        assert!(
-            before
-                .iter()
-                .any(|line| line.contains("Available commands:"))
-        );
+        ui_state.python_input = "print('started')\nwhile True:\n    pass".to_string();
+        submit_line_cancellable(
+            &mut state,
+            &mut ui_state,
+            &mut |ui_state| {
+                started.set(timeline_text_lines(ui_state).contains(&"started".to_string()));
+                Ok(())
+            },
+            &mut || started.get(),
+        )
+        .await
+        .expect("submit streamed");
 
-        execute_command(&mut state, &mut ui_state, "/clear");
-        let after_clear = timeline_text_lines(&ui_state);
+        let lines = timeline_text_lines(&ui_state);
+        assert!(lines.contains(&"started".to_string()), "{lines:?}");
         assert!(
-            !after_clear
-                .iter()
-                .any(|line| line.contains("Available commands:")),
-            "prior timeline output should be cleared"
+            lines.iter().any(|line| line.contains("KeyboardInterrupt")),
+            "{lines:?}"
         );
-        assert!(after_clear.iter().any(|line| line == "cleared"));
+    }
 
-        execute_command(&mut state, &mut ui_state, "/mode");
-        let after_followup = timeline_text_lines(&ui_state);
-        assert!(after_followup.iter().any(|line| line == "mode: py"));
+    #[tokio::test]
+    async fn time_command_and_auto_time_report_durations() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("time", dir.path());
+        let mut ui_state = test_ui_state();
+
+        execute_command(&mut state, &mut ui_state, "/time 6 * 7");
+        let lines = timeline_text_lines(&ui_state);
+        assert_eq!(lines[..2], ["cmd> /time 6 * 7", "42"]);
+        assert!(lines[2].starts_with("wall time: ") && lines[2].ends_with("ms"));
+
+        ui_state.timeline.clear();
+        ui_state.python_input = "x = 1".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit untimed");
+        assert_eq!(timeline_text_lines(&ui_state), vec!["py> x = 1"]);
+
+        ui_state.timeline.clear();
+        state.auto_time_threshold = Some(Duration::ZERO);
+        ui_state.python_input = "x = 2".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit timed");
+        let lines = timeline_text_lines(&ui_state);
+        assert_eq!(lines[0], "py> x = 2");
+        assert!(lines[1].starts_with("(took ") && lines[1].ends_with("ms)"));
     }
 
     #[test]
-    fn execute_command_history_outputs_live_history_with_optional_limit() {
+    fn diff_command_shows_differing_list_elements_and_errors() {
         let dir = tempdir().expect("tempdir");
-        let mut state = test_app_state("history", dir.path());
+        let mut state = test_app_state("diff", dir.path());
         let mut ui_state = test_ui_state();
+        state
+            .python
+            .run_exec_input("a = [1, 2, 3]\nb = [1, 5, 3]")
+            .expect("seed python state");
 
-        execute_command(&mut state, &mut ui_state, "/help");
-        execute_command(&mut state, &mut ui_state, "/mode ai");
-        execute_command(&mut state, &mut ui_state, "/history");
+        execute_command(&mut state, &mut ui_state, "/diff a ; b");
+        execute_command(&mut state, &mut ui_state, "/diff a ; list(a)");
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "cmd> /diff a ; b",
+                "--- a",
+                "+++ b",
+                "- [1, 2, 3]",
+                "+ [1, 5, 3]",
+                "cmd> /diff a ; list(a)",
+                "no differences"
+            ]
+        );
 
+        ui_state.timeline.clear();
+        execute_command(&mut state, &mut ui_state, "/diff a ; missing");
         let lines = timeline_text_lines(&ui_state);
-        let joined = lines.join("\n");
-        assert!(joined.contains("/help"));
-        assert!(joined.contains("/mode ai"));
-        assert!(joined.contains("/history"));
+        assert!(lines.iter().any(|line| line.contains("NameError")));
+    }
+
+    #[test]
+    fn format_elapsed_switches_to_seconds_above_one_second() {
+        assert_eq!(format_elapsed(Duration::from_micros(2_340)), "2.3ms");
+        assert_eq!(format_elapsed(Duration::from_millis(1_234)), "1.2s");
+    }
 
+    #[tokio::test]
+    async fn labeled_watches_update_after_each_python_submission() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("watch", dir.path());
         let mut ui_state = test_ui_state();
-        execute_command(&mut state, &mut ui_state, "/history 2");
-        let lines = timeline_text_lines(&ui_state);
-        let joined = lines.join("\n");
-        assert!(joined.contains("/history"));
-        assert!(joined.contains("/history 2"));
+        state
+            .python
+            .run_exec_input("a = 6\nb = 3")
+            .expect("seed python state");
+
+        execute_command(&mut state, &mut ui_state, "/watch add ratio = a / b");
+        execute_command(&mut state, &mut ui_state, "/watch add total = a + b");
+        ui_state.timeline.clear();
+
+        ui_state.python_input = "b = 2".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec!["py> b = 2", "ratio = 3.0", "total = 8"]
+        );
+
+        ui_state.timeline.clear();
+        ui_state.python_input = "b = 0".to_string();
+        submit_line(&mut state, &mut ui_state, &mut |_| Ok(()))
+            .await
+            .expect("submit");
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec![
+                "py> b = 0",
+                "ratio: ZeroDivisionError: division by zero",
+                "total = 6"
+            ]
+        );
         assert!(
-            !joined.contains("/help"),
-            "limited history output should omit older entries"
+            state
+                .python
+                .get_last_exception()
+                .expect("last exception")
+                .is_none(),
+            "watch failures should not replace the last error"
+        );
+
+        execute_command(&mut state, &mut ui_state, "/watch remove ratio");
+        ui_state.timeline.clear();
+        execute_command(&mut state, &mut ui_state, "/watch list");
+        assert_eq!(
+            timeline_text_lines(&ui_state),
+            vec!["cmd> /watch list", "total: a + b"]
         );
     }
 
     #[test]
-    fn execute_command_trace_prints_exact_path() {
+    fn execute_command_inspect_to_file_writes_json_and_prints_summary() {
         let dir = tempdir().expect("tempdir");
-        let mut state = test_app_state("trace", dir.path());
+        let mut state = test_app_state("inspect-to", dir.path());
         let mut ui_state = test_ui_state();
+        state
+            .python
+            .run_exec_input("value = {'a': 1, 'b': [2, 3]}")
+            .expect("seed python state");
+        let path = dir.path().join("inspect.json");
 
-        execute_command(&mut state, &mut ui_state, "/trace");
+        execute_command(
+            &mut state,
+            &mut ui_state,
+            &format!("/inspect --to {} value", path.display()),
+        );
+
+        let payload: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).expect("read inspect file"))
+                .expect("valid json");
+        assert_eq!(payload["type"]["name"], "dict");
+        assert_eq!(payload["kind"], "mapping");
         let lines = timeline_text_lines(&ui_state);
-        assert!(
-            lines.iter().any(|line| line == "cmd> /trace"),
-            "command input should be rendered in timeline"
+        assert_eq!(
+            lines.last().map(String::as_str),
+            Some(
+                format!(
+                    "inspect value: builtins.dict (mapping) written to {}",
+                    path.display()
+                )
+                .as_str()
+            )
         );
-        let trace_path = state.trace.file_path().display().to_string();
-        assert!(lines.contains(&trace_path));
+        assert!(!lines.iter().any(|line| line.contains("\"repr\"")));
     }
 
     #[test]
@@ -2198,6 +6884,40 @@ mod tests {
         assert!(joined.contains("Traceback"));
     }
 
+    #[test]
+    fn execute_command_last_error_io_shows_streams_captured_with_exception() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = test_app_state("last-error-io", dir.path());
+        let mut ui_state = test_ui_state();
+
+        let _ = state
+            .python
+            .run_user_input(
+                "import sys\nprint('working')\nprint('about to fail', file=sys.stderr)\n1 / 0",
+            )
+            .expect("python run");
+        execute_command(&mut state, &mut ui_state, "/last_error --io");
+
+        let lines = timeline_text_lines(&ui_state);
+        assert_eq!(
+            &lines[..3],
+            ["cmd> /last_error --io", "working", "about to fail"]
+        );
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("ZeroDivisionError"))
+        );
+
+        ui_state.timeline.clear();
+        execute_command(&mut state, &mut ui_state, "/last_error");
+        assert!(
+            !timeline_text_lines(&ui_state)
+                .iter()
+                .any(|line| line == "about to fail")
+        );
+    }
+
     #[test]
     fn include_and_run_execute_python_file_and_preserve_state() {
         let dir = tempdir().expect("tempdir");
@@ -2458,13 +7178,41 @@ mod tests {
             llm: None,
             agent_config: AgentConfig::default(),
             theme_config: ThemeConfig::default(),
+            key_bindings: KeyBindings::default(),
+            edit_mode: EditMode::Emacs,
+            thinking_delay: Duration::ZERO,
+            timeline_timestamps: false,
+            timeline_max_bytes: 0,
+            max_steps_shown: 0,
+            auto_page_lines: 0,
+            repr_max_chars: 0,
+            clipboard_osc52: false,
+            auto_time_threshold: None,
+            stream_output: false,
+            inspect_compact: false,
+            echo_expr: false,
+            semicolon_suppresses_output: false,
+            memory_turns: 0,
+            conversation: Vec::new(),
+            history_file: None,
+            config_path: PathBuf::from("config.toml"),
+            config_is_explicit: false,
+            history_recall: HistoryRecall::Block,
             startup_message: None,
             trace: SessionTrace::create_in_temp_dir(session_id, trace_dir).expect("trace"),
         }
     }
 
     fn test_ui_state() -> UiState {
-        UiState::new(Mode::Python, false, &ThemeConfig::default())
+        UiState::new(
+            Mode::Python,
+            false,
+            &ThemeConfig::default(),
+            KeyBindings::default(),
+            Timeline::new(),
+            None,
+            HistoryRecall::Block,
+        )
     }
 
     fn timeline_text_lines(ui_state: &UiState) -> Vec<String> {
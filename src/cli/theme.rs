@@ -7,6 +7,7 @@ use std::collections::HashMap;
 #[derive(Debug, Clone)]
 pub struct Theme {
     enabled: bool,
+    highlight: bool,
     styles: HashMap<ThemeToken, Style>,
 }
 
@@ -23,7 +24,33 @@ impl Theme {
             styles.insert(*token, merge_style(base, override_style));
         }
 
-        Self { enabled, styles }
+        Self {
+            enabled,
+            highlight: config.highlight,
+            styles,
+        }
+    }
+
+    /// Builds the unmodified `preset` theme, keeping this theme's color setting.
+    pub fn for_preset(&self, preset: ThemePreset) -> Self {
+        Self::from_config(
+            self.enabled,
+            &UserThemeConfig {
+                preset,
+                styles: HashMap::new(),
+                highlight: self.highlight,
+            },
+        )
+    }
+
+    pub fn color_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether Python text should be split into syntax-highlighted spans. Off when
+    /// color is disabled.
+    pub fn highlight(&self) -> bool {
+        self.enabled && self.highlight
     }
 
     pub fn style(&self, token: ThemeToken) -> Style {
@@ -62,15 +89,40 @@ fn default_preset_style(token: ThemeToken) -> Style {
         ThemeToken::CommandPrompt => Style::default()
             .fg(Color::Rgb(255, 203, 107))
             .add_modifier(Modifier::BOLD),
+        ThemeToken::ContinuationPrompt => Style::default()
+            .fg(Color::Rgb(224, 175, 104))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::InvalidInputPrompt => Style::default()
+            .fg(Color::Rgb(247, 118, 142))
+            .add_modifier(Modifier::BOLD),
         ThemeToken::UserInputPython | ThemeToken::UserInputAssistant => {
             Style::default().fg(Color::White)
         }
         ThemeToken::PythonValue => Style::default().fg(Color::Rgb(158, 206, 106)),
         ThemeToken::PythonStdout => Style::default().fg(Color::Rgb(192, 202, 245)),
         ThemeToken::PythonStderr => Style::default().fg(Color::Rgb(255, 158, 100)),
+        ThemeToken::PythonWarning => Style::default().fg(Color::Rgb(224, 175, 104)),
         ThemeToken::PythonTraceback => Style::default()
             .fg(Color::Rgb(247, 118, 142))
             .add_modifier(Modifier::BOLD),
+        ThemeToken::TracebackLocation => Style::default().fg(Color::Rgb(122, 162, 247)),
+        ThemeToken::TracebackCaret => Style::default()
+            .fg(Color::Rgb(255, 158, 100))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::TracebackException => Style::default()
+            .fg(Color::Rgb(255, 85, 85))
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        ThemeToken::PythonKeyword => Style::default()
+            .fg(Color::Rgb(187, 154, 247))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::PythonString => Style::default().fg(Color::Rgb(230, 200, 120)),
+        ThemeToken::PythonNumber => Style::default().fg(Color::Rgb(255, 158, 100)),
+        ThemeToken::PythonComment => Style::default()
+            .fg(Color::Rgb(105, 115, 155))
+            .add_modifier(Modifier::ITALIC),
+        ThemeToken::Timestamp => Style::default()
+            .fg(Color::Rgb(86, 95, 137))
+            .add_modifier(Modifier::DIM),
         ThemeToken::AssistantText => Style::default().fg(Color::Rgb(219, 75, 75)),
         ThemeToken::AssistantWaiting => Style::default()
             .fg(Color::Rgb(206, 120, 120))
@@ -110,15 +162,40 @@ fn light_preset_style(token: ThemeToken) -> Style {
         ThemeToken::CommandPrompt => Style::default()
             .fg(Color::Rgb(188, 76, 0))
             .add_modifier(Modifier::BOLD),
+        ThemeToken::ContinuationPrompt => Style::default()
+            .fg(Color::Rgb(154, 103, 0))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::InvalidInputPrompt => Style::default()
+            .fg(Color::Rgb(207, 34, 46))
+            .add_modifier(Modifier::BOLD),
         ThemeToken::UserInputPython | ThemeToken::UserInputAssistant => {
             Style::default().fg(Color::Rgb(36, 41, 47))
         }
         ThemeToken::PythonValue => Style::default().fg(Color::Rgb(5, 80, 40)),
         ThemeToken::PythonStdout => Style::default().fg(Color::Rgb(9, 105, 218)),
         ThemeToken::PythonStderr => Style::default().fg(Color::Rgb(188, 76, 0)),
+        ThemeToken::PythonWarning => Style::default().fg(Color::Rgb(140, 110, 0)),
         ThemeToken::PythonTraceback => Style::default()
             .fg(Color::Rgb(176, 0, 32))
             .add_modifier(Modifier::BOLD),
+        ThemeToken::TracebackLocation => Style::default().fg(Color::Rgb(9, 105, 218)),
+        ThemeToken::TracebackCaret => Style::default()
+            .fg(Color::Rgb(188, 76, 0))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::TracebackException => Style::default()
+            .fg(Color::Rgb(176, 0, 32))
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        ThemeToken::PythonKeyword => Style::default()
+            .fg(Color::Rgb(130, 80, 223))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::PythonString => Style::default().fg(Color::Rgb(10, 48, 105)),
+        ThemeToken::PythonNumber => Style::default().fg(Color::Rgb(5, 80, 174)),
+        ThemeToken::PythonComment => Style::default()
+            .fg(Color::Rgb(110, 119, 129))
+            .add_modifier(Modifier::ITALIC),
+        ThemeToken::Timestamp => Style::default()
+            .fg(Color::Rgb(110, 119, 129))
+            .add_modifier(Modifier::DIM),
         ThemeToken::AssistantText => Style::default().fg(Color::Rgb(130, 70, 0)),
         ThemeToken::AssistantWaiting => Style::default()
             .fg(Color::Rgb(130, 70, 0))
@@ -162,15 +239,38 @@ fn high_contrast_preset_style(token: ThemeToken) -> Style {
         ThemeToken::CommandPrompt => Style::default()
             .fg(Color::Rgb(255, 255, 0))
             .add_modifier(Modifier::BOLD),
+        ThemeToken::ContinuationPrompt => Style::default()
+            .fg(Color::Rgb(0, 215, 255))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::InvalidInputPrompt => Style::default()
+            .fg(Color::Rgb(255, 64, 64))
+            .add_modifier(Modifier::BOLD),
         ThemeToken::UserInputPython | ThemeToken::UserInputAssistant => {
             Style::default().fg(Color::Rgb(255, 255, 255))
         }
         ThemeToken::PythonValue => Style::default().fg(Color::Rgb(0, 255, 127)),
         ThemeToken::PythonStdout => Style::default().fg(Color::Rgb(135, 206, 250)),
         ThemeToken::PythonStderr => Style::default().fg(Color::Rgb(255, 140, 0)),
+        ThemeToken::PythonWarning => Style::default().fg(Color::Rgb(255, 215, 0)),
         ThemeToken::PythonTraceback => Style::default()
             .fg(Color::Rgb(255, 64, 64))
             .add_modifier(Modifier::BOLD),
+        ThemeToken::TracebackLocation => Style::default().fg(Color::Rgb(0, 255, 255)),
+        ThemeToken::TracebackCaret => Style::default()
+            .fg(Color::Rgb(255, 255, 0))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::TracebackException => Style::default()
+            .fg(Color::Rgb(255, 64, 64))
+            .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        ThemeToken::PythonKeyword => Style::default()
+            .fg(Color::Rgb(255, 0, 255))
+            .add_modifier(Modifier::BOLD),
+        ThemeToken::PythonString => Style::default().fg(Color::Rgb(255, 255, 0)),
+        ThemeToken::PythonNumber => Style::default().fg(Color::Rgb(0, 215, 255)),
+        ThemeToken::PythonComment => Style::default()
+            .fg(Color::Rgb(192, 192, 192))
+            .add_modifier(Modifier::ITALIC),
+        ThemeToken::Timestamp => Style::default().fg(Color::Rgb(192, 192, 192)),
         ThemeToken::AssistantText => Style::default().fg(Color::Rgb(255, 215, 0)),
         ThemeToken::AssistantWaiting => Style::default()
             .fg(Color::Rgb(255, 255, 0))
@@ -207,9 +307,11 @@ fn high_contrast_preset_style(token: ThemeToken) -> Style {
 
 fn disabled_style(token: ThemeToken) -> Style {
     match token {
-        ThemeToken::PythonPrompt | ThemeToken::AssistantPrompt | ThemeToken::CommandPrompt => {
-            Style::default().add_modifier(Modifier::BOLD)
-        }
+        ThemeToken::PythonPrompt
+        | ThemeToken::AssistantPrompt
+        | ThemeToken::CommandPrompt
+        | ThemeToken::ContinuationPrompt
+        | ThemeToken::InvalidInputPrompt => Style::default().add_modifier(Modifier::BOLD),
         _ => Style::default(),
     }
 }
@@ -297,6 +399,7 @@ mod tests {
         let mut config = ThemeConfig {
             preset: ThemePreset::Default,
             styles: HashMap::new(),
+            highlight: false,
         };
         config.styles.insert(
             ThemeToken::PythonPrompt,
@@ -1,15 +1,25 @@
+use crate::cli::highlight::{LexState, PyTokenKind, lex_line};
 use crate::cli::theme::Theme;
-use crate::config::ThemeToken;
+use crate::config::{ThemePreset, ThemeToken};
 use crate::llm::provider::LlmTokenUsageTotals;
+use ratatui::style::Modifier;
 use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Wrap};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use time::{OffsetDateTime, Time};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum OutputKind {
     UserInputPython,
     UserInputAssistant,
     PythonValue,
     PythonStdout,
     PythonStderr,
+    PythonWarning,
     PythonTraceback,
     AssistantText,
     AssistantWaiting,
@@ -19,107 +29,566 @@ pub(crate) enum OutputKind {
     SystemError,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum TimelineEntry {
     UserInputPython(String),
     UserInputCommand(String),
     OutputLine { kind: OutputKind, text: String },
+    ThemePreview { preset: ThemePreset, current: bool },
     AssistantTurn(AssistantTurn),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct AssistantTurn {
     pub(crate) prompt: String,
     pub(crate) events: Vec<AssistantStepEvent>,
     pub(crate) state: AssistantTurnState,
     pub(crate) token_usage: Option<LlmTokenUsageTotals>,
+    #[serde(skip, default = "Instant::now")]
+    pub(crate) started_at: Instant,
+    pub(crate) streamed_text: String,
+    /// Completed turns show a one-line summary of their tool steps until expanded.
+    pub(crate) steps_expanded: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum AssistantTurnState {
     InFlight,
     CompletedText(String),
     CompletedError(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum AssistantStepEvent {
     ToolRequest { text: String },
     ToolResult { text: String },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TranscriptFormat {
+    PlainText,
+    Markdown,
+}
+
+/// What a left click on a rendered timeline line does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LineClick {
+    ToggleTurnSteps(usize),
+    Copy(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StampedEntry {
+    entry: TimelineEntry,
+    /// UTC time of the push; only the first entry of a multi-line push carries it.
+    time: Option<Time>,
+    /// Approximate text size, counted against the timeline's byte budget.
+    #[serde(skip)]
+    bytes: usize,
+}
+
+/// The entries of a timeline, as written by `/session save`. Settings such as
+/// timestamps and the byte budget belong to the session that loads it and are not saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct TimelineSnapshot {
+    entries: Vec<StampedEntry>,
+}
+
+impl TimelineSnapshot {
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Width of the `HH:MM:SS ` prefix shown when timestamps are enabled.
+const TIMESTAMP_WIDTH: usize = 9;
+
 #[derive(Debug, Clone, Default)]
 pub(crate) struct Timeline {
-    entries: Vec<TimelineEntry>,
+    entries: VecDeque<StampedEntry>,
+    /// Entries dropped from the front so far, keeping returned indices stable.
+    trimmed: usize,
+    bytes: usize,
+    /// Byte budget past which the oldest entries are dropped; `0` means unlimited.
+    max_bytes: usize,
+    thinking_delay: Duration,
+    timestamps: bool,
+    /// Tool steps shown per assistant turn; older ones collapse into one line. `0` shows all.
+    max_steps_shown: usize,
+    /// Assistant turn picked with the keyboard or mouse, whose steps the toggle acts on.
+    selected_turn: Option<usize>,
 }
 
 impl Timeline {
+    #[cfg(test)]
     pub(crate) fn new() -> Self {
         Self::default()
     }
 
+    pub(crate) fn with_thinking_delay(thinking_delay: Duration) -> Self {
+        Self {
+            thinking_delay,
+            ..Self::default()
+        }
+    }
+
+    /// Prefixes each entry with the time it was pushed.
+    pub(crate) fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Drops the oldest entries once their total text exceeds `max_bytes` (`0` disables).
+    pub(crate) fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub(crate) fn with_max_steps_shown(mut self, max_steps_shown: usize) -> Self {
+        self.max_steps_shown = max_steps_shown;
+        self
+    }
+
     pub(crate) fn push_output(&mut self, kind: OutputKind, text: &str) {
-        for line in split_output_lines(text) {
-            self.entries.push(TimelineEntry::OutputLine {
+        self.push_entries(split_output_lines(text).into_iter().map(|line| {
+            TimelineEntry::OutputLine {
                 kind,
                 text: line.to_string(),
-            });
-        }
+            }
+        }));
     }
 
     pub(crate) fn push_user_input_python(&mut self, text: &str) {
-        for line in split_output_lines(text) {
-            self.entries
-                .push(TimelineEntry::UserInputPython(line.to_string()));
-        }
+        self.push_entries(
+            split_output_lines(text)
+                .into_iter()
+                .map(|line| TimelineEntry::UserInputPython(line.to_string())),
+        );
     }
 
     pub(crate) fn push_user_input_command(&mut self, text: &str) {
-        for line in split_output_lines(text) {
-            self.entries
-                .push(TimelineEntry::UserInputCommand(line.to_string()));
-        }
+        self.push_entries(
+            split_output_lines(text)
+                .into_iter()
+                .map(|line| TimelineEntry::UserInputCommand(line.to_string())),
+        );
+    }
+
+    pub(crate) fn push_theme_preview(&mut self, preset: ThemePreset, current: bool) {
+        self.push_entries([TimelineEntry::ThemePreview { preset, current }]);
     }
 
     pub(crate) fn push_assistant_turn(&mut self, prompt: String) -> usize {
-        let index = self.entries.len();
-        self.entries
-            .push(TimelineEntry::AssistantTurn(AssistantTurn {
-                prompt,
-                events: Vec::new(),
-                state: AssistantTurnState::InFlight,
-                token_usage: None,
-            }));
+        let index = self.trimmed + self.entries.len();
+        self.push_entries([TimelineEntry::AssistantTurn(AssistantTurn {
+            prompt,
+            events: Vec::new(),
+            state: AssistantTurnState::InFlight,
+            token_usage: None,
+            started_at: Instant::now(),
+            streamed_text: String::new(),
+            steps_expanded: false,
+        })]);
         index
     }
 
+    fn push_entries(&mut self, entries: impl IntoIterator<Item = TimelineEntry>) {
+        let mut time = Some(OffsetDateTime::now_utc().time());
+        for entry in entries {
+            let bytes = entry_bytes(&entry);
+            self.bytes += bytes;
+            self.entries.push_back(StampedEntry {
+                entry,
+                time: time.take(),
+                bytes,
+            });
+        }
+        self.trim_to_max_bytes();
+    }
+
+    fn trim_to_max_bytes(&mut self) {
+        if self.max_bytes == 0 {
+            return;
+        }
+        while self.bytes > self.max_bytes && self.entries.len() > 1 {
+            let Some(oldest) = self.entries.pop_front() else {
+                break;
+            };
+            self.bytes -= oldest.bytes;
+            self.trimmed += 1;
+        }
+    }
+
     pub(crate) fn assistant_turn_mut(&mut self, index: usize) -> Option<&mut AssistantTurn> {
-        match self.entries.get_mut(index) {
+        match self
+            .entries
+            .get_mut(index.checked_sub(self.trimmed)?)
+            .map(|stamped| &mut stamped.entry)
+        {
             Some(TimelineEntry::AssistantTurn(turn)) => Some(turn),
             _ => None,
         }
     }
 
+    pub(crate) fn last_assistant_turn(&self) -> Option<&AssistantTurn> {
+        self.entries
+            .iter()
+            .rev()
+            .find_map(|stamped| match &stamped.entry {
+                TimelineEntry::AssistantTurn(turn) => Some(turn),
+                _ => None,
+            })
+    }
+
+    /// Moves the selection to the previous (`older`) or next assistant turn. Starting
+    /// without a selection, going back selects the latest turn; going forward past the
+    /// latest turn clears the selection.
+    pub(crate) fn select_adjacent_turn(&mut self, older: bool) {
+        let turns = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, stamped)| matches!(stamped.entry, TimelineEntry::AssistantTurn(_)))
+            .map(|(offset, _)| self.trimmed + offset)
+            .collect::<Vec<_>>();
+        let current = self
+            .selected_turn
+            .and_then(|selected| turns.iter().position(|&index| index == selected));
+        self.selected_turn = match (current, older) {
+            (None, true) => turns.last().copied(),
+            (None, false) => None,
+            (Some(position), true) => Some(turns[position.saturating_sub(1)]),
+            (Some(position), false) => turns.get(position + 1).copied(),
+        };
+    }
+
+    /// Selects the turn at `index` and expands or collapses its tool steps.
+    pub(crate) fn toggle_turn_steps(&mut self, index: usize) {
+        if let Some(turn) = self.assistant_turn_mut(index) {
+            turn.steps_expanded = !turn.steps_expanded;
+            self.selected_turn = Some(index);
+        }
+    }
+
+    /// Toggles the selected turn's tool steps, or the latest turn's without a selection.
+    pub(crate) fn toggle_selected_turn_steps(&mut self) {
+        let index = self.selected_turn.or_else(|| {
+            self.entries
+                .iter()
+                .rposition(|stamped| matches!(stamped.entry, TimelineEntry::AssistantTurn(_)))
+                .map(|offset| self.trimmed + offset)
+        });
+        if let Some(index) = index {
+            self.toggle_turn_steps(index);
+        }
+    }
+
+    /// What clicking screen row `row` does once the timeline is wrapped to `width`
+    /// columns: the `ai>` line of an assistant turn toggles its steps, any other
+    /// non-blank line is copied.
+    pub(crate) fn click_target(
+        &self,
+        theme: &Theme,
+        show_assistant_steps: bool,
+        width: u16,
+        row: usize,
+    ) -> Option<LineClick> {
+        let (lines, entry_starts) =
+            self.render_entries(theme, show_assistant_steps, Instant::now(), false);
+        let line = line_at_wrapped_row(&lines, width, row)?;
+        let offset = entry_starts
+            .partition_point(|&start| start <= line)
+            .checked_sub(1)?;
+        if let Some(TimelineEntry::AssistantTurn(_)) =
+            self.entries.get(offset).map(|stamped| &stamped.entry)
+            && entry_starts[offset] == line
+        {
+            return Some(LineClick::ToggleTurnSteps(self.trimmed + offset));
+        }
+        let text = lines.get(line)?.to_string();
+        let text = if self.timestamps {
+            text.chars().skip(TIMESTAMP_WIDTH).collect()
+        } else {
+            text
+        };
+        (!text.trim().is_empty()).then_some(LineClick::Copy(text))
+    }
+
     pub(crate) fn render_lines(
         &self,
         theme: &Theme,
         show_assistant_steps: bool,
     ) -> Vec<Line<'static>> {
+        self.render_lines_at(theme, show_assistant_steps, Instant::now())
+    }
+
+    fn render_lines_at(
+        &self,
+        theme: &Theme,
+        show_assistant_steps: bool,
+        now: Instant,
+    ) -> Vec<Line<'static>> {
+        self.render_entries(theme, show_assistant_steps, now, false)
+            .0
+    }
+
+    /// Renders every entry, returning the lines and the first line of each entry.
+    /// `expand_steps` shows every tool step, ignoring collapsed turns and
+    /// `max_steps_shown`.
+    fn render_entries(
+        &self,
+        theme: &Theme,
+        show_assistant_steps: bool,
+        now: Instant,
+        expand_steps: bool,
+    ) -> (Vec<Line<'static>>, Vec<usize>) {
         let context = RenderContext {
             theme,
             show_assistant_steps,
+            expand_steps,
+            thinking_delay: self.thinking_delay,
+            max_steps_shown: self.max_steps_shown,
+            now,
+            python_lex_state: Cell::default(),
         };
         let mut lines = Vec::new();
-        for entry in &self.entries {
-            widget_for_entry(entry).render(&context, &mut lines);
+        let mut entry_starts = Vec::with_capacity(self.entries.len());
+        for (offset, StampedEntry { entry, time, .. }) in self.entries.iter().enumerate() {
+            // A string left open by one line of Python input continues only onto the
+            // next input line of the same block.
+            if !matches!(entry, TimelineEntry::UserInputPython(_)) {
+                context.python_lex_state.take();
+            }
+            let first_line = lines.len();
+            entry_starts.push(first_line);
+            let selected = self.selected_turn == Some(self.trimmed + offset);
+            widget_for_entry(entry, selected).render(&context, &mut lines);
+            if self.timestamps {
+                prefix_timestamp(theme, *time, &mut lines[first_line..]);
+            }
         }
 
-        lines
+        (lines, entry_starts)
     }
 
     pub(crate) fn clear(&mut self) {
+        self.trimmed += self.entries.len();
+        self.bytes = 0;
         self.entries.clear();
     }
+
+    pub(crate) fn snapshot(&self) -> TimelineSnapshot {
+        TimelineSnapshot {
+            entries: self.entries.iter().cloned().collect(),
+        }
+    }
+
+    /// Replaces the entries with saved ones, keeping this timeline's settings. Earlier
+    /// entry indices stay retired, as after [`Self::clear`].
+    pub(crate) fn restore(&mut self, snapshot: TimelineSnapshot) {
+        self.clear();
+        self.selected_turn = None;
+        for mut stamped in snapshot.entries {
+            stamped.bytes = entry_bytes(&stamped.entry);
+            self.bytes += stamped.bytes;
+            self.entries.push_back(stamped);
+        }
+        self.trim_to_max_bytes();
+    }
+
+    pub(crate) fn transcript(
+        &self,
+        theme: &Theme,
+        show_assistant_steps: bool,
+        format: TranscriptFormat,
+    ) -> String {
+        match format {
+            TranscriptFormat::PlainText => {
+                let (lines, _) =
+                    self.render_entries(theme, show_assistant_steps, Instant::now(), true);
+                let mut text = line_texts(lines).join("\n");
+                text.push('\n');
+                text
+            }
+            TranscriptFormat::Markdown => self.markdown_transcript(theme, show_assistant_steps),
+        }
+    }
+
+    /// Builds a script from the Python-mode inputs, in order, so the session can be
+    /// replayed with `python`. With `include_outputs`, each input's Python output follows
+    /// it as comments; with `skip_failed`, inputs that raised are left out.
+    pub(crate) fn python_script(&self, include_outputs: bool, skip_failed: bool) -> String {
+        struct Block<'a> {
+            input: Vec<&'a str>,
+            output: Vec<&'a str>,
+            failed: bool,
+        }
+
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut in_python_block = false;
+        for StampedEntry { entry, time, .. } in &self.entries {
+            match entry {
+                TimelineEntry::UserInputPython(text) => {
+                    // Each submitted input is one push, so only its first line is stamped.
+                    if time.is_some() || !in_python_block {
+                        blocks.push(Block {
+                            input: Vec::new(),
+                            output: Vec::new(),
+                            failed: false,
+                        });
+                    }
+                    if let Some(block) = blocks.last_mut() {
+                        block.input.push(text);
+                    }
+                    in_python_block = true;
+                }
+                TimelineEntry::OutputLine { kind, text } if in_python_block => {
+                    let Some(block) = blocks.last_mut() else {
+                        continue;
+                    };
+                    match kind {
+                        OutputKind::PythonTraceback => {
+                            block.failed = true;
+                            block.output.push(text);
+                        }
+                        OutputKind::PythonValue
+                        | OutputKind::PythonStdout
+                        | OutputKind::PythonStderr
+                        | OutputKind::PythonWarning => block.output.push(text),
+                        _ => {}
+                    }
+                }
+                TimelineEntry::OutputLine { .. } => {}
+                _ => in_python_block = false,
+            }
+        }
+
+        let mut script = String::new();
+        for block in blocks {
+            if skip_failed && block.failed {
+                continue;
+            }
+            for line in block.input {
+                script.push_str(line);
+                script.push('\n');
+            }
+            if include_outputs {
+                for line in block.output {
+                    if line.is_empty() {
+                        script.push_str("#\n");
+                    } else {
+                        script.push_str(&format!("# {line}\n"));
+                    }
+                }
+            }
+        }
+        script
+    }
+
+    fn markdown_transcript(&self, theme: &Theme, show_assistant_steps: bool) -> String {
+        let context = RenderContext {
+            theme,
+            show_assistant_steps,
+            expand_steps: true,
+            thinking_delay: self.thinking_delay,
+            max_steps_shown: self.max_steps_shown,
+            now: Instant::now(),
+            python_lex_state: Cell::default(),
+        };
+        let mut sections = Vec::new();
+        let mut block = Vec::new();
+        for StampedEntry { entry, .. } in &self.entries {
+            let TimelineEntry::AssistantTurn(turn) = entry else {
+                widget_for_entry(entry, false).render(&context, &mut block);
+                continue;
+            };
+
+            if !block.is_empty() {
+                let lines = line_texts(std::mem::take(&mut block));
+                sections.push(format!("```text\n{}\n```", lines.join("\n")));
+            }
+            sections.push(markdown_assistant_turn(turn, show_assistant_steps));
+        }
+        if !block.is_empty() {
+            sections.push(format!("```text\n{}\n```", line_texts(block).join("\n")));
+        }
+
+        let mut text = sections.join("\n\n");
+        text.push('\n');
+        text
+    }
+}
+
+/// Puts the entry time before its first line and indents the rest to match. Blank
+/// lines stay blank.
+fn prefix_timestamp(theme: &Theme, time: Option<Time>, lines: &mut [Line<'static>]) {
+    let style = theme.style(ThemeToken::Timestamp);
+    for (index, line) in lines.iter_mut().enumerate() {
+        let prefix = match time {
+            Some(time) if index == 0 => format!(
+                "{:02}:{:02}:{:02} ",
+                time.hour(),
+                time.minute(),
+                time.second()
+            ),
+            _ if line.width() == 0 => continue,
+            _ => " ".repeat(TIMESTAMP_WIDTH),
+        };
+        line.spans.insert(0, Span::styled(prefix, style));
+    }
+}
+
+fn markdown_assistant_turn(turn: &AssistantTurn, show_assistant_steps: bool) -> String {
+    let mut parts = vec![format!("**ai>** {}", turn.prompt)];
+    if show_assistant_steps && !turn.events.is_empty() {
+        let steps = turn
+            .events
+            .iter()
+            .map(|event| match event {
+                AssistantStepEvent::ToolRequest { text }
+                | AssistantStepEvent::ToolResult { text } => match text.split_once('\n') {
+                    Some((summary, body)) => {
+                        format!(
+                            "- `{summary}`\n\n  ```text\n  {}\n  ```",
+                            body.replace('\n', "\n  ")
+                        )
+                    }
+                    None => format!("- `{text}`"),
+                },
+            })
+            .collect::<Vec<_>>();
+        parts.push(steps.join("\n"));
+    }
+    match &turn.state {
+        AssistantTurnState::InFlight => {
+            if !turn.streamed_text.is_empty() {
+                parts.push(turn.streamed_text.clone());
+            }
+        }
+        AssistantTurnState::CompletedText(text) => parts.push(text.clone()),
+        AssistantTurnState::CompletedError(message) => parts.push(format!("> {message}")),
+    }
+    parts.join("\n\n")
+}
+
+/// Index of the line that covers screen row `row` when `lines` wrap to `width` columns
+/// the way the timeline paragraph wraps them.
+fn line_at_wrapped_row(lines: &[Line<'static>], width: u16, row: usize) -> Option<usize> {
+    let mut top = 0;
+    lines.iter().position(|line| {
+        top += Paragraph::new(line.clone())
+            .wrap(Wrap { trim: false })
+            .line_count(width)
+            .max(1);
+        row < top
+    })
+}
+
+fn line_texts(lines: Vec<Line<'static>>) -> Vec<String> {
+    lines.into_iter().map(|line| line.to_string()).collect()
 }
 
 trait TimelineWidget {
@@ -129,6 +598,40 @@ trait TimelineWidget {
 struct RenderContext<'a> {
     theme: &'a Theme,
     show_assistant_steps: bool,
+    /// Transcripts show every tool step, even of collapsed turns.
+    expand_steps: bool,
+    thinking_delay: Duration,
+    max_steps_shown: usize,
+    now: Instant,
+    python_lex_state: Cell<LexState>,
+}
+
+/// Styles a line of Python with `base` for plain text, splitting out keywords, strings,
+/// numbers and comments when the theme enables highlighting.
+fn python_spans(
+    theme: &Theme,
+    text: &str,
+    base: ThemeToken,
+    state: &mut LexState,
+) -> Vec<Span<'static>> {
+    let base_style = theme.style(base);
+    if !theme.highlight() {
+        return vec![Span::styled(text.to_string(), base_style)];
+    }
+
+    lex_line(text, state)
+        .into_iter()
+        .map(|(kind, segment)| {
+            let style = match kind {
+                PyTokenKind::Plain => base_style,
+                PyTokenKind::Keyword => theme.style(ThemeToken::PythonKeyword),
+                PyTokenKind::String => theme.style(ThemeToken::PythonString),
+                PyTokenKind::Number => theme.style(ThemeToken::PythonNumber),
+                PyTokenKind::Comment => theme.style(ThemeToken::PythonComment),
+            };
+            Span::styled(segment.to_string(), style)
+        })
+        .collect()
 }
 
 struct PythonInputWidget<'a> {
@@ -137,15 +640,19 @@ struct PythonInputWidget<'a> {
 
 impl TimelineWidget for PythonInputWidget<'_> {
     fn render(&self, context: &RenderContext<'_>, lines: &mut Vec<Line<'static>>) {
-        lines.push(Line::from(vec![
-            Span::styled("py> ", context.theme.style(ThemeToken::PythonPrompt)),
-            Span::styled(
-                self.text.to_string(),
-                context
-                    .theme
-                    .style(output_token_for(OutputKind::UserInputPython)),
-            ),
-        ]));
+        let mut lex_state = context.python_lex_state.get();
+        let mut spans = vec![Span::styled(
+            "py> ",
+            context.theme.style(ThemeToken::PythonPrompt),
+        )];
+        spans.extend(python_spans(
+            context.theme,
+            self.text,
+            output_token_for(OutputKind::UserInputPython),
+            &mut lex_state,
+        ));
+        context.python_lex_state.set(lex_state);
+        lines.push(Line::from(spans));
     }
 }
 
@@ -174,22 +681,149 @@ struct OutputLineWidget<'a> {
 
 impl TimelineWidget for OutputLineWidget<'_> {
     fn render(&self, context: &RenderContext<'_>, lines: &mut Vec<Line<'static>>) {
+        if self.kind == OutputKind::PythonValue {
+            lines.push(Line::from(python_spans(
+                context.theme,
+                self.text,
+                output_token_for(self.kind),
+                &mut LexState::default(),
+            )));
+            return;
+        }
+
+        let token = match self.kind {
+            OutputKind::PythonTraceback => traceback_token_for(self.text),
+            kind => output_token_for(kind),
+        };
         lines.push(Line::from(Span::styled(
             self.text.to_string(),
-            context.theme.style(output_token_for(self.kind)),
+            context.theme.style(token),
         )));
     }
 }
 
+/// Picks out the parts of a traceback worth finding in a long stack: the `File ...`
+/// frame headers, the `^^^^` markers under the failing code, and the final
+/// `ExceptionType: message` line.
+fn traceback_token_for(line: &str) -> ThemeToken {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with("File \"") {
+        return ThemeToken::TracebackLocation;
+    }
+    if !trimmed.is_empty() && trimmed.chars().all(|ch| matches!(ch, '^' | '~' | ' ')) {
+        return ThemeToken::TracebackCaret;
+    }
+    let name = line.split_once(':').map_or(line, |(name, _)| name);
+    let is_exception_name = !name.is_empty()
+        && name.split('.').all(|part| {
+            part.chars().next().is_some_and(char::is_alphabetic)
+                && part.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+        });
+    if is_exception_name && name != "Traceback" {
+        ThemeToken::TracebackException
+    } else {
+        ThemeToken::PythonTraceback
+    }
+}
+
+struct ThemePreviewWidget {
+    preset: ThemePreset,
+    current: bool,
+}
+
+impl TimelineWidget for ThemePreviewWidget {
+    fn render(&self, context: &RenderContext<'_>, lines: &mut Vec<Line<'static>>) {
+        const SAMPLES: [(&str, ThemeToken); 6] = [
+            ("py> ", ThemeToken::PythonPrompt),
+            ("42 ", ThemeToken::PythonValue),
+            ("stdout ", ThemeToken::PythonStdout),
+            ("Traceback ", ThemeToken::PythonTraceback),
+            ("ai> ", ThemeToken::AssistantPrompt),
+            ("answer", ThemeToken::AssistantText),
+        ];
+        let preview = context.theme.for_preset(self.preset);
+        let marker = if self.current { "*" } else { " " };
+        let mut spans = vec![Span::styled(
+            format!("{marker} {:<15}", self.preset.name()),
+            context
+                .theme
+                .style(output_token_for(OutputKind::SystemInfo)),
+        )];
+        spans.extend(
+            SAMPLES
+                .iter()
+                .map(|(text, token)| Span::styled(*text, preview.style(*token))),
+        );
+        lines.push(Line::from(spans));
+    }
+}
+
 struct AssistantTurnWidget<'a> {
     turn: &'a AssistantTurn,
+    selected: bool,
+}
+
+impl AssistantTurnWidget<'_> {
+    fn show_thinking_block(&self, context: &RenderContext<'_>) -> bool {
+        if !context.show_assistant_steps {
+            return false;
+        }
+
+        match self.turn.state {
+            AssistantTurnState::InFlight => {
+                context.now.saturating_duration_since(self.turn.started_at)
+                    >= context.thinking_delay
+            }
+            AssistantTurnState::CompletedText(_) | AssistantTurnState::CompletedError(_) => true,
+        }
+    }
+
+    /// Splits off the steps past `max_steps_shown`, oldest first. A step is a tool
+    /// request with the results that follow it. Returns how many were hidden and the
+    /// events still shown.
+    fn tool_calls(&self) -> usize {
+        self.turn
+            .events
+            .iter()
+            .filter(|event| matches!(event, AssistantStepEvent::ToolRequest { .. }))
+            .count()
+    }
+
+    /// Completed turns with tool calls show only a summary line until expanded.
+    fn steps_collapsed(&self, context: &RenderContext<'_>) -> bool {
+        !context.expand_steps
+            && !self.turn.steps_expanded
+            && !matches!(self.turn.state, AssistantTurnState::InFlight)
+            && self.tool_calls() > 0
+    }
+
+    fn shown_events(&self, context: &RenderContext<'_>) -> (usize, &[AssistantStepEvent]) {
+        let events = &self.turn.events;
+        let steps = self.tool_calls();
+        if context.expand_steps || context.max_steps_shown == 0 || steps <= context.max_steps_shown
+        {
+            return (0, events);
+        }
+        let hidden = steps - context.max_steps_shown;
+        let first_shown = events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| matches!(event, AssistantStepEvent::ToolRequest { .. }))
+            .nth(hidden)
+            .map_or(events.len(), |(index, _)| index);
+        (hidden, &events[first_shown..])
+    }
 }
 
 impl TimelineWidget for AssistantTurnWidget<'_> {
     fn render(&self, context: &RenderContext<'_>, lines: &mut Vec<Line<'static>>) {
         const THINKING_BLOCK_PADDING: &str = "  ";
+        let mut prompt_style = context.theme.style(ThemeToken::AssistantPrompt);
+        if self.selected {
+            prompt_style = prompt_style.add_modifier(Modifier::REVERSED);
+        }
         lines.push(Line::from(vec![
-            Span::styled("ai> ", context.theme.style(ThemeToken::AssistantPrompt)),
+            Span::styled("ai> ", prompt_style),
             Span::styled(
                 self.turn.prompt.clone(),
                 context
@@ -198,7 +832,19 @@ impl TimelineWidget for AssistantTurnWidget<'_> {
             ),
         ]));
 
-        if context.show_assistant_steps {
+        if self.show_thinking_block(context) && self.steps_collapsed(context) {
+            let tool_calls = self.tool_calls();
+            let noun = if tool_calls == 1 { "call" } else { "calls" };
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                format!("{THINKING_BLOCK_PADDING}{tool_calls} tool {noun}"),
+                context
+                    .theme
+                    .style(output_token_for(OutputKind::AssistantWaiting)),
+            )));
+            render_turn_token_details(context, lines, self.turn.token_usage.as_ref());
+            lines.push(Line::from(""));
+        } else if self.show_thinking_block(context) {
             lines.push(Line::from(""));
             lines.push(Line::from(vec![
                 Span::raw(THINKING_BLOCK_PADDING),
@@ -210,7 +856,17 @@ impl TimelineWidget for AssistantTurnWidget<'_> {
                 ),
             ]));
 
-            for event in &self.turn.events {
+            let (hidden_steps, shown_events) = self.shown_events(context);
+            if hidden_steps > 0 {
+                let noun = if hidden_steps == 1 { "step" } else { "steps" };
+                lines.push(Line::from(Span::styled(
+                    format!("{THINKING_BLOCK_PADDING}… {hidden_steps} earlier {noun} …"),
+                    context
+                        .theme
+                        .style(output_token_for(OutputKind::AssistantProgressRequest)),
+                )));
+            }
+            for event in shown_events {
                 match event {
                     AssistantStepEvent::ToolRequest { text } => {
                         lines.push(Line::from(Span::styled(
@@ -221,12 +877,14 @@ impl TimelineWidget for AssistantTurnWidget<'_> {
                         )));
                     }
                     AssistantStepEvent::ToolResult { text } => {
-                        lines.push(Line::from(Span::styled(
-                            format!("{THINKING_BLOCK_PADDING}{text}"),
-                            context
-                                .theme
-                                .style(output_token_for(OutputKind::AssistantProgressResult)),
-                        )));
+                        for line in split_output_lines(text) {
+                            lines.push(Line::from(Span::styled(
+                                format!("{THINKING_BLOCK_PADDING}{line}"),
+                                context
+                                    .theme
+                                    .style(output_token_for(OutputKind::AssistantProgressResult)),
+                            )));
+                        }
                     }
                 }
             }
@@ -237,7 +895,16 @@ impl TimelineWidget for AssistantTurnWidget<'_> {
         }
 
         match &self.turn.state {
-            AssistantTurnState::InFlight => {}
+            AssistantTurnState::InFlight => {
+                for line in split_output_lines(&self.turn.streamed_text) {
+                    lines.push(Line::from(Span::styled(
+                        line.to_string(),
+                        context
+                            .theme
+                            .style(output_token_for(OutputKind::AssistantText)),
+                    )));
+                }
+            }
             AssistantTurnState::CompletedText(text) => {
                 for line in split_output_lines(text) {
                     lines.push(Line::from(Span::styled(
@@ -270,14 +937,16 @@ fn render_turn_token_details(
     let Some(usage) = usage else {
         return;
     };
-    if usage.is_zero() {
+    if usage.is_zero() && usage.unreported_calls == 0 {
         return;
     }
 
     lines.push(Line::from(Span::styled(
         format!(
             "  Tokens: {} in, {} out, {} total",
-            usage.input_tokens, usage.output_tokens, usage.total_tokens
+            usage.display_count(usage.input_tokens),
+            usage.display_count(usage.output_tokens),
+            usage.display_count(usage.total_tokens)
         ),
         context
             .theme
@@ -285,14 +954,30 @@ fn render_turn_token_details(
     )));
 }
 
-fn widget_for_entry(entry: &TimelineEntry) -> Box<dyn TimelineWidget + '_> {
+fn widget_for_entry(entry: &TimelineEntry, selected: bool) -> Box<dyn TimelineWidget + '_> {
     match entry {
         TimelineEntry::UserInputPython(text) => Box::new(PythonInputWidget { text }),
         TimelineEntry::UserInputCommand(text) => Box::new(CommandInputWidget { text }),
         TimelineEntry::OutputLine { kind, text } => {
             Box::new(OutputLineWidget { kind: *kind, text })
         }
-        TimelineEntry::AssistantTurn(turn) => Box::new(AssistantTurnWidget { turn }),
+        TimelineEntry::ThemePreview { preset, current } => Box::new(ThemePreviewWidget {
+            preset: *preset,
+            current: *current,
+        }),
+        TimelineEntry::AssistantTurn(turn) => Box::new(AssistantTurnWidget { turn, selected }),
+    }
+}
+
+/// Size of an entry's text when pushed. Assistant turns count only their prompt, since
+/// their answer arrives later.
+fn entry_bytes(entry: &TimelineEntry) -> usize {
+    match entry {
+        TimelineEntry::UserInputPython(text)
+        | TimelineEntry::UserInputCommand(text)
+        | TimelineEntry::OutputLine { text, .. } => text.len(),
+        TimelineEntry::ThemePreview { .. } => 0,
+        TimelineEntry::AssistantTurn(turn) => turn.prompt.len(),
     }
 }
 
@@ -304,13 +989,14 @@ fn split_output_lines(text: &str) -> Vec<&str> {
     text.lines().collect()
 }
 
-fn output_token_for(kind: OutputKind) -> ThemeToken {
+pub(crate) fn output_token_for(kind: OutputKind) -> ThemeToken {
     match kind {
         OutputKind::UserInputPython => ThemeToken::UserInputPython,
         OutputKind::UserInputAssistant => ThemeToken::UserInputAssistant,
         OutputKind::PythonValue => ThemeToken::PythonValue,
         OutputKind::PythonStdout => ThemeToken::PythonStdout,
         OutputKind::PythonStderr => ThemeToken::PythonStderr,
+        OutputKind::PythonWarning => ThemeToken::PythonWarning,
         OutputKind::PythonTraceback => ThemeToken::PythonTraceback,
         OutputKind::AssistantText => ThemeToken::AssistantText,
         OutputKind::AssistantWaiting => ThemeToken::AssistantWaiting,
@@ -324,12 +1010,13 @@ fn output_token_for(kind: OutputKind) -> ThemeToken {
 #[cfg(test)]
 mod tests {
     use super::{
-        AssistantStepEvent, AssistantTurnState, OutputKind, Timeline, output_token_for,
-        split_output_lines,
+        AssistantStepEvent, AssistantTurnState, LineClick, OutputKind, Timeline, TimelineSnapshot,
+        TranscriptFormat, output_token_for, split_output_lines,
     };
     use crate::cli::theme::Theme;
-    use crate::config::ThemeToken;
+    use crate::config::{ThemeConfig, ThemePreset, ThemeToken};
     use crate::llm::provider::LlmTokenUsageTotals;
+    use std::time::Duration;
 
     fn text_lines(lines: Vec<ratatui::text::Line<'static>>) -> Vec<String> {
         lines.into_iter().map(|line| line.to_string()).collect()
@@ -349,15 +1036,76 @@ mod tests {
                 text: "<- Inspection complete: int".to_string(),
             },
         ];
+        turn.steps_expanded = true;
         turn.state = AssistantTurnState::CompletedText("x is an int".to_string());
         turn.token_usage = Some(LlmTokenUsageTotals {
             input_tokens: 10,
             output_tokens: 5,
             total_tokens: 15,
+            unreported_calls: 0,
         });
         timeline
     }
 
+    #[test]
+    fn saved_snapshot_restores_an_equal_timeline() {
+        let theme = Theme::from_config(false, &ThemeConfig::default());
+        let mut timeline = completed_turn_fixture().with_timestamps(true);
+        timeline.push_user_input_python("x = 1\nx");
+        timeline.push_output(OutputKind::PythonValue, "1");
+        timeline.push_user_input_command("/themes");
+        timeline.push_theme_preview(ThemePreset::HighContrast, false);
+        timeline.push_output(OutputKind::SystemError, "boom\nagain");
+
+        let json = serde_json::to_string(&timeline.snapshot()).expect("serialize");
+        let snapshot: TimelineSnapshot = serde_json::from_str(&json).expect("deserialize");
+        let mut restored = Timeline::new().with_timestamps(true);
+        restored.push_output(OutputKind::SystemInfo, "replaced on restore");
+        restored.restore(snapshot);
+
+        assert_eq!(
+            serde_json::to_string(&restored.snapshot()).expect("serialize again"),
+            json
+        );
+        assert_eq!(
+            text_lines(restored.render_lines(&theme, true)),
+            text_lines(timeline.render_lines(&theme, true))
+        );
+        assert_eq!(restored.bytes, timeline.bytes);
+        let turn = restored.last_assistant_turn().expect("restored turn");
+        assert_eq!(
+            turn.token_usage.as_ref().map(|usage| usage.total_tokens),
+            Some(15)
+        );
+    }
+
+    #[test]
+    fn oldest_entries_are_trimmed_past_the_byte_budget() {
+        let theme = Theme::from_config(false, &ThemeConfig::default());
+        let mut timeline = Timeline::new().with_max_bytes(250);
+        let idx = timeline.push_assistant_turn("q".to_string());
+        timeline.push_output(OutputKind::PythonStdout, &"a".repeat(100));
+        timeline.push_output(OutputKind::PythonStdout, &"b".repeat(100));
+        assert!(timeline.assistant_turn_mut(idx).is_some());
+        assert_eq!(text_lines(timeline.render_lines(&theme, true)).len(), 6);
+
+        timeline.push_output(OutputKind::PythonStdout, &"c".repeat(100));
+        assert!(timeline.assistant_turn_mut(idx).is_none());
+        assert_eq!(
+            text_lines(timeline.render_lines(&theme, true)),
+            vec!["b".repeat(100), "c".repeat(100)]
+        );
+
+        timeline.push_output(OutputKind::PythonStdout, &"d".repeat(1000));
+        assert_eq!(
+            text_lines(timeline.render_lines(&theme, true)),
+            vec!["d".repeat(1000)]
+        );
+
+        let idx = timeline.push_assistant_turn("next".to_string());
+        assert!(timeline.assistant_turn_mut(idx).is_some());
+    }
+
     #[test]
     fn split_lines_works() {
         assert_eq!(split_output_lines("a\nb\n"), vec!["a", "b"]);
@@ -382,6 +1130,104 @@ mod tests {
         assert!(lines.is_empty());
     }
 
+    #[test]
+    fn timestamps_prefix_only_the_first_line_of_each_entry() {
+        let mut timeline = completed_turn_fixture().with_timestamps(true);
+        timeline.push_output(OutputKind::PythonStdout, "first\nsecond");
+
+        let lines = text_lines(timeline.render_lines(&Theme::new(false), true));
+        let is_stamped = |line: &str, rest: &str| {
+            let (time, tail) = line.split_at(9);
+            time.len() == 9
+                && time.chars().enumerate().all(|(i, ch)| match i {
+                    2 | 5 => ch == ':',
+                    8 => ch == ' ',
+                    _ => ch.is_ascii_digit(),
+                })
+                && tail == rest
+        };
+        assert!(is_stamped(&lines[0], "ai> inspect x"), "{lines:?}");
+        assert!(is_stamped(&lines[lines.len() - 2], "first"), "{lines:?}");
+        assert_eq!(lines[lines.len() - 1], "         second");
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2], "           Thinking...");
+        assert!(
+            lines[1..lines.len() - 2]
+                .iter()
+                .all(|line| line.is_empty() || line.starts_with("         ")),
+            "{lines:?}"
+        );
+
+        let plain = text_lines(completed_turn_fixture().render_lines(&Theme::new(false), true));
+        assert_eq!(plain[0], "ai> inspect x");
+    }
+
+    #[test]
+    fn highlighting_splits_python_input_and_values_into_token_spans() {
+        let mut timeline = Timeline::new();
+        timeline.push_user_input_python("s = '''one\ntwo''' # note");
+        timeline.push_output(OutputKind::PythonValue, "[1, 'a']");
+        let theme = Theme::from_config(
+            true,
+            &ThemeConfig {
+                highlight: true,
+                ..ThemeConfig::default()
+            },
+        );
+
+        let lines = timeline.render_lines(&theme, true);
+        let styled = |line: usize| {
+            lines[line]
+                .spans
+                .iter()
+                .map(|span| (span.content.to_string(), span.style))
+                .collect::<Vec<_>>()
+        };
+        let string_style = theme.style(ThemeToken::PythonString);
+        assert_eq!(styled(0)[2], ("'''one".to_string(), string_style));
+        assert_eq!(styled(1)[1], ("two'''".to_string(), string_style));
+        assert_eq!(
+            styled(1)[3],
+            ("# note".to_string(), theme.style(ThemeToken::PythonComment))
+        );
+        assert_eq!(
+            styled(2)[1],
+            ("1".to_string(), theme.style(ThemeToken::PythonNumber))
+        );
+
+        let plain = timeline.render_lines(&Theme::new(true), true);
+        assert_eq!(plain[0].spans.len(), 2);
+        assert_eq!(text_lines(plain), text_lines(lines));
+    }
+
+    #[test]
+    fn traceback_headers_carets_and_exception_lines_get_their_own_styles() {
+        let mut timeline = Timeline::new();
+        timeline.push_output(
+            OutputKind::PythonTraceback,
+            "Traceback (most recent call last):\n  File \"<input>\", line 1, in <module>\n    1 / 0\n    ~~^~~\nZeroDivisionError: division by zero",
+        );
+        let theme = Theme::new(true);
+
+        let lines = timeline.render_lines(&theme, true);
+        let styles = lines
+            .iter()
+            .map(|line| line.spans[0].style)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            styles,
+            [
+                ThemeToken::PythonTraceback,
+                ThemeToken::TracebackLocation,
+                ThemeToken::PythonTraceback,
+                ThemeToken::TracebackCaret,
+                ThemeToken::TracebackException,
+            ]
+            .map(|token| theme.style(token))
+        );
+        assert_eq!(text_lines(lines)[4], "ZeroDivisionError: division by zero");
+    }
+
     #[test]
     fn render_assistant_turn_hides_steps_when_toggle_off() {
         let lines = text_lines(completed_turn_fixture().render_lines(&Theme::new(false), false));
@@ -422,6 +1268,104 @@ mod tests {
         );
     }
 
+    #[test]
+    fn older_steps_collapse_past_max_steps_shown() {
+        let mut timeline = Timeline::new().with_max_steps_shown(2);
+        let idx = timeline.push_assistant_turn("inspect everything".to_string());
+        let turn = timeline.assistant_turn_mut(idx).expect("assistant turn");
+        for step in 1..=5 {
+            turn.events.push(AssistantStepEvent::ToolRequest {
+                text: format!("-> Inspecting: x{step}"),
+            });
+            turn.events.push(AssistantStepEvent::ToolResult {
+                text: format!("<- Inspection complete: x{step}"),
+            });
+        }
+        turn.steps_expanded = true;
+        turn.state = AssistantTurnState::CompletedText("done".to_string());
+
+        let lines = text_lines(timeline.render_lines(&Theme::new(false), true));
+        let steps_start = lines
+            .iter()
+            .position(|line| line == "  Thinking...")
+            .expect("thinking block")
+            + 1;
+        assert_eq!(
+            lines[steps_start..steps_start + 5],
+            [
+                "  … 3 earlier steps …",
+                "  -> Inspecting: x4",
+                "  <- Inspection complete: x4",
+                "  -> Inspecting: x5",
+                "  <- Inspection complete: x5",
+            ]
+        );
+        assert!(!lines.iter().any(|line| line.contains("x3")));
+        assert_eq!(
+            timeline.last_assistant_turn().map(|turn| turn.events.len()),
+            Some(10)
+        );
+    }
+
+    #[test]
+    fn completed_turns_collapse_steps_until_toggled() {
+        // The fixture turn starts expanded; toggling collapses it and selects it.
+        let mut timeline = completed_turn_fixture();
+        let first = 0;
+        timeline.toggle_turn_steps(first);
+        timeline.push_output(OutputKind::SystemInfo, "between");
+        let second = timeline.push_assistant_turn("inspect y".to_string());
+        let turn = timeline.assistant_turn_mut(second).expect("second turn");
+        turn.events = vec![
+            AssistantStepEvent::ToolRequest {
+                text: "-> Inspecting: y".to_string(),
+            },
+            AssistantStepEvent::ToolResult {
+                text: "<- Inspection complete: str".to_string(),
+            },
+        ];
+        turn.state = AssistantTurnState::CompletedText("y is a str".to_string());
+        let theme = Theme::new(false);
+
+        let lines = text_lines(timeline.render_lines(&theme, true));
+        assert_eq!(
+            lines.iter().filter(|line| *line == "  1 tool call").count(),
+            2
+        );
+        assert!(
+            !lines
+                .iter()
+                .any(|line| line.starts_with("  -> Inspecting:"))
+        );
+
+        timeline.select_adjacent_turn(false);
+        timeline.toggle_selected_turn_steps();
+        let lines = text_lines(timeline.render_lines(&theme, true));
+        assert!(lines.iter().any(|line| line == "  -> Inspecting: y"));
+        assert!(!lines.iter().any(|line| line == "  -> Inspecting: x"));
+
+        let first_turn_line = lines
+            .iter()
+            .position(|line| line == "ai> inspect x")
+            .expect("first turn");
+        assert_eq!(
+            timeline.click_target(&theme, true, 80, first_turn_line),
+            Some(LineClick::ToggleTurnSteps(first))
+        );
+        assert_eq!(
+            timeline.click_target(&theme, true, 80, first_turn_line + 1),
+            None
+        );
+        let between = lines.iter().position(|line| line == "between");
+        assert_eq!(
+            timeline.click_target(&theme, true, 80, between.expect("between")),
+            Some(LineClick::Copy("between".to_string()))
+        );
+
+        let transcript = timeline.transcript(&theme, true, TranscriptFormat::PlainText);
+        assert!(transcript.contains("  -> Inspecting: x"));
+    }
+
     #[test]
     fn toggle_is_retroactive_for_completed_turn() {
         let timeline = completed_turn_fixture();
@@ -479,6 +1423,80 @@ mod tests {
         );
     }
 
+    #[test]
+    fn inflight_turn_hides_thinking_block_until_delay_elapses() {
+        let mut timeline = Timeline::with_thinking_delay(Duration::from_millis(500));
+        let idx = timeline.push_assistant_turn("inspect y".to_string());
+        let turn = timeline
+            .assistant_turn_mut(idx)
+            .expect("assistant turn index should exist");
+        turn.events = vec![AssistantStepEvent::ToolRequest {
+            text: "-> Inspecting: y".to_string(),
+        }];
+        let started_at = turn.started_at;
+
+        let quick = text_lines(timeline.render_lines_at(
+            &Theme::new(false),
+            true,
+            started_at + Duration::from_millis(100),
+        ));
+        assert!(quick.iter().any(|line| line == "ai> inspect y"));
+        assert!(!quick.iter().any(|line| line == "  Thinking..."));
+        assert!(
+            !quick
+                .iter()
+                .any(|line| line.starts_with("  -> Inspecting:"))
+        );
+
+        let slow = text_lines(timeline.render_lines_at(
+            &Theme::new(false),
+            true,
+            started_at + Duration::from_millis(600),
+        ));
+        assert!(slow.iter().any(|line| line == "  Thinking..."));
+        assert!(slow.iter().any(|line| line.starts_with("  -> Inspecting:")));
+    }
+
+    #[test]
+    fn inflight_turn_renders_streamed_text_until_completed() {
+        let mut timeline = Timeline::new();
+        let idx = timeline.push_assistant_turn("explain x".to_string());
+        let turn = timeline
+            .assistant_turn_mut(idx)
+            .expect("assistant turn index should exist");
+        turn.streamed_text.push_str("x is ");
+        turn.streamed_text.push_str("an int\nso far");
+
+        let partial = text_lines(timeline.render_lines(&Theme::new(false), false));
+        assert_eq!(partial, vec!["ai> explain x", "x is an int", "so far"]);
+
+        let turn = timeline
+            .assistant_turn_mut(idx)
+            .expect("assistant turn index should exist");
+        turn.state = AssistantTurnState::CompletedText("x is an int".to_string());
+        let done = text_lines(timeline.render_lines(&Theme::new(false), false));
+        assert_eq!(done, vec!["ai> explain x", "x is an int"]);
+    }
+
+    #[test]
+    fn completed_turn_shows_thinking_block_regardless_of_delay() {
+        let mut timeline = Timeline::with_thinking_delay(Duration::from_secs(60));
+        let idx = timeline.push_assistant_turn("inspect x".to_string());
+        let turn = timeline
+            .assistant_turn_mut(idx)
+            .expect("assistant turn index should exist");
+        turn.events = vec![AssistantStepEvent::ToolRequest {
+            text: "-> Inspecting: x".to_string(),
+        }];
+        turn.steps_expanded = true;
+        turn.state = AssistantTurnState::CompletedText("x is an int".to_string());
+        let started_at = turn.started_at;
+
+        let lines = text_lines(timeline.render_lines_at(&Theme::new(false), true, started_at));
+        assert!(lines.iter().any(|line| line == "  Thinking..."));
+        assert!(lines.iter().any(|line| line == "x is an int"));
+    }
+
     #[test]
     fn assistant_error_renders_message() {
         let mut timeline = Timeline::new();
@@ -492,6 +1510,7 @@ mod tests {
             input_tokens: 2,
             output_tokens: 1,
             total_tokens: 3,
+            unreported_calls: 0,
         });
 
         let lines = text_lines(timeline.render_lines(&Theme::new(false), false));
@@ -540,6 +1559,28 @@ mod tests {
         assert_eq!(lines[tokens_idx + 1], "");
     }
 
+    #[test]
+    fn multi_line_tool_result_keeps_column_alignment() {
+        let mut timeline = Timeline::new();
+        let idx = timeline.push_assistant_turn("show df".to_string());
+        let turn = timeline
+            .assistant_turn_mut(idx)
+            .expect("assistant turn index should exist");
+        turn.events.push(AssistantStepEvent::ToolResult {
+            text: "<- Evaluated:\n   a   b\n0  1  10".to_string(),
+        });
+        turn.state = AssistantTurnState::CompletedText("done".to_string());
+
+        let lines = text_lines(timeline.render_lines(&Theme::new(false), true));
+        let start = lines
+            .iter()
+            .position(|line| line.ends_with("<- Evaluated:"))
+            .expect("tool result");
+        let padding = lines[start].trim_end_matches("<- Evaluated:");
+        assert_eq!(lines[start + 1], format!("{padding}   a   b"));
+        assert_eq!(lines[start + 2], format!("{padding}0  1  10"));
+    }
+
     #[test]
     fn mixed_entries_render_in_order() {
         let mut timeline = Timeline::new();
@@ -559,6 +1600,7 @@ mod tests {
         turn.events.push(AssistantStepEvent::ToolResult {
             text: "<- Inspection complete: int".to_string(),
         });
+        turn.steps_expanded = true;
         turn.state = AssistantTurnState::CompletedText("x is an int".to_string());
 
         let lines = text_lines(timeline.render_lines(&Theme::new(false), true));
@@ -598,6 +1640,40 @@ mod tests {
         assert!(lines.iter().any(|line| line == "x is an int"));
     }
 
+    #[test]
+    fn transcript_plain_text_matches_rendered_lines() {
+        let mut timeline = completed_turn_fixture();
+        timeline.push_user_input_python("x = 1");
+
+        let text = timeline.transcript(&Theme::new(false), false, TranscriptFormat::PlainText);
+        assert_eq!(text, "ai> inspect x\nx is an int\npy> x = 1\n");
+    }
+
+    #[test]
+    fn transcript_markdown_fences_python_and_formats_assistant_turns() {
+        let mut timeline = Timeline::new();
+        timeline.push_user_input_python("x = 1");
+        timeline.push_output(OutputKind::PythonValue, "1");
+        let idx = timeline.push_assistant_turn("inspect x".to_string());
+        let turn = timeline
+            .assistant_turn_mut(idx)
+            .expect("assistant turn index should exist");
+        turn.events.push(AssistantStepEvent::ToolRequest {
+            text: "-> Inspecting: x".to_string(),
+        });
+        turn.state = AssistantTurnState::CompletedText("x is an **int**".to_string());
+
+        let with_steps = timeline.transcript(&Theme::new(false), true, TranscriptFormat::Markdown);
+        assert_eq!(
+            with_steps,
+            "```text\npy> x = 1\n1\n```\n\n**ai>** inspect x\n\n- `-> Inspecting: x`\n\nx is an **int**\n"
+        );
+
+        let without_steps =
+            timeline.transcript(&Theme::new(false), false, TranscriptFormat::Markdown);
+        assert!(!without_steps.contains("Inspecting"));
+    }
+
     #[test]
     fn multiline_entries_split_and_preserve_order() {
         let mut timeline = Timeline::new();
@@ -1,32 +1,149 @@
 use anyhow::{Result, anyhow, bail};
-use serde::Deserialize;
-use std::collections::HashMap;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::fs;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 pub const DEFAULT_GEMINI_MODEL: &str = "gemini-3-flash-preview";
 pub const DEFAULT_GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+pub const DEFAULT_GEMINI_MAX_RETRIES: u32 = 2;
+pub const DEFAULT_GEMINI_BASE_BACKOFF_MS: u64 = 500;
+pub const DEFAULT_GEMINI_REQUEST_TIMEOUT_MS: u64 = 30_000;
+pub const DEFAULT_GEMINI_MAX_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+pub const DEFAULT_THINKING_DELAY_MS: u64 = 0;
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+pub const DEFAULT_INSPECT_TIMEOUT_MS: u64 = 1000;
+pub const DEFAULT_MAX_INPUT_BYTES: usize = 1024 * 1024;
+pub const DEFAULT_REPL_REPR_MAX_CHARS: usize = 10_000;
+pub const DEFAULT_INVALID_RESPONSE_RETRIES: usize = 1;
+pub const DEFAULT_TRACE_MAX_BYTES: u64 = 50 * 1024 * 1024;
+pub const DEFAULT_TIMELINE_MAX_BYTES: usize = 16 * 1024 * 1024;
+const MAX_INVALID_RESPONSE_RETRIES: usize = 5;
+const MAX_MEMORY_TURNS: usize = 20;
 
 const CONFIG_DIR_NAME: &str = "pychat.ai";
 const CONFIG_FILE_NAME: &str = "config.toml";
-
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Per-project config, found in the working directory or one of its parents.
+const PROJECT_CONFIG_FILE_NAME: &str = ".pychat.ai.toml";
+/// Top-level keys a project config may set. A cloned repository must not be able to
+/// redirect the API key (`gemini_base_url`, `gemini_auth`), supply its own key, or let
+/// the assistant change the session. `startup_file` runs Python at launch, so it is only
+/// accepted from projects the user config lists in `trusted_projects`.
+const PROJECT_CONFIG_KEYS: &[&str] = &[
+    "gemini_model",
+    "gemini_max_retries",
+    "gemini_base_backoff_ms",
+    "gemini_request_timeout_ms",
+    "gemini_max_response_bytes",
+    "thinking_delay_ms",
+    "history_size",
+    "history_recall",
+    "edit_mode",
+    "repl_repr_max_chars",
+    "auto_time_threshold_ms",
+    "auto_retry_turn",
+    "allow_eval",
+    "clipboard_osc52",
+    "timeline_timestamps",
+    "timeline_max_bytes",
+    "max_steps_shown",
+    "auto_page_lines",
+    "assistant_mode",
+    "trace_format",
+    "trace_max_bytes",
+    "session_id_format",
+    "python",
+    "agent",
+    "assistant",
+    "trace",
+    "theme",
+    "keys",
+];
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct AppConfig {
     pub config_path: PathBuf,
     pub config_is_explicit: bool,
+    /// `.pychat.ai.toml` merged over the user config, when one was found.
+    pub project_config_path: Option<PathBuf>,
+    /// Project directories whose `.pychat.ai.toml` may set `startup_file`.
+    pub trusted_projects: Vec<PathBuf>,
     pub gemini_api_key: Option<String>,
     pub gemini_model: String,
     pub gemini_base_url: String,
-    pub startup_file: Option<PathBuf>,
+    pub gemini_max_retries: u32,
+    pub gemini_base_backoff_ms: u64,
+    /// Gemini HTTP requests without a complete response by then fail; 0 disables it.
+    pub gemini_request_timeout_ms: u64,
+    /// Gemini responses with a longer body are aborted; 0 disables the limit.
+    pub gemini_max_response_bytes: usize,
+    pub gemini_auth: GeminiAuth,
+    /// Startup scripts, run in order before the REPL starts.
+    pub startup_files: Vec<PathBuf>,
+    pub thinking_delay_ms: u64,
+    pub history_size: usize,
+    pub history_recall: HistoryRecall,
+    pub repl_repr_max_chars: usize,
+    /// Python input running at least this long reports its duration; 0 disables it.
+    pub auto_time_threshold_ms: u64,
+    pub inspect_timeout_ms: u64,
+    /// Python input larger than this is rejected before compiling; 0 disables the limit.
+    pub max_input_bytes: usize,
+    /// Shows Python stdout in the timeline while the code is still running.
+    pub stream_output: bool,
+    /// `/inspect` prints single-line JSON instead of pretty-printing it.
+    pub inspect_compact: bool,
+    /// Python values are shown after the expression that produced them (`x + 1 => 4`).
+    pub echo_expr: bool,
+    /// Expression input ending in `;` is evaluated without showing its value.
+    pub semicolon_suppresses_output: bool,
+    /// Bare function and class names show their signature instead of their repr.
+    pub callable_summaries: bool,
+    /// Repair prompts sent after invalid or empty model responses before giving up.
+    pub invalid_response_retries: usize,
+    /// Earlier questions and answers sent along with each new question; 0 disables it.
+    pub memory_turns: usize,
+    /// Sampling temperature sent to the model; `None` keeps the model's default.
+    pub temperature: Option<f64>,
+    pub max_output_tokens: Option<u32>,
+    pub top_p: Option<f64>,
+    pub auto_retry_turn: bool,
+    pub allow_eval: bool,
+    /// Offers the assistant the `apply_code` tool, which can change session globals.
+    pub allow_apply: bool,
+    pub clipboard_osc52: bool,
+    /// Prefixes timeline entries with the UTC time they were added.
+    pub timeline_timestamps: bool,
+    /// Text budget for the timeline; the oldest entries are dropped past it (`0` disables).
+    pub timeline_max_bytes: usize,
+    /// Tool steps shown per assistant turn; older ones collapse into one line (`0` shows all).
+    pub max_steps_shown: usize,
+    /// Results longer than this many lines open in the pager instead of the timeline (`0` disables).
+    pub auto_page_lines: usize,
+    pub assistant_mode: AssistantMode,
+    pub trace_format: TraceFormat,
+    /// Extra header and query parameter names whose values are masked in HTTP traces.
+    pub trace_redact: Vec<String>,
+    /// Trace size after which it continues in a new numbered file; 0 means unlimited.
+    pub trace_max_bytes: u64,
+    pub session_id_format: SessionIdFormat,
     pub theme: ThemeConfig,
+    pub keys: KeyBindings,
+    pub edit_mode: EditMode,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ThemeConfig {
     pub preset: ThemePreset,
     pub styles: HashMap<ThemeToken, StyleOverride>,
+    /// Highlights keywords, strings, numbers and comments in Python input and values.
+    pub highlight: bool,
 }
 
 impl Default for ThemeConfig {
@@ -34,17 +151,33 @@ impl Default for ThemeConfig {
         Self {
             preset: ThemePreset::Default,
             styles: HashMap::new(),
+            highlight: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ThemePreset {
     Default,
     Light,
     HighContrast,
 }
 
+impl ThemePreset {
+    pub const fn all() -> [Self; 3] {
+        [Self::Default, Self::Light, Self::HighContrast]
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Light => "light",
+            Self::HighContrast => "high-contrast",
+        }
+    }
+}
+
 impl FromStr for ThemePreset {
     type Err = String;
 
@@ -58,17 +191,180 @@ impl FromStr for ThemePreset {
     }
 }
 
+/// How assistant mode answers: through the tool-calling agent loop, or with a single
+/// tool-less request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AssistantMode {
+    #[default]
+    Agent,
+    Chat,
+}
+
+impl FromStr for AssistantMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "agent" => Ok(Self::Agent),
+            "chat" => Ok(Self::Chat),
+            _ => Err(format!(
+                "unknown assistant mode '{value}' (expected agent or chat)"
+            )),
+        }
+    }
+}
+
+/// Whether a multi-line submission is recalled from history as one block or line by
+/// line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistoryRecall {
+    #[default]
+    Block,
+    Line,
+}
+
+impl FromStr for HistoryRecall {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "block" => Ok(Self::Block),
+            "line" => Ok(Self::Line),
+            _ => Err(format!(
+                "unknown history recall '{value}' (expected block or line)"
+            )),
+        }
+    }
+}
+
+/// Key handling for the input box: the default Emacs-style editing, or vi-style Normal
+/// and Insert modes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditMode {
+    #[default]
+    Emacs,
+    Vi,
+}
+
+impl FromStr for EditMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "emacs" => Ok(Self::Emacs),
+            "vi" => Ok(Self::Vi),
+            _ => Err(format!(
+                "unknown edit mode '{value}' (expected emacs or vi)"
+            )),
+        }
+    }
+}
+
+/// How the API key is sent to the Gemini endpoint: as the `key` query parameter, as an
+/// `Authorization: Bearer` header, or as the value of a named header, for gateways
+/// and proxies that expect one of the latter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GeminiAuth {
+    #[default]
+    QueryKey,
+    Bearer,
+    Header(String),
+}
+
+impl FromStr for GeminiAuth {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "query_key" => Ok(Self::QueryKey),
+            "bearer" => Ok(Self::Bearer),
+            _ => match value.strip_prefix("header:") {
+                Some(name)
+                    if !name.is_empty()
+                        && name
+                            .chars()
+                            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') =>
+                {
+                    Ok(Self::Header(name.to_string()))
+                }
+                _ => Err(format!(
+                    "unknown auth mode '{value}' (expected query_key, bearer or header:<name>)"
+                )),
+            },
+        }
+    }
+}
+
+/// Layout of the session trace file: timestamped text lines, or one JSON object per
+/// record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    #[default]
+    Text,
+    Jsonl,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Self::Text),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(format!(
+                "unknown trace format '{value}' (expected text or jsonl)"
+            )),
+        }
+    }
+}
+
+/// Shape of the session id shown in `/trace` and used in the trace file name:
+/// `<hex-millis>-<hex-pid>`, a random UUID, or a UTC timestamp with the pid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionIdFormat {
+    #[default]
+    Hex,
+    Uuid,
+    Timestamp,
+}
+
+impl FromStr for SessionIdFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "hex" => Ok(Self::Hex),
+            "uuid" => Ok(Self::Uuid),
+            "timestamp" => Ok(Self::Timestamp),
+            _ => Err(format!(
+                "unknown session id format '{value}' (expected hex, uuid or timestamp)"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ThemeToken {
     PythonPrompt,
     AssistantPrompt,
     CommandPrompt,
+    ContinuationPrompt,
+    InvalidInputPrompt,
     UserInputPython,
     UserInputAssistant,
     PythonValue,
     PythonStdout,
     PythonStderr,
+    PythonWarning,
     PythonTraceback,
+    TracebackLocation,
+    TracebackCaret,
+    TracebackException,
+    PythonKeyword,
+    PythonString,
+    PythonNumber,
+    PythonComment,
+    Timestamp,
     AssistantText,
     AssistantWaiting,
     AssistantProgressRequest,
@@ -93,12 +389,23 @@ impl FromStr for ThemeToken {
             "python_prompt" => Ok(Self::PythonPrompt),
             "assistant_prompt" => Ok(Self::AssistantPrompt),
             "command_prompt" => Ok(Self::CommandPrompt),
+            "continuation_prompt" => Ok(Self::ContinuationPrompt),
+            "invalid_input_prompt" => Ok(Self::InvalidInputPrompt),
             "user_input_python" => Ok(Self::UserInputPython),
             "user_input_assistant" => Ok(Self::UserInputAssistant),
             "python_value" => Ok(Self::PythonValue),
             "python_stdout" => Ok(Self::PythonStdout),
             "python_stderr" => Ok(Self::PythonStderr),
+            "python_warning" => Ok(Self::PythonWarning),
             "python_traceback" => Ok(Self::PythonTraceback),
+            "traceback_location" => Ok(Self::TracebackLocation),
+            "traceback_caret" => Ok(Self::TracebackCaret),
+            "traceback_exception" => Ok(Self::TracebackException),
+            "python_keyword" => Ok(Self::PythonKeyword),
+            "python_string" => Ok(Self::PythonString),
+            "python_number" => Ok(Self::PythonNumber),
+            "python_comment" => Ok(Self::PythonComment),
+            "timestamp" => Ok(Self::Timestamp),
             "assistant_text" => Ok(Self::AssistantText),
             "assistant_waiting" => Ok(Self::AssistantWaiting),
             "assistant_progress_request" => Ok(Self::AssistantProgressRequest),
@@ -119,17 +426,28 @@ impl FromStr for ThemeToken {
 }
 
 impl ThemeToken {
-    pub const fn all() -> [Self; 23] {
+    pub const fn all() -> [Self; 34] {
         [
             Self::PythonPrompt,
             Self::AssistantPrompt,
             Self::CommandPrompt,
+            Self::ContinuationPrompt,
+            Self::InvalidInputPrompt,
             Self::UserInputPython,
             Self::UserInputAssistant,
             Self::PythonValue,
             Self::PythonStdout,
             Self::PythonStderr,
+            Self::PythonWarning,
             Self::PythonTraceback,
+            Self::TracebackLocation,
+            Self::TracebackCaret,
+            Self::TracebackException,
+            Self::PythonKeyword,
+            Self::PythonString,
+            Self::PythonNumber,
+            Self::PythonComment,
+            Self::Timestamp,
             Self::AssistantText,
             Self::AssistantWaiting,
             Self::AssistantProgressRequest,
@@ -214,20 +532,310 @@ impl FromStr for ThemeModifier {
     }
 }
 
+/// Actions that can be bound to keys in the `[keys]` section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    ToggleMode,
+    Newline,
+    ToggleSteps,
+    SelectPreviousTurn,
+    SelectNextTurn,
+    ToggleTurnSteps,
+    Quit,
+}
+
+impl KeyAction {
+    pub const fn all() -> [Self; 7] {
+        [
+            Self::ToggleMode,
+            Self::Newline,
+            Self::ToggleSteps,
+            Self::SelectPreviousTurn,
+            Self::SelectNextTurn,
+            Self::ToggleTurnSteps,
+            Self::Quit,
+        ]
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::ToggleMode => "toggle_mode",
+            Self::Newline => "newline",
+            Self::ToggleSteps => "toggle_steps",
+            Self::SelectPreviousTurn => "select_previous_turn",
+            Self::SelectNextTurn => "select_next_turn",
+            Self::ToggleTurnSteps => "toggle_turn_steps",
+            Self::Quit => "quit",
+        }
+    }
+
+    const fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Self::ToggleMode => &["tab", "backtab"],
+            Self::Newline => &["shift-enter", "ctrl-enter", "ctrl-j"],
+            Self::ToggleSteps => &["ctrl-t"],
+            Self::SelectPreviousTurn => &["alt-up"],
+            Self::SelectNextTurn => &["alt-down"],
+            Self::ToggleTurnSteps => &["ctrl-o"],
+            Self::Quit => &["ctrl-c", "ctrl-d"],
+        }
+    }
+}
+
+impl FromStr for KeyAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|action| action.name() == value)
+            .ok_or_else(|| format!("unknown action '{value}'"))
+    }
+}
+
+/// A key with modifiers, written like `ctrl-t`, `alt-enter` or `f2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyCombo {
+    /// Matches `key` when it has exactly this combo's modifiers. SHIFT is ignored for
+    /// characters and back-tab, where terminals fold it into the key itself.
+    pub fn matches(&self, key: KeyEvent) -> bool {
+        let ignored = match self.code {
+            KeyCode::Char(_) | KeyCode::BackTab => KeyModifiers::SHIFT,
+            _ => KeyModifiers::NONE,
+        };
+        key.code == self.code && key.modifiers - ignored == self.modifiers - ignored
+    }
+
+    /// Whether this combo would type a character, so binding it would make that
+    /// character impossible to enter.
+    fn is_printable(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && (self.modifiers - KeyModifiers::SHIFT).is_empty()
+    }
+}
+
+impl std::fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl-"),
+            (KeyModifiers::ALT, "alt-"),
+            (KeyModifiers::SHIFT, "shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Tab => f.write_str("tab"),
+            KeyCode::BackTab => f.write_str("backtab"),
+            KeyCode::Enter => f.write_str("enter"),
+            KeyCode::Esc => f.write_str("esc"),
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Backspace => f.write_str("backspace"),
+            KeyCode::Up => f.write_str("up"),
+            KeyCode::Down => f.write_str("down"),
+            KeyCode::Char(ch) => write!(f, "{ch}"),
+            KeyCode::F(number) => write!(f, "f{number}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let lowered = value.trim().to_ascii_lowercase();
+        let mut parts = lowered.split(['-', '+']).collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or_default();
+        let mut modifiers = KeyModifiers::NONE;
+        for part in parts {
+            modifiers |= match part {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{part}' in '{value}'")),
+            };
+        }
+
+        let code = match key {
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "backspace" => KeyCode::Backspace,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => KeyCode::Char(ch),
+                    _ => key
+                        .strip_prefix('f')
+                        .and_then(|number| number.parse::<u8>().ok())
+                        .filter(|number| (1..=12).contains(number))
+                        .map(KeyCode::F)
+                        .ok_or_else(|| format!("unknown key '{key}' in '{value}'"))?,
+                }
+            }
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+/// The key combos bound to each [`KeyAction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    bindings: Vec<(KeyAction, KeyCombo)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = KeyAction::all()
+            .into_iter()
+            .flat_map(|action| {
+                action.default_keys().iter().map(move |key| {
+                    let combo = KeyCombo::from_str(key).expect("default key combos parse");
+                    (action, combo)
+                })
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl KeyBindings {
+    pub fn action_for(&self, key: KeyEvent) -> Option<KeyAction> {
+        self.bindings
+            .iter()
+            .find(|(_, combo)| combo.matches(key))
+            .map(|(action, _)| *action)
+    }
+
+    /// Replaces every combo bound to `action` with `combos`.
+    pub fn rebind(&mut self, action: KeyAction, combos: Vec<KeyCombo>) {
+        self.bindings.retain(|(bound, _)| *bound != action);
+        self.bindings
+            .extend(combos.into_iter().map(|combo| (action, combo)));
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct RawFileConfig {
     gemini_api_key: Option<String>,
     gemini_model: Option<String>,
     gemini_base_url: Option<String>,
-    startup_file: Option<String>,
+    gemini_max_retries: Option<u32>,
+    gemini_base_backoff_ms: Option<u64>,
+    gemini_request_timeout_ms: Option<u64>,
+    gemini_max_response_bytes: Option<usize>,
+    gemini_auth: Option<String>,
+    startup_file: Option<RawStartupFiles>,
+    trusted_projects: Option<Vec<String>>,
+    thinking_delay_ms: Option<u64>,
+    history_size: Option<usize>,
+    history_recall: Option<String>,
+    edit_mode: Option<String>,
+    repl_repr_max_chars: Option<usize>,
+    auto_time_threshold_ms: Option<u64>,
+    auto_retry_turn: Option<bool>,
+    allow_eval: Option<bool>,
+    allow_apply: Option<bool>,
+    clipboard_osc52: Option<bool>,
+    timeline_timestamps: Option<bool>,
+    timeline_max_bytes: Option<usize>,
+    max_steps_shown: Option<usize>,
+    auto_page_lines: Option<usize>,
+    assistant_mode: Option<String>,
+    trace_format: Option<String>,
+    trace_max_bytes: Option<u64>,
+    session_id_format: Option<String>,
+    python: Option<RawPythonConfig>,
+    agent: Option<RawAgentConfig>,
+    assistant: Option<RawAssistantConfig>,
+    trace: Option<RawTraceConfig>,
     theme: Option<RawThemeConfig>,
+    keys: Option<BTreeMap<String, RawKeyCombos>>,
+}
+
+/// A `[keys]` entry accepts either a single key combo or a list of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawKeyCombos {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl RawKeyCombos {
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            Self::One(key) => vec![key.as_str()],
+            Self::Many(keys) => keys.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// `startup_file` accepts either a single path or a list of paths.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawStartupFiles {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl RawStartupFiles {
+    fn paths(&self) -> Vec<&str> {
+        match self {
+            Self::One(path) => vec![path.as_str()],
+            Self::Many(paths) => paths.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPythonConfig {
+    inspect_timeout_ms: Option<u64>,
+    max_input_bytes: Option<usize>,
+    stream_output: Option<bool>,
+    inspect_compact: Option<bool>,
+    echo_expr: Option<bool>,
+    semicolon_suppresses_output: Option<bool>,
+    callable_summaries: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAgentConfig {
+    invalid_response_retries: Option<usize>,
+    memory_turns: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAssistantConfig {
+    temperature: Option<f64>,
+    max_output_tokens: Option<u32>,
+    top_p: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTraceConfig {
+    redact: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct RawThemeConfig {
     name: Option<String>,
+    highlight: Option<bool>,
     styles: Option<HashMap<String, RawStyleOverride>>,
 }
 
@@ -244,11 +852,98 @@ impl AppConfig {
         Self::load_with_path(None)
     }
 
+    /// Validates the config the same way startup does, for `--check-config` and
+    /// `/config check`, and summarizes which files passed.
+    pub fn check(config_path_override: Option<&Path>) -> Result<String> {
+        let config = Self::load_with_path(config_path_override)?;
+        let path = config.config_path.display();
+        let report = if config.config_path.is_file() {
+            format!("config ok: {path}")
+        } else {
+            format!("config ok: {path} does not exist, defaults apply")
+        };
+        Ok(match &config.project_config_path {
+            Some(project) => format!("{report}; project config ok: {}", project.display()),
+            None => report,
+        })
+    }
+
+    /// Text printed by `--print-config`: where the config file was looked up, then every
+    /// resolved value with the API key masked.
+    pub fn describe(&self) -> String {
+        let source = if self.config_is_explicit {
+            "from --config"
+        } else {
+            "default location"
+        };
+        let found = if self.config_path.is_file() {
+            ""
+        } else {
+            ", not found"
+        };
+        let project = self
+            .project_config_path
+            .as_ref()
+            .map_or_else(|| "none".to_string(), |path| path.display().to_string());
+        let masked = Self {
+            gemini_api_key: self.gemini_api_key.as_ref().map(|_| "***".to_string()),
+            ..self.clone()
+        };
+        format!(
+            "config file: {} ({source}{found})\nproject config: {project}\n{masked:#?}",
+            self.config_path.display()
+        )
+    }
+
     pub fn load_with_path(config_path_override: Option<&Path>) -> Result<Self> {
         let (config_path, require_config_file) = resolve_config_path(config_path_override)?;
-        let file_config = load_file_config(&config_path, require_config_file)?;
+        let user_config = load_file_config(&config_path, require_config_file)?;
+        let project_config = match config_path_override {
+            Some(_) => None,
+            None => env::current_dir()
+                .ok()
+                .and_then(|cwd| find_project_config(&cwd))
+                .map(|path| {
+                    let trusted = trusted_projects(user_config.as_ref(), &config_path)?;
+                    load_project_config(&path, &trusted).map(|table| (path, table))
+                })
+                .transpose()?,
+        };
         dotenvy::dotenv().ok();
 
+        // The project config is validated on its own first, so a bad value it sets is
+        // reported against the project file rather than the user config.
+        if let Some((project_path, project_table)) = &project_config {
+            Self::from_table(Some(project_table.clone()), project_path, false)?;
+        }
+        let project_config_path = project_config.as_ref().map(|(path, _)| path.clone());
+        let file_config = match (user_config, project_config) {
+            (None, None) => None,
+            (Some(user), None) => Some(user),
+            (None, Some((_, project))) => Some(project),
+            (Some(mut user), Some((_, project))) => {
+                merge_config_tables(&mut user, project);
+                Some(user)
+            }
+        };
+        Ok(Self {
+            project_config_path,
+            ..Self::from_table(file_config, &config_path, require_config_file)?
+        })
+    }
+
+    /// Resolves every value from a config table, with errors attributed to `config_path`.
+    fn from_table(
+        file_config: Option<toml::Table>,
+        config_path: &Path,
+        require_config_file: bool,
+    ) -> Result<Self> {
+        let config_path = config_path.to_path_buf();
+        let file_config = file_config
+            .map(|table| toml::Value::Table(table).try_into::<RawFileConfig>())
+            .transpose()
+            .map_err(|err| anyhow!("Failed to load config {}: {err}", config_path.display()))?;
+
         let file_api_key = file_config
             .as_ref()
             .and_then(|cfg| cfg.gemini_api_key.as_ref())
@@ -261,39 +956,288 @@ impl AppConfig {
             .as_ref()
             .and_then(|cfg| cfg.gemini_base_url.as_ref())
             .and_then(|value| non_empty(value).map(ToOwned::to_owned));
-        let startup_file = resolve_startup_file(
-            file_config
-                .as_ref()
-                .and_then(|cfg| cfg.startup_file.as_deref())
-                .and_then(non_empty),
-            &config_path,
-        )?;
+        let startup_files = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.startup_file.as_ref())
+            .map(RawStartupFiles::paths)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(non_empty)
+            .map(|path| resolve_startup_file(path, &config_path))
+            .collect::<Result<Vec<_>>>()?;
+        let trusted_projects = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.trusted_projects.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from(dir)))
+            .collect();
+
+        let gemini_max_retries = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.gemini_max_retries)
+            .unwrap_or(DEFAULT_GEMINI_MAX_RETRIES);
+        let gemini_base_backoff_ms = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.gemini_base_backoff_ms)
+            .unwrap_or(DEFAULT_GEMINI_BASE_BACKOFF_MS);
+        let gemini_request_timeout_ms = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.gemini_request_timeout_ms)
+            .unwrap_or(DEFAULT_GEMINI_REQUEST_TIMEOUT_MS);
+        let gemini_max_response_bytes = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.gemini_max_response_bytes)
+            .unwrap_or(DEFAULT_GEMINI_MAX_RESPONSE_BYTES);
+        let gemini_auth = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.gemini_auth.as_deref())
+            .map(GeminiAuth::from_str)
+            .transpose()
+            .map_err(|reason| config_error(&config_path, "gemini_auth", &reason))?
+            .unwrap_or_default();
+        let thinking_delay_ms = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.thinking_delay_ms)
+            .unwrap_or(DEFAULT_THINKING_DELAY_MS);
+        let history_size = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.history_size)
+            .unwrap_or(DEFAULT_HISTORY_SIZE);
+        let repl_repr_max_chars = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.repl_repr_max_chars)
+            .unwrap_or(DEFAULT_REPL_REPR_MAX_CHARS);
+        let auto_time_threshold_ms = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.auto_time_threshold_ms)
+            .unwrap_or(0);
+        let inspect_timeout_ms = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.python.as_ref())
+            .and_then(|python| python.inspect_timeout_ms)
+            .unwrap_or(DEFAULT_INSPECT_TIMEOUT_MS);
+        let max_input_bytes = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.python.as_ref())
+            .and_then(|python| python.max_input_bytes)
+            .unwrap_or(DEFAULT_MAX_INPUT_BYTES);
+        let stream_output = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.python.as_ref())
+            .and_then(|python| python.stream_output)
+            .unwrap_or(false);
+        let inspect_compact = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.python.as_ref())
+            .and_then(|python| python.inspect_compact)
+            .unwrap_or(false);
+        let echo_expr = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.python.as_ref())
+            .and_then(|python| python.echo_expr)
+            .unwrap_or(false);
+        let semicolon_suppresses_output = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.python.as_ref())
+            .and_then(|python| python.semicolon_suppresses_output)
+            .unwrap_or(false);
+        let callable_summaries = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.python.as_ref())
+            .and_then(|python| python.callable_summaries)
+            .unwrap_or(false);
+        let invalid_response_retries = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.agent.as_ref())
+            .and_then(|agent| agent.invalid_response_retries)
+            .unwrap_or(DEFAULT_INVALID_RESPONSE_RETRIES);
+        if invalid_response_retries > MAX_INVALID_RESPONSE_RETRIES {
+            return Err(config_error(
+                &config_path,
+                "agent.invalid_response_retries",
+                &format!("must be between 0 and {MAX_INVALID_RESPONSE_RETRIES}"),
+            ));
+        }
+        let memory_turns = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.agent.as_ref())
+            .and_then(|agent| agent.memory_turns)
+            .unwrap_or(0);
+        if memory_turns > MAX_MEMORY_TURNS {
+            return Err(config_error(
+                &config_path,
+                "agent.memory_turns",
+                &format!("must be between 0 and {MAX_MEMORY_TURNS}"),
+            ));
+        }
+        let assistant = file_config.as_ref().and_then(|cfg| cfg.assistant.as_ref());
+        let temperature = assistant.and_then(|assistant| assistant.temperature);
+        if temperature.is_some_and(|value| !(0.0..=2.0).contains(&value)) {
+            return Err(config_error(
+                &config_path,
+                "assistant.temperature",
+                "must be between 0.0 and 2.0",
+            ));
+        }
+        let max_output_tokens = assistant.and_then(|assistant| assistant.max_output_tokens);
+        if max_output_tokens == Some(0) {
+            return Err(config_error(
+                &config_path,
+                "assistant.max_output_tokens",
+                "must be at least 1",
+            ));
+        }
+        let top_p = assistant.and_then(|assistant| assistant.top_p);
+        if top_p.is_some_and(|value| !(0.0..=1.0).contains(&value)) {
+            return Err(config_error(
+                &config_path,
+                "assistant.top_p",
+                "must be between 0.0 and 1.0",
+            ));
+        }
+        let auto_retry_turn = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.auto_retry_turn)
+            .unwrap_or(false);
+        let allow_eval = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.allow_eval)
+            .unwrap_or(true);
+        let allow_apply = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.allow_apply)
+            .unwrap_or(false);
+        let clipboard_osc52 = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.clipboard_osc52)
+            .unwrap_or(false);
+        let timeline_timestamps = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.timeline_timestamps)
+            .unwrap_or(false);
+        let timeline_max_bytes = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.timeline_max_bytes)
+            .unwrap_or(DEFAULT_TIMELINE_MAX_BYTES);
+        let max_steps_shown = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.max_steps_shown)
+            .unwrap_or(0);
+        let auto_page_lines = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.auto_page_lines)
+            .unwrap_or(0);
+        let history_recall = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.history_recall.as_deref())
+            .map(HistoryRecall::from_str)
+            .transpose()
+            .map_err(|reason| config_error(&config_path, "history_recall", &reason))?
+            .unwrap_or_default();
+        let edit_mode = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.edit_mode.as_deref())
+            .map(EditMode::from_str)
+            .transpose()
+            .map_err(|reason| config_error(&config_path, "edit_mode", &reason))?
+            .unwrap_or_default();
+        let assistant_mode = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.assistant_mode.as_deref())
+            .map(AssistantMode::from_str)
+            .transpose()
+            .map_err(|reason| config_error(&config_path, "assistant_mode", &reason))?
+            .unwrap_or_default();
+        let trace_format = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.trace_format.as_deref())
+            .map(TraceFormat::from_str)
+            .transpose()
+            .map_err(|reason| config_error(&config_path, "trace_format", &reason))?
+            .unwrap_or_default();
+        let session_id_format = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.session_id_format.as_deref())
+            .map(SessionIdFormat::from_str)
+            .transpose()
+            .map_err(|reason| config_error(&config_path, "session_id_format", &reason))?
+            .unwrap_or_default();
+        let trace_max_bytes = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.trace_max_bytes)
+            .unwrap_or(DEFAULT_TRACE_MAX_BYTES);
+        let trace_redact = file_config
+            .as_ref()
+            .and_then(|cfg| cfg.trace.as_ref())
+            .and_then(|trace| trace.redact.clone())
+            .unwrap_or_default();
 
         let theme = validate_theme(
             file_config.as_ref().and_then(|cfg| cfg.theme.as_ref()),
             &config_path,
         )?;
 
+        let keys = validate_keys(
+            file_config.as_ref().and_then(|cfg| cfg.keys.as_ref()),
+            &config_path,
+        )?;
+
         Ok(Self {
             config_path: config_path.clone(),
             config_is_explicit: require_config_file,
+            project_config_path: None,
+            trusted_projects,
             gemini_api_key: env_non_empty("GEMINI_API_KEY").or(file_api_key),
             gemini_model: file_model.unwrap_or_else(|| DEFAULT_GEMINI_MODEL.to_string()),
             gemini_base_url: file_base_url.unwrap_or_else(|| DEFAULT_GEMINI_BASE_URL.to_string()),
-            startup_file,
+            gemini_max_retries,
+            gemini_base_backoff_ms,
+            gemini_request_timeout_ms,
+            gemini_max_response_bytes,
+            gemini_auth,
+            startup_files,
+            thinking_delay_ms,
+            history_size,
+            history_recall,
+            repl_repr_max_chars,
+            auto_time_threshold_ms,
+            inspect_timeout_ms,
+            max_input_bytes,
+            stream_output,
+            inspect_compact,
+            echo_expr,
+            semicolon_suppresses_output,
+            callable_summaries,
+            invalid_response_retries,
+            memory_turns,
+            temperature,
+            max_output_tokens,
+            top_p,
+            auto_retry_turn,
+            allow_eval,
+            allow_apply,
+            clipboard_osc52,
+            timeline_timestamps,
+            timeline_max_bytes,
+            max_steps_shown,
+            auto_page_lines,
+            assistant_mode,
+            trace_format,
+            trace_redact,
+            trace_max_bytes,
+            session_id_format,
             theme,
+            keys,
+            edit_mode,
         })
     }
 }
 
-fn resolve_startup_file(startup_file: Option<&str>, config_path: &Path) -> Result<Option<PathBuf>> {
-    let Some(startup_file) = startup_file else {
-        return Ok(None);
-    };
-
+fn resolve_startup_file(startup_file: &str, config_path: &Path) -> Result<PathBuf> {
     let path = PathBuf::from(startup_file);
     if path.is_absolute() {
-        return Ok(Some(path));
+        return Ok(path);
     }
 
     let config_dir = config_path.parent().ok_or_else(|| {
@@ -303,7 +1247,45 @@ fn resolve_startup_file(startup_file: Option<&str>, config_path: &Path) -> Resul
         )
     })?;
 
-    Ok(Some(config_dir.join(path)))
+    Ok(config_dir.join(path))
+}
+
+/// Sets `theme.name` to `preset` in the config file at `config_path`, keeping its other
+/// settings. A missing file is created, readable by the owner only. Comments in an
+/// existing file are not preserved.
+pub fn save_theme_preset(config_path: &Path, preset: ThemePreset) -> Result<()> {
+    let mut table = if config_path.is_file() {
+        let text = fs::read_to_string(config_path)
+            .map_err(|err| anyhow!("failed to read {}: {err}", config_path.display()))?;
+        toml::from_str::<toml::Table>(&text)
+            .map_err(|err| anyhow!("failed to parse {}: {err}", config_path.display()))?
+    } else {
+        toml::Table::new()
+    };
+
+    let theme = table
+        .entry("theme")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let Some(theme) = theme.as_table_mut() else {
+        bail!("{}: theme is not a table", config_path.display());
+    };
+    theme.insert(
+        "name".to_string(),
+        toml::Value::String(preset.name().to_string()),
+    );
+
+    let text = toml::to_string(&table)?;
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options
+        .open(config_path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|err| anyhow!("failed to write {}: {err}", config_path.display()))
 }
 
 fn resolve_config_path(config_path_override: Option<&Path>) -> Result<(PathBuf, bool)> {
@@ -335,10 +1317,8 @@ fn discover_config_path() -> Result<PathBuf> {
         .join(CONFIG_FILE_NAME))
 }
 
-fn load_file_config(
-    config_path: &Path,
-    require_config_file: bool,
-) -> Result<Option<RawFileConfig>> {
+/// Reads a config file as a TOML table, after checking that it only uses known keys.
+fn load_file_config(config_path: &Path, require_config_file: bool) -> Result<Option<toml::Table>> {
     if !config_path.is_file() {
         if require_config_file {
             bail!(
@@ -356,17 +1336,123 @@ fn load_file_config(
         )
     })?;
 
-    toml::from_str(&config_text)
+    toml::from_str::<RawFileConfig>(&config_text)
+        .and_then(|_| toml::from_str::<toml::Table>(&config_text))
         .map(Some)
         .map_err(|err| anyhow!("Failed to load config {}: {err}", config_path.display()))
 }
 
+/// Looks for `.pychat.ai.toml` in `dir` and then in each parent directory.
+fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Reads the directories listed in the user config's `trusted_projects`.
+fn trusted_projects(user_config: Option<&toml::Table>, config_path: &Path) -> Result<Vec<PathBuf>> {
+    let Some(value) = user_config.and_then(|table| table.get("trusted_projects")) else {
+        return Ok(Vec::new());
+    };
+    let reason = "expected a list of directory paths";
+    value
+        .as_array()
+        .ok_or_else(|| config_error(config_path, "trusted_projects", reason))?
+        .iter()
+        .map(|dir| {
+            let dir = dir
+                .as_str()
+                .ok_or_else(|| config_error(config_path, "trusted_projects", reason))?;
+            Ok(fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from(dir)))
+        })
+        .collect()
+}
+
+/// Reads a project config, rejecting keys outside [`PROJECT_CONFIG_KEYS`]. A project
+/// whose directory is in `trusted` may also set `startup_file`; relative paths are made
+/// relative to the project config's directory, since the merged config resolves them
+/// against the user config's.
+fn load_project_config(path: &Path, trusted: &[PathBuf]) -> Result<toml::Table> {
+    let mut table = load_file_config(path, true)?.unwrap_or_default();
+    let project_dir = path.parent().unwrap_or(Path::new(""));
+    let is_trusted = fs::canonicalize(project_dir).is_ok_and(|dir| trusted.contains(&dir));
+    if let Some(key) = table.keys().find(|key| {
+        !(PROJECT_CONFIG_KEYS.contains(&key.as_str()) || is_trusted && *key == "startup_file")
+    }) {
+        let reason = if key == "startup_file" {
+            format!(
+                "not allowed in a project config unless trusted_projects in the user config lists {}",
+                project_dir.display()
+            )
+        } else {
+            "not allowed in a project config; set it in the user config instead".to_string()
+        };
+        return Err(config_error(path, key, &reason));
+    }
+
+    let anchor = |value: &mut toml::Value| {
+        if let toml::Value::String(file) = value
+            && !file.trim().is_empty()
+            && Path::new(file.as_str()).is_relative()
+        {
+            *file = project_dir.join(file.as_str()).display().to_string();
+        }
+    };
+    match table.get_mut("startup_file") {
+        Some(toml::Value::Array(files)) => files.iter_mut().for_each(anchor),
+        Some(file) => anchor(file),
+        None => {}
+    }
+    Ok(table)
+}
+
+/// Overlays `project` on `user`: keys set in the project config win, and nested tables
+/// such as `[python]` are merged key by key.
+fn merge_config_tables(user: &mut toml::Table, project: toml::Table) {
+    for (key, value) in project {
+        match (user.get_mut(&key), value) {
+            (Some(toml::Value::Table(user_table)), toml::Value::Table(project_table)) => {
+                merge_config_tables(user_table, project_table);
+            }
+            (_, value) => {
+                user.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Picks the preset used when `theme.name` is unset, from the terminal background
+/// reported in `COLORFGBG`.
+fn detect_preset() -> ThemePreset {
+    detect_preset_from(env::var("COLORFGBG").ok().as_deref())
+}
+
+/// `COLORFGBG` is `fg;bg` (some terminals insert a middle field), with ANSI color
+/// indexes. Backgrounds 7 and 9-15 are light; anything unparsable falls back to the
+/// default (dark) preset.
+fn detect_preset_from(colorfgbg: Option<&str>) -> ThemePreset {
+    let background = colorfgbg
+        .and_then(|value| value.rsplit(';').next())
+        .and_then(|bg| bg.trim().parse::<u8>().ok());
+    match background {
+        Some(7 | 9..=15) => ThemePreset::Light,
+        _ => ThemePreset::Default,
+    }
+}
+
 fn validate_theme(raw_theme: Option<&RawThemeConfig>, config_path: &Path) -> Result<ThemeConfig> {
     let Some(theme) = raw_theme else {
-        return Ok(ThemeConfig::default());
+        return Ok(ThemeConfig {
+            preset: detect_preset(),
+            ..ThemeConfig::default()
+        });
     };
 
-    let mut config = ThemeConfig::default();
+    let mut config = ThemeConfig {
+        preset: detect_preset(),
+        highlight: theme.highlight.unwrap_or(false),
+        ..ThemeConfig::default()
+    };
 
     if let Some(name) = &theme.name {
         config.preset = ThemePreset::from_str(name)
@@ -393,11 +1479,62 @@ fn validate_theme(raw_theme: Option<&RawThemeConfig>, config_path: &Path) -> Res
     Ok(config)
 }
 
-fn parse_color(
-    value: Option<&str>,
+/// Applies `[keys]` over the default bindings; each listed action replaces all of its
+/// default keys. A key bound to two actions is an error.
+fn validate_keys(
+    raw_keys: Option<&BTreeMap<String, RawKeyCombos>>,
     config_path: &Path,
-    token_name: &str,
-    field_name: &str,
+) -> Result<KeyBindings> {
+    let mut bindings = KeyBindings::default();
+    let Some(raw_keys) = raw_keys else {
+        return Ok(bindings);
+    };
+
+    for (action_name, raw_combos) in raw_keys {
+        let key_path = format!("keys.{action_name}");
+        let action = KeyAction::from_str(action_name)
+            .map_err(|reason| config_error(config_path, &key_path, &reason))?;
+        let combos = raw_combos
+            .keys()
+            .into_iter()
+            .map(KeyCombo::from_str)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|reason| config_error(config_path, &key_path, &reason))?;
+        if let Some(combo) = combos.iter().find(|combo| combo.is_printable()) {
+            return Err(config_error(
+                config_path,
+                &key_path,
+                &format!("'{combo}' types a character; add ctrl or alt"),
+            ));
+        }
+        bindings.rebind(action, combos);
+    }
+
+    for (index, (action, combo)) in bindings.bindings.iter().enumerate() {
+        if let Some((other, _)) = bindings.bindings[..index]
+            .iter()
+            .find(|(other, bound)| other != action && bound == combo)
+        {
+            return Err(config_error(
+                config_path,
+                "keys",
+                &format!(
+                    "'{combo}' is bound to both {} and {}",
+                    other.name(),
+                    action.name()
+                ),
+            ));
+        }
+    }
+
+    Ok(bindings)
+}
+
+fn parse_color(
+    value: Option<&str>,
+    config_path: &Path,
+    token_name: &str,
+    field_name: &str,
 ) -> Result<Option<HexColor>> {
     let Some(value) = value else {
         return Ok(None);
@@ -466,43 +1603,756 @@ fn config_error(config_path: &Path, key_path: &str, reason: &str) -> anyhow::Err
 #[cfg(test)]
 mod tests {
     use super::{
-        AppConfig, DEFAULT_GEMINI_BASE_URL, DEFAULT_GEMINI_MODEL, HexColor, ThemeConfig,
-        ThemePreset, ThemeToken,
+        AppConfig, AssistantMode, DEFAULT_GEMINI_BASE_BACKOFF_MS, DEFAULT_GEMINI_BASE_URL,
+        DEFAULT_GEMINI_MAX_RESPONSE_BYTES, DEFAULT_GEMINI_MAX_RETRIES, DEFAULT_GEMINI_MODEL,
+        DEFAULT_GEMINI_REQUEST_TIMEOUT_MS, DEFAULT_HISTORY_SIZE, DEFAULT_INSPECT_TIMEOUT_MS,
+        DEFAULT_INVALID_RESPONSE_RETRIES, DEFAULT_MAX_INPUT_BYTES, DEFAULT_REPL_REPR_MAX_CHARS,
+        DEFAULT_THINKING_DELAY_MS, DEFAULT_TIMELINE_MAX_BYTES, DEFAULT_TRACE_MAX_BYTES, EditMode,
+        GeminiAuth, HexColor, HistoryRecall, KeyAction, SessionIdFormat, ThemeConfig, ThemePreset,
+        ThemeToken, TraceFormat, detect_preset_from, save_theme_preset,
     };
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
     use serial_test::serial;
     use std::env;
     use std::fs;
     use std::path::{Path, PathBuf};
 
-    fn reset_vars() {
+    fn reset_vars() {
+        unsafe {
+            env::remove_var("GEMINI_API_KEY");
+            env::remove_var("GEMINI_MODEL");
+            env::remove_var("GEMINI_BASE_URL");
+            env::remove_var("XDG_CONFIG_HOME");
+            env::remove_var("COLORFGBG");
+        }
+    }
+
+    fn with_cwd<T>(path: &Path, f: impl FnOnce() -> T) -> T {
+        let cwd = env::current_dir().expect("current dir");
+        env::set_current_dir(path).expect("set current dir");
+        let result = f();
+        env::set_current_dir(cwd).expect("restore current dir");
+        result
+    }
+
+    #[test]
+    #[serial]
+    fn load_uses_default_model_when_unset() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.gemini_model, DEFAULT_GEMINI_MODEL);
+        assert_eq!(cfg.gemini_max_retries, DEFAULT_GEMINI_MAX_RETRIES);
+        assert_eq!(cfg.gemini_base_backoff_ms, DEFAULT_GEMINI_BASE_BACKOFF_MS);
+        assert_eq!(
+            cfg.gemini_request_timeout_ms,
+            DEFAULT_GEMINI_REQUEST_TIMEOUT_MS
+        );
+        assert_eq!(
+            cfg.gemini_max_response_bytes,
+            DEFAULT_GEMINI_MAX_RESPONSE_BYTES
+        );
+        assert_eq!(cfg.gemini_auth, GeminiAuth::QueryKey);
+        assert_eq!(cfg.thinking_delay_ms, DEFAULT_THINKING_DELAY_MS);
+        assert_eq!(cfg.history_size, DEFAULT_HISTORY_SIZE);
+        assert_eq!(cfg.repl_repr_max_chars, DEFAULT_REPL_REPR_MAX_CHARS);
+        assert!(!cfg.clipboard_osc52);
+        assert!(!cfg.timeline_timestamps);
+        assert_eq!(cfg.timeline_max_bytes, DEFAULT_TIMELINE_MAX_BYTES);
+        assert_eq!(cfg.max_steps_shown, 0);
+        assert_eq!(cfg.auto_page_lines, 0);
+        assert_eq!(cfg.auto_time_threshold_ms, 0);
+        assert_eq!(cfg.assistant_mode, AssistantMode::Agent);
+        assert_eq!(cfg.history_recall, HistoryRecall::Block);
+        assert_eq!(cfg.edit_mode, EditMode::Emacs);
+        assert_eq!(cfg.trace_format, TraceFormat::Text);
+        assert!(cfg.trace_redact.is_empty());
+        assert_eq!(cfg.trace_max_bytes, DEFAULT_TRACE_MAX_BYTES);
+        assert_eq!(cfg.session_id_format, SessionIdFormat::Hex);
+        assert_eq!(cfg.inspect_timeout_ms, DEFAULT_INSPECT_TIMEOUT_MS);
+        assert_eq!(cfg.max_input_bytes, DEFAULT_MAX_INPUT_BYTES);
+        assert!(!cfg.stream_output);
+        assert!(!cfg.inspect_compact);
+        assert!(!cfg.echo_expr);
+        assert!(!cfg.semicolon_suppresses_output);
+        assert!(!cfg.callable_summaries);
+        assert_eq!(cfg.temperature, None);
+        assert_eq!(cfg.max_output_tokens, None);
+        assert_eq!(cfg.top_p, None);
+        assert_eq!(
+            cfg.invalid_response_retries,
+            DEFAULT_INVALID_RESPONSE_RETRIES
+        );
+        assert_eq!(cfg.memory_turns, 0);
+        assert!(!cfg.auto_retry_turn);
+        assert!(cfg.allow_eval);
+        assert!(!cfg.allow_apply);
+        assert_eq!(cfg.theme, ThemeConfig::default());
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_thinking_delay_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "thinking_delay_ms = 250").expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.thinking_delay_ms, 250);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_history_size_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "history_size = 50").expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.history_size, 50);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_repl_repr_max_chars_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "repl_repr_max_chars = 200")
+            .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.repl_repr_max_chars, 200);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_auto_time_threshold_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "auto_time_threshold_ms = 1500",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.auto_time_threshold_ms, 1500);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_timeline_timestamps_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "timeline_timestamps = true\ntimeline_max_bytes = 4096\nmax_steps_shown = 5\nauto_page_lines = 40\n",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert!(cfg.timeline_timestamps);
+        assert_eq!(cfg.timeline_max_bytes, 4096);
+        assert_eq!(cfg.max_steps_shown, 5);
+        assert_eq!(cfg.auto_page_lines, 40);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_clipboard_osc52_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "clipboard_osc52 = true").expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert!(cfg.clipboard_osc52);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_assistant_mode_and_rejects_unknown_values() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "assistant_mode = \"chat\"")
+            .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.assistant_mode, AssistantMode::Chat);
+
+        fs::write(config_dir.join("config.toml"), "assistant_mode = \"fast\"")
+            .expect("write config");
+        let err = with_cwd(tmp.path(), || AppConfig::load().expect_err("invalid mode"));
+        assert!(
+            err.to_string()
+                .contains("assistant_mode: unknown assistant mode 'fast'")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn describe_reports_config_source_and_masks_api_key() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_path = tmp.path().join("custom.toml");
+        fs::write(
+            &config_path,
+            "gemini_api_key = \"file-secret\"\nhistory_size = 7",
+        )
+        .expect("write config");
+
+        reset_vars();
+        let cfg = with_cwd(tmp.path(), || {
+            AppConfig::load_with_path(Some(&config_path)).expect("load config")
+        });
+        let text = cfg.describe();
+
+        assert!(text.starts_with(&format!(
+            "config file: {} (from --config)\nproject config: none\n",
+            config_path.display()
+        )));
+        assert!(text.contains("history_size: 7,"));
+        assert!(text.contains("gemini_api_key: Some(\n        \"***\",\n    ),"));
+        assert!(!text.contains("file-secret"));
+    }
+
+    #[test]
+    #[serial]
+    fn describe_lists_the_merged_project_config() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(tmp.path().join(".pychat.ai.toml"), "history_size = 9")
+            .expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        let project_path = tmp
+            .path()
+            .canonicalize()
+            .expect("canonical tmp dir")
+            .join(".pychat.ai.toml");
+        let text = cfg.describe();
+
+        assert!(
+            text.contains(&format!("\nproject config: {}\n", project_path.display())),
+            "{text}"
+        );
+        assert!(text.contains("history_size: 9,"));
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_history_recall_and_rejects_unknown_values() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "history_recall = \"line\"")
+            .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.history_recall, HistoryRecall::Line);
+
+        fs::write(config_dir.join("config.toml"), "history_recall = \"word\"")
+            .expect("write config");
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("invalid recall")
+        });
+        assert!(
+            err.to_string()
+                .contains("history_recall: unknown history recall 'word'")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_edit_mode_and_rejects_unknown_values() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "edit_mode = \"vi\"").expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.edit_mode, EditMode::Vi);
+
+        fs::write(config_dir.join("config.toml"), "edit_mode = \"nano\"").expect("write config");
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("invalid edit mode")
+        });
+        assert!(
+            err.to_string()
+                .contains("edit_mode: unknown edit mode 'nano'")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn save_theme_preset_persists_the_name_and_keeps_other_settings() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_path = tmp.path().join("pychat.ai").join("config.toml");
+        reset_vars();
+
+        save_theme_preset(&config_path, ThemePreset::HighContrast).expect("save new config");
+        let cfg = AppConfig::load_with_path(Some(&config_path)).expect("load config");
+        assert_eq!(cfg.theme.preset, ThemePreset::HighContrast);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&config_path)
+                .expect("metadata")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::write(
+            &config_path,
+            "history_size = 5\n\n[theme]\nname = \"default\"\n\n[theme.styles.python_prompt]\nfg = \"#1F6FEB\"\n",
+        )
+        .expect("write config");
+        save_theme_preset(&config_path, ThemePreset::Light).expect("save preset");
+
+        let cfg = AppConfig::load_with_path(Some(&config_path)).expect("load config");
+        assert_eq!(cfg.theme.preset, ThemePreset::Light);
+        assert_eq!(cfg.history_size, 5);
+        assert_eq!(
+            cfg.theme.styles[&ThemeToken::PythonPrompt].fg,
+            Some("#1F6FEB".parse::<HexColor>().expect("color"))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn load_remaps_keys_and_rejects_unknown_actions_and_keys() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[keys]\ntoggle_steps = \"alt-s\"\nquit = [\"ctrl-q\", \"F10\"]\n",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        assert_eq!(
+            cfg.keys
+                .action_for(key(KeyCode::Char('s'), KeyModifiers::ALT)),
+            Some(KeyAction::ToggleSteps)
+        );
+        assert_eq!(
+            cfg.keys
+                .action_for(key(KeyCode::Char('t'), KeyModifiers::CONTROL)),
+            None
+        );
+        assert_eq!(
+            cfg.keys.action_for(key(KeyCode::F(10), KeyModifiers::NONE)),
+            Some(KeyAction::Quit)
+        );
+        assert_eq!(
+            cfg.keys
+                .action_for(key(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            None
+        );
+        assert_eq!(
+            cfg.keys
+                .action_for(key(KeyCode::Char('j'), KeyModifiers::CONTROL)),
+            Some(KeyAction::Newline)
+        );
+        assert_eq!(
+            cfg.keys.action_for(key(
+                KeyCode::Char('s'),
+                KeyModifiers::ALT | KeyModifiers::CONTROL
+            )),
+            None
+        );
+        assert_eq!(
+            cfg.keys
+                .action_for(key(KeyCode::F(10), KeyModifiers::CONTROL)),
+            None
+        );
+        assert_eq!(
+            cfg.keys
+                .action_for(key(KeyCode::BackTab, KeyModifiers::SHIFT)),
+            Some(KeyAction::ToggleMode)
+        );
+
+        for (config, expected) in [
+            (
+                "[keys]\nsubmit = \"ctrl-s\"",
+                "keys.submit: unknown action 'submit'",
+            ),
+            (
+                "[keys]\nquit = \"hyper-q\"",
+                "keys.quit: unknown modifier 'hyper' in 'hyper-q'",
+            ),
+            (
+                "[keys]\nnewline = \"ctrl-return\"",
+                "keys.newline: unknown key 'return' in 'ctrl-return'",
+            ),
+            (
+                "[keys]\nquit = \"ctrl-t\"",
+                "keys: 'ctrl-t' is bound to both toggle_steps and quit",
+            ),
+            (
+                "[keys]\nquit = \"q\"",
+                "keys.quit: 'q' types a character; add ctrl or alt",
+            ),
+            (
+                "[keys]\ntoggle_steps = [\"ctrl-t\", \"space\"]",
+                "keys.toggle_steps: 'space' types a character; add ctrl or alt",
+            ),
+        ] {
+            fs::write(config_dir.join("config.toml"), config).expect("write config");
+            let err = with_cwd(tmp.path(), || {
+                AppConfig::load().expect_err("load should fail")
+            });
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_session_id_format_and_rejects_unknown_values() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "session_id_format = \"uuid\"",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.session_id_format, SessionIdFormat::Uuid);
+
+        fs::write(
+            config_dir.join("config.toml"),
+            "session_id_format = \"ulid\"",
+        )
+        .expect("write config");
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("invalid format")
+        });
+        assert!(
+            err.to_string()
+                .contains("session_id_format: unknown session id format 'ulid'")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_gemini_retry_policy_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "gemini_max_retries = 0\ngemini_base_backoff_ms = 100\ngemini_request_timeout_ms = 5000\ngemini_max_response_bytes = 1024",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.gemini_max_retries, 0);
+        assert_eq!(cfg.gemini_base_backoff_ms, 100);
+        assert_eq!(cfg.gemini_request_timeout_ms, 5000);
+        assert_eq!(cfg.gemini_max_response_bytes, 1024);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_gemini_auth_and_rejects_unknown_modes() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        for (value, expected) in [
+            ("query_key", GeminiAuth::QueryKey),
+            ("bearer", GeminiAuth::Bearer),
+            (
+                "header:X-Api-Key",
+                GeminiAuth::Header("X-Api-Key".to_string()),
+            ),
+        ] {
+            fs::write(
+                config_dir.join("config.toml"),
+                format!("gemini_auth = \"{value}\""),
+            )
+            .expect("write config");
+            let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+            assert_eq!(cfg.gemini_auth, expected);
+        }
+
+        for value in ["basic", "header:", "header:X Key"] {
+            fs::write(
+                config_dir.join("config.toml"),
+                format!("gemini_auth = \"{value}\""),
+            )
+            .expect("write config");
+            let err = with_cwd(tmp.path(), || {
+                AppConfig::load().expect_err("invalid auth mode")
+            });
+            assert!(
+                err.to_string()
+                    .contains(&format!("gemini_auth: unknown auth mode '{value}'")),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_auto_retry_turn_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "auto_retry_turn = true").expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert!(cfg.auto_retry_turn);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_allow_eval_and_allow_apply_from_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "allow_eval = false\nallow_apply = true",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert!(!cfg.allow_eval);
+        assert!(cfg.allow_apply);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_trace_format_max_bytes_and_redact_list() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "trace_format = \"jsonl\"\ntrace_max_bytes = 0\n\n[trace]\nredact = [\"x-session-token\"]\n",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.trace_format, TraceFormat::Jsonl);
+        assert_eq!(cfg.trace_max_bytes, 0);
+        assert_eq!(cfg.trace_redact, vec!["x-session-token".to_string()]);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_python_section() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[python]\ninspect_timeout_ms = 3000\nmax_input_bytes = 4096\nstream_output = true\ninspect_compact = true\necho_expr = true\nsemicolon_suppresses_output = true\ncallable_summaries = true",
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.inspect_timeout_ms, 3000);
+        assert_eq!(cfg.max_input_bytes, 4096);
+        assert!(cfg.stream_output);
+        assert!(cfg.inspect_compact);
+        assert!(cfg.echo_expr);
+        assert!(cfg.semicolon_suppresses_output);
+        assert!(cfg.callable_summaries);
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_and_validates_assistant_section() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[assistant]\ntemperature = 0.2\nmax_output_tokens = 1024\ntop_p = 0.9",
+        )
+        .expect("write config");
+
+        reset_vars();
         unsafe {
-            env::remove_var("GEMINI_API_KEY");
-            env::remove_var("GEMINI_MODEL");
-            env::remove_var("GEMINI_BASE_URL");
-            env::remove_var("XDG_CONFIG_HOME");
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
         }
-    }
 
-    fn with_cwd<T>(path: &Path, f: impl FnOnce() -> T) -> T {
-        let cwd = env::current_dir().expect("current dir");
-        env::set_current_dir(path).expect("set current dir");
-        let result = f();
-        env::set_current_dir(cwd).expect("restore current dir");
-        result
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.temperature, Some(0.2));
+        assert_eq!(cfg.max_output_tokens, Some(1024));
+        assert_eq!(cfg.top_p, Some(0.9));
+
+        fs::write(
+            config_dir.join("config.toml"),
+            "[assistant]\ntemperature = 2.5",
+        )
+        .expect("write config");
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("load should fail")
+        });
+        assert!(
+            err.to_string()
+                .contains("assistant.temperature: must be between 0.0 and 2.0")
+        );
+
+        fs::write(config_dir.join("config.toml"), "[assistant]\ntop_p = -0.1")
+            .expect("write config");
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("load should fail")
+        });
+        assert!(
+            err.to_string()
+                .contains("assistant.top_p: must be between 0.0 and 1.0")
+        );
     }
 
     #[test]
     #[serial]
-    fn load_uses_default_model_when_unset() {
+    fn load_reads_and_validates_agent_section() {
         let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[agent]\ninvalid_response_retries = 3\nmemory_turns = 4",
+        )
+        .expect("write config");
+
         reset_vars();
         unsafe {
             env::set_var("XDG_CONFIG_HOME", tmp.path());
         }
 
         let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
-        assert_eq!(cfg.gemini_model, DEFAULT_GEMINI_MODEL);
-        assert_eq!(cfg.theme, ThemeConfig::default());
+        assert_eq!(cfg.invalid_response_retries, 3);
+        assert_eq!(cfg.memory_turns, 4);
+
+        fs::write(
+            config_dir.join("config.toml"),
+            "[agent]\ninvalid_response_retries = 6",
+        )
+        .expect("write config");
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("load should fail")
+        });
+        assert!(
+            err.to_string()
+                .contains("agent.invalid_response_retries: must be between 0 and 5")
+        );
+
+        fs::write(config_dir.join("config.toml"), "[agent]\nmemory_turns = 21")
+            .expect("write config");
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("load should fail")
+        });
+        assert!(
+            err.to_string()
+                .contains("agent.memory_turns: must be between 0 and 20")
+        );
     }
 
     #[test]
@@ -581,6 +2431,223 @@ gemini_base_url = "https://example.com"
         assert_eq!(cfg.gemini_model, "from_file");
     }
 
+    #[test]
+    #[serial]
+    fn load_merges_project_config_found_above_the_cwd() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "gemini_model = \"user-model\"\nhistory_size = 5\n\n[python]\ninspect_timeout_ms = 3000\n",
+        )
+        .expect("write user config");
+        let project = tmp.path().join("project");
+        let nested = project.join("src").join("pkg");
+        fs::create_dir_all(&nested).expect("create nested dir");
+        fs::write(
+            project.join(".pychat.ai.toml"),
+            "gemini_model = \"project-model\"\n\n[python]\nmax_input_bytes = 4096\n",
+        )
+        .expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(&nested, || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.gemini_model, "project-model");
+        assert_eq!(cfg.history_size, 5);
+        assert_eq!(cfg.inspect_timeout_ms, 3000);
+        assert_eq!(cfg.max_input_bytes, 4096);
+        assert_eq!(
+            cfg.project_config_path,
+            Some(
+                project
+                    .canonicalize()
+                    .expect("canonical project dir")
+                    .join(".pychat.ai.toml")
+            )
+        );
+
+        let explicit = with_cwd(&nested, || {
+            AppConfig::load_with_path(Some(&config_dir.join("config.toml"))).expect("load config")
+        });
+        assert_eq!(explicit.gemini_model, "user-model");
+        assert_eq!(explicit.project_config_path, None);
+    }
+
+    #[test]
+    #[serial]
+    fn load_rejects_endpoint_auth_and_startup_keys_in_project_config() {
+        for (key, value) in [
+            ("gemini_base_url", "\"https://attacker.example\""),
+            ("gemini_auth", "\"query\""),
+            ("gemini_api_key", "\"key\""),
+            ("startup_file", "\"evil.py\""),
+            ("allow_apply", "true"),
+        ] {
+            let tmp = tempfile::tempdir().expect("tempdir");
+            fs::write(
+                tmp.path().join(".pychat.ai.toml"),
+                format!("{key} = {value}"),
+            )
+            .expect("write project config");
+
+            reset_vars();
+            unsafe {
+                env::set_var("XDG_CONFIG_HOME", tmp.path());
+            }
+
+            let err = with_cwd(tmp.path(), || {
+                AppConfig::load().expect_err("restricted project key")
+            })
+            .to_string();
+            assert!(err.contains(".pychat.ai.toml"), "{err}");
+            assert!(err.contains(key), "{err}");
+            assert!(err.contains("not allowed in a project config"), "{err}");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn load_accepts_startup_file_from_a_trusted_project() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let project = tmp.path().join("project");
+        fs::create_dir_all(project.join("tools")).expect("create project dir");
+        let project = project.canonicalize().expect("canonical project dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            format!("trusted_projects = [{:?}]\n", project.display().to_string()),
+        )
+        .expect("write user config");
+        fs::write(
+            project.join(".pychat.ai.toml"),
+            "startup_file = \"tools/imports.py\"\n",
+        )
+        .expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(&project, || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.startup_files, vec![project.join("tools/imports.py")]);
+        assert_eq!(cfg.trusted_projects, vec![project]);
+    }
+
+    #[test]
+    #[serial]
+    fn load_rejects_startup_file_from_an_untrusted_project() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let trusted = tmp.path().join("trusted");
+        let project = tmp.path().join("project");
+        fs::create_dir_all(&trusted).expect("create trusted dir");
+        fs::create_dir_all(&project).expect("create project dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            format!("trusted_projects = [{:?}]\n", trusted.display().to_string()),
+        )
+        .expect("write user config");
+        fs::write(
+            project.join(".pychat.ai.toml"),
+            "startup_file = \"evil.py\"\n",
+        )
+        .expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let err = with_cwd(&project, || {
+            AppConfig::load().expect_err("untrusted startup_file")
+        })
+        .to_string();
+        assert!(err.contains("startup_file"), "{err}");
+        assert!(err.contains("trusted_projects"), "{err}");
+    }
+
+    #[test]
+    #[serial]
+    fn load_rejects_trusted_projects_that_is_not_a_list_of_paths() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "trusted_projects = [1]\n")
+            .expect("write user config");
+        let project = tmp.path().join("project");
+        fs::create_dir_all(&project).expect("create project dir");
+        fs::write(project.join(".pychat.ai.toml"), "history_size = 5\n")
+            .expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let err = with_cwd(&project, || {
+            AppConfig::load().expect_err("invalid trusted_projects")
+        })
+        .to_string();
+        assert!(err.contains("config.toml"), "{err}");
+        assert!(err.contains("trusted_projects"), "{err}");
+    }
+
+    #[test]
+    #[serial]
+    fn load_reports_invalid_project_values_against_the_project_file() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "history_size = 5\n").expect("write user config");
+        let project = tmp.path().join("project");
+        fs::create_dir_all(&project).expect("create project dir");
+        fs::write(project.join(".pychat.ai.toml"), "edit_mode = \"nano\"\n")
+            .expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let err = with_cwd(&project, || {
+            AppConfig::load().expect_err("invalid project value")
+        })
+        .to_string();
+        assert!(err.contains(".pychat.ai.toml"), "{err}");
+        assert!(!err.contains("config.toml:"), "{err}");
+        assert!(err.contains("edit_mode"), "{err}");
+    }
+
+    #[test]
+    #[serial]
+    fn load_rejects_unknown_keys_in_project_config() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        fs::write(
+            tmp.path().join(".pychat.ai.toml"),
+            "gemini_modle = \"typo\"",
+        )
+        .expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let err = with_cwd(tmp.path(), || {
+            AppConfig::load().expect_err("unknown project key")
+        });
+        assert!(err.to_string().contains(".pychat.ai.toml"));
+        assert!(err.to_string().contains("gemini_modle"));
+    }
+
     #[test]
     #[serial]
     fn load_with_path_uses_explicit_config_file() {
@@ -628,8 +2695,8 @@ gemini_base_url = "https://example.com"
 
         let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
         assert_eq!(
-            cfg.startup_file,
-            Some(config_dir.join(PathBuf::from("scripts/bootstrap.py")))
+            cfg.startup_files,
+            vec![config_dir.join(PathBuf::from("scripts/bootstrap.py"))]
         );
     }
 
@@ -652,7 +2719,7 @@ gemini_base_url = "https://example.com"
         }
 
         let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
-        assert_eq!(cfg.startup_file, Some(startup_path));
+        assert_eq!(cfg.startup_files, vec![startup_path]);
     }
 
     #[test]
@@ -669,7 +2736,35 @@ gemini_base_url = "https://example.com"
         }
 
         let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
-        assert_eq!(cfg.startup_file, None);
+        assert!(cfg.startup_files.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn load_reads_startup_file_list_in_order() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let helpers = tmp.path().join("helpers.py");
+        fs::write(
+            config_dir.join("config.toml"),
+            format!(
+                r#"startup_file = ["imports.py", "{}", " "]"#,
+                helpers.display()
+            ),
+        )
+        .expect("write config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(
+            cfg.startup_files,
+            vec![config_dir.join("imports.py"), helpers]
+        );
     }
 
     #[test]
@@ -727,6 +2822,66 @@ gemini_base_url = "https://example.com"
         assert!(err.to_string().contains("unknown field"));
     }
 
+    #[test]
+    #[serial]
+    fn check_without_a_path_validates_and_names_the_project_config() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(config_dir.join("config.toml"), "history_size = 5\n").expect("write user config");
+        let project = tmp.path().join("project");
+        fs::create_dir_all(&project).expect("create project dir");
+        let project = project.canonicalize().expect("canonical project dir");
+        let project_config = project.join(".pychat.ai.toml");
+        fs::write(&project_config, "history_size = 7\n").expect("write project config");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+        }
+
+        assert_eq!(
+            with_cwd(&project, || AppConfig::check(None).expect("valid config")),
+            format!(
+                "config ok: {}; project config ok: {}",
+                config_dir.join("config.toml").display(),
+                project_config.display()
+            )
+        );
+
+        fs::write(&project_config, "edit_mode = \"nano\"\n").expect("write project config");
+        let err = with_cwd(&project, || {
+            AppConfig::check(None).expect_err("invalid project config")
+        })
+        .to_string();
+        assert!(err.contains(".pychat.ai.toml"), "{err}");
+        assert!(err.contains("edit_mode"), "{err}");
+    }
+
+    #[test]
+    #[serial]
+    fn check_reports_ok_or_the_exact_validation_error() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_path = tmp.path().join("custom.toml");
+        fs::write(&config_path, "[theme]\nname = \"light\"\n").expect("write config");
+        reset_vars();
+
+        assert_eq!(
+            AppConfig::check(Some(&config_path)).expect("valid config"),
+            format!("config ok: {}", config_path.display())
+        );
+
+        fs::write(&config_path, "[theme.styles.prompt]\nfg = \"#ffffff\"\n").expect("write config");
+        let err = AppConfig::check(Some(&config_path)).expect_err("unknown token");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Failed to load config {}: theme.styles.prompt: unknown token 'prompt'",
+                config_path.display()
+            )
+        );
+    }
+
     #[test]
     #[serial]
     fn load_fails_on_unknown_style_token() {
@@ -825,6 +2980,7 @@ modifiers = ["sparkly"]
             r##"
 [theme]
 name = "light"
+highlight = true
 
 [theme.styles.python_prompt]
 fg = "#A0B1C2"
@@ -839,6 +2995,7 @@ fg = "#A0B1C2"
 
         let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
         assert_eq!(cfg.theme.preset, ThemePreset::Light);
+        assert!(cfg.theme.highlight);
         let style = cfg
             .theme
             .styles
@@ -853,4 +3010,40 @@ fg = "#A0B1C2"
             })
         );
     }
+
+    #[test]
+    fn detect_preset_reads_background_from_colorfgbg() {
+        assert_eq!(detect_preset_from(Some("0;15")), ThemePreset::Light);
+        assert_eq!(detect_preset_from(Some("0;default;7")), ThemePreset::Light);
+        assert_eq!(detect_preset_from(Some("15;0")), ThemePreset::Default);
+        assert_eq!(detect_preset_from(Some("15;default")), ThemePreset::Default);
+        assert_eq!(detect_preset_from(None), ThemePreset::Default);
+    }
+
+    #[test]
+    #[serial]
+    fn load_detects_preset_only_when_theme_name_is_unset() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config_dir = tmp.path().join("pychat.ai");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+
+        reset_vars();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", tmp.path());
+            env::set_var("COLORFGBG", "0;15");
+        }
+
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.theme.preset, ThemePreset::Light);
+
+        fs::write(
+            config_dir.join("config.toml"),
+            "[theme]\nname = \"high-contrast\"\n",
+        )
+        .expect("write config");
+        let cfg = with_cwd(tmp.path(), || AppConfig::load().expect("load config"));
+        assert_eq!(cfg.theme.preset, ThemePreset::HighContrast);
+
+        reset_vars();
+    }
 }
@@ -1,25 +1,72 @@
 use crate::trace::SessionTrace;
 use reqwest::Client;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
 use serde::Serialize;
 use std::fmt;
+use std::time::Duration;
+
+/// Request and response bodies longer than this are cut short in the trace.
+const MAX_TRACED_BODY_BYTES: usize = 256 * 1024;
 
 #[derive(Clone)]
 pub struct HttpClient {
     inner: Client,
     trace: Option<SessionTrace>,
+    request_timeout: Option<Duration>,
+    /// Responses with a longer body are aborted; 0 means unlimited.
+    max_response_bytes: usize,
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    Request(reqwest::Error),
+    /// The response body grew past `max_response_bytes` and was not read further.
+    BodyTooLarge {
+        limit: usize,
+    },
+}
+
+impl HttpError {
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Request(err) if err.is_timeout())
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Request(err) => write!(f, "{err}"),
+            Self::BodyTooLarge { limit } => {
+                write!(f, "response body exceeded the {limit}-byte limit")
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
 }
 
 impl fmt::Debug for HttpClient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("HttpClient")
             .field("trace_enabled", &self.trace.is_some())
+            .field("request_timeout", &self.request_timeout)
+            .field("max_response_bytes", &self.max_response_bytes)
             .finish()
     }
 }
 
 impl HttpClient {
     pub fn new(inner: Client) -> Self {
-        Self { inner, trace: None }
+        Self {
+            inner,
+            trace: None,
+            request_timeout: None,
+            max_response_bytes: 0,
+        }
     }
 
     pub fn with_trace(mut self, trace: SessionTrace) -> Self {
@@ -27,61 +74,202 @@ impl HttpClient {
         self
     }
 
+    /// Fails a request that has not finished within `timeout`, counting from when it
+    /// starts connecting until the whole body is read. A zero duration disables it.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = (!timeout.is_zero()).then_some(timeout);
+        self
+    }
+
+    /// Aborts reading a response once its body is longer than `max_bytes`, so a
+    /// misbehaving endpoint cannot exhaust memory. Zero disables the limit.
+    pub fn with_max_response_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_response_bytes = max_bytes;
+        self
+    }
+
+    /// A copy with a new connection pool, so a retry does not reuse a connection that
+    /// just failed.
+    pub fn reconnected(&self) -> Self {
+        Self {
+            inner: Client::new(),
+            ..self.clone()
+        }
+    }
+
     pub async fn post_json<T: Serialize + ?Sized>(
         &self,
         url: &str,
         query: &[(&str, &str)],
+        headers: &[(&str, &str)],
         payload: &T,
-    ) -> Result<HttpResponseData, reqwest::Error> {
+    ) -> Result<HttpResponseData, HttpError> {
+        self.post_json_stream(url, query, headers, payload, |_| {})
+            .await
+    }
+
+    /// Sends a JSON POST and hands each body chunk of a successful response to `on_chunk`
+    /// as it arrives. The complete body is still returned and traced once at the end.
+    pub async fn post_json_stream<T: Serialize + ?Sized, F: FnMut(&[u8])>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        payload: &T,
+        mut on_chunk: F,
+    ) -> Result<HttpResponseData, HttpError> {
+        let mut response = self.send_json(url, query, headers, payload).await?;
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let success = response.status().is_success();
+
+        let mut body = Vec::new();
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(err) => {
+                    self.trace_error(&err);
+                    return Err(err.into());
+                }
+            };
+            if self.max_response_bytes > 0 && body.len() + chunk.len() > self.max_response_bytes {
+                let err = HttpError::BodyTooLarge {
+                    limit: self.max_response_bytes,
+                };
+                if let Some(trace) = &self.trace {
+                    trace.log_http_error(&err.to_string());
+                }
+                return Err(err);
+            }
+            if success {
+                on_chunk(&chunk);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8_lossy(&body).into_owned();
+
+        if let Some(trace) = &self.trace {
+            trace.log_http_response(status, &headers, &traced_body(&body));
+        }
+
+        Ok(HttpResponseData {
+            status,
+            retry_after: retry_after(&headers),
+            body,
+        })
+    }
+
+    /// Builds and traces a JSON POST exactly as `post_json` would, without sending it.
+    pub fn trace_unsent_json<T: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        payload: &T,
+    ) -> Result<(), reqwest::Error> {
+        self.build_traced_request(url, query, headers, payload)
+            .map(drop)
+    }
+
+    async fn send_json<T: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        payload: &T,
+    ) -> Result<reqwest::Response, HttpError> {
+        let request = self.build_traced_request(url, query, headers, payload)?;
+        match self.inner.execute(request).await {
+            Ok(response) => Ok(response),
+            Err(err) => {
+                self.trace_error(&err);
+                Err(err.into())
+            }
+        }
+    }
+
+    fn build_traced_request<T: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        headers: &[(&str, &str)],
+        payload: &T,
+    ) -> Result<reqwest::Request, reqwest::Error> {
         let body_json = serde_json::to_string(payload)
             .unwrap_or_else(|err| format!("{{\"_serialization_error\":\"{err}\"}}"));
 
-        let request = self.inner.post(url).query(query).json(payload).build()?;
+        let mut builder = self.inner.post(url).query(query).json(payload);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        if let Some(timeout) = self.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        let request = builder.build()?;
         if let Some(trace) = &self.trace {
             trace.log_http_request(
                 request.method().as_str(),
                 request.url().as_str(),
                 request.headers(),
-                &body_json,
+                &traced_body(&body_json),
             );
         }
+        Ok(request)
+    }
 
-        let response = match self.inner.execute(request).await {
-            Ok(response) => response,
-            Err(err) => {
-                if let Some(trace) = &self.trace {
-                    trace.log_http_error(&err.to_string());
-                }
-                return Err(err);
-            }
+    /// reqwest errors embed the request URL, so it is redacted before tracing.
+    fn trace_error(&self, err: &reqwest::Error) {
+        let Some(trace) = &self.trace else {
+            return;
         };
-        let status = response.status().as_u16();
-        let headers = response.headers().clone();
-        let body = response.text().await?;
-
-        if let Some(trace) = &self.trace {
-            trace.log_http_response(status, &headers, &body);
+        let mut message = err.to_string();
+        if let Some(url) = err.url() {
+            message = message.replace(url.as_str(), &trace.redact_url(url.as_str()));
         }
-
-        Ok(HttpResponseData { status, body })
+        trace.log_http_error(&message);
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HttpResponseData {
     pub status: u16,
+    /// Delay requested by a `Retry-After` header given in seconds.
+    pub retry_after: Option<Duration>,
     pub body: String,
 }
 
+fn traced_body(body: &str) -> std::borrow::Cow<'_, str> {
+    if body.len() <= MAX_TRACED_BODY_BYTES {
+        return body.into();
+    }
+    let mut end = MAX_TRACED_BODY_BYTES;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}... [truncated, {} bytes in total]",
+        &body[..end],
+        body.len()
+    )
+    .into()
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::HttpClient;
+    use super::{HttpClient, HttpError, MAX_TRACED_BODY_BYTES};
     use crate::trace::SessionTrace;
     use reqwest::Client;
     use serde_json::json;
     use std::fs;
+    use std::time::Duration;
     use tempfile::tempdir;
-    use wiremock::matchers::{method, path, query_param};
+    use wiremock::matchers::{header, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -89,6 +277,7 @@ mod tests {
         let server = MockServer::start().await;
         Mock::given(method("POST"))
             .and(path("/v1/test"))
+            .and(header("authorization", "Bearer gateway-token"))
             .respond_with(ResponseTemplate::new(200).set_body_json(json!({"ok":true})))
             .mount(&server)
             .await;
@@ -98,17 +287,37 @@ mod tests {
             .post_json(
                 &format!("{}/v1/test", server.uri()),
                 &[],
+                &[("authorization", "Bearer gateway-token")],
                 &json!({"ok":true}),
             )
             .await
             .expect("request should succeed");
 
         assert_eq!(response.status, 200);
+        assert_eq!(response.retry_after, None);
         assert_eq!(response.body, "{\"ok\":true}");
     }
 
     #[tokio::test]
-    async fn post_json_writes_full_raw_http_trace_when_trace_enabled() {
+    async fn post_json_reports_retry_after_seconds() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "7"))
+            .mount(&server)
+            .await;
+
+        let client = HttpClient::new(Client::new());
+        let response = client
+            .post_json(&server.uri(), &[], &[], &json!({}))
+            .await
+            .expect("request should succeed");
+
+        assert_eq!(response.status, 429);
+        assert_eq!(response.retry_after, Some(Duration::from_secs(7)));
+    }
+
+    #[tokio::test]
+    async fn post_json_writes_raw_http_trace_with_api_key_redacted() {
         let server = MockServer::start().await;
         Mock::given(method("POST"))
             .and(path("/v1/test"))
@@ -123,7 +332,7 @@ mod tests {
 
         let dir = tempdir().expect("tempdir");
         let trace = SessionTrace::create_in_temp_dir("test-session", dir.path()).expect("trace");
-        let trace_file = trace.file_path().to_path_buf();
+        let trace_file = trace.file_path();
 
         let client = HttpClient::new(Client::new()).with_trace(trace.clone());
 
@@ -131,6 +340,7 @@ mod tests {
             .post_json(
                 &format!("{}/v1/test", server.uri()),
                 &[("key", "super-secret")],
+                &[],
                 &json!({"token":"request-secret"}),
             )
             .await
@@ -139,9 +349,102 @@ mod tests {
         assert_eq!(response.status, 200);
         let trace_text = fs::read_to_string(trace_file).expect("read trace file");
 
-        assert!(trace_text.contains("key=super-secret"));
+        assert!(!trace_text.contains("super-secret"));
+        assert!(trace_text.contains("key=***"));
         assert!(trace_text.contains("\"token\":\"request-secret\""));
         assert!(trace_text.contains("x-api-key: response-secret"));
         assert!(trace_text.contains("\"api_key\":\"response-secret\""));
     }
+
+    #[tokio::test]
+    async fn post_json_aborts_responses_over_the_byte_limit() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("x".repeat(2048)))
+            .mount(&server)
+            .await;
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_temp_dir("limit-session", dir.path()).expect("trace");
+        let trace_file = trace.file_path();
+        let client = HttpClient::new(Client::new())
+            .with_trace(trace)
+            .with_max_response_bytes(1024);
+
+        let err = client
+            .post_json(&server.uri(), &[], &[], &json!({}))
+            .await
+            .expect_err("body over the limit");
+
+        assert!(matches!(err, HttpError::BodyTooLarge { limit: 1024 }));
+        assert_eq!(
+            err.to_string(),
+            "response body exceeded the 1024-byte limit"
+        );
+        let trace_text = fs::read_to_string(trace_file).expect("read trace file");
+        assert!(trace_text.contains("response body exceeded the 1024-byte limit"));
+
+        let unlimited = HttpClient::new(Client::new());
+        let response = unlimited
+            .post_json(&server.uri(), &[], &[], &json!({}))
+            .await
+            .expect("no limit");
+        assert_eq!(response.body.len(), 2048);
+    }
+
+    #[tokio::test]
+    async fn trace_truncates_long_bodies() {
+        let body = "y".repeat(MAX_TRACED_BODY_BYTES + 10);
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body.clone()))
+            .mount(&server)
+            .await;
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_temp_dir("long-session", dir.path()).expect("trace");
+        let trace_file = trace.file_path();
+        let client = HttpClient::new(Client::new()).with_trace(trace);
+
+        let response = client
+            .post_json(&server.uri(), &[], &[], &json!({}))
+            .await
+            .expect("request should succeed");
+
+        assert_eq!(response.body, body);
+        let trace_text = fs::read_to_string(trace_file).expect("read trace file");
+        assert!(!trace_text.contains(&body));
+        assert!(trace_text.contains(&format!("... [truncated, {} bytes in total]", body.len())));
+    }
+
+    #[tokio::test]
+    async fn post_json_stream_reports_chunks_and_returns_full_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/stream"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("data: one\n\ndata: two\n\n"))
+            .mount(&server)
+            .await;
+
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_temp_dir("stream-session", dir.path()).expect("trace");
+        let trace_file = trace.file_path();
+        let client = HttpClient::new(Client::new()).with_trace(trace);
+
+        let mut received = Vec::new();
+        let response = client
+            .post_json_stream(
+                &format!("{}/v1/stream", server.uri()),
+                &[],
+                &[],
+                &json!({"ok":true}),
+                |chunk| received.extend_from_slice(chunk),
+            )
+            .await
+            .expect("request should succeed");
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "data: one\n\ndata: two\n\n");
+        assert_eq!(received, response.body.as_bytes());
+        let trace_text = fs::read_to_string(trace_file).expect("read trace file");
+        assert_eq!(trace_text.matches("data: two").count(), 1);
+    }
 }
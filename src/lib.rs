@@ -8,40 +8,104 @@ pub mod trace;
 
 use agent::AgentConfig;
 use anyhow::{Result, anyhow, bail};
-use cli::{AppState, CliArgs, Mode, run_repl};
-use config::AppConfig;
+use cli::{AppState, CliArgs, HistoryFile, Mode, OneShot, run_one_shot, run_plain, run_repl};
+use config::{AppConfig, AssistantMode, GeminiAuth, SessionIdFormat};
 use http::client::HttpClient;
 use llm::gemini::GeminiProvider;
+use llm::provider::GenerationConfig;
 use python::{PythonSession, UserRunResult};
+use std::collections::hash_map::RandomState;
 use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, IsTerminal, Read};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
-use trace::SessionTrace;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use trace::{SessionTrace, TraceOptions};
 
-pub async fn run(args: CliArgs) -> Result<()> {
+/// Runs the app for `args`. One-shot runs (`--eval`, `--ask`) exit with a failure code
+/// when the Python input raises or the answer is degraded.
+pub async fn run(args: CliArgs) -> Result<ExitCode> {
+    if args.check_config {
+        return Ok(match AppConfig::check(args.config.as_deref()) {
+            Ok(report) => {
+                println!("{report}");
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        });
+    }
+    if args.print_config {
+        println!(
+            "{}",
+            AppConfig::load_with_path(args.config.as_deref())?.describe()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
     let python = PythonSession::initialize()?;
     if args.smoke_python {
         let version_repr = python
             .eval_expr("__import__('sys').version.split()[0]")?
             .value_repr;
         println!("smoke-python: ok version={version_repr}");
-        return Ok(());
+        return Ok(ExitCode::SUCCESS);
     }
     let config = if let Some(path) = args.config.as_deref() {
         AppConfig::load_with_path(Some(path))?
     } else {
         AppConfig::load()?
     };
-    let startup_message = run_startup_script_if_configured(&python, &config)?;
-    let session_id = generate_session_id();
-    let trace = SessionTrace::create(&session_id)?;
-    let http = HttpClient::new(reqwest::Client::new()).with_trace(trace.clone());
+    let startup_message = startup_message(
+        config.project_config_path.as_deref(),
+        run_startup_script_if_configured(&python, &config)?,
+    );
+    let session_id = generate_session_id(config.session_id_format);
+    // A custom auth header carries the API key, so it is masked like `key`.
+    let mut redact = config.trace_redact.clone();
+    if let GeminiAuth::Header(name) = &config.gemini_auth {
+        redact.push(name.clone());
+    }
+    let trace = SessionTrace::create(
+        &session_id,
+        &TraceOptions {
+            format: config.trace_format,
+            redact,
+            max_bytes: config.trace_max_bytes,
+        },
+    )?;
+    let python = python
+        .with_trace(trace.clone())
+        .with_inspect_timeout(Duration::from_millis(config.inspect_timeout_ms))
+        .with_max_input_bytes(config.max_input_bytes)
+        .with_callable_summaries(config.callable_summaries);
+    let http = HttpClient::new(reqwest::Client::new())
+        .with_trace(trace.clone())
+        .with_request_timeout(Duration::from_millis(config.gemini_request_timeout_ms))
+        .with_max_response_bytes(config.gemini_max_response_bytes);
+    // A dry run never sends the key, so it works without one.
+    let api_key = config
+        .gemini_api_key
+        .clone()
+        .or_else(|| args.dry_run.then(|| "dry-run".to_string()));
     let llm = GeminiProvider::new(
         http,
-        config.gemini_api_key.clone(),
+        api_key,
         config.gemini_model.clone(),
         config.gemini_base_url.clone(),
     )
+    .map(|llm| {
+        llm.with_retry_policy(config.gemini_max_retries, config.gemini_base_backoff_ms)
+            .with_generation_config(GenerationConfig {
+                temperature: config.temperature,
+                max_output_tokens: config.max_output_tokens,
+                top_p: config.top_p,
+            })
+            .with_auth(config.gemini_auth.clone())
+            .with_dry_run(args.dry_run)
+    })
     .ok();
 
     let mut app_state = AppState {
@@ -49,24 +113,122 @@ pub async fn run(args: CliArgs) -> Result<()> {
         session_id,
         python,
         llm,
-        agent_config: AgentConfig::default(),
+        agent_config: AgentConfig {
+            invalid_response_retries: config.invalid_response_retries,
+            auto_retry_turn: config.auto_retry_turn,
+            allow_eval: config.allow_eval,
+            allow_apply: config.allow_apply,
+            chat_only: config.assistant_mode == AssistantMode::Chat,
+            ..AgentConfig::default()
+        },
         theme_config: config.theme.clone(),
+        key_bindings: config.keys.clone(),
+        edit_mode: config.edit_mode,
+        thinking_delay: Duration::from_millis(config.thinking_delay_ms),
+        timeline_timestamps: config.timeline_timestamps,
+        timeline_max_bytes: config.timeline_max_bytes,
+        max_steps_shown: config.max_steps_shown,
+        auto_page_lines: config.auto_page_lines,
+        repr_max_chars: config.repl_repr_max_chars,
+        clipboard_osc52: config.clipboard_osc52,
+        auto_time_threshold: (config.auto_time_threshold_ms > 0)
+            .then(|| Duration::from_millis(config.auto_time_threshold_ms)),
+        stream_output: config.stream_output,
+        inspect_compact: config.inspect_compact,
+        echo_expr: config.echo_expr,
+        semicolon_suppresses_output: config.semicolon_suppresses_output,
+        memory_turns: config.memory_turns,
+        conversation: Vec::new(),
+        history_recall: config.history_recall,
+        history_file: (config.history_size > 0)
+            .then(|| HistoryFile::from_env(config.history_size).ok())
+            .flatten(),
         startup_message,
+        config_path: config.config_path.clone(),
+        config_is_explicit: config.config_is_explicit,
         trace,
     };
 
-    run_repl(&mut app_state).await
+    let one_shot = match (args.eval, args.ask) {
+        (Some(code), _) => Some(OneShot::Eval(code)),
+        (None, Some(question)) => Some(OneShot::Ask(question)),
+        // Piped input cannot drive the TUI, so it runs as a script instead.
+        (None, None) if !args.plain && !io::stdin().is_terminal() => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source)?;
+            Some(OneShot::Script(source))
+        }
+        (None, None) => None,
+    };
+    if let Some(one_shot) = one_shot {
+        let succeeded = run_one_shot(
+            &mut app_state,
+            &one_shot,
+            &mut io::stdout().lock(),
+            &mut io::stderr().lock(),
+        )
+        .await?;
+        report_trace_write_failure(&app_state.trace);
+        return Ok(if succeeded {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    }
+
+    if args.plain {
+        run_plain(&mut app_state).await?;
+    } else {
+        run_repl(&mut app_state).await?;
+    }
+    report_trace_write_failure(&app_state.trace);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints a trace write failure the REPL did not get to show, such as one from a
+/// one-shot run or while the session was closing.
+fn report_trace_write_failure(trace: &SessionTrace) {
+    if let Some(message) = trace.take_write_failure() {
+        eprintln!("PyChat.ai {message}");
+    }
 }
 
 fn run_startup_script_if_configured(
     python: &PythonSession,
     config: &AppConfig,
 ) -> Result<Option<String>> {
-    let Some(path) = startup_script_path(config)? else {
-        return Ok(None);
-    };
+    let paths = startup_script_paths(config)?;
+    for path in &paths {
+        run_startup_script(python, path)?;
+    }
+
+    let executed = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    Ok(match executed.as_slice() {
+        [] => None,
+        [path] => Some(format!("Startup file {path} was executed")),
+        paths => Some(format!(
+            "Startup files were executed in order: {}",
+            paths.join(", ")
+        )),
+    })
+}
+
+/// The first timeline lines: which project config was merged in, then which startup
+/// files ran.
+fn startup_message(project_config: Option<&Path>, startup_files: Option<String>) -> Option<String> {
+    let lines = project_config
+        .map(|path| format!("Project config {} was loaded", path.display()))
+        .into_iter()
+        .chain(startup_files)
+        .collect::<Vec<_>>();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
 
-    let source = fs::read_to_string(&path).map_err(|err| {
+fn run_startup_script(python: &PythonSession, path: &Path) -> Result<()> {
+    let source = fs::read_to_string(path).map_err(|err| {
         anyhow!(
             "Failed to load startup file {}: unable to read file: {err}",
             path.display()
@@ -74,10 +236,7 @@ fn run_startup_script_if_configured(
     })?;
 
     match python.run_exec_input(&source)? {
-        UserRunResult::Executed(_) => Ok(Some(format!(
-            "Startup file {} was executed",
-            path.display()
-        ))),
+        UserRunResult::Executed(_) => Ok(()),
         UserRunResult::Failed { exception, .. } => {
             bail!(
                 "Failed to execute startup file {}:\n{}",
@@ -91,13 +250,13 @@ fn run_startup_script_if_configured(
     }
 }
 
-fn startup_script_path(config: &AppConfig) -> Result<Option<PathBuf>> {
-    if let Some(path) = &config.startup_file {
-        return Ok(Some(path.clone()));
+fn startup_script_paths(config: &AppConfig) -> Result<Vec<PathBuf>> {
+    if !config.startup_files.is_empty() {
+        return Ok(config.startup_files.clone());
     }
 
     if config.config_is_explicit {
-        return Ok(None);
+        return Ok(Vec::new());
     }
 
     let config_dir = config.config_path.parent().ok_or_else(|| {
@@ -108,9 +267,9 @@ fn startup_script_path(config: &AppConfig) -> Result<Option<PathBuf>> {
     })?;
     let implicit_startup = config_dir.join("startup.py");
     if is_regular_file(&implicit_startup) {
-        Ok(Some(implicit_startup))
+        Ok(vec![implicit_startup])
     } else {
-        Ok(None)
+        Ok(Vec::new())
     }
 }
 
@@ -120,24 +279,68 @@ fn is_regular_file(path: &Path) -> bool {
         .unwrap_or(false)
 }
 
-fn generate_session_id() -> String {
-    let millis = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_or(0, |duration| duration.as_millis());
-    format!("{millis:x}-{:x}", std::process::id())
+fn generate_session_id(format: SessionIdFormat) -> String {
+    let pid = std::process::id();
+    match format {
+        SessionIdFormat::Hex => {
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis());
+            format!("{millis:x}-{pid:x}")
+        }
+        SessionIdFormat::Uuid => {
+            // Each RandomState is freshly seeded, so its hasher output is random.
+            let random = |salt: u32| {
+                let mut hasher = RandomState::new().build_hasher();
+                hasher.write_u32(salt);
+                hasher.finish()
+            };
+            let (high, low) = (random(pid), random(!pid));
+            // Version 4, RFC 4122 variant.
+            let high = (high & !0xf000) | 0x4000;
+            let low = (low & !(0b11 << 62)) | (0b10 << 62);
+            format!(
+                "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                high >> 32,
+                (high >> 16) & 0xffff,
+                high & 0xffff,
+                low >> 48,
+                low & 0xffff_ffff_ffff
+            )
+        }
+        SessionIdFormat::Timestamp => {
+            let now = time::OffsetDateTime::now_utc();
+            format!(
+                "{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z-{pid:x}",
+                now.year(),
+                u8::from(now.month()),
+                now.day(),
+                now.hour(),
+                now.minute(),
+                now.second(),
+                now.millisecond()
+            )
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{generate_session_id, run_startup_script_if_configured, startup_script_path};
-    use crate::config::{AppConfig, ThemeConfig};
+    use super::{
+        generate_session_id, run_startup_script_if_configured, startup_message,
+        startup_script_paths,
+    };
+    use crate::config::{
+        AppConfig, AssistantMode, EditMode, GeminiAuth, HistoryRecall, KeyBindings,
+        SessionIdFormat, ThemeConfig, TraceFormat,
+    };
     use crate::python::PythonSession;
     use std::fs;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn generated_session_id_has_expected_shape() {
-        let session_id = generate_session_id();
+        let session_id = generate_session_id(SessionIdFormat::Hex);
         let mut parts = session_id.split('-');
         let ts = parts.next().expect("timestamp segment");
         let pid = parts.next().expect("pid segment");
@@ -157,6 +360,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn uuid_and_timestamp_session_ids_have_expected_shapes() {
+        let uuid = generate_session_id(SessionIdFormat::Uuid);
+        let groups: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|group| group.len()).collect::<Vec<_>>(),
+            vec![8, 4, 4, 4, 12],
+            "unexpected uuid {uuid}"
+        );
+        assert!(
+            groups
+                .iter()
+                .all(|group| group.chars().all(|ch| ch.is_ascii_hexdigit()))
+        );
+        assert!(groups[2].starts_with('4'), "uuid should be version 4");
+        assert!(
+            matches!(groups[3].chars().next(), Some('8' | '9' | 'a' | 'b')),
+            "uuid should use the RFC 4122 variant"
+        );
+        assert_ne!(uuid, generate_session_id(SessionIdFormat::Uuid));
+
+        let timestamp = generate_session_id(SessionIdFormat::Timestamp);
+        let (time, pid) = timestamp.split_once("Z-").expect("pid suffix");
+        let (date, clock) = time.split_once('T').expect("date and time");
+        let (seconds, millis) = clock.split_once('.').expect("milliseconds");
+        assert_eq!(date.len(), 8, "unexpected timestamp {timestamp}");
+        assert_eq!(seconds.len(), 6, "unexpected timestamp {timestamp}");
+        assert_eq!(millis.len(), 3, "unexpected timestamp {timestamp}");
+        assert!(
+            [date, seconds, millis]
+                .iter()
+                .all(|part| part.chars().all(|ch| ch.is_ascii_digit()))
+        );
+        assert!(!pid.is_empty() && pid.chars().all(|ch| ch.is_ascii_hexdigit()));
+    }
+
     #[test]
     fn startup_script_path_uses_implicit_startup_when_not_explicit() {
         let tmp = tempfile::tempdir().expect("tempdir");
@@ -168,15 +407,54 @@ mod tests {
         let cfg = AppConfig {
             config_path: config_dir.join("config.toml"),
             config_is_explicit: false,
+            project_config_path: None,
+            trusted_projects: Vec::new(),
             gemini_api_key: None,
             gemini_model: "model".to_string(),
             gemini_base_url: "https://example.com".to_string(),
-            startup_file: None,
+            gemini_max_retries: 0,
+            gemini_base_backoff_ms: 0,
+            gemini_request_timeout_ms: 0,
+            gemini_max_response_bytes: 0,
+            gemini_auth: GeminiAuth::QueryKey,
+            startup_files: Vec::new(),
+            thinking_delay_ms: 0,
+            history_size: 0,
+            history_recall: HistoryRecall::Block,
+            repl_repr_max_chars: 0,
+            auto_time_threshold_ms: 0,
+            inspect_timeout_ms: 0,
+            max_input_bytes: 0,
+            stream_output: false,
+            inspect_compact: false,
+            echo_expr: false,
+            semicolon_suppresses_output: false,
+            callable_summaries: false,
+            invalid_response_retries: 1,
+            memory_turns: 0,
+            temperature: None,
+            max_output_tokens: None,
+            top_p: None,
+            auto_retry_turn: false,
+            allow_eval: true,
+            allow_apply: false,
+            clipboard_osc52: false,
+            timeline_timestamps: false,
+            timeline_max_bytes: 0,
+            max_steps_shown: 0,
+            auto_page_lines: 0,
+            assistant_mode: AssistantMode::Agent,
+            trace_format: TraceFormat::Text,
+            trace_redact: Vec::new(),
+            trace_max_bytes: 0,
+            session_id_format: SessionIdFormat::Hex,
             theme: ThemeConfig::default(),
+            keys: KeyBindings::default(),
+            edit_mode: EditMode::default(),
         };
 
-        let selected = startup_script_path(&cfg).expect("select startup");
-        assert_eq!(selected, Some(startup));
+        let selected = startup_script_paths(&cfg).expect("select startup");
+        assert_eq!(selected, vec![startup]);
     }
 
     #[test]
@@ -189,15 +467,69 @@ mod tests {
         let cfg = AppConfig {
             config_path: config_dir.join("config.toml"),
             config_is_explicit: true,
+            project_config_path: None,
+            trusted_projects: Vec::new(),
             gemini_api_key: None,
             gemini_model: "model".to_string(),
             gemini_base_url: "https://example.com".to_string(),
-            startup_file: None,
+            gemini_max_retries: 0,
+            gemini_base_backoff_ms: 0,
+            gemini_request_timeout_ms: 0,
+            gemini_max_response_bytes: 0,
+            gemini_auth: GeminiAuth::QueryKey,
+            startup_files: Vec::new(),
+            thinking_delay_ms: 0,
+            history_size: 0,
+            history_recall: HistoryRecall::Block,
+            repl_repr_max_chars: 0,
+            auto_time_threshold_ms: 0,
+            inspect_timeout_ms: 0,
+            max_input_bytes: 0,
+            stream_output: false,
+            inspect_compact: false,
+            echo_expr: false,
+            semicolon_suppresses_output: false,
+            callable_summaries: false,
+            invalid_response_retries: 1,
+            memory_turns: 0,
+            temperature: None,
+            max_output_tokens: None,
+            top_p: None,
+            auto_retry_turn: false,
+            allow_eval: true,
+            allow_apply: false,
+            clipboard_osc52: false,
+            timeline_timestamps: false,
+            timeline_max_bytes: 0,
+            max_steps_shown: 0,
+            auto_page_lines: 0,
+            assistant_mode: AssistantMode::Agent,
+            trace_format: TraceFormat::Text,
+            trace_redact: Vec::new(),
+            trace_max_bytes: 0,
+            session_id_format: SessionIdFormat::Hex,
             theme: ThemeConfig::default(),
+            keys: KeyBindings::default(),
+            edit_mode: EditMode::default(),
         };
 
-        let selected = startup_script_path(&cfg).expect("select startup");
-        assert_eq!(selected, None);
+        let selected = startup_script_paths(&cfg).expect("select startup");
+        assert!(selected.is_empty());
+    }
+
+    #[test]
+    fn startup_message_reports_project_config_before_startup_files() {
+        assert_eq!(startup_message(None, None), None);
+        assert_eq!(
+            startup_message(
+                Some(Path::new("/repo/.pychat.ai.toml")),
+                Some("Startup file /repo/init.py was executed".to_string()),
+            )
+            .as_deref(),
+            Some(
+                "Project config /repo/.pychat.ai.toml was loaded\nStartup file /repo/init.py was executed"
+            )
+        );
     }
 
     #[test]
@@ -209,11 +541,50 @@ mod tests {
         let cfg = AppConfig {
             config_path: tmp.path().join("config.toml"),
             config_is_explicit: true,
+            project_config_path: None,
+            trusted_projects: Vec::new(),
             gemini_api_key: None,
             gemini_model: "model".to_string(),
             gemini_base_url: "https://example.com".to_string(),
-            startup_file: Some(startup_path.clone()),
+            gemini_max_retries: 0,
+            gemini_base_backoff_ms: 0,
+            gemini_request_timeout_ms: 0,
+            gemini_max_response_bytes: 0,
+            gemini_auth: GeminiAuth::QueryKey,
+            startup_files: vec![startup_path.clone()],
+            thinking_delay_ms: 0,
+            history_size: 0,
+            history_recall: HistoryRecall::Block,
+            repl_repr_max_chars: 0,
+            auto_time_threshold_ms: 0,
+            inspect_timeout_ms: 0,
+            max_input_bytes: 0,
+            stream_output: false,
+            inspect_compact: false,
+            echo_expr: false,
+            semicolon_suppresses_output: false,
+            callable_summaries: false,
+            invalid_response_retries: 1,
+            memory_turns: 0,
+            temperature: None,
+            max_output_tokens: None,
+            top_p: None,
+            auto_retry_turn: false,
+            allow_eval: true,
+            allow_apply: false,
+            clipboard_osc52: false,
+            timeline_timestamps: false,
+            timeline_max_bytes: 0,
+            max_steps_shown: 0,
+            auto_page_lines: 0,
+            assistant_mode: AssistantMode::Agent,
+            trace_format: TraceFormat::Text,
+            trace_redact: Vec::new(),
+            trace_max_bytes: 0,
+            session_id_format: SessionIdFormat::Hex,
             theme: ThemeConfig::default(),
+            keys: KeyBindings::default(),
+            edit_mode: EditMode::default(),
         };
         let python = PythonSession::initialize().expect("python session");
 
@@ -240,11 +611,50 @@ mod tests {
         let cfg = AppConfig {
             config_path: PathBuf::from("config.toml"),
             config_is_explicit: true,
+            project_config_path: None,
+            trusted_projects: Vec::new(),
             gemini_api_key: None,
             gemini_model: "model".to_string(),
             gemini_base_url: "https://example.com".to_string(),
-            startup_file: Some(startup_path.clone()),
+            gemini_max_retries: 0,
+            gemini_base_backoff_ms: 0,
+            gemini_request_timeout_ms: 0,
+            gemini_max_response_bytes: 0,
+            gemini_auth: GeminiAuth::QueryKey,
+            startup_files: vec![startup_path.clone()],
+            thinking_delay_ms: 0,
+            history_size: 0,
+            history_recall: HistoryRecall::Block,
+            repl_repr_max_chars: 0,
+            auto_time_threshold_ms: 0,
+            inspect_timeout_ms: 0,
+            max_input_bytes: 0,
+            stream_output: false,
+            inspect_compact: false,
+            echo_expr: false,
+            semicolon_suppresses_output: false,
+            callable_summaries: false,
+            invalid_response_retries: 1,
+            memory_turns: 0,
+            temperature: None,
+            max_output_tokens: None,
+            top_p: None,
+            auto_retry_turn: false,
+            allow_eval: true,
+            allow_apply: false,
+            clipboard_osc52: false,
+            timeline_timestamps: false,
+            timeline_max_bytes: 0,
+            max_steps_shown: 0,
+            auto_page_lines: 0,
+            assistant_mode: AssistantMode::Agent,
+            trace_format: TraceFormat::Text,
+            trace_redact: Vec::new(),
+            trace_max_bytes: 0,
+            session_id_format: SessionIdFormat::Hex,
             theme: ThemeConfig::default(),
+            keys: KeyBindings::default(),
+            edit_mode: EditMode::default(),
         };
         let python = PythonSession::initialize().expect("python session");
 
@@ -252,4 +662,92 @@ mod tests {
         assert!(err.to_string().contains("Failed to execute startup file"));
         assert!(err.to_string().contains("ZeroDivisionError"));
     }
+
+    #[test]
+    fn run_startup_scripts_in_order_and_stop_at_first_failure() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let imports = tmp.path().join("imports.py");
+        let helpers = tmp.path().join("helpers.py");
+        let broken = tmp.path().join("broken.py");
+        let never = tmp.path().join("never.py");
+        fs::write(&imports, "base = 40\n").expect("write imports");
+        fs::write(&helpers, "answer = base + 2\n").expect("write helpers");
+        fs::write(&broken, "1 / 0\n").expect("write broken");
+        fs::write(&never, "reached = True\n").expect("write never");
+
+        let cfg = AppConfig {
+            config_path: PathBuf::from("config.toml"),
+            config_is_explicit: true,
+            project_config_path: None,
+            trusted_projects: Vec::new(),
+            gemini_api_key: None,
+            gemini_model: "model".to_string(),
+            gemini_base_url: "https://example.com".to_string(),
+            gemini_max_retries: 0,
+            gemini_base_backoff_ms: 0,
+            gemini_request_timeout_ms: 0,
+            gemini_max_response_bytes: 0,
+            gemini_auth: GeminiAuth::QueryKey,
+            startup_files: vec![imports.clone(), helpers.clone()],
+            thinking_delay_ms: 0,
+            history_size: 0,
+            history_recall: HistoryRecall::Block,
+            repl_repr_max_chars: 0,
+            auto_time_threshold_ms: 0,
+            inspect_timeout_ms: 0,
+            max_input_bytes: 0,
+            stream_output: false,
+            inspect_compact: false,
+            echo_expr: false,
+            semicolon_suppresses_output: false,
+            callable_summaries: false,
+            invalid_response_retries: 1,
+            memory_turns: 0,
+            temperature: None,
+            max_output_tokens: None,
+            top_p: None,
+            auto_retry_turn: false,
+            allow_eval: true,
+            allow_apply: false,
+            clipboard_osc52: false,
+            timeline_timestamps: false,
+            timeline_max_bytes: 0,
+            max_steps_shown: 0,
+            auto_page_lines: 0,
+            assistant_mode: AssistantMode::Agent,
+            trace_format: TraceFormat::Text,
+            trace_redact: Vec::new(),
+            trace_max_bytes: 0,
+            session_id_format: SessionIdFormat::Hex,
+            theme: ThemeConfig::default(),
+            keys: KeyBindings::default(),
+            edit_mode: EditMode::default(),
+        };
+        let python = PythonSession::initialize().expect("python session");
+
+        let message = run_startup_script_if_configured(&python, &cfg).expect("startup runs");
+        assert_eq!(
+            message,
+            Some(format!(
+                "Startup files were executed in order: {}, {}",
+                imports.display(),
+                helpers.display()
+            ))
+        );
+        assert_eq!(
+            python.eval_expr("answer").expect("read answer").value_repr,
+            "42"
+        );
+
+        let cfg = AppConfig {
+            startup_files: vec![broken.clone(), never],
+            ..cfg
+        };
+        let err = run_startup_script_if_configured(&python, &cfg).expect_err("startup fails");
+        assert!(err.to_string().contains(&format!(
+            "Failed to execute startup file {}",
+            broken.display()
+        )));
+        assert!(python.eval_expr("reached").is_err());
+    }
 }
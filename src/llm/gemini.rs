@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use super::provider::{
     AssistantCandidate, AssistantInput, AssistantMessage, AssistantOutput, AssistantPart,
-    AssistantRole, FunctionDeclaration, LlmError, LlmProvider, LlmResult, LlmTokenUsage,
-    ToolCallingMode,
+    AssistantRole, FunctionDeclaration, GenerationConfig, LlmError, LlmProvider, LlmResult,
+    LlmTokenUsage, ToolCallingMode,
 };
-use crate::http::client::HttpClient;
+use crate::config::{DEFAULT_GEMINI_BASE_BACKOFF_MS, DEFAULT_GEMINI_MAX_RETRIES, GeminiAuth};
+use crate::http::client::{HttpClient, HttpError};
+
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone)]
 pub struct GeminiProvider {
@@ -14,6 +18,11 @@ pub struct GeminiProvider {
     api_key: String,
     model: String,
     base_url: String,
+    auth: GeminiAuth,
+    max_retries: u32,
+    base_backoff: Duration,
+    generation: GenerationConfig,
+    dry_run: bool,
 }
 
 impl GeminiProvider {
@@ -32,9 +41,50 @@ impl GeminiProvider {
             api_key,
             model,
             base_url: base_url.trim_end_matches('/').to_string(),
+            auth: GeminiAuth::QueryKey,
+            max_retries: DEFAULT_GEMINI_MAX_RETRIES,
+            base_backoff: Duration::from_millis(DEFAULT_GEMINI_BASE_BACKOFF_MS),
+            generation: GenerationConfig::default(),
+            dry_run: false,
         })
     }
 
+    /// Sets how many times a request failing with a transient status (429, 500, 502,
+    /// 503, 504) or a transport error is retried, and the initial backoff between
+    /// attempts. The backoff doubles on each retry unless the server sends `Retry-After`.
+    pub fn with_retry_policy(mut self, max_retries: u32, base_backoff_ms: u64) -> Self {
+        self.max_retries = max_retries;
+        self.base_backoff = Duration::from_millis(base_backoff_ms);
+        self
+    }
+
+    /// Sets how the API key is sent with each request.
+    pub fn with_auth(mut self, auth: GeminiAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Sends requests to `model` instead, keeping every other setting.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Sends `generation` as `generationConfig` with every request.
+    pub fn with_generation_config(mut self, generation: GenerationConfig) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Writes each request to the trace and fails it with `LlmError::DryRun` instead of
+    /// sending it, so prompts and tool declarations can be checked without using quota.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// The non-streaming endpoint, used only by `generate` for the tool-less final call
+    /// after the step limit; answers go through [`Self::stream_endpoint`].
     fn endpoint(&self) -> String {
         format!(
             "{}/v1beta/models/{}:generateContent",
@@ -42,7 +92,66 @@ impl GeminiProvider {
         )
     }
 
-    fn build_request(input: &AssistantInput) -> GeminiGenerateRequest {
+    /// The streaming endpoint answers are requested from, with a placeholder for the API
+    /// key where `auth` sends it.
+    pub(crate) fn redacted_endpoint(&self) -> String {
+        let endpoint = format!("{}?alt=sse", self.stream_endpoint());
+        match &self.auth {
+            GeminiAuth::QueryKey => format!("{endpoint}&key=<redacted>"),
+            GeminiAuth::Bearer => format!("{endpoint} (Authorization: Bearer <redacted>)"),
+            GeminiAuth::Header(name) => format!("{endpoint} ({name}: <redacted>)"),
+        }
+    }
+
+    fn auth_query(&self) -> Option<(&str, &str)> {
+        matches!(self.auth, GeminiAuth::QueryKey).then_some(("key", self.api_key.as_str()))
+    }
+
+    fn auth_header(&self) -> Option<(&str, String)> {
+        match &self.auth {
+            GeminiAuth::QueryKey => None,
+            GeminiAuth::Bearer => Some(("authorization", format!("Bearer {}", self.api_key))),
+            GeminiAuth::Header(name) => Some((name.as_str(), self.api_key.clone())),
+        }
+    }
+
+    fn stream_endpoint(&self) -> String {
+        format!(
+            "{}/v1beta/models/{}:streamGenerateContent",
+            self.base_url, self.model
+        )
+    }
+
+    fn trace_dry_run(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        payload: &GeminiGenerateRequest,
+    ) -> LlmResult<AssistantOutput> {
+        let mut query = query.to_vec();
+        query.extend(self.auth_query());
+        let auth_header = self.auth_header();
+        let headers: Vec<_> = auth_header.iter().map(|(n, v)| (*n, v.as_str())).collect();
+        self.client
+            .trace_unsent_json(url, &query, &headers, payload)
+            .map_err(|err| transport_error(&err.into()))?;
+        Err(LlmError::DryRun)
+    }
+
+    fn is_retryable_status(status: u16) -> bool {
+        matches!(status, 429 | 500 | 502 | 503 | 504)
+    }
+
+    /// Returns how long to wait before retry number `attempt` (starting at zero).
+    fn backoff_delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(MAX_RETRY_AFTER);
+        }
+        let backoff = self.base_backoff.saturating_mul(1 << attempt.min(16));
+        backoff.mul_f64(0.5 + jitter_fraction() / 2.0)
+    }
+
+    fn build_request(&self, input: &AssistantInput) -> GeminiGenerateRequest {
         GeminiGenerateRequest {
             contents: input
                 .messages
@@ -74,9 +183,15 @@ impl GeminiProvider {
                 function_calling_config: GeminiFunctionCallingConfig {
                     mode: match input.tool_calling_mode {
                         ToolCallingMode::Auto => "AUTO".to_string(),
+                        ToolCallingMode::None => "NONE".to_string(),
                     },
                 },
             }),
+            generation_config: (!self.generation.is_default()).then_some(GeminiGenerationConfig {
+                temperature: self.generation.temperature,
+                max_output_tokens: self.generation.max_output_tokens,
+                top_p: self.generation.top_p,
+            }),
         }
     }
 
@@ -208,17 +323,40 @@ impl GeminiProvider {
 }
 
 impl LlmProvider for GeminiProvider {
+    fn reconnected(&self) -> Option<Self> {
+        Some(Self {
+            client: self.client.reconnected(),
+            ..self.clone()
+        })
+    }
+
     async fn generate(&self, input: AssistantInput) -> LlmResult<AssistantOutput> {
-        let payload = Self::build_request(&input);
-        let resp = self
-            .client
-            .post_json(
-                self.endpoint().as_str(),
-                &[("key", self.api_key.as_str())],
-                &payload,
-            )
-            .await
-            .map_err(|err| LlmError::Transport(err.to_string()))?;
+        let payload = self.build_request(&input);
+        if self.dry_run {
+            return self.trace_dry_run(&self.endpoint(), &[], &payload);
+        }
+        let query: Vec<_> = self.auth_query().into_iter().collect();
+        let auth_header = self.auth_header();
+        let headers: Vec<_> = auth_header.iter().map(|(n, v)| (*n, v.as_str())).collect();
+        let mut attempt = 0;
+        let resp = loop {
+            let result = self
+                .client
+                .post_json(self.endpoint().as_str(), &query, &headers, &payload)
+                .await;
+            let retry_after = match result {
+                Ok(resp)
+                    if attempt < self.max_retries && Self::is_retryable_status(resp.status) =>
+                {
+                    resp.retry_after
+                }
+                Ok(resp) => break resp,
+                Err(HttpError::Request(_)) if attempt < self.max_retries => None,
+                Err(err) => return Err(transport_error(&err)),
+            };
+            tokio::time::sleep(self.backoff_delay(attempt, retry_after)).await;
+            attempt += 1;
+        };
 
         if !(200..300).contains(&resp.status) {
             let status = resp.status;
@@ -231,6 +369,201 @@ impl LlmProvider for GeminiProvider {
             .map_err(|err| LlmError::Parse(err.to_string()))?;
         Self::extract_output(parsed)
     }
+
+    async fn generate_stream<F: FnMut(&str) + Send>(
+        &self,
+        input: AssistantInput,
+        on_text: &mut F,
+    ) -> LlmResult<AssistantOutput> {
+        let payload = self.build_request(&input);
+        if self.dry_run {
+            return self.trace_dry_run(&self.stream_endpoint(), &[("alt", "sse")], &payload);
+        }
+        let mut decoder = SseDecoder::default();
+        let mut merged = GeminiGenerateResponse::default();
+        let mut parse_error = None;
+        let mut handle_event = |data: String| {
+            if parse_error.is_some() {
+                return;
+            }
+            match serde_json::from_str::<GeminiGenerateResponse>(&data) {
+                Ok(chunk) => {
+                    for text in chunk_texts(&chunk) {
+                        on_text(text);
+                    }
+                    merged.merge_chunk(chunk);
+                }
+                Err(err) => parse_error = Some(LlmError::Parse(err.to_string())),
+            }
+        };
+
+        // Error responses never reach the decoder, so a retry only has to avoid replaying
+        // text after a transport failure cut a successful stream short.
+        let mut query = vec![("alt", "sse")];
+        query.extend(self.auth_query());
+        let auth_header = self.auth_header();
+        let headers: Vec<_> = auth_header.iter().map(|(n, v)| (*n, v.as_str())).collect();
+        let mut attempt = 0;
+        let mut received = false;
+        let resp = loop {
+            let result = self
+                .client
+                .post_json_stream(
+                    self.stream_endpoint().as_str(),
+                    &query,
+                    &headers,
+                    &payload,
+                    |bytes| {
+                        received = true;
+                        for data in decoder.push(bytes) {
+                            handle_event(data);
+                        }
+                    },
+                )
+                .await;
+            let retry_after = match result {
+                Ok(resp)
+                    if attempt < self.max_retries && Self::is_retryable_status(resp.status) =>
+                {
+                    resp.retry_after
+                }
+                Ok(resp) => break resp,
+                Err(HttpError::Request(_)) if attempt < self.max_retries && !received => None,
+                Err(err) => return Err(transport_error(&err)),
+            };
+            tokio::time::sleep(self.backoff_delay(attempt, retry_after)).await;
+            attempt += 1;
+        };
+
+        if !(200..300).contains(&resp.status) {
+            let status = resp.status;
+            let body = resp.body.chars().take(400).collect::<String>();
+            return Err(LlmError::HttpStatus { status, body });
+        }
+
+        if let Some(data) = decoder.finish() {
+            handle_event(data);
+        }
+        if let Some(err) = parse_error {
+            return Err(err);
+        }
+        Self::extract_output(merged)
+    }
+}
+
+/// A pseudo-random value in `[0, 1)` used to spread out retries from concurrent clients.
+fn jitter_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    f64::from(nanos % 1000) / 1000.0
+}
+
+/// Splits a `text/event-stream` body into the payloads of its `data:` fields.
+#[derive(Debug, Default)]
+struct SseDecoder {
+    /// Bytes of the unfinished last line; only complete lines are decoded, so a UTF-8
+    /// character split across network chunks stays intact.
+    buffer: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(newline) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line_bytes = self.buffer.drain(..=newline).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line_bytes[..newline]);
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                if let Some(event) = self.take_event() {
+                    events.push(event);
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data
+                    .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+        }
+        events
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        let rest = String::from_utf8_lossy(&rest);
+        if let Some(value) = rest.trim_end_matches(['\r', '\n']).strip_prefix("data:") {
+            self.data
+                .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+        }
+        self.take_event()
+    }
+
+    fn take_event(&mut self) -> Option<String> {
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(std::mem::take(&mut self.data).join("\n"))
+    }
+}
+
+fn chunk_texts(chunk: &GeminiGenerateResponse) -> Vec<&str> {
+    chunk
+        .candidates
+        .first()
+        .map(|candidate| {
+            candidate
+                .content
+                .parts
+                .iter()
+                .filter_map(|part| part.text.as_deref())
+                .filter(|text| !text.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl GeminiGenerateResponse {
+    fn merge_chunk(&mut self, chunk: GeminiGenerateResponse) {
+        for (index, candidate) in chunk.candidates.into_iter().enumerate() {
+            match self.candidates.get_mut(index) {
+                Some(existing) => existing.merge_chunk(candidate),
+                None => self.candidates.push(candidate),
+            }
+        }
+        if chunk.usage_metadata.is_some() {
+            self.usage_metadata = chunk.usage_metadata;
+        }
+    }
+}
+
+impl GeminiCandidate {
+    fn merge_chunk(&mut self, chunk: GeminiCandidate) {
+        for part in chunk.content.parts {
+            match self.content.parts.last_mut() {
+                Some(last) if last.is_plain_text() && part.text.is_some() => {
+                    let text = part.text.unwrap_or_default();
+                    last.text.get_or_insert_with(String::new).push_str(&text);
+                    if part.thought_signature.is_some() {
+                        last.thought_signature = part.thought_signature;
+                    }
+                }
+                _ => self.content.parts.push(part),
+            }
+        }
+        if chunk.finish_reason.is_some() {
+            self.finish_reason = chunk.finish_reason;
+        }
+    }
+}
+
+impl GeminiPartResponse {
+    fn is_plain_text(&self) -> bool {
+        self.text.is_some()
+            && self.function_call.is_none()
+            && self.function_response.is_none()
+            && self.thought_signature.is_none()
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -243,6 +576,19 @@ struct GeminiGenerateRequest {
     tools: Vec<GeminiTool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_config: Option<GeminiToolConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation_config: Option<GeminiGenerationConfig>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -311,9 +657,10 @@ struct GeminiFunctionCallingConfig {
     mode: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GeminiGenerateResponse {
+    #[serde(default)]
     candidates: Vec<GeminiCandidate>,
     #[serde(default)]
     usage_metadata: Option<GeminiUsageMetadata>,
@@ -333,13 +680,16 @@ struct GeminiUsageMetadata {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GeminiCandidate {
+    /// Missing on candidates blocked by safety filters.
+    #[serde(default)]
     content: GeminiResponseContent,
     #[serde(default)]
     finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct GeminiResponseContent {
+    #[serde(default)]
     parts: Vec<GeminiPartResponse>,
 }
 
@@ -356,16 +706,29 @@ struct GeminiPartResponse {
     thought_signature: Option<String>,
 }
 
+/// Separates a request that timed out, usually a hung connection, from other
+/// transport failures.
+fn transport_error(err: &HttpError) -> LlmError {
+    if err.is_timeout() {
+        LlmError::Timeout(err.to_string())
+    } else {
+        LlmError::Transport(err.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::GeminiProvider;
+    use super::{GeminiProvider, MAX_RETRY_AFTER, SseDecoder};
+    use crate::config::GeminiAuth;
     use crate::http::client::HttpClient;
     use crate::llm::provider::{
         AssistantInput, AssistantMessage, AssistantPart, AssistantRole, FunctionDeclaration,
-        LlmError, LlmProvider, ToolCallingMode,
+        GenerationConfig, LlmError, LlmProvider, ToolCallingMode,
     };
+    use crate::trace::SessionTrace;
     use serde_json::json;
-    use wiremock::matchers::{body_string_contains, method, path, query_param};
+    use std::time::Duration;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     fn basic_input() -> AssistantInput {
@@ -429,6 +792,126 @@ mod tests {
         assert!(out.usage.is_none());
     }
 
+    #[test]
+    fn redacted_endpoint_shows_the_streaming_url_without_the_key() {
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            "https://example.com/".to_string(),
+        )
+        .expect("provider");
+
+        assert_eq!(
+            provider.redacted_endpoint(),
+            "https://example.com/v1beta/models/test-model:streamGenerateContent?alt=sse&key=<redacted>"
+        );
+        assert_eq!(
+            provider.with_auth(GeminiAuth::Bearer).redacted_endpoint(),
+            "https://example.com/v1beta/models/test-model:streamGenerateContent?alt=sse (Authorization: Bearer <redacted>)"
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_sends_the_key_as_the_configured_auth_header() {
+        for (auth, name, value) in [
+            (GeminiAuth::Bearer, "authorization", "Bearer test-key"),
+            (
+                GeminiAuth::Header("x-api-key".to_string()),
+                "x-api-key",
+                "test-key",
+            ),
+        ] {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/v1beta/models/test-model:generateContent"))
+                .and(header(name, value))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "candidates": [{"content": {"parts": [{"text": "ok"}]}}]
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+            let provider = GeminiProvider::new(
+                HttpClient::new(reqwest::Client::new()),
+                Some("test-key".to_string()),
+                "test-model".to_string(),
+                server.uri(),
+            )
+            .expect("provider")
+            .with_auth(auth);
+
+            provider
+                .generate(basic_input())
+                .await
+                .expect("success response");
+            let requests = server.received_requests().await.expect("requests");
+            assert_eq!(requests[0].url.query(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn dry_run_traces_the_request_without_sending_it() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let dir = tempfile::tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_temp_dir("dry", dir.path()).expect("trace");
+
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()).with_trace(trace.clone()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider")
+        .with_dry_run(true);
+
+        let err = provider
+            .generate(basic_input())
+            .await
+            .expect_err("dry run should not answer");
+        assert_eq!(err, LlmError::DryRun);
+        let err = provider
+            .generate_stream(basic_input(), &mut |_: &str| {})
+            .await
+            .expect_err("dry run should not answer");
+        assert_eq!(err, LlmError::DryRun);
+
+        let content = std::fs::read_to_string(trace.file_path()).expect("read trace");
+        assert_eq!(content.matches("POST ").count(), 2, "trace:\n{content}");
+        assert!(content.contains("functionDeclarations"));
+        assert!(content.contains("alt=sse"));
+        assert!(!content.contains("test-key"));
+    }
+
+    #[test]
+    fn build_request_sends_generation_config_only_when_set() {
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            "https://example.com".to_string(),
+        )
+        .expect("provider");
+        let request = serde_json::to_value(provider.build_request(&basic_input())).expect("json");
+        assert!(request.get("generationConfig").is_none());
+
+        let provider = provider.with_generation_config(GenerationConfig {
+            temperature: Some(0.25),
+            max_output_tokens: Some(512),
+            top_p: None,
+        });
+        let request = serde_json::to_value(provider.build_request(&basic_input())).expect("json");
+        assert_eq!(
+            request["generationConfig"],
+            json!({"temperature": 0.25, "maxOutputTokens": 512})
+        );
+    }
+
     #[tokio::test]
     async fn generate_parses_usage_metadata() {
         let server = MockServer::start().await;
@@ -468,6 +951,46 @@ mod tests {
         assert_eq!(usage.total_tokens, Some(18));
     }
 
+    #[tokio::test]
+    async fn generate_parses_safety_candidate_without_content() {
+        let server = MockServer::start().await;
+        let body = r#"{
+            "candidates": [
+                {"finishReason":"SAFETY","safetyRatings":[]},
+                {"finishReason":"STOP","content":{"parts":[{"text":"hi"}]}}
+            ]
+        }"#;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+            .mount(&server)
+            .await;
+
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider");
+
+        let out = provider
+            .generate(basic_input())
+            .await
+            .expect("success response");
+        assert_eq!(out.candidates.len(), 2);
+        assert!(out.candidates[0].safety_blocked);
+        assert!(out.candidates[0].message.parts.is_empty());
+        assert!(!out.candidates[1].safety_blocked);
+        assert_eq!(
+            out.candidates[1].message.parts,
+            vec![AssistantPart::Text {
+                text: "hi".to_string(),
+                thought_signature: None,
+            }]
+        );
+    }
+
     #[tokio::test]
     async fn generate_maps_http_error_status() {
         let server = MockServer::start().await;
@@ -499,6 +1022,98 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn generate_retries_transient_status_then_succeeds() {
+        let server = MockServer::start().await;
+        let body =
+            r#"{"candidates":[{"finishReason":"STOP","content":{"parts":[{"text":"hi"}]}}]}"#;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider")
+        .with_retry_policy(2, 1);
+
+        let out = provider
+            .generate(basic_input())
+            .await
+            .expect("success after retries");
+        assert_eq!(out.candidates.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn generate_does_not_retry_client_errors() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider")
+        .with_retry_policy(2, 1);
+
+        let err = provider
+            .generate(basic_input())
+            .await
+            .expect_err("expected bad request");
+        assert!(matches!(err, LlmError::HttpStatus { status: 400, .. }));
+    }
+
+    #[test]
+    fn backoff_delay_doubles_with_jitter_and_honors_retry_after() {
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            "https://example.com".to_string(),
+        )
+        .expect("provider")
+        .with_retry_policy(3, 100);
+
+        for attempt in 0..3 {
+            let full = Duration::from_millis(100 << attempt);
+            let delay = provider.backoff_delay(attempt, None);
+            assert!(delay >= full / 2 && delay <= full, "{delay:?}");
+        }
+        assert_eq!(
+            provider.backoff_delay(0, Some(Duration::from_secs(4))),
+            Duration::from_secs(4)
+        );
+        assert_eq!(
+            provider.backoff_delay(0, Some(Duration::from_secs(3600))),
+            MAX_RETRY_AFTER
+        );
+    }
+
     #[tokio::test]
     async fn generate_serializes_function_response_and_thought_signature() {
         let server = MockServer::start().await;
@@ -588,6 +1203,132 @@ mod tests {
         assert_eq!(err, LlmError::EmptyCandidates);
     }
 
+    #[tokio::test]
+    async fn generate_stream_reports_text_chunks_and_merges_output() {
+        let server = MockServer::start().await;
+        let body = concat!(
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hel\"}]}}]}\r\n\r\n",
+            "data: {\"candidates\":[{\"finishReason\":\"STOP\",\"content\":{\"parts\":[{\"text\":\"lo\"}]}}],",
+            "\"usageMetadata\":{\"promptTokenCount\":3,\"candidatesTokenCount\":2,\"totalTokenCount\":5}}\r\n\r\n",
+        );
+
+        Mock::given(method("POST"))
+            .and(path("/v1beta/models/test-model:streamGenerateContent"))
+            .and(query_param("alt", "sse"))
+            .and(query_param("key", "test-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider");
+
+        let mut chunks = Vec::new();
+        let out = provider
+            .generate_stream(basic_input(), &mut |text: &str| {
+                chunks.push(text.to_string())
+            })
+            .await
+            .expect("success response");
+
+        assert_eq!(chunks, vec!["Hel".to_string(), "lo".to_string()]);
+        assert_eq!(out.candidates.len(), 1);
+        assert_eq!(out.candidates[0].finish_reason.as_deref(), Some("STOP"));
+        assert_eq!(
+            out.candidates[0].message.parts,
+            vec![AssistantPart::Text {
+                text: "Hello".to_string(),
+                thought_signature: None,
+            }]
+        );
+        assert_eq!(out.usage.expect("usage").total_tokens, Some(5));
+    }
+
+    #[tokio::test]
+    async fn generate_stream_maps_http_error_status() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new()),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider")
+        .with_retry_policy(2, 1);
+
+        let mut chunks = Vec::new();
+        let err = provider
+            .generate_stream(basic_input(), &mut |text: &str| {
+                chunks.push(text.to_string())
+            })
+            .await
+            .expect_err("expected status error");
+
+        assert!(chunks.is_empty());
+        assert!(matches!(err, LlmError::HttpStatus { status: 503, .. }));
+    }
+
+    #[tokio::test]
+    async fn generate_maps_request_timeout_to_timeout_error() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(10)))
+            .mount(&server)
+            .await;
+
+        let provider = GeminiProvider::new(
+            HttpClient::new(reqwest::Client::new())
+                .with_request_timeout(Duration::from_millis(100)),
+            Some("test-key".to_string()),
+            "test-model".to_string(),
+            server.uri(),
+        )
+        .expect("provider")
+        .with_retry_policy(0, 1);
+
+        let started = std::time::Instant::now();
+        let err = provider
+            .generate(basic_input())
+            .await
+            .expect_err("expected timeout");
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(err, LlmError::Timeout(_)));
+        assert!(err.to_string().starts_with("provider connection hung"));
+    }
+
+    #[test]
+    fn sse_decoder_handles_events_split_across_chunks() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b"data: {\"a\"").is_empty());
+        assert_eq!(decoder.push(b":1}\n\ndata: tail"), vec!["{\"a\":1}"]);
+        assert_eq!(decoder.finish(), Some("tail".to_string()));
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn sse_decoder_keeps_multibyte_characters_split_across_chunks() {
+        let bytes = "data: é\n\n".as_bytes();
+        let split = "data: ".len() + 1;
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(&bytes[..split]).is_empty());
+        assert_eq!(decoder.push(&bytes[split..]), vec!["é"]);
+        assert_eq!(decoder.finish(), None);
+    }
+
     #[test]
     fn new_requires_api_key() {
         let err = GeminiProvider::new(
@@ -1,6 +1,7 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -47,6 +48,21 @@ pub struct FunctionDeclaration {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ToolCallingMode {
     Auto,
+    None,
+}
+
+/// Sampling settings sent with every request; `None` leaves the model's default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationConfig {
+    pub temperature: Option<f64>,
+    pub max_output_tokens: Option<u32>,
+    pub top_p: Option<f64>,
+}
+
+impl GenerationConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -71,16 +87,19 @@ pub struct LlmTokenUsage {
     pub total_tokens: Option<u32>,
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LlmTokenUsageTotals {
     pub input_tokens: u64,
     pub output_tokens: u64,
     pub total_tokens: u64,
+    /// Responses that carried no usage metadata, so the totals are a lower bound.
+    pub unreported_calls: u64,
 }
 
 impl LlmTokenUsageTotals {
     pub fn add_usage(&mut self, usage: Option<&LlmTokenUsage>) {
         let Some(usage) = usage else {
+            self.unreported_calls = self.unreported_calls.saturating_add(1);
             return;
         };
 
@@ -95,6 +114,16 @@ impl LlmTokenUsageTotals {
         }
     }
 
+    /// Formats one of the token counts, marking it `?` (or `N+?`) when some responses
+    /// did not report usage.
+    pub fn display_count(&self, value: u64) -> String {
+        match (self.unreported_calls, value) {
+            (0, value) => value.to_string(),
+            (_, 0) => "?".to_string(),
+            (_, value) => format!("{value}+?"),
+        }
+    }
+
     pub fn is_zero(&self) -> bool {
         self.input_tokens == 0 && self.output_tokens == 0 && self.total_tokens == 0
     }
@@ -103,6 +132,7 @@ impl LlmTokenUsageTotals {
         self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
         self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
         self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+        self.unreported_calls = self.unreported_calls.saturating_add(other.unreported_calls);
     }
 }
 
@@ -115,8 +145,15 @@ pub struct AssistantOutput {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LlmError {
     MissingApiKey,
-    HttpStatus { status: u16, body: String },
+    HttpStatus {
+        status: u16,
+        body: String,
+    },
     Transport(String),
+    /// The request got no complete response within the HTTP request timeout.
+    Timeout(String),
+    /// Dry-run mode traced the request instead of sending it.
+    DryRun,
     Parse(String),
     EmptyCandidates,
 }
@@ -129,6 +166,14 @@ impl Display for LlmError {
                 write!(f, "provider request failed with status {status}: {body}")
             }
             Self::Transport(msg) => write!(f, "provider transport error: {msg}"),
+            Self::Timeout(msg) => write!(
+                f,
+                "provider connection hung: no response before the request timeout ({msg})"
+            ),
+            Self::DryRun => write!(
+                f,
+                "dry run: the request was written to the trace and not sent"
+            ),
             Self::Parse(msg) => write!(f, "provider parse error: {msg}"),
             Self::EmptyCandidates => write!(f, "provider returned no candidates"),
         }
@@ -144,4 +189,24 @@ pub trait LlmProvider {
         &self,
         input: AssistantInput,
     ) -> impl std::future::Future<Output = LlmResult<AssistantOutput>> + Send;
+
+    /// Like `generate`, but reports answer text incrementally through `on_text` as it
+    /// arrives. The returned output always carries the complete response.
+    fn generate_stream<F: FnMut(&str) + Send>(
+        &self,
+        input: AssistantInput,
+        on_text: &mut F,
+    ) -> impl std::future::Future<Output = LlmResult<AssistantOutput>> + Send {
+        let _ = on_text;
+        self.generate(input)
+    }
+
+    /// A copy of this provider with fresh connections, used before retrying a turn that
+    /// failed with a transport error. `None` means there is nothing to reconnect.
+    fn reconnected(&self) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
@@ -1,9 +1,10 @@
 use anyhow::Result;
 use clap::Parser;
 use pychat_ai::cli::CliArgs;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let args = CliArgs::parse();
     pychat_ai::run(args).await
 }
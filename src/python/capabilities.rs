@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
 use serde_json::Value;
 
@@ -27,11 +28,20 @@ pub struct EvalInfo {
     pub value_repr: String,
     pub stdout: String,
     pub stderr: String,
+    pub tabular: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecInfo {
+    pub stdout: String,
+    pub stderr: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CapabilityError {
     PythonException(ExceptionInfo),
+    /// The expression was still running when the inspect timeout ran out.
+    Timeout(Duration),
     InvalidResultShape(String),
     Internal(String),
 }
@@ -40,6 +50,13 @@ impl Display for CapabilityError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::PythonException(exc) => write!(f, "{}: {}", exc.exc_type, exc.message),
+            Self::Timeout(timeout) => {
+                write!(
+                    f,
+                    "inspect timed out after {} seconds",
+                    timeout.as_secs_f64()
+                )
+            }
             Self::InvalidResultShape(msg) => write!(f, "invalid capability result shape: {msg}"),
             Self::Internal(msg) => write!(f, "internal capability error: {msg}"),
         }
@@ -54,4 +71,6 @@ pub trait CapabilityProvider {
     fn list_globals(&self) -> CapabilityResult<Vec<GlobalEntry>>;
     fn inspect(&self, expr: &str) -> CapabilityResult<InspectInfo>;
     fn eval_expr(&self, expr: &str) -> CapabilityResult<EvalInfo>;
+    /// Executes statements in the session globals, so definitions persist.
+    fn exec_code(&self, code: &str) -> CapabilityResult<ExecInfo>;
 }
@@ -1,20 +1,27 @@
 use anyhow::{Result, anyhow};
 use pyo3::prelude::*;
 use pyo3::types::{
-    PyAnyMethods, PyDict, PyDictMethods, PyFloat, PyList, PyModule, PyString, PyTuple,
+    PyAnyMethods, PyCFunction, PyDict, PyDictMethods, PyFloat, PyList, PyModule, PyString, PyTuple,
 };
 use serde_json::Value;
-use std::sync::Mutex;
+use std::ffi::CString;
+use std::os::raw::c_long;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::time::Duration;
 
 use super::capabilities::{
-    CapabilityError, CapabilityProvider, CapabilityResult, EvalInfo, GlobalEntry, InspectInfo,
+    CapabilityError, CapabilityProvider, CapabilityResult, EvalInfo, ExecInfo, GlobalEntry,
+    InspectInfo,
 };
+use crate::trace::SessionTrace;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ExecResult {
     pub stdout: String,
     pub stderr: String,
+    /// Messages reported through the `warnings` module, kept apart from stderr.
+    pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,6 +29,9 @@ pub struct EvalResult {
     pub value_repr: String,
     pub stdout: String,
     pub stderr: String,
+    pub warnings: Vec<String>,
+    /// Set when the value comes from pandas or numpy, whose reprs lay out aligned columns.
+    pub tabular: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -38,6 +48,7 @@ pub enum UserRunResult {
     Failed {
         stdout: String,
         stderr: String,
+        warnings: Vec<String>,
         exception: ExceptionInfo,
     },
 }
@@ -51,28 +62,195 @@ pub enum InputCompleteness {
 
 pub struct PythonSession {
     globals: Py<PyDict>,
-    last_exception: Mutex<Option<ExceptionInfo>>,
+    /// The last exception, with whatever the failing run printed before raising.
+    last_exception: Mutex<Option<(ExceptionInfo, ExecResult)>>,
     source_counter: AtomicU64,
+    trace: Option<SessionTrace>,
+    inspect_timeout: Duration,
+    max_input_bytes: usize,
+    /// Bare function and class names evaluate to their signature instead of their repr.
+    callable_summaries: bool,
+}
+
+/// Interrupts code run by [`PythonSession::run_user_input_streaming`] from another
+/// thread. Signals only reach Python's main thread, so the worker running the code is
+/// sent an asynchronous `KeyboardInterrupt` instead.
+#[derive(Debug, Default)]
+pub struct PythonInterrupt {
+    state: Mutex<InterruptState>,
+}
+
+#[derive(Debug, Default)]
+struct InterruptState {
+    /// `threading.get_ident()` of the thread running user code, while it runs.
+    thread_id: Option<c_long>,
+    /// Set when an interrupt arrives before the code has started.
+    requested: bool,
+}
+
+impl PythonInterrupt {
+    /// Raises `KeyboardInterrupt` in the running code at its next bytecode instruction,
+    /// so a blocking call such as `time.sleep` finishes first. Asked before the code
+    /// starts, the code does not run at all.
+    pub fn interrupt(&self) {
+        // The worker holds the GIL when it locks `state` in `start` and `finish`, so the
+        // GIL is always taken first; locking `state` and then waiting for it would deadlock.
+        Python::attach(|_py| {
+            let mut state = self.state.lock().expect("interrupt state lock");
+            match state.thread_id {
+                Some(thread_id) => raise_keyboard_interrupt(thread_id),
+                None => state.requested = true,
+            }
+        });
+    }
+
+    /// Records the thread about to run user code. Returns false, without recording it,
+    /// when an interrupt already arrived.
+    fn start(&self, thread_id: c_long) -> bool {
+        let mut state = self.state.lock().expect("interrupt state lock");
+        if std::mem::take(&mut state.requested) {
+            return false;
+        }
+        state.thread_id = Some(thread_id);
+        true
+    }
+
+    /// Forgets the thread and drops an interrupt that arrived too late to be raised, so
+    /// it cannot fire in whatever the thread runs next.
+    fn finish(&self) {
+        let mut state = self.state.lock().expect("interrupt state lock");
+        if let Some(thread_id) = state.thread_id.take() {
+            // SAFETY: called with the GIL held; a null exception clears a pending one.
+            unsafe { pyo3::ffi::PyThreadState_SetAsyncExc(thread_id, std::ptr::null_mut()) };
+        }
+        state.requested = false;
+    }
+}
+
+/// Must be called with the GIL held.
+fn raise_keyboard_interrupt(thread_id: c_long) {
+    // SAFETY: the GIL is held and `PyExc_KeyboardInterrupt` is a static exception type.
+    unsafe { pyo3::ffi::PyThreadState_SetAsyncExc(thread_id, pyo3::ffi::PyExc_KeyboardInterrupt) };
 }
 
-const INSPECT_EVAL_TIMEOUT_SECONDS: f64 = 1.0;
+// Raised by the inspect timeout handler. Being a class user code cannot reach, it tells
+// the timeout apart from any `TimeoutError` the evaluated expression raises itself.
+pyo3::create_exception!(
+    pychat_ai,
+    InspectTimeoutError,
+    pyo3::exceptions::PyTimeoutError
+);
+
+const DEFAULT_INSPECT_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_INPUT_BYTES: usize = 1024 * 1024;
 const MIN_TIMER_DELAY_SECONDS: f64 = 1e-6;
+const SAMPLE_REPR_MAX_LEVEL: usize = 3;
+const SAMPLE_REPR_MAX_CHARS: usize = 200;
 static SOURCE_REGISTRATION_ID: AtomicU64 = AtomicU64::new(0);
 
+/// Runs pip in a subprocess. The embedded interpreter's `sys.executable` can be the
+/// host binary rather than Python, so a real Python executable is looked up first.
+const PIP_RUNNER: &std::ffi::CStr = cr#"
+import importlib, importlib.util, os, shlex, shutil, subprocess, sys
+
+def python_executable():
+    for candidate in (sys.executable, getattr(sys, "_base_executable", "")):
+        if candidate and os.path.basename(candidate).lower().startswith("python"):
+            return candidate
+    major, minor = sys.version_info[:2]
+    for name in (f"python{major}.{minor}", f"python{major}"):
+        for directory in ("bin", "Scripts"):
+            candidate = os.path.join(sys.exec_prefix, directory, name)
+            if os.path.exists(candidate):
+                return candidate
+    return shutil.which(f"python{major}.{minor}")
+
+def run_pip(args, dry_run):
+    executable = python_executable()
+    if executable is None:
+        raise RuntimeError("could not find a Python executable to run pip with")
+    command = [executable, "-m", "pip", *args]
+    if dry_run:
+        print(shlex.join(command))
+        return
+    if importlib.util.find_spec("pip") is None:
+        raise RuntimeError("pip is not available in this Python environment")
+    result = subprocess.run(command, capture_output=True, text=True)
+    sys.stdout.write(result.stdout)
+    sys.stderr.write(result.stderr)
+    if result.returncode != 0:
+        sys.stderr.write(f"pip exited with status {result.returncode}\n")
+    importlib.invalidate_caches()
+"#;
+
+/// Completes an identifier or dotted name against the session globals, preferring jedi
+/// when it is installed and falling back to the standard library's rlcompleter.
+const COMPLETER: &std::ffi::CStr = cr#"
+import rlcompleter
+
+def complete(text, namespace):
+    try:
+        import jedi
+    except ImportError:
+        jedi = None
+    if jedi is not None:
+        try:
+            completions = jedi.Interpreter(text, [namespace]).complete()
+            return sorted({text + completion.complete for completion in completions})
+        except Exception:
+            pass
+    completer = rlcompleter.Completer(namespace)
+    matches = []
+    while (match := completer.complete(text, len(matches))) is not None:
+        matches.append(match)
+    return sorted(set(matches))
+"#;
+
+/// Replacement for `warnings.showwarning` that records each formatted warning instead
+/// of writing it to stderr.
+const WARNING_COLLECTOR: &std::ffi::CStr = cr#"
+import warnings
+
+class WarningCollector:
+    def __init__(self):
+        self.messages = []
+
+    def __call__(self, message, category, filename, lineno, file=None, line=None):
+        text = warnings.formatwarning(message, category, filename, lineno, line)
+        self.messages.append(text.rstrip("\n"))
+"#;
+
+/// A `StringIO` that also hands every write to `sink`, so output can be shown while the
+/// code that prints it is still running.
+const STREAMING_WRITER: &std::ffi::CStr = cr#"
+import io
+
+class StreamingWriter(io.StringIO):
+    def __init__(self, sink):
+        super().__init__()
+        self._sink = sink
+
+    def write(self, text):
+        count = super().write(text)
+        self._sink(text)
+        return count
+"#;
+
 #[allow(dead_code)]
 impl PythonSession {
     pub fn initialize() -> Result<Self> {
         Python::attach(|py| -> Result<Self> {
-            let globals = PyDict::new(py);
-            let builtins = PyModule::import(py, "builtins")?;
-            globals.set_item("__builtins__", builtins)?;
-            globals.set_item("__name__", "__main__")?;
+            let globals = Self::fresh_globals(py)?;
             Self::health_check(py, &globals)?;
 
             let session = Self {
                 globals: globals.unbind(),
                 last_exception: Mutex::new(None),
                 source_counter: AtomicU64::new(0),
+                trace: None,
+                inspect_timeout: DEFAULT_INSPECT_TIMEOUT,
+                max_input_bytes: DEFAULT_MAX_INPUT_BYTES,
+                callable_summaries: false,
             };
 
             if !session.is_healthy() {
@@ -83,6 +261,52 @@ impl PythonSession {
         })
     }
 
+    /// Replaces the session globals with a fresh namespace and forgets the last
+    /// exception. The trace, inspect timeout and input limit are kept.
+    pub fn reset(&mut self) -> Result<()> {
+        let globals = Python::attach(|py| -> Result<Py<PyDict>> {
+            let globals = Self::fresh_globals(py)?;
+            Self::health_check(py, &globals)?;
+            Ok(globals.unbind())
+        })?;
+        self.globals = globals;
+        self.store_last_exception(None)
+    }
+
+    fn fresh_globals(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+        let globals = PyDict::new(py);
+        let builtins = PyModule::import(py, "builtins")?;
+        globals.set_item("__builtins__", builtins)?;
+        globals.set_item("__name__", "__main__")?;
+        Ok(globals)
+    }
+
+    pub fn with_trace(mut self, trace: SessionTrace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Limits how long inspection-style evaluations may run. A zero duration disables
+    /// the limit.
+    pub fn with_inspect_timeout(mut self, timeout: Duration) -> Self {
+        self.inspect_timeout = timeout;
+        self
+    }
+
+    /// Rejects user input larger than `max_bytes` before it is compiled. Zero disables
+    /// the limit.
+    pub fn with_max_input_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_input_bytes = max_bytes;
+        self
+    }
+
+    /// Shows a bare function or class name, such as `fn`, as its signature (`fn(x)`)
+    /// instead of `<function fn at 0x...>`. Other callables keep their repr.
+    pub fn with_callable_summaries(mut self, enabled: bool) -> Self {
+        self.callable_summaries = enabled;
+        self
+    }
+
     #[allow(dead_code)]
     pub fn exec_code(&self, code: &str) -> Result<ExecResult> {
         Python::attach(|py| -> Result<ExecResult> {
@@ -91,6 +315,54 @@ impl PythonSession {
         })
     }
 
+    /// Inspects `expr` like the `inspect` capability, but with a one-off `timeout` in
+    /// place of the configured one. A zero duration disables the limit.
+    pub fn inspect_with_timeout(
+        &self,
+        expr: &str,
+        timeout: Duration,
+    ) -> CapabilityResult<InspectInfo> {
+        Python::attach(|py| {
+            self.inspect_expr(py, expr, timeout)
+                .map(|value| InspectInfo { value })
+        })
+    }
+
+    /// Returns the qualified type name of `expr`, omitting the module for builtins.
+    pub fn type_of(&self, expr: &str) -> CapabilityResult<String> {
+        Python::attach(|py| {
+            let value = self.eval_guarded(py, expr, "<type>", true, self.inspect_timeout)?;
+            let payload = self.type_payload(py, &value);
+            let module = payload["module"].as_str().unwrap_or_default();
+            let key = if module == "builtins" {
+                "name"
+            } else {
+                "qualified"
+            };
+            Ok(payload[key].as_str().unwrap_or_default().to_string())
+        })
+    }
+
+    /// Returns the repr of `expr` for a watch. Failures do not replace the last error.
+    pub fn watch_repr(&self, expr: &str) -> CapabilityResult<String> {
+        Python::attach(|py| {
+            let value = self.eval_guarded(py, expr, "<watch>", false, self.inspect_timeout)?;
+            Ok(self.safe_repr(py, &value).0)
+        })
+    }
+
+    /// Returns the text `/diff` compares for `expr`: a string's own content, otherwise
+    /// the repr.
+    pub fn diff_text(&self, expr: &str) -> CapabilityResult<String> {
+        Python::attach(|py| {
+            let value = self.eval_guarded(py, expr, "<diff>", true, self.inspect_timeout)?;
+            match value.cast::<PyString>() {
+                Ok(text) => Ok(text.to_string_lossy().into_owned()),
+                Err(_) => Ok(self.safe_repr(py, &value).0),
+            }
+        })
+    }
+
     #[allow(dead_code)]
     pub fn eval_expr(&self, expr: &str) -> Result<EvalResult> {
         Python::attach(|py| -> Result<EvalResult> {
@@ -100,67 +372,196 @@ impl PythonSession {
     }
 
     pub fn run_user_input(&self, line: &str) -> Result<UserRunResult> {
+        Python::attach(|py| self.run_user_input_in(py, self.globals.bind(py), line, None))
+    }
+
+    /// Runs `line` like [`Self::run_user_input`], also sending each stdout write to
+    /// `on_stdout` as it happens. The result still carries the complete stdout. Meant to
+    /// run on a worker thread; `interrupt` stops the code from the thread that waits.
+    pub fn run_user_input_streaming(
+        &self,
+        line: &str,
+        on_stdout: mpsc::Sender<String>,
+        interrupt: &PythonInterrupt,
+    ) -> Result<UserRunResult> {
         Python::attach(|py| -> Result<UserRunResult> {
-            let eval_filename = self
-                .register_source(py, line, "eval")
-                .map_err(|err| pyo3::exceptions::PyRuntimeError::new_err(err.to_string()))?;
-            match self.compile_source(py, line, &eval_filename, "eval") {
-                Ok(compiled) => {
-                    let compiled = compiled.unbind();
-                    let output = self.capture_output(py, |py| {
-                        let globals = self.globals.bind(py);
-                        let value = self.eval_compiled(py, globals, compiled.bind(py))?;
-                        let value_repr = self.safe_repr(py, &value).0;
-                        Ok(Some(value_repr))
+            let thread_id = PyModule::import(py, "threading")?
+                .getattr("get_ident")?
+                .call0()?
+                .extract::<u64>()? as c_long;
+            let sink = PyCFunction::new_closure(
+                py,
+                None,
+                None,
+                move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
+                    let text = args.get_item(0)?;
+                    let text = text.cast::<PyString>()?.to_string_lossy().into_owned();
+                    let _ = on_stdout.send(text);
+                    PyResult::Ok(())
+                },
+            )?;
+            if !interrupt.start(thread_id) {
+                let err = pyo3::exceptions::PyKeyboardInterrupt::new_err(());
+                let exception = self.capture_exception(py, &err)?;
+                self.store_last_exception(Some(exception.clone()))?;
+                return Ok(UserRunResult::Failed {
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    warnings: Vec::new(),
+                    exception,
+                });
+            }
+            let result =
+                self.run_user_input_in(py, self.globals.bind(py), line, Some(sink.as_any()));
+            interrupt.finish();
+            result
+        })
+    }
+
+    /// Runs `body` like [`Self::run_user_input`], but in a copy of the globals where
+    /// `name` is bound to the value of `expr`. The session globals are left unchanged.
+    pub fn run_with_binding(&self, name: &str, expr: &str, body: &str) -> Result<UserRunResult> {
+        Python::attach(|py| -> Result<UserRunResult> {
+            let globals = self.globals.bind(py);
+            let filename = self.register_source(py, expr, "eval")?;
+            let value = match self
+                .compile_source(py, expr, &filename, "eval")
+                .and_then(|compiled| self.eval_compiled(py, globals, &compiled))
+            {
+                Ok(value) => value,
+                Err(err) => {
+                    let exception = self.capture_exception(py, &err)?;
+                    self.store_last_exception(Some(exception.clone()))?;
+                    return Ok(UserRunResult::Failed {
+                        stdout: String::new(),
+                        stderr: String::new(),
+                        warnings: Vec::new(),
+                        exception,
+                    });
+                }
+            };
+            let scope = globals.copy()?;
+            scope.set_item(name, value)?;
+            self.run_user_input_in(py, &scope, body, None)
+        })
+    }
+
+    fn run_user_input_in(
+        &self,
+        py: Python<'_>,
+        globals: &Bound<'_, PyDict>,
+        line: &str,
+        stdout_sink: Option<&Bound<'_, PyAny>>,
+    ) -> Result<UserRunResult> {
+        if self.max_input_bytes > 0 && line.len() > self.max_input_bytes {
+            anyhow::bail!(
+                "input is {} bytes, over the {}-byte limit (python.max_input_bytes)",
+                line.len(),
+                self.max_input_bytes
+            );
+        }
+        let eval_filename = self
+            .register_source(py, line, "eval")
+            .map_err(|err| pyo3::exceptions::PyRuntimeError::new_err(err.to_string()))?;
+        match self.compile_source(py, line, &eval_filename, "eval") {
+            Ok(compiled) => {
+                let compiled = compiled.unbind();
+                let mut tabular = false;
+                let output = self.capture_output_to(py, stdout_sink, |py| {
+                    let value = self.eval_compiled(py, globals, compiled.bind(py))?;
+                    // Like the standard REPL's displayhook, `builtins._` holds the last
+                    // value. A global `_` defined by the user is left to shadow it.
+                    if !value.is_none() && !globals.contains("_")? {
+                        PyModule::import(py, "builtins")?.setattr("_", &value)?;
+                    }
+                    if let Some(summary) = self.callable_summary(py, line, &value) {
+                        return Ok(Some(summary));
+                    }
+                    tabular = self.is_tabular_value(py, &value);
+                    let value_repr = self.safe_repr(py, &value).0;
+                    Ok(Some(value_repr))
+                })?;
+                if let Some(exception) = output.exception {
+                    Ok(UserRunResult::Failed {
+                        stdout: output.stdout,
+                        stderr: output.stderr,
+                        warnings: output.warnings,
+                        exception,
+                    })
+                } else {
+                    Ok(UserRunResult::Evaluated(EvalResult {
+                        value_repr: output.value_repr.unwrap_or_default(),
+                        stdout: output.stdout,
+                        stderr: output.stderr,
+                        warnings: output.warnings,
+                        tabular,
+                    }))
+                }
+            }
+            Err(err) => {
+                if err.is_instance_of::<pyo3::exceptions::PySyntaxError>(py) {
+                    let output = self.capture_output_to(py, stdout_sink, |py| {
+                        let filename = self.register_source(py, line, "exec").map_err(|err| {
+                            pyo3::exceptions::PyRuntimeError::new_err(err.to_string())
+                        })?;
+                        let compiled = self.compile_source(py, line, &filename, "exec")?;
+                        self.exec_compiled(py, globals, &compiled)?;
+                        Ok(None)
                     })?;
                     if let Some(exception) = output.exception {
                         Ok(UserRunResult::Failed {
                             stdout: output.stdout,
                             stderr: output.stderr,
+                            warnings: output.warnings,
                             exception,
                         })
                     } else {
-                        Ok(UserRunResult::Evaluated(EvalResult {
-                            value_repr: output.value_repr.unwrap_or_default(),
+                        Ok(UserRunResult::Executed(ExecResult {
                             stdout: output.stdout,
                             stderr: output.stderr,
+                            warnings: output.warnings,
                         }))
                     }
+                } else {
+                    let exception = self.capture_exception(py, &err)?;
+                    self.store_last_exception(Some(exception.clone()))?;
+                    Ok(UserRunResult::Failed {
+                        stdout: String::new(),
+                        stderr: String::new(),
+                        warnings: Vec::new(),
+                        exception,
+                    })
                 }
-                Err(err) => {
-                    if err.is_instance_of::<pyo3::exceptions::PySyntaxError>(py) {
-                        let output = self.capture_output(py, |py| {
-                            let filename =
-                                self.register_source(py, line, "exec").map_err(|err| {
-                                    pyo3::exceptions::PyRuntimeError::new_err(err.to_string())
-                                })?;
-                            let globals = self.globals.bind(py);
-                            let compiled = self.compile_source(py, line, &filename, "exec")?;
-                            self.exec_compiled(py, globals, &compiled)?;
-                            Ok(None)
-                        })?;
-                        if let Some(exception) = output.exception {
-                            Ok(UserRunResult::Failed {
-                                stdout: output.stdout,
-                                stderr: output.stderr,
-                                exception,
-                            })
-                        } else {
-                            Ok(UserRunResult::Executed(ExecResult {
-                                stdout: output.stdout,
-                                stderr: output.stderr,
-                            }))
-                        }
-                    } else {
-                        let exception = self.capture_exception(py, &err)?;
-                        self.store_last_exception(Some(exception.clone()))?;
-                        Ok(UserRunResult::Failed {
-                            stdout: String::new(),
-                            stderr: String::new(),
-                            exception,
-                        })
-                    }
-                }
+            }
+        }
+    }
+
+    /// Runs `python -m pip <args>` for the interpreter's environment, capturing pip's
+    /// output. With `dry_run` the command is only printed.
+    pub fn run_pip(&self, args: &[String], dry_run: bool) -> Result<UserRunResult> {
+        Python::attach(|py| -> Result<UserRunResult> {
+            let output = self.capture_output(py, |py| {
+                let runner =
+                    PyModule::from_code(py, PIP_RUNNER, c"<pychat.ai-pip>", c"_pychat_ai_pip")?;
+                runner
+                    .getattr("run_pip")?
+                    .call1((PyList::new(py, args)?, dry_run))?;
+                Ok(None)
+            })?;
+
+            if let Some(exception) = output.exception {
+                Ok(UserRunResult::Failed {
+                    stdout: output.stdout,
+                    stderr: output.stderr,
+                    warnings: output.warnings,
+                    exception,
+                })
+            } else {
+                Ok(UserRunResult::Executed(ExecResult {
+                    stdout: output.stdout,
+                    stderr: output.stderr,
+                    warnings: output.warnings,
+                }))
             }
         })
     }
@@ -181,17 +582,36 @@ impl PythonSession {
                 Ok(UserRunResult::Failed {
                     stdout: output.stdout,
                     stderr: output.stderr,
+                    warnings: output.warnings,
                     exception,
                 })
             } else {
                 Ok(UserRunResult::Executed(ExecResult {
                     stdout: output.stdout,
                     stderr: output.stderr,
+                    warnings: output.warnings,
                 }))
             }
         })
     }
 
+    /// Returns the completions for `prefix`, each being the full replacement text.
+    pub fn complete(&self, prefix: &str) -> Result<Vec<String>> {
+        Python::attach(|py| -> Result<Vec<String>> {
+            let completer = PyModule::from_code(
+                py,
+                COMPLETER,
+                c"<pychat.ai-completer>",
+                c"_pychat_ai_completer",
+            )?;
+            let matches = completer
+                .getattr("complete")?
+                .call1((prefix, self.globals.bind(py)))?
+                .extract()?;
+            Ok(matches)
+        })
+    }
+
     pub fn check_input_completeness(&self, source: &str) -> Result<InputCompleteness> {
         Python::attach(|py| -> Result<InputCompleteness> {
             let codeop = PyModule::import(py, "codeop")?;
@@ -223,6 +643,14 @@ impl PythonSession {
     }
 
     #[allow(dead_code)]
+    /// The embedded interpreter's version, such as `3.12.1`.
+    pub fn python_version(&self) -> String {
+        Python::attach(|py| {
+            let info = py.version_info();
+            format!("{}.{}.{}", info.major, info.minor, info.patch)
+        })
+    }
+
     pub fn list_globals(&self) -> Result<Vec<GlobalEntry>> {
         Python::attach(|py| -> Result<Vec<GlobalEntry>> {
             let globals = self.globals.bind(py);
@@ -246,11 +674,31 @@ impl PythonSession {
         })
     }
 
+    /// Runs a full garbage collection and returns the number of unreachable objects
+    /// found together with the number of objects the collector still tracks.
+    pub fn collect_garbage(&self) -> Result<(usize, usize)> {
+        let result = self
+            .exec_code("print(__import__('gc').collect(), len(__import__('gc').get_objects()))")?;
+        let mut counts = result.stdout.split_whitespace().map(str::parse::<usize>);
+        match (counts.next(), counts.next()) {
+            (Some(Ok(collected)), Some(Ok(tracked))) => Ok((collected, tracked)),
+            _ => Err(anyhow!("unexpected gc output: {}", result.stdout.trim())),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn get_last_exception(&self) -> Result<Option<ExceptionInfo>> {
         self.last_exception
             .lock()
-            .map(|value| value.clone())
+            .map(|value| value.as_ref().map(|(exception, _)| exception.clone()))
+            .map_err(|err| anyhow!("failed to lock last_exception: {err}"))
+    }
+
+    /// Returns the stdout/stderr captured by the run that raised the last exception.
+    pub fn get_last_exception_output(&self) -> Result<Option<ExecResult>> {
+        self.last_exception
+            .lock()
+            .map(|value| value.as_ref().map(|(_, output)| output.clone()))
             .map_err(|err| anyhow!("failed to lock last_exception: {err}"))
     }
 
@@ -307,21 +755,30 @@ impl PythonSession {
         py: Python<'py>,
         globals: &Bound<'py, PyDict>,
         compiled: &Bound<'py, PyAny>,
-        timeout_seconds: f64,
+        timeout: Duration,
     ) -> PyResult<Bound<'py, PyAny>> {
+        if timeout.is_zero() {
+            return self.eval_compiled(py, globals, compiled);
+        }
         let timeout_context = match self.inspect_timeout_context(py)? {
             Some(ctx) => ctx,
             None => return self.eval_compiled(py, globals, compiled),
         };
 
-        let timeout_handler = PyModule::from_code(
+        let timeout_seconds = timeout.as_secs_f64();
+        let handler_code = CString::new(format!(
+            "def _pychat_ai_timeout_handler(_signum, _frame):
+    raise InspectTimeoutError('inspect timed out after {timeout_seconds} seconds')"
+        ))
+        .map_err(|err| pyo3::exceptions::PyRuntimeError::new_err(err.to_string()))?;
+        let handler_module = PyModule::from_code(
             py,
-            c"def _pychat_ai_timeout_handler(_signum, _frame):
-    raise TimeoutError('inspect timed out after 1.0 seconds')",
+            &handler_code,
             c"<pychat.ai-timeout-handler>",
             c"_pychat_ai_timeout_handler",
-        )?
-        .getattr("_pychat_ai_timeout_handler")?;
+        )?;
+        handler_module.setattr("InspectTimeoutError", py.get_type::<InspectTimeoutError>())?;
+        let timeout_handler = handler_module.getattr("_pychat_ai_timeout_handler")?;
 
         timeout_context
             .signal
@@ -489,6 +946,15 @@ impl PythonSession {
     }
 
     fn store_last_exception(&self, value: Option<ExceptionInfo>) -> Result<()> {
+        self.store_last_exception_with_output(
+            value.map(|exception| (exception, ExecResult::default())),
+        )
+    }
+
+    fn store_last_exception_with_output(
+        &self,
+        value: Option<(ExceptionInfo, ExecResult)>,
+    ) -> Result<()> {
         let mut guard = self
             .last_exception
             .lock()
@@ -499,12 +965,14 @@ impl PythonSession {
 
     fn eval_expr_inner(&self, py: Python<'_>, expr: &str) -> Result<EvalResult, ExceptionInfo> {
         let globals = self.globals.bind(py);
+        let mut tabular = false;
         let output = self.capture_output(py, |py| {
             let filename = self
                 .register_source(py, expr, "eval")
                 .map_err(|err| pyo3::exceptions::PyRuntimeError::new_err(err.to_string()))?;
             let compiled = self.compile_source(py, expr, &filename, "eval")?;
             let value = self.eval_compiled(py, globals, &compiled)?;
+            tabular = self.is_tabular_value(py, &value);
             let value_repr = self.safe_repr(py, &value).0;
             Ok(Some(value_repr))
         });
@@ -518,6 +986,8 @@ impl PythonSession {
                         value_repr: output.value_repr.unwrap_or_default(),
                         stdout: output.stdout,
                         stderr: output.stderr,
+                        warnings: output.warnings,
+                        tabular,
                     })
                 }
             }
@@ -552,6 +1022,7 @@ impl PythonSession {
                     Ok(ExecResult {
                         stdout: output.stdout,
                         stderr: output.stderr,
+                        warnings: output.warnings,
                     })
                 }
             }
@@ -567,35 +1038,43 @@ impl PythonSession {
         }
     }
 
-    fn inspect_expr(&self, py: Python<'_>, expr: &str) -> CapabilityResult<Value> {
+    fn inspect_expr(
+        &self,
+        py: Python<'_>,
+        expr: &str,
+        timeout: Duration,
+    ) -> CapabilityResult<Value> {
+        let value = self.eval_guarded(py, expr, "<inspect>", true, timeout)?;
+        self.build_inspect_payload(py, &value)
+            .map_err(CapabilityError::PythonException)
+    }
+
+    /// Evaluates `expr` under `timeout`, failing with [`CapabilityError::Timeout`] when it
+    /// runs out. Exceptions are recorded as the last error only when `remember_exception`
+    /// is set.
+    fn eval_guarded<'py>(
+        &self,
+        py: Python<'py>,
+        expr: &str,
+        filename: &str,
+        remember_exception: bool,
+        timeout: Duration,
+    ) -> CapabilityResult<Bound<'py, PyAny>> {
         let globals = self.globals.bind(py);
-        let value = match self.compile_source(py, expr, "<inspect>", "eval") {
-            Ok(compiled) => match self.eval_compiled_with_timeout(
-                py,
-                globals,
-                &compiled,
-                INSPECT_EVAL_TIMEOUT_SECONDS,
-            ) {
-                Ok(value) => value,
-                Err(err) => {
-                    let exception = self
-                        .capture_exception(py, &err)
-                        .map_err(Self::cap_internal)?;
-                    let _ = self.store_last_exception(Some(exception.clone()));
-                    return Err(CapabilityError::PythonException(exception));
-                }
-            },
-            Err(err) => {
+        self.compile_source(py, expr, filename, "eval")
+            .and_then(|compiled| self.eval_compiled_with_timeout(py, globals, &compiled, timeout))
+            .or_else(|err| {
                 let exception = self
                     .capture_exception(py, &err)
                     .map_err(Self::cap_internal)?;
-                let _ = self.store_last_exception(Some(exception.clone()));
-                return Err(CapabilityError::PythonException(exception));
-            }
-        };
-
-        self.build_inspect_payload(py, &value)
-            .map_err(CapabilityError::PythonException)
+                if remember_exception {
+                    let _ = self.store_last_exception(Some(exception.clone()));
+                }
+                if err.is_instance_of::<InspectTimeoutError>(py) {
+                    return Err(CapabilityError::Timeout(timeout));
+                }
+                Err(CapabilityError::PythonException(exception))
+            })
     }
 
     fn build_inspect_payload(
@@ -799,6 +1278,11 @@ impl PythonSession {
         })
     }
 
+    fn is_tabular_value(&self, py: Python<'_>, value: &Bound<'_, PyAny>) -> bool {
+        let payload = self.type_payload(py, value);
+        payload["module"].as_str().is_some_and(is_tabular_module)
+    }
+
     fn doc_payload(&self, py: Python<'_>, value: &Bound<'_, PyAny>) -> Value {
         let doc = match value.getattr("__doc__") {
             Ok(v) => v,
@@ -876,14 +1360,22 @@ impl PythonSession {
             return None;
         }
 
+        let sample_repr = self.sample_repr_fn(_py);
+        let item_repr = |item: &Bound<'_, PyAny>| match &sample_repr {
+            Some(repr) => repr
+                .call1((item,))
+                .and_then(|text| text.extract::<String>())
+                .unwrap_or_else(|_| self.safe_repr(_py, item).0),
+            None => self.safe_repr(_py, item).0,
+        };
         let mut items = Vec::new();
         if let Ok(dict) = value.cast::<PyDict>() {
             for (key, item) in dict.iter() {
                 if items.len() >= super::capabilities::INSPECT_SAMPLE_MAX_ITEMS {
                     break;
                 }
-                let key_repr = self.safe_repr(_py, &key).0;
-                let item_repr = self.safe_repr(_py, &item).0;
+                let key_repr = item_repr(&key);
+                let item_repr = item_repr(&item);
                 items.push(Value::String(format!("{key_repr}: {item_repr}")));
             }
         } else if kind == "sequence" || kind == "set" {
@@ -905,7 +1397,7 @@ impl PythonSession {
                 if items.len() >= super::capabilities::INSPECT_SAMPLE_MAX_ITEMS {
                     break;
                 }
-                items.push(Value::String(self.safe_repr(_py, &item).0));
+                items.push(Value::String(item_repr(&item)));
             }
         } else {
             return None;
@@ -920,6 +1412,19 @@ impl PythonSession {
         }))
     }
 
+    /// A `reprlib` repr for sample items. Its depth limit keeps nested cycles and deeply
+    /// nested containers from recursing, and long strings are shortened.
+    fn sample_repr_fn<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        let repr = PyModule::import(py, "reprlib")
+            .and_then(|reprlib| reprlib.getattr("Repr"))
+            .and_then(|class| class.call0())
+            .ok()?;
+        repr.setattr("maxlevel", SAMPLE_REPR_MAX_LEVEL).ok()?;
+        repr.setattr("maxstring", SAMPLE_REPR_MAX_CHARS).ok()?;
+        repr.setattr("maxother", SAMPLE_REPR_MAX_CHARS).ok()?;
+        repr.getattr("repr").ok()
+    }
+
     fn members_payload(&self, py: Python<'_>, value: &Bound<'_, PyAny>) -> Value {
         let builtins = match PyModule::import(py, "builtins") {
             Ok(v) => v,
@@ -1013,18 +1518,51 @@ impl PythonSession {
         })
     }
 
+    /// `name(signature)` for a function or class typed as a bare name, when
+    /// `callable_summaries` is on and the signature is known.
+    fn callable_summary(
+        &self,
+        py: Python<'_>,
+        source: &str,
+        value: &Bound<'_, PyAny>,
+    ) -> Option<String> {
+        let name = source.trim();
+        if !self.callable_summaries || !is_bare_name(name) {
+            return None;
+        }
+        // Instances with `__call__` are data too, so only routines and classes qualify.
+        let inspect = PyModule::import(py, "inspect").ok()?;
+        let is_routine_or_class = ["isroutine", "isclass"].into_iter().any(|check| {
+            inspect
+                .call_method1(check, (value,))
+                .and_then(|result| result.is_truthy())
+                .unwrap_or(false)
+        });
+        if !is_routine_or_class {
+            return None;
+        }
+        let signature = Self::signature_text(Some(&inspect), value)?;
+        Some(format!("{name}{signature}"))
+    }
+
+    fn signature_text(
+        inspect: Option<&Bound<'_, PyModule>>,
+        value: &Bound<'_, PyAny>,
+    ) -> Option<String> {
+        inspect
+            .and_then(|module| module.getattr("signature").ok())
+            .and_then(|f| f.call1((value,)).ok())
+            .and_then(|v| v.str().ok())
+            .map(|v| v.to_string_lossy().into_owned())
+    }
+
     fn callable_payload(&self, py: Python<'_>, value: &Bound<'_, PyAny>) -> Value {
         let module = value
             .getattr("__module__")
             .ok()
             .and_then(|v| v.extract::<String>().ok());
         let inspect = PyModule::import(py, "inspect").ok();
-        let signature = inspect
-            .as_ref()
-            .and_then(|module| module.getattr("signature").ok())
-            .and_then(|f| f.call1((value,)).ok())
-            .and_then(|v| v.str().ok())
-            .map(|v| v.to_string_lossy().into_owned());
+        let signature = Self::signature_text(inspect.as_ref(), value);
         let doc_text = self
             .doc_payload(py, value)
             .get("text")
@@ -1062,43 +1600,82 @@ impl PythonSession {
     }
 
     fn capture_output<F>(&self, py: Python<'_>, operation: F) -> Result<CapturedOutput>
+    where
+        F: FnOnce(Python<'_>) -> PyResult<Option<String>>,
+    {
+        self.capture_output_to(py, None, operation)
+    }
+
+    /// Like [`Self::capture_output`]; with a `stdout_sink`, every stdout write is also
+    /// passed to it while `operation` runs.
+    fn capture_output_to<F>(
+        &self,
+        py: Python<'_>,
+        stdout_sink: Option<&Bound<'_, PyAny>>,
+        operation: F,
+    ) -> Result<CapturedOutput>
     where
         F: FnOnce(Python<'_>) -> PyResult<Option<String>>,
     {
         let sys = PyModule::import(py, "sys")?;
         let io = PyModule::import(py, "io")?;
-        let stdout_buffer = io.getattr("StringIO")?.call0()?;
+        let stdout_buffer = match stdout_sink {
+            Some(sink) => PyModule::from_code(
+                py,
+                STREAMING_WRITER,
+                c"<pychat.ai-stream>",
+                c"_pychat_ai_stream",
+            )?
+            .getattr("StreamingWriter")?
+            .call1((sink,))?,
+            None => io.getattr("StringIO")?.call0()?,
+        };
         let stderr_buffer = io.getattr("StringIO")?.call0()?;
         let previous_stdout = sys.getattr("stdout")?.unbind();
         let previous_stderr = sys.getattr("stderr")?.unbind();
         sys.setattr("stdout", &stdout_buffer)?;
         sys.setattr("stderr", &stderr_buffer)?;
         let mut redirect_guard = StdioRedirectGuard::new(sys, previous_stdout, previous_stderr);
+        let warning_collector = PyModule::from_code(
+            py,
+            WARNING_COLLECTOR,
+            c"<pychat.ai-warnings>",
+            c"_pychat_ai_warnings",
+        )?
+        .getattr("WarningCollector")?
+        .call0()?;
+        let warnings_module = PyModule::import(py, "warnings")?;
+        let showwarning_guard = ShowWarningGuard::new(warnings_module, &warning_collector)?;
 
         let operation_result = operation(py);
-        let stdout = stdout_buffer
-            .getattr("getvalue")?
-            .call0()?
-            .extract::<String>()?;
-        let stderr = stderr_buffer
-            .getattr("getvalue")?
-            .call0()?
-            .extract::<String>()?;
+        drop(showwarning_guard);
+        let stdout = self.captured_text(&stdout_buffer, "stdout")?;
+        let stderr = self.captured_text(&stderr_buffer, "stderr")?;
+        let warnings: Vec<String> = warning_collector.getattr("messages")?.extract()?;
         redirect_guard.restore()?;
 
         match operation_result {
             Ok(value_repr) => Ok(CapturedOutput {
                 stdout,
                 stderr,
+                warnings,
                 value_repr,
                 exception: None,
             }),
             Err(err) => {
                 let exception = self.capture_exception(py, &err)?;
-                self.store_last_exception(Some(exception.clone()))?;
+                self.store_last_exception_with_output(Some((
+                    exception.clone(),
+                    ExecResult {
+                        stdout: stdout.clone(),
+                        stderr: stderr.clone(),
+                        warnings: warnings.clone(),
+                    },
+                )))?;
                 Ok(CapturedOutput {
                     stdout,
                     stderr,
+                    warnings,
                     value_repr: None,
                     exception: Some(exception),
                 })
@@ -1108,6 +1685,26 @@ impl PythonSession {
 }
 
 impl PythonSession {
+    /// Reads a capture buffer, replacing text that is not valid UTF-8 (such as lone
+    /// surrogates) instead of failing the whole run.
+    fn captured_text(&self, buffer: &Bound<'_, PyAny>, stream: &str) -> Result<String> {
+        let value = buffer.getattr("getvalue")?.call0()?;
+        let text = value
+            .cast::<PyString>()
+            .map_err(|err| anyhow!("captured {stream} is not a string: {err}"))?;
+        match text.to_str() {
+            Ok(text) => Ok(text.to_string()),
+            Err(_) => {
+                if let Some(trace) = &self.trace {
+                    trace.log_python_warning(&format!(
+                        "captured {stream} contained invalid UTF-8; replaced undecodable characters"
+                    ));
+                }
+                Ok(text.to_string_lossy().into_owned())
+            }
+        }
+    }
+
     fn cap_internal(err: impl std::fmt::Display) -> CapabilityError {
         CapabilityError::Internal(err.to_string())
     }
@@ -1120,10 +1717,7 @@ impl CapabilityProvider for PythonSession {
     }
 
     fn inspect(&self, expr: &str) -> CapabilityResult<InspectInfo> {
-        Python::attach(|py| {
-            self.inspect_expr(py, expr)
-                .map(|value| InspectInfo { value })
-        })
+        self.inspect_with_timeout(expr, self.inspect_timeout)
     }
 
     fn eval_expr(&self, expr: &str) -> CapabilityResult<EvalInfo> {
@@ -1132,6 +1726,17 @@ impl CapabilityProvider for PythonSession {
                 value_repr: result.value_repr,
                 stdout: result.stdout,
                 stderr: result.stderr,
+                tabular: result.tabular,
+            }),
+            Err(exception) => Err(CapabilityError::PythonException(exception)),
+        })
+    }
+
+    fn exec_code(&self, code: &str) -> CapabilityResult<ExecInfo> {
+        Python::attach(|py| match self.exec_code_inner(py, code) {
+            Ok(result) => Ok(ExecInfo {
+                stdout: result.stdout,
+                stderr: result.stderr,
             }),
             Err(exception) => Err(CapabilityError::PythonException(exception)),
         })
@@ -1141,6 +1746,7 @@ impl CapabilityProvider for PythonSession {
 struct CapturedOutput {
     stdout: String,
     stderr: String,
+    warnings: Vec<String>,
     value_repr: Option<String>,
     exception: Option<ExceptionInfo>,
 }
@@ -1195,6 +1801,33 @@ impl Drop for StdioRedirectGuard<'_> {
     }
 }
 
+/// Routes `warnings.showwarning` to a collector while user code runs, restoring the
+/// previous hook when dropped.
+struct ShowWarningGuard<'py> {
+    warnings: Bound<'py, PyModule>,
+    previous_showwarning: Py<PyAny>,
+}
+
+impl<'py> ShowWarningGuard<'py> {
+    fn new(warnings: Bound<'py, PyModule>, collector: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let previous_showwarning = warnings.getattr("showwarning")?.unbind();
+        warnings.setattr("showwarning", collector)?;
+        Ok(Self {
+            warnings,
+            previous_showwarning,
+        })
+    }
+}
+
+impl Drop for ShowWarningGuard<'_> {
+    fn drop(&mut self) {
+        let py = self.warnings.py();
+        let _ = self
+            .warnings
+            .setattr("showwarning", self.previous_showwarning.bind(py));
+    }
+}
+
 struct InspectTimeoutContext<'py> {
     signal: Bound<'py, PyModule>,
     sigalrm: Bound<'py, PyAny>,
@@ -1203,6 +1836,21 @@ struct InspectTimeoutContext<'py> {
     previous_timer: (f64, f64),
 }
 
+/// A name or dotted attribute path, such as `fn` or `np.linalg.norm`.
+fn is_bare_name(text: &str) -> bool {
+    text.split('.').all(|segment| {
+        let mut chars = segment.chars();
+        chars
+            .next()
+            .is_some_and(|first| first.is_alphabetic() || first == '_')
+            && chars.all(|ch| ch.is_alphanumeric() || ch == '_')
+    })
+}
+
+fn is_tabular_module(module: &str) -> bool {
+    matches!(module.split('.').next(), Some("pandas" | "numpy"))
+}
+
 #[cfg(test)]
 mod tests {
     use std::panic::{AssertUnwindSafe, catch_unwind};
@@ -1213,8 +1861,11 @@ mod tests {
     use pyo3::{PyResult, Python};
 
     use crate::python::{CapabilityError, CapabilityProvider};
+    use crate::trace::SessionTrace;
+    use std::fs;
+    use tempfile::tempdir;
 
-    use super::{InputCompleteness, PythonSession, UserRunResult};
+    use super::{ExecResult, InputCompleteness, PythonInterrupt, PythonSession, UserRunResult};
 
     static SIGNAL_TEST_MUTEX: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
 
@@ -1226,6 +1877,47 @@ mod tests {
         assert_eq!(eval.value_repr, "42");
     }
 
+    #[test]
+    fn eval_flags_pandas_and_numpy_values_as_tabular() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .exec_code("class Frame:\n    pass\nFrame.__module__ = 'pandas.core.frame'")
+            .expect("define stub");
+
+        assert!(session.eval_expr("Frame()").expect("eval frame").tabular);
+        assert!(!session.eval_expr("[1, 2]").expect("eval list").tabular);
+    }
+
+    #[test]
+    fn reset_clears_globals_and_last_exception() {
+        let mut session = PythonSession::initialize().expect("python session");
+        session.exec_code("polluted = 1").expect("exec polluted");
+        let _ = session.eval_expr("missing_name");
+        assert!(
+            session
+                .get_last_exception()
+                .expect("last exception")
+                .is_some()
+        );
+
+        session.reset().expect("reset session");
+
+        assert!(
+            session
+                .get_last_exception()
+                .expect("last exception")
+                .is_none()
+        );
+        let err = session
+            .eval_expr("polluted")
+            .expect_err("reset should drop globals");
+        assert!(err.to_string().contains("NameError"));
+        assert_eq!(
+            session.eval_expr("__name__").expect("eval name").value_repr,
+            "'__main__'"
+        );
+    }
+
     #[test]
     fn eval_sees_prior_exec_defined_globals() {
         let session = PythonSession::initialize().expect("python session");
@@ -1254,6 +1946,269 @@ mod tests {
         assert_eq!(roundtrip.value_repr, "123");
     }
 
+    #[test]
+    fn run_with_binding_uses_temporary_scope_and_leaves_globals_unchanged() {
+        let session = PythonSession::initialize().expect("python session");
+        session.exec_code("base = 10").expect("exec base");
+
+        let evaluated = session
+            .run_with_binding("x", "base * 2", "x + base")
+            .expect("evaluate body");
+        assert!(matches!(
+            evaluated,
+            UserRunResult::Evaluated(ref r) if r.value_repr == "30"
+        ));
+        let executed = session
+            .run_with_binding("x", "1", "y = x")
+            .expect("execute body");
+        assert!(matches!(executed, UserRunResult::Executed(_)));
+
+        for name in ["x", "y"] {
+            let err = session
+                .eval_expr(name)
+                .expect_err("temporary names should not leak into globals");
+            assert!(err.to_string().contains("NameError"));
+        }
+        assert_eq!(
+            session.eval_expr("base").expect("eval base").value_repr,
+            "10"
+        );
+    }
+
+    #[test]
+    fn run_user_input_streaming_sends_stdout_before_the_run_finishes() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .exec_code("import threading\ngate = threading.Event()")
+            .expect("create gate");
+        let (chunk_tx, chunk_rx) = std::sync::mpsc::channel();
+        let interrupt = PythonInterrupt::default();
+
+        std::thread::scope(|scope| {
+            let worker = scope.spawn(|| {
+                session.run_user_input_streaming(
+                    "print('tick 0')\ngate.wait()\nprint('tick 1')",
+                    chunk_tx,
+                    &interrupt,
+                )
+            });
+
+            let first = chunk_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("first chunk");
+            assert_eq!(first, "tick 0");
+            // The code is blocked on the gate, so this chunk cannot come from a finished run.
+            assert!(!worker.is_finished());
+            Python::attach(|py| {
+                session
+                    .globals
+                    .bind(py)
+                    .get_item("gate")
+                    .expect("gate defined")
+                    .call_method0("set")
+                    .expect("open gate");
+            });
+
+            let result = worker.join().expect("worker thread").expect("run");
+            assert_eq!(
+                result,
+                UserRunResult::Executed(ExecResult {
+                    stdout: "tick 0\ntick 1\n".to_string(),
+                    stderr: String::new(),
+                    warnings: Vec::new(),
+                })
+            );
+        });
+        let rest: String = chunk_rx.try_iter().collect();
+        assert_eq!(rest, "\ntick 1\n");
+    }
+
+    #[test]
+    fn python_interrupt_racing_the_end_of_a_run_does_not_deadlock() {
+        let session = std::sync::Arc::new(PythonSession::initialize().expect("python session"));
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let runner = std::sync::Arc::clone(&session);
+        std::thread::spawn(move || {
+            for _ in 0..200 {
+                let interrupt = PythonInterrupt::default();
+                std::thread::scope(|scope| {
+                    let (chunk_tx, chunk_rx) = std::sync::mpsc::channel();
+                    let worker = scope.spawn(|| {
+                        runner.run_user_input_streaming("print('go')", chunk_tx, &interrupt)
+                    });
+                    // Once the code has printed it is about to finish, which is the window
+                    // where an interrupt meets `finish`.
+                    chunk_rx
+                        .recv_timeout(Duration::from_secs(5))
+                        .expect("code started");
+                    while !worker.is_finished() {
+                        interrupt.interrupt();
+                    }
+                    let _ = worker.join().expect("worker thread");
+                });
+            }
+            done_tx.send(()).expect("report done");
+        });
+
+        done_rx
+            .recv_timeout(Duration::from_secs(30))
+            .expect("interrupting as runs start and finish should not deadlock");
+        let (chunk_tx, _chunk_rx) = std::sync::mpsc::channel();
+        assert!(matches!(
+            session
+                .run_user_input_streaming("1 + 1", chunk_tx, &PythonInterrupt::default())
+                .expect("run"),
+            UserRunResult::Evaluated(_)
+        ));
+    }
+
+    #[test]
+    fn python_interrupt_stops_streaming_code_on_its_worker_thread() {
+        let session = PythonSession::initialize().expect("python session");
+        let (chunk_tx, chunk_rx) = std::sync::mpsc::channel();
+        let interrupt = PythonInterrupt::default();
+
+        std::thread::scope(|scope| {
+            let worker = scope.spawn(|| {
+                session.run_user_input_streaming(
+                    "print('started')\nwhile True:\n    pass",
+                    chunk_tx,
+                    &interrupt,
+                )
+            });
+
+            let first = chunk_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("first chunk");
+            assert_eq!(first, "started");
+            interrupt.interrupt();
+
+            match worker.join().expect("worker thread").expect("run") {
+                UserRunResult::Failed {
+                    stdout, exception, ..
+                } => {
+                    assert_eq!(stdout, "started\n");
+                    assert_eq!(exception.exc_type, "KeyboardInterrupt");
+                }
+                other => panic!("expected an interrupted run, got {other:?}"),
+            }
+        });
+
+        let interrupt = PythonInterrupt::default();
+        interrupt.interrupt();
+        let (chunk_tx, _chunk_rx) = std::sync::mpsc::channel();
+        let result = session
+            .run_user_input_streaming("x = 1", chunk_tx, &interrupt)
+            .expect("run");
+        assert!(matches!(
+            result,
+            UserRunResult::Failed { exception, .. } if exception.exc_type == "KeyboardInterrupt"
+        ));
+        assert!(session.eval_expr("x").is_err());
+        let (chunk_tx, _chunk_rx) = std::sync::mpsc::channel();
+        assert!(matches!(
+            session
+                .run_user_input_streaming("1 + 1", chunk_tx, &interrupt)
+                .expect("run"),
+            UserRunResult::Evaluated(_)
+        ));
+    }
+
+    #[test]
+    fn callable_summaries_show_bare_function_names_as_signatures() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .exec_code(
+                "def fn(x, y=2):\n    return x + y\nclass Adder:\n    def __call__(self, x):\n        return x\nadder = Adder()",
+            )
+            .expect("define callables");
+        let value_repr = |session: &PythonSession, line: &str| match session
+            .run_user_input(line)
+            .expect("evaluate")
+        {
+            UserRunResult::Evaluated(result) => result.value_repr,
+            other => panic!("unexpected result: {other:?}"),
+        };
+
+        assert!(value_repr(&session, "fn").starts_with("<function fn at 0x"));
+
+        let session = session.with_callable_summaries(true);
+        assert_eq!(value_repr(&session, "fn "), "fn(x, y=2)");
+        assert_eq!(
+            session.eval_expr("_ is fn").expect("underscore").value_repr,
+            "True"
+        );
+        // Calls, other expressions and callable instances keep their repr.
+        assert_eq!(value_repr(&session, "fn(1)"), "3");
+        assert!(value_repr(&session, "(fn)").starts_with("<function fn at 0x"));
+        assert!(value_repr(&session, "adder").contains("Adder object at 0x"));
+    }
+
+    #[test]
+    fn run_user_input_rejects_input_over_max_input_bytes() {
+        let session = PythonSession::initialize()
+            .expect("python session")
+            .with_max_input_bytes(16);
+
+        let err = session
+            .run_user_input(&format!("x = '{}'", "a".repeat(32)))
+            .expect_err("oversized input should be rejected");
+        assert_eq!(
+            err.to_string(),
+            "input is 38 bytes, over the 16-byte limit (python.max_input_bytes)"
+        );
+        let globals = session.list_globals().expect("list globals");
+        assert!(globals.iter().all(|entry| entry.name != "x"));
+
+        let result = session.run_user_input("1 + 1").expect("small input runs");
+        assert!(matches!(result, UserRunResult::Evaluated(_)));
+    }
+
+    #[test]
+    fn complete_returns_matching_globals_builtins_and_attributes() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .exec_code("import os\nmy_value = 1\nmy_other = 2")
+            .expect("seed globals");
+
+        let names = session.complete("my_").expect("complete names");
+        assert_eq!(names, vec!["my_other", "my_value"]);
+
+        let builtins = session.complete("isinst").expect("complete builtins");
+        assert!(builtins.iter().any(|name| name.starts_with("isinstance")));
+
+        let attributes = session.complete("os.pat").expect("complete attributes");
+        assert!(attributes.iter().any(|name| name == "os.path"));
+
+        assert!(
+            session
+                .complete("zz_missing")
+                .expect("no matches")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn run_pip_dry_run_prints_command_without_running_it() {
+        let session = PythonSession::initialize().expect("python session");
+        let result = session
+            .run_pip(&["install".to_string(), "some package".to_string()], true)
+            .expect("run pip");
+
+        let UserRunResult::Executed(output) = result else {
+            panic!("dry run should not fail: {result:?}");
+        };
+        assert!(
+            output
+                .stdout
+                .trim_end()
+                .ends_with(" -m pip install 'some package'"),
+            "{}",
+            output.stdout
+        );
+        assert!(output.stderr.is_empty());
+    }
+
     #[test]
     fn input_completeness_classifies_complete_incomplete_and_invalid() {
         let session = PythonSession::initialize().expect("python session");
@@ -1308,6 +2263,62 @@ mod tests {
         assert_eq!(result.stderr, "oops\n");
     }
 
+    #[test]
+    fn captures_warnings_apart_from_stderr_and_restores_showwarning() {
+        let session = PythonSession::initialize().expect("python session");
+        let before = Python::attach(|py| {
+            PyModule::import(py, "warnings")
+                .and_then(|warnings| warnings.getattr("showwarning"))
+                .expect("showwarning")
+                .unbind()
+        });
+
+        let result = session
+            .run_user_input(
+                "import sys, warnings\nwarnings.warn('old api', DeprecationWarning)\nprint('real', file=sys.stderr)",
+            )
+            .expect("run with warning");
+
+        let UserRunResult::Executed(output) = result else {
+            panic!("expected executed result: {result:?}");
+        };
+        assert_eq!(output.stderr, "real\n");
+        assert_eq!(output.warnings.len(), 1);
+        assert!(
+            output.warnings[0].contains("DeprecationWarning: old api"),
+            "{:?}",
+            output.warnings
+        );
+        Python::attach(|py| {
+            let after = PyModule::import(py, "warnings")
+                .and_then(|warnings| warnings.getattr("showwarning"))
+                .expect("showwarning after");
+            assert!(after.is(before.bind(py)));
+        });
+    }
+
+    #[test]
+    fn captures_output_with_lone_surrogates_lossily_and_traces_warning() {
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_temp_dir("surrogates", dir.path()).expect("trace");
+        let session = PythonSession::initialize()
+            .expect("python session")
+            .with_trace(trace.clone());
+
+        let result = session
+            .exec_code(
+                "import sys\nsys.stdout.write('bad \\udcff byte')\nprint('ok', file=sys.stderr)",
+            )
+            .expect("exec with surrogate output");
+
+        assert!(result.stdout.starts_with("bad \u{fffd}"));
+        assert!(result.stdout.ends_with("\u{fffd} byte"));
+        assert_eq!(result.stderr, "ok\n");
+        let trace_text = fs::read_to_string(trace.file_path()).expect("read trace");
+        assert!(trace_text.contains("[py.warn"));
+        assert!(trace_text.contains("captured stdout contained invalid UTF-8"));
+    }
+
     #[test]
     fn list_globals_returns_name_and_type_excluding_internals() {
         let session = PythonSession::initialize().expect("python session");
@@ -1335,6 +2346,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn last_value_is_bound_in_the_builtins_module_unless_a_global_underscore_exists() {
+        let session = PythonSession::initialize().expect("python session");
+        let value_repr = |session: &PythonSession, line: &str| match session
+            .run_user_input(line)
+            .expect("evaluate")
+        {
+            UserRunResult::Evaluated(result) => result.value_repr,
+            other => panic!("unexpected result: {other:?}"),
+        };
+
+        value_repr(&session, "6 * 7");
+        assert_eq!(value_repr(&session, "_"), "42");
+        assert!(
+            !session
+                .list_globals()
+                .expect("list globals")
+                .iter()
+                .any(|entry| entry.name == "_")
+        );
+
+        session.exec_code("_ = 'mine'").expect("define _");
+        value_repr(&session, "1 + 1");
+        assert_eq!(value_repr(&session, "_"), "'mine'");
+        assert!(
+            session
+                .list_globals()
+                .expect("list globals")
+                .iter()
+                .any(|entry| entry.name == "_" && entry.type_name == "str")
+        );
+
+        // Session code sees the real builtins module, including later additions to it.
+        session
+            .exec_code("import builtins\nbuiltins.pychat_ai_test_builtin = 1")
+            .expect("patch builtins");
+        assert_eq!(value_repr(&session, "pychat_ai_test_builtin"), "1");
+        session
+            .exec_code("del builtins.pychat_ai_test_builtin")
+            .expect("unpatch builtins");
+    }
+
     #[test]
     fn exception_payload_contains_type_message_and_traceback() {
         let session = PythonSession::initialize().expect("python session");
@@ -1379,6 +2432,21 @@ mod tests {
         assert_eq!(replaced.exc_type, "NameError");
     }
 
+    #[test]
+    fn last_exception_output_keeps_streams_printed_before_failure() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .run_user_input("import sys\nsys.stderr.write('partial\\n')\nraise ValueError('bad')")
+            .expect("failure");
+
+        let output = session
+            .get_last_exception_output()
+            .expect("get output")
+            .expect("output exists");
+        assert_eq!(output.stderr, "partial\n");
+        assert_eq!(output.stdout, "");
+    }
+
     #[test]
     fn capability_eval_expr_returns_value_and_output_streams() {
         let session = PythonSession::initialize().expect("python session");
@@ -1400,6 +2468,24 @@ mod tests {
         assert_eq!(inspect.value["type"]["name"], "int");
     }
 
+    #[test]
+    fn type_of_reports_builtin_name_and_module_qualified_user_types() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .exec_code("class Outer:\n    class Inner:\n        pass")
+            .expect("define classes");
+
+        assert_eq!(session.type_of("{}").expect("dict type"), "dict");
+        assert_eq!(
+            session.type_of("Outer.Inner()").expect("nested type"),
+            "__main__.Outer.Inner"
+        );
+        match session.type_of("missing_name") {
+            Err(CapabilityError::PythonException(exc)) => assert_eq!(exc.exc_type, "NameError"),
+            other => panic!("expected NameError, got {other:?}"),
+        }
+    }
+
     #[test]
     fn capability_inspect_list_has_size_and_sample_metadata() {
         let session = PythonSession::initialize().expect("python session");
@@ -1479,6 +2565,29 @@ mod tests {
         assert_eq!(inspect.value["sample"]["shown"], 1);
     }
 
+    #[test]
+    fn capability_inspect_samples_nested_cycles_with_bounded_reprs() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .exec_code(
+                "d = {'inner': {}}\nd['inner']['outer'] = d\ndeep = []\ncur = deep\nfor _ in range(100000):\n    cur.append([])\n    cur = cur[0]\nitems = [d, deep]",
+            )
+            .expect("seed nested cycles");
+
+        let inspect = CapabilityProvider::inspect(&session, "d").expect("inspect dict");
+        assert_eq!(
+            inspect.value["sample"]["items"][0],
+            "'inner': {'outer': {'inner': {'outer': {...}}}}"
+        );
+
+        let inspect = CapabilityProvider::inspect(&session, "items").expect("inspect list");
+        let sample = inspect.value["sample"]["items"]
+            .as_array()
+            .expect("sample items");
+        assert_eq!(sample.len(), 2);
+        assert_eq!(sample[1], "[[[[...]]]]");
+    }
+
     #[test]
     fn capability_inspect_handles_broken_repr() {
         let session = PythonSession::initialize().expect("python session");
@@ -1663,11 +2772,86 @@ signal.signal(signal.SIGALRM, _prev_alarm_handler)"#,
             .expect_err("inspect should timeout");
         // Allow scheduler/signal delivery jitter on loaded CI and local systems.
         assert!(started.elapsed() < Duration::from_millis(2300));
+        assert!(
+            matches!(err, CapabilityError::Timeout(_)),
+            "expected Timeout, got {err:?}"
+        );
+    }
+
+    #[test]
+    fn capability_inspect_honors_configured_timeout() {
+        let _signal_guard = SIGNAL_TEST_MUTEX.lock().expect("lock signal test mutex");
+        let session = PythonSession::initialize()
+            .expect("python session")
+            .with_inspect_timeout(Duration::from_millis(200));
+        let timeout_supported = session
+            .eval_expr("hasattr(__import__('signal'), 'SIGALRM') and hasattr(__import__('signal'), 'ITIMER_REAL')")
+            .expect("check signal")
+            .value_repr;
+        let runs_on_main_thread = session
+            .eval_expr(
+                "__import__('threading').current_thread() is __import__('threading').main_thread()",
+            )
+            .expect("check thread")
+            .value_repr;
+        if timeout_supported != "True" || runs_on_main_thread != "True" {
+            return;
+        }
+
+        // Sleeps shorter than the default timeout, so only the configured limit can fire.
+        let err = CapabilityProvider::inspect(&session, "__import__('time').sleep(0.5)")
+            .expect_err("inspect should timeout");
+        assert!(
+            matches!(err, CapabilityError::Timeout(timeout) if timeout == Duration::from_millis(200)),
+            "expected Timeout, got {err:?}"
+        );
+        assert!(err.to_string().contains("after 0.2 seconds"));
+
+        let session = session.with_inspect_timeout(Duration::ZERO);
+        CapabilityProvider::inspect(&session, "__import__('time').sleep(1.2)")
+            .expect("zero timeout should disable the limit");
+    }
+
+    #[test]
+    fn inspect_with_timeout_overrides_the_configured_timeout_for_one_call() {
+        let _signal_guard = SIGNAL_TEST_MUTEX.lock().expect("lock signal test mutex");
+        let session = PythonSession::initialize()
+            .expect("python session")
+            .with_inspect_timeout(Duration::from_millis(200));
+        let timeout_supported = session
+            .eval_expr("hasattr(__import__('signal'), 'SIGALRM') and hasattr(__import__('signal'), 'ITIMER_REAL')")
+            .expect("check signal")
+            .value_repr;
+        let runs_on_main_thread = session
+            .eval_expr(
+                "__import__('threading').current_thread() is __import__('threading').main_thread()",
+            )
+            .expect("check thread")
+            .value_repr;
+        if timeout_supported != "True" || runs_on_main_thread != "True" {
+            return;
+        }
+
+        let slow = "__import__('time').sleep(0.5)";
+        CapabilityProvider::inspect(&session, slow).expect_err("configured timeout fires");
+        session
+            .inspect_with_timeout(slow, Duration::from_millis(2000))
+            .expect("raised timeout lets the call finish");
+        CapabilityProvider::inspect(&session, slow).expect_err("configured timeout still applies");
+    }
+
+    #[test]
+    fn capability_inspect_keeps_timeout_errors_raised_by_the_expression() {
+        let session = PythonSession::initialize().expect("python session");
+        session
+            .exec_code(
+                "def fake_timeout():\n    raise TimeoutError('inspect timed out after 1 seconds')",
+            )
+            .expect("define helper");
+        let err = CapabilityProvider::inspect(&session, "fake_timeout()")
+            .expect_err("raised timeout error");
         match err {
-            CapabilityError::PythonException(exc) => {
-                assert_eq!(exc.exc_type, "TimeoutError");
-                assert!(exc.message.contains("inspect timed out"));
-            }
+            CapabilityError::PythonException(exc) => assert_eq!(exc.exc_type, "TimeoutError"),
             other => panic!("expected PythonException, got {other:?}"),
         }
     }
@@ -3,8 +3,12 @@ mod capabilities;
 mod interpreter;
 
 #[allow(unused_imports)]
-pub use capabilities::{CapabilityError, CapabilityProvider, EvalInfo, GlobalEntry, InspectInfo};
+pub use capabilities::{
+    CapabilityError, CapabilityProvider, CapabilityResult, EvalInfo, ExecInfo, GlobalEntry,
+    InspectInfo,
+};
 #[allow(unused_imports)]
 pub use interpreter::{
-    EvalResult, ExceptionInfo, ExecResult, InputCompleteness, PythonSession, UserRunResult,
+    EvalResult, ExceptionInfo, ExecResult, InputCompleteness, PythonInterrupt, PythonSession,
+    UserRunResult,
 };
@@ -1,6 +1,9 @@
+use crate::config::TraceFormat;
 use crate::llm::provider::LlmTokenUsageTotals;
 use anyhow::{Result, anyhow, bail};
+use reqwest::Url;
 use reqwest::header::HeaderMap;
+use serde_json::json;
 use std::env;
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufWriter, Write};
@@ -13,6 +16,19 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use time::OffsetDateTime;
 
 const TRACE_DIR_NAME: &str = "pychat.ai/traces";
+/// Header and query parameter names whose values are always masked in HTTP traces.
+const DEFAULT_REDACTED_NAMES: [&str; 3] = ["authorization", "x-goog-api-key", "key"];
+const REDACTED: &str = "***";
+
+/// How the session trace is written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceOptions {
+    pub format: TraceFormat,
+    /// Header and query parameter names to mask in addition to the built-in ones.
+    pub redact: Vec<String>,
+    /// Size after which the trace continues in a new numbered file; 0 means unlimited.
+    pub max_bytes: u64,
+}
 
 #[derive(Clone)]
 pub struct SessionTrace {
@@ -20,18 +36,38 @@ pub struct SessionTrace {
 }
 
 struct TraceInner {
-    writer: Mutex<BufWriter<File>>,
-    file_path: PathBuf,
+    file: Mutex<TraceFile>,
+    session_id: String,
+    trace_dir: PathBuf,
+    /// Lowercase header and query parameter names to mask in HTTP traces.
+    redacted_names: Vec<String>,
+    max_bytes: u64,
     write_failed: AtomicBool,
+    /// The first write failure, until the REPL takes it to show in the timeline.
+    pending_failure: Mutex<Option<String>>,
+}
+
+struct TraceFile {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    /// Path of the first file in this format; rotated files add a `-N` suffix to its stem.
+    base_path: PathBuf,
+    format: TraceFormat,
+    bytes_written: u64,
+    part: u32,
 }
 
 impl SessionTrace {
-    pub fn create(session_id: &str) -> Result<Self> {
+    pub fn create(session_id: &str, options: &TraceOptions) -> Result<Self> {
         let trace_dir = resolve_trace_dir_from_env()?;
-        Self::create_in_dir(session_id, &trace_dir)
+        Self::create_in_dir(session_id, &trace_dir, options)
     }
 
-    fn create_in_dir(session_id: &str, trace_dir: &Path) -> Result<Self> {
+    pub(crate) fn create_in_dir(
+        session_id: &str,
+        trace_dir: &Path,
+        options: &TraceOptions,
+    ) -> Result<Self> {
         fs::create_dir_all(trace_dir).map_err(|err| {
             anyhow!(
                 "Failed to create trace directory {}: {err}",
@@ -39,30 +75,64 @@ impl SessionTrace {
             )
         })?;
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map_or(0, |duration| duration.as_secs());
-        let file_name = format!("session-{session_id}-{timestamp}.log");
-        let file_path = trace_dir.join(&file_name);
-        let file = create_trace_file(&file_path)
-            .map_err(|err| anyhow!("Failed to create trace file {}: {err}", file_path.display()))?;
+        let file = open_trace_file(session_id, trace_dir, options.format)?;
+        let mut redacted_names: Vec<String> = DEFAULT_REDACTED_NAMES
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        for name in &options.redact {
+            let name = name.trim().to_ascii_lowercase();
+            if !name.is_empty() && !redacted_names.contains(&name) {
+                redacted_names.push(name);
+            }
+        }
 
         Ok(Self {
             inner: Arc::new(TraceInner {
-                writer: Mutex::new(BufWriter::new(file)),
-                file_path,
+                file: Mutex::new(file),
+                session_id: session_id.to_string(),
+                trace_dir: trace_dir.to_path_buf(),
+                redacted_names,
+                max_bytes: options.max_bytes,
                 write_failed: AtomicBool::new(false),
+                pending_failure: Mutex::new(None),
             }),
         })
     }
 
     #[cfg(any(test, feature = "test-support"))]
     pub fn create_in_temp_dir(session_id: &str, trace_dir: &Path) -> Result<Self> {
-        Self::create_in_dir(session_id, trace_dir)
+        Self::create_in_dir(session_id, trace_dir, &TraceOptions::default())
+    }
+
+    /// Path of the file currently being written, which changes when the trace rotates.
+    pub fn file_path(&self) -> PathBuf {
+        match self.inner.file.lock() {
+            Ok(file) => file.path.clone(),
+            Err(poisoned) => poisoned.into_inner().path.clone(),
+        }
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        match self.inner.file.lock() {
+            Ok(file) => file.format,
+            Err(poisoned) => poisoned.into_inner().format,
+        }
     }
 
-    pub fn file_path(&self) -> &Path {
-        &self.inner.file_path
+    /// Closes the current file and continues the trace in a new file written in
+    /// `format`, returning its path. Every clone of this trace follows the switch.
+    pub fn switch_format(&self, format: TraceFormat) -> Result<PathBuf> {
+        let next = open_trace_file(&self.inner.session_id, &self.inner.trace_dir, format)?;
+        let path = next.path.clone();
+        let mut file = self
+            .inner
+            .file
+            .lock()
+            .map_err(|_| anyhow!("failed to acquire trace writer lock"))?;
+        let _ = file.writer.flush();
+        *file = next;
+        Ok(path)
     }
 
     pub fn log_input_python(&self, text: &str) {
@@ -77,28 +147,126 @@ impl SessionTrace {
         self.log_lines(kind, text);
     }
 
+    /// Logs a completed assistant answer. Text traces write it line by line as `ai.out`;
+    /// JSONL traces write one `ai.answer` record holding the full text. Fallback text
+    /// given in place of an answer is logged as `ai.degraded` along with `reason`.
+    pub fn log_assistant_answer(&self, text: &str, degraded_reason: Option<&str>) {
+        match (self.format(), degraded_reason) {
+            (TraceFormat::Text, None) => self.log_lines("ai.out", text),
+            (TraceFormat::Text, Some(reason)) => {
+                self.log_single("ai.degraded", &format!("reason: {reason}"));
+                self.log_lines("ai.degraded", text);
+            }
+            (TraceFormat::Jsonl, None) => self.write_record(json!({
+                "ts": current_timestamp(),
+                "kind": "ai.answer",
+                "text": text,
+                "degraded": false,
+            })),
+            (TraceFormat::Jsonl, Some(reason)) => self.write_record(json!({
+                "ts": current_timestamp(),
+                "kind": "ai.degraded",
+                "text": text,
+                "degraded": true,
+                "reason": reason,
+            })),
+        }
+    }
+
+    /// Logs an outgoing request with redacted headers and URL. JSONL traces write one
+    /// record with the headers as a map; text traces write one line per header.
     pub fn log_http_request(&self, method: &str, url: &str, headers: &HeaderMap, body: &str) {
-        self.log_single("ai.http.in", &format!("{method} {url}"));
-        for (name, value) in headers {
-            let value = value
-                .to_str()
-                .map(std::string::ToString::to_string)
-                .unwrap_or_else(|_| "<non-utf8>".to_string());
-            self.log_single("ai.http.in", &format!("{}: {}", name.as_str(), value));
+        let url = self.redact_url(url);
+        let headers = self.header_values(headers, true);
+        match self.format() {
+            TraceFormat::Text => {
+                self.log_single("ai.http.in", &format!("{method} {url}"));
+                self.log_header_lines("ai.http.in", &headers);
+                self.log_lines("ai.http.in", body);
+            }
+            TraceFormat::Jsonl => self.write_record(json!({
+                "ts": current_timestamp(),
+                "kind": "ai.http.in",
+                "method": method,
+                "url": url,
+                "headers": header_map(&headers),
+                "body": body,
+            })),
+        }
+    }
+
+    /// Masks the values of redacted query parameters in `url`. Unparsable URLs are
+    /// returned unchanged.
+    pub fn redact_url(&self, url: &str) -> String {
+        let Ok(mut parsed) = Url::parse(url) else {
+            return url.to_string();
+        };
+        let pairs: Vec<(String, String)> = parsed.query_pairs().into_owned().collect();
+        if !pairs.iter().any(|(name, _)| self.is_redacted(name)) {
+            return url.to_string();
         }
-        self.log_lines("ai.http.in", body);
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs.iter().map(|(name, value)| {
+                let value = if self.is_redacted(name) {
+                    REDACTED
+                } else {
+                    value.as_str()
+                };
+                (name.as_str(), value)
+            }));
+        parsed.to_string()
+    }
+
+    fn is_redacted(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.inner.redacted_names.contains(&name)
     }
 
     pub fn log_http_response(&self, status: u16, headers: &HeaderMap, body: &str) {
-        self.log_single("ai.http.out", &format!("HTTP {status}"));
+        let headers = self.header_values(headers, false);
+        match self.format() {
+            TraceFormat::Text => {
+                self.log_single("ai.http.out", &format!("HTTP {status}"));
+                self.log_header_lines("ai.http.out", &headers);
+                self.log_lines("ai.http.out", body);
+            }
+            TraceFormat::Jsonl => self.write_record(json!({
+                "ts": current_timestamp(),
+                "kind": "ai.http.out",
+                "status": status,
+                "headers": header_map(&headers),
+                "body": body,
+            })),
+        }
+    }
+
+    fn header_values(&self, headers: &HeaderMap, redact: bool) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if redact && self.is_redacted(name.as_str()) {
+                    REDACTED.to_string()
+                } else {
+                    value
+                        .to_str()
+                        .map(std::string::ToString::to_string)
+                        .unwrap_or_else(|_| "<non-utf8>".to_string())
+                };
+                (name.as_str().to_string(), value)
+            })
+            .collect()
+    }
+
+    fn log_header_lines(&self, kind: &str, headers: &[(String, String)]) {
         for (name, value) in headers {
-            let value = value
-                .to_str()
-                .map(std::string::ToString::to_string)
-                .unwrap_or_else(|_| "<non-utf8>".to_string());
-            self.log_single("ai.http.out", &format!("{}: {}", name.as_str(), value));
+            self.log_single(kind, &format!("{name}: {value}"));
         }
-        self.log_lines("ai.http.out", body);
+    }
+
+    pub fn log_python_warning(&self, message: &str) {
+        self.log_single("py.warn", message);
     }
 
     pub fn log_http_error(&self, message: &str) {
@@ -128,27 +296,132 @@ impl SessionTrace {
 
     fn log_single(&self, kind: &str, text: &str) {
         let timestamp = current_timestamp();
-        self.write_raw(&format!("[{timestamp}] [{:<11}] {text}\n", kind));
+        match self.format() {
+            TraceFormat::Text => self.write_raw(&format!("[{timestamp}] [{:<11}] {text}\n", kind)),
+            TraceFormat::Jsonl => self.write_record(json!({
+                "ts": timestamp,
+                "kind": kind,
+                "text": text,
+            })),
+        }
+    }
+
+    fn write_record(&self, record: serde_json::Value) {
+        self.write_raw(&format!("{record}\n"));
     }
 
     fn write_raw(&self, text: &str) {
-        let Ok(mut writer) = self.inner.writer.lock() else {
+        let Ok(mut file) = self.inner.file.lock() else {
             self.report_write_failure("failed to acquire trace writer lock");
             return;
         };
 
-        if writer.write_all(text.as_bytes()).is_err() || writer.flush().is_err() {
+        let len = text.len() as u64;
+        if self.inner.max_bytes > 0
+            && file.bytes_written > 0
+            && file.bytes_written + len > self.inner.max_bytes
+            && let Err(err) = self.rotate(&mut file)
+        {
+            self.report_write_failure(&format!("failed to rotate trace file: {err}"));
+            return;
+        }
+
+        if file.writer.write_all(text.as_bytes()).is_err() || file.writer.flush().is_err() {
             self.report_write_failure("failed to write to trace file");
+            return;
+        }
+        file.bytes_written += len;
+    }
+
+    /// Continues the trace in the next numbered file, created with the same
+    /// owner-only permissions as the first one.
+    fn rotate(&self, file: &mut TraceFile) -> std::io::Result<()> {
+        let part = file.part + 1;
+        let path = rotated_path(&file.base_path, part);
+        file.writer = BufWriter::new(create_trace_file(&path)?);
+        file.path = path;
+        file.bytes_written = 0;
+        file.part = part;
+        Ok(())
+    }
+
+    /// Returns the first write failure once, so it can be shown where the user sees it;
+    /// stderr is hidden behind the TUI's alternate screen.
+    pub fn take_write_failure(&self) -> Option<String> {
+        match self.inner.pending_failure.lock() {
+            Ok(mut pending) => pending.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
         }
     }
 
     fn report_write_failure(&self, message: &str) {
         if !self.inner.write_failed.swap(true, Ordering::Relaxed) {
-            eprintln!("PyChat.ai trace warning: {message}");
+            let message = format!("trace warning: {message}");
+            match self.inner.pending_failure.lock() {
+                Ok(mut pending) => *pending = Some(message),
+                Err(poisoned) => *poisoned.into_inner() = Some(message),
+            }
         }
     }
 }
 
+/// Repeated headers are joined with `, `, as HTTP allows.
+fn header_map(headers: &[(String, String)]) -> serde_json::Map<String, serde_json::Value> {
+    let mut map = serde_json::Map::new();
+    for (name, value) in headers {
+        let joined = match map.get(name).and_then(serde_json::Value::as_str) {
+            Some(existing) => format!("{existing}, {value}"),
+            None => value.clone(),
+        };
+        map.insert(name.clone(), joined.into());
+    }
+    map
+}
+
+/// Creates `session-<id>-<unix seconds>.<ext>` in `trace_dir`. A name already taken,
+/// say by switching formats back within a second, gets a numbered `-sN` suffix
+/// instead of being overwritten.
+fn open_trace_file(session_id: &str, trace_dir: &Path, format: TraceFormat) -> Result<TraceFile> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let extension = match format {
+        TraceFormat::Text => "log",
+        TraceFormat::Jsonl => "jsonl",
+    };
+    let mut file_path = trace_dir.join(format!("session-{session_id}-{timestamp}.{extension}"));
+    let mut suffix = 1;
+    while file_path.exists() {
+        file_path = trace_dir.join(format!(
+            "session-{session_id}-{timestamp}-s{suffix}.{extension}"
+        ));
+        suffix += 1;
+    }
+    let file = create_trace_file(&file_path)
+        .map_err(|err| anyhow!("Failed to create trace file {}: {err}", file_path.display()))?;
+    Ok(TraceFile {
+        writer: BufWriter::new(file),
+        path: file_path.clone(),
+        base_path: file_path,
+        format,
+        bytes_written: 0,
+        part: 0,
+    })
+}
+
+/// `session-x-1.log` becomes `session-x-1-2.log` for part 2.
+fn rotated_path(base_path: &Path, part: u32) -> PathBuf {
+    let stem = base_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match base_path.extension() {
+        Some(extension) => format!("{stem}-{part}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{part}"),
+    };
+    base_path.with_file_name(file_name)
+}
+
 #[cfg(unix)]
 fn create_trace_file(path: &Path) -> std::io::Result<File> {
     OpenOptions::new()
@@ -200,8 +473,10 @@ fn resolve_trace_dir(xdg_state_home: Option<&str>, home_dir: Option<&Path>) -> R
 
 #[cfg(test)]
 mod tests {
-    use super::{SessionTrace, resolve_trace_dir};
+    use super::{SessionTrace, TraceOptions, resolve_trace_dir};
+    use crate::config::TraceFormat;
     use crate::llm::provider::LlmTokenUsageTotals;
+    use reqwest::header::HeaderMap;
     use std::fs;
     #[cfg(unix)]
     use std::os::unix::fs::PermissionsExt;
@@ -244,7 +519,7 @@ mod tests {
     fn trace_line_uses_iso_timestamp_and_padded_kind() {
         let dir = tempdir().expect("tempdir");
         let trace = SessionTrace::create_in_temp_dir("abc", dir.path()).expect("trace");
-        let path = trace.file_path().to_path_buf();
+        let path = trace.file_path();
         trace.log_output("py.out", "value");
 
         let content = fs::read_to_string(path).expect("read trace");
@@ -258,12 +533,13 @@ mod tests {
     fn trace_logs_session_token_summary() {
         let dir = tempdir().expect("tempdir");
         let trace = SessionTrace::create_in_temp_dir("abc", dir.path()).expect("trace");
-        let path = trace.file_path().to_path_buf();
+        let path = trace.file_path();
 
         trace.log_session_token_summary(&LlmTokenUsageTotals {
             input_tokens: 12,
             output_tokens: 5,
             total_tokens: 17,
+            unreported_calls: 0,
         });
 
         let content = fs::read_to_string(path).expect("read trace");
@@ -273,6 +549,192 @@ mod tests {
         );
     }
 
+    #[test]
+    fn jsonl_trace_logs_one_answer_object_per_turn() {
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_dir(
+            "abc",
+            dir.path(),
+            &TraceOptions {
+                format: TraceFormat::Jsonl,
+                ..TraceOptions::default()
+            },
+        )
+        .expect("trace");
+        let path = trace.file_path();
+        assert_eq!(path.extension().and_then(|ext| ext.to_str()), Some("jsonl"));
+
+        trace.log_input_assistant("explain x");
+        trace.log_assistant_answer("x is 42.\nIt is an int.", None);
+        trace.log_assistant_answer("Assistant hit a per-step timeout.", Some("step_timeout"));
+
+        let records = fs::read_to_string(path)
+            .expect("read trace")
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("json record"))
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["kind"], "ai.in");
+        assert_eq!(records[1]["kind"], "ai.answer");
+        assert_eq!(records[1]["text"], "x is 42.\nIt is an int.");
+        assert_eq!(records[1]["degraded"], false);
+        assert_eq!(records[2]["kind"], "ai.degraded");
+        assert_eq!(records[2]["degraded"], true);
+        assert_eq!(records[2]["reason"], "step_timeout");
+    }
+
+    #[test]
+    fn switching_format_continues_in_a_new_file() {
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_temp_dir("abc", dir.path()).expect("trace");
+        let text_path = trace.file_path();
+        trace.log_output("py.out", "before");
+
+        let jsonl_path = trace.switch_format(TraceFormat::Jsonl).expect("switch");
+        trace.clone().log_output("py.out", "after");
+
+        assert_ne!(jsonl_path, text_path);
+        assert_eq!(trace.file_path(), jsonl_path);
+        assert_eq!(trace.format(), TraceFormat::Jsonl);
+        assert_eq!(
+            jsonl_path.extension().and_then(|ext| ext.to_str()),
+            Some("jsonl")
+        );
+        let text = fs::read_to_string(&text_path).expect("read text trace");
+        assert!(text.contains("[py.out     ] before"));
+        assert!(!text.contains("after"));
+        let record: serde_json::Value =
+            serde_json::from_str(fs::read_to_string(&jsonl_path).expect("read jsonl").trim())
+                .expect("json record");
+        assert_eq!(record["text"], "after");
+
+        let back_path = trace.switch_format(TraceFormat::Text).expect("switch back");
+        assert_ne!(back_path, text_path);
+        assert!(
+            fs::read_to_string(&text_path)
+                .expect("reread text trace")
+                .contains("before")
+        );
+    }
+
+    #[test]
+    fn http_request_trace_masks_built_in_and_configured_secrets() {
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_dir(
+            "abc",
+            dir.path(),
+            &TraceOptions {
+                redact: vec!["X-Session-Token".to_string()],
+                ..TraceOptions::default()
+            },
+        )
+        .expect("trace");
+        let path = trace.file_path();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-goog-api-key", "header-secret".parse().expect("header"));
+        headers.insert("x-session-token", "session-secret".parse().expect("header"));
+        headers.insert("content-type", "application/json".parse().expect("header"));
+
+        trace.log_http_request(
+            "POST",
+            "https://example.com/v1/m:generate?alt=sse&key=query-secret",
+            &headers,
+            "{}",
+        );
+
+        let content = fs::read_to_string(path).expect("read trace");
+        for secret in ["header-secret", "session-secret", "query-secret"] {
+            assert!(!content.contains(secret), "trace content:\n{content}");
+        }
+        assert!(content.contains("POST https://example.com/v1/m:generate?alt=sse&key=***"));
+        assert!(content.contains("x-goog-api-key: ***"));
+        assert!(content.contains("content-type: application/json"));
+    }
+
+    #[test]
+    fn jsonl_trace_logs_http_exchanges_as_structured_records() {
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_dir(
+            "abc",
+            dir.path(),
+            &TraceOptions {
+                format: TraceFormat::Jsonl,
+                ..TraceOptions::default()
+            },
+        )
+        .expect("trace");
+        let path = trace.file_path();
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().expect("header"));
+        headers.insert("x-goog-api-key", "header-secret".parse().expect("header"));
+
+        trace.log_http_request(
+            "POST",
+            "https://example.com/v1/m?key=query-secret",
+            &headers,
+            "{\"a\":1}\n",
+        );
+        trace.log_http_response(200, &headers, "ok");
+
+        let records = fs::read_to_string(path)
+            .expect("read trace")
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("json record"))
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["kind"], "ai.http.in");
+        assert_eq!(records[0]["method"], "POST");
+        assert_eq!(records[0]["url"], "https://example.com/v1/m?key=***");
+        assert_eq!(records[0]["headers"]["content-type"], "application/json");
+        assert_eq!(records[0]["headers"]["x-goog-api-key"], "***");
+        assert_eq!(records[0]["body"], "{\"a\":1}\n");
+        assert_eq!(records[1]["kind"], "ai.http.out");
+        assert_eq!(records[1]["status"], 200);
+        assert_eq!(records[1]["body"], "ok");
+    }
+
+    #[test]
+    fn trace_rotates_to_numbered_owner_only_files_past_max_bytes() {
+        let dir = tempdir().expect("tempdir");
+        let trace = SessionTrace::create_in_dir(
+            "abc",
+            dir.path(),
+            &TraceOptions {
+                max_bytes: 120,
+                ..TraceOptions::default()
+            },
+        )
+        .expect("trace");
+        let first_path = trace.file_path();
+
+        trace.log_output("py.out", "first");
+        trace.log_output("py.out", "second");
+        trace.log_output("py.out", "third");
+
+        let rotated_path = trace.file_path();
+        assert_ne!(rotated_path, first_path);
+        assert!(
+            rotated_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with("-1.log"))
+        );
+        let first = fs::read_to_string(&first_path).expect("read first trace");
+        let rotated = fs::read_to_string(&rotated_path).expect("read rotated trace");
+        assert!(first.contains("first") && first.contains("second"));
+        assert!(!first.contains("third"));
+        assert!(rotated.contains("third"));
+        #[cfg(unix)]
+        assert_eq!(
+            fs::metadata(&rotated_path)
+                .expect("metadata")
+                .permissions()
+                .mode()
+                & 0o777,
+            0o600
+        );
+    }
+
     #[cfg(unix)]
     #[test]
     fn trace_file_permissions_are_owner_only() {
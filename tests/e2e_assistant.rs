@@ -21,16 +21,14 @@ fn assistant_mode_happy_path_with_mock_provider_writes_response_and_stays_intera
     let server = rt.block_on(MockServer::start());
     rt.block_on(async {
         Mock::given(method("POST"))
-            .and(path_matcher("/v1beta/models/gemini-test:generateContent"))
+            .and(path_matcher("/v1beta/models/gemini-test:streamGenerateContent"))
             .and(query_param("key", "test-key"))
             .respond_with(
                 ResponseTemplate::new(200).set_body_raw(
-                    r#"{
-                        "candidates": [
-                            {"finishReason":"STOP","content":{"parts":[{"text":"Mock assistant says hello"}]}}
-                        ]
-                    }"#,
-                    "application/json",
+                    sse_body(
+                        r#"{"candidates":[{"finishReason":"STOP","content":{"parts":[{"text":"Mock assistant says hello"}]}}]}"#,
+                    ),
+                    "text/event-stream",
                 ),
             )
             .mount(&server)
@@ -67,7 +65,7 @@ fn assistant_mode_degraded_failure_then_recovery_allows_next_prompt() {
     let server = rt.block_on(MockServer::start());
     rt.block_on(async {
         Mock::given(method("POST"))
-            .and(path_matcher("/v1beta/models/gemini-test:generateContent"))
+            .and(path_matcher("/v1beta/models/gemini-test:streamGenerateContent"))
             .and(query_param("key", "test-key"))
             .and(body_string_contains("first question"))
             .respond_with(ResponseTemplate::new(500).set_body_string("provider down"))
@@ -76,17 +74,15 @@ fn assistant_mode_degraded_failure_then_recovery_allows_next_prompt() {
             .await;
 
         Mock::given(method("POST"))
-            .and(path_matcher("/v1beta/models/gemini-test:generateContent"))
+            .and(path_matcher("/v1beta/models/gemini-test:streamGenerateContent"))
             .and(query_param("key", "test-key"))
             .and(body_string_contains("second question"))
             .respond_with(
                 ResponseTemplate::new(200).set_body_raw(
-                    r#"{
-                        "candidates": [
-                            {"finishReason":"STOP","content":{"parts":[{"text":"Recovered answer"}]}}
-                        ]
-                    }"#,
-                    "application/json",
+                    sse_body(
+                        r#"{"candidates":[{"finishReason":"STOP","content":{"parts":[{"text":"Recovered answer"}]}}]}"#,
+                    ),
+                    "text/event-stream",
                 ),
             )
             .expect(1)
@@ -126,6 +122,10 @@ fn assistant_mode_degraded_failure_then_recovery_allows_next_prompt() {
     );
 }
 
+fn sse_body(event_json: &str) -> String {
+    format!("data: {event_json}\n\n")
+}
+
 fn spawn_app_with_mock_provider(server: &MockServer) -> (Session, TempDir, TempDir, TempDir) {
     let config_home = tempfile::tempdir().expect("create XDG_CONFIG_HOME tempdir");
     let state_home = tempfile::tempdir().expect("create XDG_STATE_HOME tempdir");
@@ -154,7 +154,8 @@ fn write_test_config(dir: &Path, base_url: &str) -> PathBuf {
     let content = format!(
         "gemini_api_key = \"test-key\"\n\
          gemini_model = \"gemini-test\"\n\
-         gemini_base_url = \"{}\"\n",
+         gemini_base_url = \"{}\"\n\
+         gemini_max_retries = 0\n",
         base_url
     );
     fs::write(&path, content).expect("write test config");
@@ -0,0 +1,60 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use tempfile::tempdir;
+
+#[test]
+fn plain_flag_prints_timeline_lines_without_ansi_codes() {
+    let home_dir = tempdir().expect("create temp home");
+    let config_home = tempdir().expect("create temp xdg config home");
+    let state_home = tempdir().expect("create temp xdg state home");
+
+    let mut child = Command::new(binary_path())
+        .arg("--plain")
+        .env_remove("GEMINI_API_KEY")
+        .env("HOME", home_dir.path())
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .env("XDG_STATE_HOME", state_home.path())
+        .env("PYCHAT_AI_FORCE_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn --plain");
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(b"x = 40 + 2\ndef double(n):\n    return n * 2\n\ndouble(x)\n/mode\n")
+        .expect("write stdin");
+    let output = child.wait_with_output().expect("wait for --plain");
+
+    assert!(
+        output.status.success(),
+        "--plain should exit successfully, stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).expect("stdout is utf-8");
+    let lines = stdout.lines().collect::<Vec<_>>();
+    for expected in [
+        "py> x = 40 + 2",
+        "py> double(x)",
+        "84",
+        "cmd> /mode",
+        "mode: py",
+    ] {
+        assert!(
+            lines.contains(&expected),
+            "missing line {expected:?} in: {stdout:?}"
+        );
+    }
+    assert!(stdout.contains("def double(n):"));
+    assert!(
+        !stdout.contains('\u{1b}'),
+        "unexpected ANSI codes: {stdout:?}"
+    );
+}
+
+fn binary_path() -> String {
+    std::env::var("CARGO_BIN_EXE_pychat_ai")
+        .unwrap_or_else(|_| "target/debug/pychat_ai".to_string())
+}
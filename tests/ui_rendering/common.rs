@@ -1,5 +1,5 @@
 use anyhow::Result;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use pychat_ai::cli::test_support::{UiHarness, deterministic_app_state};
 use ratatui::layout::Rect;
 
@@ -10,6 +10,13 @@ pub fn new_harness(session_id: &str, width: u16, height: u16) -> Result<UiHarnes
     Ok(harness)
 }
 
+pub fn new_color_harness(session_id: &str, width: u16, height: u16) -> Result<UiHarness> {
+    let state = deterministic_app_state(session_id)?;
+    let mut harness = UiHarness::with_color(width, height, state, true)?;
+    harness.render()?;
+    Ok(harness)
+}
+
 pub async fn type_text(harness: &mut UiHarness, text: &str) -> Result<()> {
     for ch in text.chars() {
         harness
@@ -30,12 +37,24 @@ pub async fn press_tab(harness: &mut UiHarness) -> Result<()> {
         .await
 }
 
+pub async fn press_backtab(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT))
+        .await
+}
+
 pub async fn press_enter(harness: &mut UiHarness) -> Result<()> {
     harness
         .send_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
         .await
 }
 
+pub async fn press_shift_enter(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT))
+        .await
+}
+
 pub async fn press_up(harness: &mut UiHarness) -> Result<()> {
     harness
         .send_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE))
@@ -48,18 +67,80 @@ pub async fn press_down(harness: &mut UiHarness) -> Result<()> {
         .await
 }
 
+pub async fn press_ctrl_u(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL))
+        .await
+}
+
 pub async fn press_ctrl_t(harness: &mut UiHarness) -> Result<()> {
     harness
         .send_key(KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL))
         .await
 }
 
+pub async fn press_ctrl_o(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL))
+        .await
+}
+
 pub async fn press_ctrl_j(harness: &mut UiHarness) -> Result<()> {
     harness
         .send_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL))
         .await
 }
 
+pub async fn press_ctrl_r(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL))
+        .await
+}
+
+pub async fn press_left(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE))
+        .await
+}
+
+pub async fn press_right(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Right, KeyModifiers::NONE))
+        .await
+}
+
+pub async fn press_home(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Home, KeyModifiers::NONE))
+        .await
+}
+
+pub async fn press_key(
+    harness: &mut UiHarness,
+    code: KeyCode,
+    modifiers: KeyModifiers,
+) -> Result<()> {
+    harness.send_key(KeyEvent::new(code, modifiers)).await
+}
+
+pub async fn press_ctrl_e(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL))
+        .await
+}
+
+pub async fn press_backspace(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE))
+        .await
+}
+
+pub async fn press_esc(harness: &mut UiHarness) -> Result<()> {
+    harness
+        .send_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE))
+        .await
+}
+
 pub fn scroll_up(harness: &mut UiHarness, column: u16, row: u16) -> Result<()> {
     harness.send_mouse(MouseEvent {
         kind: MouseEventKind::ScrollUp,
@@ -78,6 +159,15 @@ pub fn scroll_down(harness: &mut UiHarness, column: u16, row: u16) -> Result<()>
     })
 }
 
+pub fn left_click(harness: &mut UiHarness, column: u16, row: u16) -> Result<()> {
+    harness.send_mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column,
+        row,
+        modifiers: KeyModifiers::NONE,
+    })
+}
+
 pub fn normalized_text(text: &str) -> String {
     text.replace("\r\n", "\n")
         .lines()
@@ -1,8 +1,10 @@
 use anyhow::Result;
+use pychat_ai::config::ThemeToken;
 
 use crate::ui_rendering::common::{
-    new_harness, press_down, press_enter, press_tab, press_up, submit_line, timeline_snapshot,
-    type_text,
+    new_harness, press_backspace, press_backtab, press_ctrl_e, press_ctrl_r, press_ctrl_u,
+    press_down, press_enter, press_esc, press_home, press_left, press_right, press_shift_enter,
+    press_tab, press_up, submit_line, timeline_snapshot, type_text,
 };
 
 #[tokio::test]
@@ -29,6 +31,69 @@ async fn enter_on_incomplete_python_input_inserts_newline_then_executes_when_com
     Ok(())
 }
 
+#[tokio::test]
+async fn shift_enter_in_assistant_mode_adds_a_line_and_enter_submits() -> Result<()> {
+    let mut harness = new_harness("assistant-multiline", 100, 24)?;
+
+    press_tab(&mut harness).await?;
+    type_text(&mut harness, "first line").await?;
+    press_shift_enter(&mut harness).await?;
+    type_text(&mut harness, "second line").await?;
+    let view = harness.ui_state_view();
+    assert_eq!(view.prompt, "ai> ");
+    assert_eq!(view.input, "first line\nsecond line");
+
+    press_enter(&mut harness).await?;
+    assert_eq!(harness.ui_state_view().input, "");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn tab_completes_python_names_and_cycles_through_candidates() -> Result<()> {
+    let mut harness = new_harness("tab-completion", 100, 24)?;
+    submit_line(&mut harness, "my_value = 1").await?;
+    submit_line(&mut harness, "my_other = 2").await?;
+
+    type_text(&mut harness, "my_v").await?;
+    press_tab(&mut harness).await?;
+    assert_eq!(harness.ui_state_view().input, "my_value");
+
+    type_text(&mut harness, " + my_").await?;
+    press_tab(&mut harness).await?;
+    assert_eq!(harness.ui_state_view().input, "my_value + my_");
+    harness.render()?;
+    assert!(harness.buffer_text().contains("my_other  my_value"));
+
+    press_tab(&mut harness).await?;
+    assert_eq!(harness.ui_state_view().input, "my_value + my_other");
+    press_tab(&mut harness).await?;
+    assert_eq!(harness.ui_state_view().input, "my_value + my_value");
+    assert_eq!(harness.ui_state_view().prompt, "py> ");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bracketed_paste_inserts_multiline_code_without_running_it() -> Result<()> {
+    let mut harness = new_harness("bracketed-paste", 100, 24)?;
+
+    harness.send_paste("def f():\r\n    if True:\r\n        return 42\r\n");
+    assert_eq!(
+        harness.ui_state_view().input,
+        "def f():\n    if True:\n        return 42\n"
+    );
+    harness.render()?;
+    assert!(!timeline_snapshot(&harness)?.contains("def f():"));
+
+    press_enter(&mut harness).await?;
+    submit_line(&mut harness, "f()").await?;
+    harness.render()?;
+    assert!(timeline_snapshot(&harness)?.contains("42"));
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn up_down_history_navigation_works_across_python_and_assistant_modes() -> Result<()> {
     let mut harness = new_harness("phase3-history-nav", 100, 24)?;
@@ -75,3 +140,123 @@ async fn python_failure_does_not_prevent_next_successful_submission() -> Result<
 
     Ok(())
 }
+
+#[tokio::test]
+async fn ctrl_r_searches_history_backward_and_accepts_or_cancels() -> Result<()> {
+    let mut harness = new_harness("phase3-reverse-search", 100, 24)?;
+
+    submit_line(&mut harness, "alpha = 1").await?;
+    submit_line(&mut harness, "beta = 2").await?;
+    submit_line(&mut harness, "alpha + beta").await?;
+
+    type_text(&mut harness, "draft").await?;
+    press_ctrl_r(&mut harness).await?;
+    type_text(&mut harness, "alp").await?;
+    harness.render()?;
+    assert!(
+        harness
+            .buffer_text()
+            .contains("(reverse-i-search)`alp': alpha + beta")
+    );
+
+    press_ctrl_r(&mut harness).await?;
+    harness.render()?;
+    assert!(
+        harness
+            .buffer_text()
+            .contains("(reverse-i-search)`alp': alpha = 1")
+    );
+    press_enter(&mut harness).await?;
+    assert_eq!(harness.ui_state_view().input, "alpha = 1");
+
+    press_backtab(&mut harness).await?;
+    type_text(&mut harness, "question").await?;
+    press_ctrl_r(&mut harness).await?;
+    type_text(&mut harness, "beta").await?;
+    press_esc(&mut harness).await?;
+    let view = harness.ui_state_view();
+    assert_eq!(view.prompt, "ai> ");
+    assert_eq!(view.input, "question");
+
+    press_tab(&mut harness).await?;
+    assert_eq!(harness.ui_state_view().input, "alpha = 1");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cursor_keys_edit_in_the_middle_of_the_line() -> Result<()> {
+    let mut harness = new_harness("phase3-cursor-edit", 100, 24)?;
+
+    type_text(&mut harness, "vale = 3").await?;
+    press_home(&mut harness).await?;
+    for _ in 0..3 {
+        press_right(&mut harness).await?;
+    }
+    type_text(&mut harness, "u").await?;
+    assert_eq!(harness.ui_state_view().input, "value = 3");
+
+    press_ctrl_e(&mut harness).await?;
+    press_backspace(&mut harness).await?;
+    type_text(&mut harness, "4").await?;
+    press_left(&mut harness).await?;
+    press_left(&mut harness).await?;
+    press_backspace(&mut harness).await?;
+    type_text(&mut harness, "=").await?;
+    assert_eq!(harness.ui_state_view().input, "value = 4");
+
+    press_enter(&mut harness).await?;
+    submit_line(&mut harness, "value * 2").await?;
+    harness.render()?;
+
+    let timeline = timeline_snapshot(&harness)?;
+    assert!(timeline.contains("py> value = 4"));
+    assert!(timeline.contains("8"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn input_prompt_token_follows_python_input_completeness() -> Result<()> {
+    let mut harness = new_harness("phase3-completeness-tint", 100, 24)?;
+
+    type_text(&mut harness, "if True:").await?;
+    harness.render()?;
+    assert_eq!(
+        harness.ui_state_view().prompt_token,
+        ThemeToken::ContinuationPrompt
+    );
+
+    press_ctrl_u(&mut harness).await?;
+    type_text(&mut harness, "x = )").await?;
+    harness.render()?;
+    assert_eq!(
+        harness.ui_state_view().prompt_token,
+        ThemeToken::InvalidInputPrompt
+    );
+
+    press_ctrl_u(&mut harness).await?;
+    type_text(&mut harness, "x = 1").await?;
+    harness.render()?;
+    assert_eq!(
+        harness.ui_state_view().prompt_token,
+        ThemeToken::PythonPrompt
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_last_emits_osc52_sequence_when_enabled() -> Result<()> {
+    let mut harness = new_harness("phase3-copy-osc52", 100, 24)?;
+    harness.app_state_mut().clipboard_osc52 = true;
+
+    submit_line(&mut harness, "40 + 2").await?;
+    submit_line(&mut harness, "/copy last").await?;
+    harness.render()?;
+
+    assert_eq!(harness.clipboard_bytes(), b"\x1b]52;c;NDI=\x1b\\");
+    assert!(timeline_snapshot(&harness)?.contains("copied last output to the clipboard (OSC 52)"));
+
+    Ok(())
+}
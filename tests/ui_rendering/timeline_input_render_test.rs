@@ -1,8 +1,8 @@
 use anyhow::Result;
 
 use crate::ui_rendering::common::{
-    input_snapshot, motd_snapshot, new_harness, press_ctrl_j, press_ctrl_t, press_tab,
-    status_snapshot, submit_line, timeline_snapshot, type_text,
+    input_snapshot, motd_snapshot, new_color_harness, new_harness, press_ctrl_j, press_ctrl_o,
+    press_ctrl_t, press_tab, status_snapshot, submit_line, timeline_snapshot, type_text,
 };
 
 #[tokio::test]
@@ -85,6 +85,13 @@ async fn assistant_thinking_block_toggle_is_retroactive() -> Result<()> {
     )?;
     harness.render()?;
 
+    let collapsed = timeline_snapshot(&harness)?;
+    assert!(collapsed.contains("  1 tool call"));
+    assert!(!collapsed.contains("  -> Inspecting: value"));
+
+    press_ctrl_o(&mut harness).await?;
+    harness.render()?;
+
     let shown = timeline_snapshot(&harness)?;
     assert!(shown.contains("  Thinking..."));
     assert!(shown.contains("  -> Inspecting: value"));
@@ -130,3 +137,37 @@ async fn scoped_snapshots_for_timeline_and_status() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn themes_command_previews_each_preset_in_its_colors() -> Result<()> {
+    let mut harness = new_color_harness("phase3-themes", 100, 24)?;
+    submit_line(&mut harness, "/themes").await?;
+    harness.render()?;
+
+    let lines = harness.buffer_lines();
+    let mut prompt_colors = Vec::new();
+    for preset in ["default", "light", "high-contrast"] {
+        let row = lines
+            .iter()
+            .position(|line| line.contains(&format!(" {preset} ")) && line.contains("py> "))
+            .unwrap_or_else(|| panic!("missing preview row for {preset}"));
+        let column = lines[row].find("py> ").expect("sample prompt");
+        let style = harness
+            .cell_style(
+                u16::try_from(column).expect("column"),
+                u16::try_from(row).expect("row"),
+            )
+            .expect("sample cell");
+        assert!(style.fg.is_some(), "sample for {preset} should be styled");
+        prompt_colors.push(style.fg);
+    }
+
+    prompt_colors.dedup();
+    assert_eq!(
+        prompt_colors.len(),
+        3,
+        "each preset should use its own colors"
+    );
+
+    Ok(())
+}
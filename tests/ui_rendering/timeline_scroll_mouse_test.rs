@@ -1,6 +1,12 @@
 use anyhow::Result;
+use crossterm::event::{KeyCode, KeyModifiers};
 
-use crate::ui_rendering::common::{new_harness, scroll_down, scroll_up};
+use pychat_ai::cli::test_support::UiHarness;
+
+use crate::ui_rendering::common::{
+    left_click, new_harness, press_esc, press_key, scroll_down, scroll_up, submit_line,
+    timeline_snapshot,
+};
 
 #[tokio::test]
 async fn mouse_wheel_scroll_only_applies_inside_timeline_region() -> Result<()> {
@@ -67,3 +73,123 @@ async fn timeline_scroll_clamps_to_valid_bounds() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn page_keys_scroll_the_timeline_and_keep_the_offset_on_new_output() -> Result<()> {
+    let mut harness = new_harness("phase3-page-keys", 100, 24)?;
+
+    for i in 0..32 {
+        harness.seed_assistant_turn_completed(
+            &format!("inspect page_{i}"),
+            &[("request", "-> Inspecting"), ("result", "<- Done")],
+            "ok",
+        )?;
+    }
+    harness.render()?;
+    let page = usize::from(harness.regions()?.timeline.height);
+
+    press_key(&mut harness, KeyCode::PageUp, KeyModifiers::NONE).await?;
+    assert_eq!(harness.ui_state_view().timeline_scroll, page);
+    press_key(&mut harness, KeyCode::PageUp, KeyModifiers::NONE).await?;
+    assert_eq!(harness.ui_state_view().timeline_scroll, 2 * page);
+    press_key(&mut harness, KeyCode::PageDown, KeyModifiers::NONE).await?;
+    assert_eq!(harness.ui_state_view().timeline_scroll, page);
+
+    submit_line(&mut harness, "print('late output')").await?;
+    assert_eq!(harness.ui_state_view().timeline_scroll, page);
+
+    press_key(&mut harness, KeyCode::Home, KeyModifiers::CONTROL).await?;
+    let top = harness.ui_state_view().timeline_scroll;
+    assert!(top > 2 * page);
+    press_key(&mut harness, KeyCode::PageUp, KeyModifiers::NONE).await?;
+    assert_eq!(harness.ui_state_view().timeline_scroll, top);
+
+    press_key(&mut harness, KeyCode::End, KeyModifiers::CONTROL).await?;
+    assert_eq!(harness.ui_state_view().timeline_scroll, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn page_command_shows_a_long_value_in_a_pager_that_esc_closes() -> Result<()> {
+    let mut harness = new_harness("phase3-pager", 100, 24)?;
+
+    submit_line(&mut harness, "/page list(range(2000))").await?;
+    harness.render()?;
+    let screen = harness.buffer_text();
+    assert!(screen.contains("┌ list(range(2000)) ─"), "{screen}");
+    assert!(screen.contains("[0, 1, 2, 3"), "{screen}");
+    assert!(screen.contains("Esc close"), "{screen}");
+
+    press_key(&mut harness, KeyCode::PageDown, KeyModifiers::NONE).await?;
+    harness.render()?;
+    assert!(!harness.buffer_text().contains("[0, 1, 2, 3"));
+    assert_eq!(harness.ui_state_view().input, "");
+
+    press_esc(&mut harness).await?;
+    harness.render()?;
+    let screen = harness.buffer_text();
+    assert!(!screen.contains("Esc close"), "{screen}");
+    assert!(!screen.contains("[0, 1, 2, 3"), "{screen}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn inspect_output_over_auto_page_lines_opens_in_the_pager() -> Result<()> {
+    let mut harness = new_harness("phase3-auto-page", 100, 24)?;
+    harness.app_state_mut().auto_page_lines = 5;
+
+    submit_line(&mut harness, "/inspect {'a': 1, 'b': 2, 'c': 3}").await?;
+    harness.render()?;
+    let screen = harness.buffer_text();
+    assert!(screen.contains("┌ inspect ─"), "{screen}");
+
+    press_esc(&mut harness).await?;
+    harness.render()?;
+    let screen = harness.buffer_text();
+    assert!(
+        screen.contains("lines opened in the pager (Esc closes it)"),
+        "{screen}"
+    );
+    assert!(!screen.contains("┌ inspect ─"), "{screen}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn clicks_below_a_wrapped_line_hit_the_line_drawn_at_that_row() -> Result<()> {
+    let mut harness = new_harness("phase3-mouse-wrapped-click", 40, 24)?;
+    harness.app_state_mut().clipboard_osc52 = true;
+
+    submit_line(&mut harness, "print('a' * 100)").await?;
+    submit_line(&mut harness, "print('after')").await?;
+    harness.seed_assistant_turn_completed(
+        "inspect x",
+        &[("request", "-> Inspecting: x"), ("result", "<- Done")],
+        "ok",
+    )?;
+    harness.render()?;
+
+    let regions = harness.regions()?;
+    let row_of = |harness: &UiHarness, text: &str| {
+        (regions.timeline.y..regions.timeline.y + regions.timeline.height)
+            .find(|&row| harness.line(row).is_some_and(|line| line.trim() == text))
+            .expect("row on screen")
+    };
+
+    let after = row_of(&harness, "after");
+    left_click(&mut harness, regions.timeline.x + 2, after)?;
+    assert_eq!(harness.clipboard_bytes(), b"\x1b]52;c;YWZ0ZXI=\x1b\\");
+
+    let steps_before = timeline_snapshot(&harness)?.contains("-> Inspecting: x");
+    let turn = row_of(&harness, "ai> inspect x");
+    left_click(&mut harness, regions.timeline.x + 2, turn)?;
+    harness.render()?;
+    assert_ne!(
+        timeline_snapshot(&harness)?.contains("-> Inspecting: x"),
+        steps_before
+    );
+
+    Ok(())
+}